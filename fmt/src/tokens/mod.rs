@@ -365,6 +365,7 @@ impl<'a> Token<'a> {
             | GrammarRule::k_IEQUALS
             | GrammarRule::k_IMPORT
             | GrammarRule::k_IN
+            | GrammarRule::k_INCLUDE
             | GrammarRule::k_ISTARTSWITH
             | GrammarRule::k_MATCHES
             | GrammarRule::k_META
@@ -378,6 +379,7 @@ impl<'a> Token<'a> {
             | GrammarRule::k_STARTSWITH
             | GrammarRule::k_STRINGS
             | GrammarRule::k_THEM
+            | GrammarRule::k_TO
             | GrammarRule::k_TRUE
             | GrammarRule::k_WIDE
             | GrammarRule::k_XOR => Token::Keyword(src),
@@ -0,0 +1,9 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// Native code in serialized rules is loaded without validation, so when
+// built for fuzzing the deserializer only accepts rules that contain the
+// WASM module in binary form (see `Compiler::reproducible_serialization`).
+fuzz_target!(|data: &[u8]| {
+    let _ = yara_x::Rules::deserialize(data);
+});
@@ -0,0 +1,210 @@
+use yara_x_parser::ast;
+use yara_x_parser::report::ReportBuilder;
+use yara_x_parser::Parser;
+
+use crate::compiler::atoms::quality::{atom_quality, AtomsQuality};
+use crate::compiler::{re_error_to_compile_error, Atom, CompileError, Error};
+use crate::re;
+
+/// An atom extracted by [`AtomsExtractor`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedAtom {
+    /// The bytes in the atom.
+    pub bytes: Vec<u8>,
+    /// Quality of the atom. Higher values mean that the atom is less likely
+    /// to appear in the scanned data by chance.
+    pub quality: i32,
+    /// Number of bytes before the atom where a match of the pattern could
+    /// start.
+    pub backtrack: usize,
+}
+
+/// Quality of the whole set of atoms returned by [`AtomsExtractor`].
+///
+/// This is the same measure used by the compiler when choosing between
+/// different sets of atoms for a pattern. Sets can be compared with the usual
+/// comparison operators, the greater the set, the better.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AtomsSetQuality(AtomsQuality);
+
+impl AtomsSetQuality {
+    /// Length of the shortest atom in the set.
+    pub fn min_atom_len(&self) -> usize {
+        self.0.min_atom_len()
+    }
+
+    /// Quality of the worst atom in the set.
+    pub fn min_atom_quality(&self) -> i32 {
+        self.0.min_atom_quality()
+    }
+
+    /// Average quality of the atoms in the set.
+    pub fn avg_atom_quality(&self) -> f64 {
+        self.0.avg_atom_quality()
+    }
+}
+
+/// Atoms extracted by [`AtomsExtractor::extract`].
+#[derive(Debug)]
+pub struct ExtractedAtoms {
+    /// The extracted atoms.
+    pub atoms: Vec<ExtractedAtom>,
+    /// Quality of the atoms as a whole.
+    pub quality: AtomsSetQuality,
+}
+
+/// Extracts the atoms that the compiler would choose for a given regular
+/// expression.
+///
+/// Atoms are the short substrings that the scanner searches for before
+/// verifying whether a pattern actually matches. This type is useful for
+/// comparing the atoms chosen for different variants of a pattern, without
+/// having to build the rules. The atoms are the ones produced by the
+/// Thompson/PikeVM regexp compiler, regexps that the compiler handles in some
+/// other way (e.g: the ones that can be split into literal pieces) may end up
+/// using different atoms when they are part of a rule.
+///
+/// # Example
+///
+/// ```rust
+/// # use yara_x::AtomsExtractor;
+/// let extracted = AtomsExtractor::new().extract("http://").unwrap();
+/// assert_eq!(extracted.atoms.len(), 1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AtomsExtractor {
+    nocase: bool,
+    wide: bool,
+    ascii: bool,
+    preferred_atom_length: Option<usize>,
+}
+
+impl AtomsExtractor {
+    /// Creates a new atoms extractor.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Extract atoms as if the pattern had the `nocase` modifier.
+    pub fn nocase(&mut self, yes: bool) -> &mut Self {
+        self.nocase = yes;
+        self
+    }
+
+    /// Extract atoms as if the pattern had the `wide` modifier.
+    pub fn wide(&mut self, yes: bool) -> &mut Self {
+        self.wide = yes;
+        self
+    }
+
+    /// Extract atoms as if the pattern had the `ascii` modifier. This is
+    /// relevant only when combined with [`AtomsExtractor::wide`].
+    pub fn ascii(&mut self, yes: bool) -> &mut Self {
+        self.ascii = yes;
+        self
+    }
+
+    /// Extract atoms as if [`Compiler::preferred_atom_length`] was called
+    /// with `len`.
+    pub fn preferred_atom_length(&mut self, len: usize) -> &mut Self {
+        self.preferred_atom_length = Some(len);
+        self
+    }
+
+    /// Returns the atoms extracted from `regexp`.
+    ///
+    /// `regexp` is a regular expression written with the same syntax used
+    /// in YARA rules, but without the enclosing slashes (e.g: `a(b|c)d`).
+    /// Literal strings can be passed as well, as long as they don't contain
+    /// characters that have a special meaning in regular expressions.
+    pub fn extract(&self, regexp: &str) -> Result<ExtractedAtoms, Error> {
+        let src = format!(
+            "rule atoms {{ strings: $a = /{}/ condition: $a }}",
+            escape_slashes(regexp),
+        );
+
+        // The regexp is parsed as part of a rule, so that errors are
+        // reported exactly as the compiler would do.
+        let report_builder = ReportBuilder::new();
+        let mut parser = Parser::new();
+
+        parser.set_report_builder(&report_builder);
+
+        let ast = parser.build_ast(src.as_str())?;
+
+        let regexp = match ast.rules[0].patterns.as_deref() {
+            Some([ast::Pattern::Regexp(pattern)]) => &pattern.regexp,
+            _ => unreachable!(),
+        };
+
+        let hir = re::parser::Parser::new()
+            .force_case_insensitive(self.nocase)
+            .allow_mixed_greediness(false)
+            .parse(regexp)
+            .map_err(|err| {
+                Box::new(re_error_to_compile_error(
+                    &report_builder,
+                    regexp,
+                    err,
+                ))
+            })?;
+
+        let regexp_atoms: Vec<Atom> = re::thompson::Compiler::new()
+            .preferred_atom_len(self.preferred_atom_length)
+            .compile(&hir, &mut Vec::new())
+            .map_err(|err| match err {
+                re::Error::TooLarge => Box::new(CompileError::invalid_regexp(
+                    &report_builder,
+                    "regexp is too large".to_string(),
+                    regexp.span,
+                    None,
+                )),
+                _ => unreachable!(),
+            })?
+            .into_iter()
+            .map(|atom| atom.atom)
+            .collect();
+
+        // Like in the compiler, patterns with the `wide` modifier use the
+        // atoms interleaved with zeroes, followed by the atoms in their
+        // original form if the `ascii` modifier is used too.
+        let mut atoms = Vec::with_capacity(regexp_atoms.len() * 2);
+
+        if self.wide {
+            atoms.extend(regexp_atoms.iter().cloned().map(Atom::make_wide));
+        }
+
+        if self.ascii || !self.wide {
+            atoms.extend(regexp_atoms);
+        }
+
+        Ok(ExtractedAtoms {
+            quality: AtomsSetQuality(AtomsQuality::from_atoms(&atoms)),
+            atoms: atoms
+                .iter()
+                .map(|atom| ExtractedAtom {
+                    bytes: atom.as_ref().to_vec(),
+                    quality: atom_quality(atom.as_ref()),
+                    backtrack: atom.backtrack().into(),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Escapes the slashes in `regexp` that are not already escaped, so that
+/// it can be enclosed in slashes.
+fn escape_slashes(regexp: &str) -> String {
+    let mut result = String::with_capacity(regexp.len());
+    let mut escaped = false;
+
+    for c in regexp.chars() {
+        if c == '/' && !escaped {
+            result.push('\\');
+        }
+        escaped = c == '\\' && !escaped;
+        result.push(c);
+    }
+
+    result
+}
@@ -52,8 +52,10 @@ will end up using the `"Look"` atom alone, but in `/a(bcd|efg)h/` atoms `"bcd"`
 and `"efg"` will be used because `"a"` and `"h"` are too short.
  */
 
+mod extractor;
 mod mask;
 mod quality;
+mod table;
 
 use std::collections::Bound;
 use std::iter;
@@ -66,11 +68,14 @@ use regex_syntax::hir::literal::Literal;
 use serde::{Deserialize, Serialize};
 use smallvec::{smallvec, SmallVec, ToSmallVec};
 
+pub use crate::compiler::atoms::extractor::*;
 pub(crate) use crate::compiler::atoms::mask::ByteMaskCombinator;
 pub(crate) use crate::compiler::atoms::quality::best_atom_in_bytes;
 pub(crate) use crate::compiler::atoms::quality::best_range_in_bytes;
 pub(crate) use crate::compiler::atoms::quality::best_range_in_masked_bytes;
 pub(crate) use crate::compiler::atoms::quality::AtomsQuality;
+pub(crate) use crate::compiler::atoms::table::AtomTable;
+pub use crate::compiler::atoms::table::AtomTableBuilder;
 
 use crate::compiler::{SubPatternFlagSet, SubPatternFlags};
 
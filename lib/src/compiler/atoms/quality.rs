@@ -178,6 +178,7 @@ where
 ///
 /// Instances of [`AtomsQuality`] are compared for determining which set of
 /// atoms is better.
+#[derive(Debug)]
 pub(crate) struct AtomsQuality {
     num_exact_atoms: usize,
     num_inexact_atoms: usize,
@@ -230,6 +231,16 @@ impl AtomsQuality {
         AtomsQuality::new(atoms.as_ref().iter(), |atom| atom.is_exact())
     }
 
+    #[inline]
+    pub fn min_atom_len(&self) -> usize {
+        self.min_atom_len
+    }
+
+    #[inline]
+    pub fn min_atom_quality(&self) -> i32 {
+        self.min_atom_quality
+    }
+
     #[inline]
     pub fn avg_atom_quality(&self) -> f64 {
         self.sum_atom_quality as f64
@@ -254,6 +265,37 @@ impl AtomsQuality {
         self
     }
 
+    /// Compares this set of atoms with `other`, like [`Ord::cmp`] does, but
+    /// favoring the set whose minimum atom length is closer to
+    /// `preferred_len` when the minimum atom qualities of both sets are
+    /// close. If `preferred_len` is `None` this is equivalent to
+    /// [`Ord::cmp`].
+    pub fn cmp_with_preferred_len(
+        &self,
+        other: &Self,
+        preferred_len: Option<usize>,
+    ) -> Ordering {
+        // Maximum difference between the minimum atom qualities of the two
+        // sets for considering them close. This is roughly the quality
+        // contributed by a single byte.
+        const CLOSE_QUALITY: u32 = 20;
+
+        if let Some(preferred_len) = preferred_len {
+            let preferred_len = min(preferred_len, DESIRED_ATOM_SIZE);
+            let dist_self = self.min_atom_len.abs_diff(preferred_len);
+            let dist_other = other.min_atom_len.abs_diff(preferred_len);
+
+            if dist_self != dist_other
+                && self.min_atom_quality.abs_diff(other.min_atom_quality)
+                    <= CLOSE_QUALITY
+            {
+                return dist_other.cmp(&dist_self);
+            }
+        }
+
+        self.cmp(other)
+    }
+
     #[inline]
     pub fn min() -> Self {
         Self {
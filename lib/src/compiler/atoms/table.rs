@@ -0,0 +1,195 @@
+use aho_corasick::{AhoCorasick, Input, Match};
+use itertools::{Either, Itertools};
+
+use crate::compiler::SubPatternAtom;
+#[cfg(doc)]
+use crate::compiler::{Compiler, Rules};
+
+/// Default value for [`AtomTableBuilder::max_segments`].
+const DEFAULT_MAX_SEGMENTS: usize = 16;
+
+/// Table that allows searching for all the atoms in the scanned data.
+///
+/// The table consists in one or more segments, each segment is an
+/// Aho-Corasick automaton containing a contiguous range of atoms. Rules
+/// built with [`Compiler::build`] always have a single segment, multiple
+/// segments only appear when rules are built with an [`AtomTableBuilder`].
+#[derive(Clone)]
+pub(crate) struct AtomTable {
+    /// Each segment is a pair `(first_atom, automaton)`, where `first_atom`
+    /// is the index of the first atom in the automaton.
+    segments: Vec<(usize, AhoCorasick)>,
+}
+
+impl AtomTable {
+    /// Creates a table with a single segment containing all the atoms.
+    pub fn new(atoms: &[SubPatternAtom]) -> Self {
+        Self { segments: vec![(0, new_automaton(atoms))] }
+    }
+
+    /// Returns the length of the longest atom in the table.
+    pub fn max_atom_len(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|(_, ac)| ac.max_pattern_len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns an iterator over all the atoms found in `input`, including
+    /// overlapping ones.
+    ///
+    /// The index of the atom is returned in [`Match::pattern`], and it's
+    /// relative to the whole table, not to the segment where the atom was
+    /// found. Matches are yielded in increasing order of their end offset,
+    /// regardless of the number of segments.
+    pub fn find_overlapping_iter<'a, 'h: 'a>(
+        &'a self,
+        input: Input<'h>,
+    ) -> impl Iterator<Item = Match> + 'a {
+        // The common case is a table with a single segment, where the
+        // matches don't need to be merged nor translated.
+        if let [(_, ac)] = self.segments.as_slice() {
+            return Either::Left(ac.find_overlapping_iter(input));
+        }
+
+        Either::Right(
+            self.segments
+                .iter()
+                .map(move |(first_atom, ac)| {
+                    ac.find_overlapping_iter(input.clone()).map(move |m| {
+                        Match::must(
+                            first_atom + m.pattern().as_usize(),
+                            m.span(),
+                        )
+                    })
+                })
+                .kmerge_by(|a, b| a.end() < b.end()),
+        )
+    }
+}
+
+/// Builds the atom tables for rules that grow over time, without
+/// rebuilding the whole table each time.
+///
+/// When rules are added one at a time and rebuilt after each addition,
+/// most of the build time is spent in creating the Aho-Corasick automaton
+/// that contains the atoms of every pattern. An [`AtomTableBuilder`]
+/// remembers the automata built for previous [`Rules`], and when new rules
+/// are built with [`Compiler::build_with_atom_table`], only the atoms that
+/// were not present in the previous rules are put in a new automaton. The
+/// resulting [`Rules`] produce exactly the same scan results as the ones
+/// created with [`Compiler::build`].
+///
+/// The automata can be reused only if the atoms in the previous rules are
+/// a prefix of the atoms in the new ones. This is the case when the new
+/// rules are compiled by adding the same sources in the same order, followed
+/// by some new sources. Otherwise, for instance when some rule is removed or
+/// modified, or the compiler uses different settings, the whole table is
+/// rebuilt.
+///
+/// Each automaton is an additional pass over the scanned data, so scanning
+/// becomes slower as more automata are created. For this reason the whole
+/// table is also rebuilt into a single automaton when the number of automata
+/// reaches [`AtomTableBuilder::max_segments`]. Once the final set of rules is
+/// known, it's better to build them with [`Compiler::build`].
+///
+/// # Example
+///
+/// ```rust
+/// # use yara_x::{AtomTableBuilder, Compiler};
+/// let mut atom_table = AtomTableBuilder::new();
+///
+/// let mut compiler = Compiler::new();
+/// compiler.add_source(r#"rule foo { strings: $a = "foo" condition: $a }"#).unwrap();
+/// let rules = compiler.build_with_atom_table(&mut atom_table);
+///
+/// let mut compiler = Compiler::new();
+/// compiler.add_source(r#"rule foo { strings: $a = "foo" condition: $a }"#).unwrap();
+/// compiler.add_source(r#"rule bar { strings: $b = "bar" condition: $b }"#).unwrap();
+/// let rules = compiler.build_with_atom_table(&mut atom_table);
+///
+/// assert_eq!(atom_table.num_segments(), 2);
+/// ```
+pub struct AtomTableBuilder {
+    /// Atoms in the table built by the last call to [`Self::build`].
+    atoms: Vec<Box<[u8]>>,
+    /// Segments in the table built by the last call to [`Self::build`].
+    segments: Vec<(usize, AhoCorasick)>,
+    max_segments: usize,
+}
+
+impl Default for AtomTableBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AtomTableBuilder {
+    /// Creates a new [`AtomTableBuilder`].
+    pub fn new() -> Self {
+        Self {
+            atoms: Vec::new(),
+            segments: Vec::new(),
+            max_segments: DEFAULT_MAX_SEGMENTS,
+        }
+    }
+
+    /// Maximum number of automata in the atom table.
+    ///
+    /// When adding new atoms would exceed this number, the whole table is
+    /// rebuilt into a single automaton. The default value is 16. A value of
+    /// 1 effectively disables incremental building.
+    pub fn max_segments(&mut self, n: usize) -> &mut Self {
+        self.max_segments = n.max(1);
+        self
+    }
+
+    /// Returns the number of automata in the last table built.
+    pub fn num_segments(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Discards all the automata, forcing the next build to create the whole
+    /// table from scratch.
+    pub fn reset(&mut self) -> &mut Self {
+        self.atoms.clear();
+        self.segments.clear();
+        self
+    }
+
+    /// Builds the atom table for `atoms`, reusing the automata from the
+    /// previous table when possible.
+    pub(crate) fn build(&mut self, atoms: &[SubPatternAtom]) -> AtomTable {
+        let is_prefix = self.atoms.len() <= atoms.len()
+            && self
+                .atoms
+                .iter()
+                .zip(atoms)
+                .all(|(a, b)| a.as_ref() == b.as_slice());
+
+        let has_new_atoms = self.atoms.len() < atoms.len();
+
+        if !is_prefix
+            || (has_new_atoms && self.segments.len() >= self.max_segments)
+        {
+            self.reset();
+        }
+
+        // An empty table still needs one automaton, even if it's empty.
+        if self.segments.is_empty() || has_new_atoms {
+            let first_atom = self.atoms.len();
+            let new_atoms = &atoms[first_atom..];
+            self.segments.push((first_atom, new_automaton(new_atoms)));
+            self.atoms
+                .extend(new_atoms.iter().map(|atom| atom.as_slice().into()));
+        }
+
+        AtomTable { segments: self.segments.clone() }
+    }
+}
+
+fn new_automaton(atoms: &[SubPatternAtom]) -> AhoCorasick {
+    AhoCorasick::new(atoms.iter().map(|x| x.as_slice()))
+        .expect("failed to build Aho-Corasick automaton")
+}
@@ -1,11 +1,16 @@
 use itertools::Itertools;
+use rustc_hash::FxHashMap;
 use std::mem::size_of;
 use std::rc::Rc;
 
+use yara_x_parser::ast;
+use yara_x_parser::ast::Span;
 use yara_x_parser::report::ReportBuilder;
 
 use crate::compiler::ir::PatternIdx;
-use crate::compiler::{ir, IdentId, RuleId, RuleInfo, Warnings};
+use crate::compiler::{
+    ir, IdentId, PatternRewriter, RuleId, RuleInfo, Warnings, XorRangeHook,
+};
 use crate::string_pool::StringPool;
 use crate::symbols::{StackedSymbolTable, SymbolLookup};
 use crate::types::Type;
@@ -33,6 +38,13 @@ pub(in crate::compiler) struct CompileContext<'a, 'src, 'sym> {
     /// in the current rule.
     pub current_rule_patterns: &'a mut Vec<ir::PatternInRule<'src>>,
 
+    /// Patterns that are not declared in the current rule, but created while
+    /// compiling its condition, like the ones used by `content_icontains`.
+    /// Once the condition is compiled they are appended to the declared
+    /// patterns, so their indexes come after the indexes of the patterns
+    /// in `current_rule_patterns`.
+    pub synthetic_patterns: Vec<ir::PatternInRule<'src>>,
+
     /// Warnings generated during the compilation.
     pub warnings: &'a mut Warnings,
 
@@ -45,6 +57,56 @@ pub(in crate::compiler) struct CompileContext<'a, 'src, 'sym> {
 
     /// Allow invalid escape sequences in regular expressions.
     pub relaxed_re_syntax: bool,
+
+    /// Minimum number of matches that the condition of the current rule
+    /// guarantees for each pattern, as returned by [`ir::min_matches`].
+    pub min_matches: FxHashMap<&'src str, i64>,
+
+    /// Function that rewrites patterns before they are compiled, if any.
+    pub pattern_rewriter: Option<&'a PatternRewriter<'a>>,
+
+    /// Function that narrows the key range of `xor` patterns, if any.
+    pub xor_range_hook: Option<&'a XorRangeHook<'a>>,
+
+    /// Fragments that can be used in hex patterns, indexed by name. Each
+    /// fragment is accompanied by the span of its identifier.
+    pub fragments: &'a FxHashMap<String, (Span, ast::HexTokens)>,
+
+    /// Names of the functions that can't be called. See
+    /// [`crate::Compiler::disable_function`].
+    pub disabled_functions: &'a [String],
+
+    /// Number of calls to functions that read integers from the scanned
+    /// data (e.g: `uint32(...)`) found in the current rule so far.
+    pub data_reads: usize,
+
+    /// Rules used in the condition of the current rule, in the order in
+    /// which they appear for the first time.
+    pub used_rules: Vec<RuleId>,
+
+    /// Modules used in the condition of the current rule, in the order in
+    /// which they appear for the first time.
+    pub used_modules: Vec<IdentId>,
+
+    /// Maximum nesting depth of the condition, if any. See
+    /// [`crate::Compiler::max_condition_depth`].
+    pub max_condition_depth: Option<usize>,
+
+    /// Maximum size of regular expressions, if any. See
+    /// [`crate::Compiler::max_regexp_size`].
+    pub max_regexp_size: Option<usize>,
+
+    /// Maximum number of iterations in loops, if any. See
+    /// [`crate::Compiler::max_loop_iterations`].
+    pub max_loop_iterations: Option<usize>,
+
+    /// Nesting depth of the expression being compiled.
+    pub depth: usize,
+
+    /// Number of times that the expression being compiled is executed
+    /// because of the loops that contain it. Only loops over ranges whose
+    /// bounds are known at compile time are taken into account.
+    pub loop_iterations: u64,
 }
 
 impl<'a, 'src, 'sym> CompileContext<'a, 'src, 'sym> {
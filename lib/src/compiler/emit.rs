@@ -181,6 +181,13 @@ pub(in crate::compiler) struct EmitContext<'a> {
     /// Information about the rule whose condition is being emitted.
     pub current_rule: &'a RuleInfo,
 
+    /// ID of the rule whose condition is being emitted.
+    pub current_rule_id: RuleId,
+
+    /// True if undefined values found while evaluating the condition must
+    /// be signaled to the scanner. See [`crate::Compiler::strict_undefined`].
+    pub strict_undefined: bool,
+
     /// Table with all the symbols (functions, variables) used by WASM.
     pub wasm_symbols: &'a WasmSymbols,
 
@@ -273,15 +280,51 @@ pub(super) fn emit_rule_condition(
         );
     }
 
+    // When the scanner excludes rules because of their tags, it sets the
+    // global variable `rule_filter_enabled` to true, and the condition is
+    // evaluated only if `rule_is_excluded` returns false for this rule.
+    instr.global_get(ctx.wasm_symbols.rule_filter_enabled);
+    instr.if_else(
+        I32,
+        |then_| {
+            then_.i32_const(rule_id.0);
+            then_.call(
+                ctx.function_id(wasm::export__rule_is_excluded.mangled_name),
+            );
+        },
+        |else_| {
+            else_.i32_const(0);
+        },
+    );
+
+    instr.unop(UnaryOp::I32Eqz);
+    instr.if_else(
+        None,
+        |then_| {
+            emit_rule_condition_check(ctx, then_, rule_id, condition);
+        },
+        |_| {},
+    );
+}
+
+/// Emits the code that evaluates the condition of a rule, and calls
+/// `rule_match` or `global_rule_no_match` depending on the result.
+fn emit_rule_condition_check(
+    ctx: &mut EmitContext,
+    instr: &mut InstrSeqBuilder,
+    rule_id: RuleId,
+    condition: &mut Expr,
+) {
     // Emit WASM code for the rule's condition.
     catch_undef(
         ctx,
         I32,
-        &mut instr,
+        instr,
         |ctx, instr| {
             emit_bool_expr(ctx, instr, condition);
         },
-        |_, instr| {
+        |ctx, instr| {
+            emit_undef_signal(ctx, instr);
             instr.i32_const(0);
         },
     );
@@ -429,9 +472,10 @@ fn emit_expr(
                             emit_func_call(ctx, instr, func);
                             ctx.lookup_list.clear();
                         }
-                        TypeValue::Regexp(_) => {
+                        TypeValue::Regexp(_) | TypeValue::Range => {
                             // The value of an identifier can't be a regular
-                            // expression.
+                            // expression or a range, these types are used
+                            // only for function arguments and results.
                             unreachable!();
                         }
                         TypeValue::Unknown => {
@@ -462,6 +506,10 @@ fn emit_expr(
             emit_pattern_length(ctx, instr, expr);
         }
 
+        Expr::WeightSum { patterns } => {
+            emit_weight_sum(ctx, instr, patterns);
+        }
+
         Expr::FieldAccess { operands } => {
             emit_field_access(ctx, instr, operands.as_mut());
         }
@@ -801,7 +849,8 @@ fn emit_and(
                     |ctx, instr| {
                         emit_bool_expr(ctx, instr, operand);
                     },
-                    |_, instr| {
+                    |ctx, instr| {
+                        emit_undef_signal(ctx, instr);
                         instr.i32_const(0);
                     },
                 );
@@ -865,7 +914,8 @@ fn emit_or(
                     |ctx, instr| {
                         emit_bool_expr(ctx, instr, operand);
                     },
-                    |_, instr| {
+                    |ctx, instr| {
+                        emit_undef_signal(ctx, instr);
                         instr.i32_const(0);
                     },
                 );
@@ -1053,6 +1103,40 @@ fn emit_pattern_match(
                 ctx.function_id(wasm::export__is_pat_match_in.mangled_name),
             );
         }
+        MatchAnchor::InExpr(expr) => {
+            // Expressions of type range leave both the lower and upper
+            // bounds in the stack.
+            emit_expr(ctx, instr, expr);
+            instr.call(
+                ctx.function_id(wasm::export__is_pat_match_in.mangled_name),
+            );
+        }
+    }
+}
+
+/// Emits the code that returns the sum of the weights of the patterns that
+/// matched at least once.
+fn emit_weight_sum(
+    ctx: &mut EmitContext,
+    instr: &mut InstrSeqBuilder,
+    patterns: &[(PatternIdx, i64)],
+) {
+    // Make sure the pattern search phase is executed, as the sum depends
+    // on patterns.
+    emit_lazy_pattern_search(ctx, instr);
+
+    instr.i64_const(0);
+
+    for (pattern, weight) in patterns {
+        // Push the pattern ID and check if it matched, the result is 1 if
+        // the pattern matched and 0 if not. This value is multiplied by the
+        // weight and added to the accumulated sum.
+        instr.i32_const(ctx.pattern_id(*pattern).into());
+        emit_check_for_pattern_match(ctx, instr);
+        instr.unop(UnaryOp::I64ExtendUI32);
+        instr.i64_const(*weight);
+        instr.binop(BinaryOp::I64Mul);
+        instr.binop(BinaryOp::I64Add);
     }
 }
 
@@ -1198,7 +1282,11 @@ fn emit_pattern_length(
 
 /// Emits the code that checks if rule has matched.
 ///
-/// The emitted code leaves 0 or 1 at the top of the stack.
+/// The emitted code leaves 0 or 1 at the top of the stack. A rule can
+/// reference only the rules declared before it, which have been evaluated
+/// already, so the referenced rule's condition is not evaluated again, the
+/// reference costs a single memory load. This is why there's nothing to
+/// gain by inlining the conditions of referenced rules.
 fn emit_check_for_rule_match(
     ctx: &mut EmitContext,
     instr: &mut InstrSeqBuilder,
@@ -1499,6 +1587,12 @@ fn emit_of_pattern_set(
                         wasm::export__is_pat_match_in.mangled_name,
                     ));
                 }
+                MatchAnchor::InExpr(expr) => {
+                    emit_expr(ctx, instr, expr);
+                    instr.call(ctx.function_id(
+                        wasm::export__is_pat_match_in.mangled_name,
+                    ));
+                }
             }
         },
         // After each iteration.
@@ -1971,7 +2065,9 @@ fn emit_for<I, B, C, A>(
 
         let p = match quantifier {
             Quantifier::Percentage(expr) => Some((expr, true)),
-            Quantifier::Expr(expr) => Some((expr, false)),
+            Quantifier::Expr(expr) | Quantifier::Range(expr, _) => {
+                Some((expr, false))
+            }
             _ => None,
         };
 
@@ -2020,6 +2116,20 @@ fn emit_for<I, B, C, A>(
             ),
         };
 
+        // `upper_count` is the upper bound in quantifiers like `2 to 4`. The
+        // loop is false as soon as the number of loop conditions that returned
+        // `true` exceeds this value.
+        let upper_count = match quantifier {
+            Quantifier::Range(_, upper) => {
+                let upper_count = stack_frame.new_var(Type::Integer);
+                set_var(ctx, instr, upper_count, |ctx, instr| {
+                    emit_expr(ctx, instr, upper);
+                });
+                upper_count
+            }
+            _ => Var { ty: Type::Integer, index: 0 },
+        };
+
         instr.loop_(I32, |block| {
             let loop_start = block.id();
 
@@ -2109,6 +2219,42 @@ fn emit_for<I, B, C, A>(
                         },
                     );
                 }
+                Quantifier::Range(..) => {
+                    block.if_else(
+                        None,
+                        |then_| {
+                            // The condition was true, increment count.
+                            incr_var(ctx, then_, count);
+
+                            // Is count > upper_count?
+                            load_var(ctx, then_, count);
+                            load_var(ctx, then_, upper_count);
+                            then_.binop(BinaryOp::I64GtS);
+
+                            // count > upper_count, break the loop with
+                            // result false.
+                            then_.if_else(
+                                None,
+                                |then_| {
+                                    then_.i32_const(0);
+                                    then_.br(loop_end);
+                                },
+                                |_| {},
+                            );
+                        },
+                        |_| {},
+                    );
+
+                    incr_i_and_repeat(ctx, block, n, i, loop_start);
+
+                    // If this point is reached we have iterated over the whole
+                    // range 0..n and `count` never exceeded `upper_count`. The
+                    // loop is true if `count` reached `max_count`, which is
+                    // the lower bound.
+                    load_var(ctx, block, count);
+                    load_var(ctx, block, max_count);
+                    block.binop(BinaryOp::I64GeS);
+                }
                 Quantifier::Percentage(_) | Quantifier::Expr(_) => {
                     block.if_else(
                         None,
@@ -2775,6 +2921,16 @@ fn catch_undef(
     ctx.exception_handler_stack.pop();
 }
 
+/// Emits a call to `rule_undef` when strict handling of undefined values is
+/// enabled, signaling that an undefined value was found in a boolean context
+/// while evaluating the current rule. If not enabled, this is a no-op.
+fn emit_undef_signal(ctx: &mut EmitContext, instr: &mut InstrSeqBuilder) {
+    if ctx.strict_undefined {
+        instr.i32_const(ctx.current_rule_id.0);
+        instr.call(ctx.function_id(wasm::export__rule_undef.mangled_name));
+    }
+}
+
 /// Throws an exception when an undefined value is found.
 ///
 /// For more information see [`catch_undef`].
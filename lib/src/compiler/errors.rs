@@ -9,6 +9,7 @@ use yara_x_parser::ast::Span;
 use yara_x_parser::report::Level;
 use yara_x_parser::report::ReportBuilder;
 use yara_x_parser::Error as ParseError;
+use yara_x_parser::Warning;
 
 /// Errors returned while serializing/deserializing compiled rules.
 #[derive(Error, Debug)]
@@ -22,6 +23,17 @@ pub enum SerializationError {
     #[error("invalid YARA-X compiled rules file")]
     InvalidEncoding(#[from] bincode::Error),
 
+    /// The rules use functions added with [`crate::Compiler::add_function`],
+    /// which can't be serialized.
+    #[error("rules with host functions can't be serialized")]
+    HostFunctions,
+
+    /// The signature in the serialized rules doesn't match the key passed
+    /// to [`crate::Rules::deserialize_verified`], or the rules are not
+    /// signed at all.
+    #[error("signature mismatch in YARA-X compiled rules file")]
+    SignatureMismatch,
+
     /// I/O error while trying to read or write serialized data.
     #[error(transparent)]
     IoError(#[from] io::Error),
@@ -45,8 +57,23 @@ pub enum Error {
 
     #[error(transparent)]
     VariableError(#[from] VariableError),
+
+    /// The signature passed to [`crate::Compiler::add_function`] is not
+    /// valid, or its return type doesn't match the one of the function.
+    #[error("invalid signature for function `{name}`: `{signature}`")]
+    InvalidFunctionSignature { name: String, signature: String },
+
+    /// A warning that must be treated as an error. See
+    /// [`crate::Compiler::warning_as_error`].
+    #[error(transparent)]
+    WarningAsError(Box<Warning>),
 }
 
+/// Error returned when a warning code doesn't correspond to any warning.
+#[derive(Error, Debug, Eq, PartialEq)]
+#[error("invalid warning code: `{0}`")]
+pub struct InvalidWarningCode(pub(crate) String);
+
 /// An error occurred during the compilation process.
 #[derive(DeriveError, Eq, PartialEq)]
 #[allow(missing_docs)]
@@ -126,10 +153,72 @@ pub enum CompileError {
     #[label("module `{identifier}` not found", span)]
     UnknownModule { detailed_report: String, identifier: String, span: Span },
 
+    #[error("can not include `{file_name}`")]
+    #[label("{reason}", span)]
+    IncludeError {
+        detailed_report: String,
+        file_name: String,
+        reason: String,
+        span: Span,
+    },
+
+    #[error("include statements are not allowed")]
+    #[label("includes are disabled", span)]
+    IncludeNotAllowed { detailed_report: String, span: Span },
+
+    #[error("module `{module}` is disabled")]
+    #[label("the use of module `{module}` is not allowed", span)]
+    ModuleDisabled { detailed_report: String, module: String, span: Span },
+
+    #[error("function `{function}` is disabled")]
+    #[label("the use of function `{function}` is not allowed", span)]
+    FunctionDisabled { detailed_report: String, function: String, span: Span },
+
     #[error("invalid range")]
     #[label("higher bound must be greater or equal than lower bound", span)]
     InvalidRange { detailed_report: String, span: Span },
 
+    #[error("condition is too deeply nested")]
+    #[label("this expression exceeds the maximum depth of {max_depth}", span)]
+    ConditionTooDeep { detailed_report: String, max_depth: usize, span: Span },
+
+    #[error("too many patterns in rule `{rule}`")]
+    #[label(
+        "this rule has {num_patterns} patterns, the maximum is {max_patterns}",
+        span
+    )]
+    TooManyPatterns {
+        detailed_report: String,
+        rule: String,
+        num_patterns: usize,
+        max_patterns: usize,
+        span: Span,
+    },
+
+    #[error("regexp too large")]
+    #[label(
+        "the size of this regexp is {size}, the maximum is {max_size}",
+        span
+    )]
+    RegexpTooLarge {
+        detailed_report: String,
+        size: usize,
+        max_size: usize,
+        span: Span,
+    },
+
+    #[error("loop has too many iterations")]
+    #[label(
+        "this loop runs {iterations} iterations in total, the maximum is {max_iterations}",
+        span
+    )]
+    TooManyIterations {
+        detailed_report: String,
+        iterations: u64,
+        max_iterations: usize,
+        span: Span,
+    },
+
     #[error("duplicate rule `{new_rule}`")]
     #[label(
         "`{new_rule}` declared here for the first time",
@@ -189,6 +278,16 @@ pub enum CompileError {
         note: Option<String>,
     },
 
+    #[error("invalid rewritten pattern")]
+    #[label("{error}", span)]
+    #[note(note)]
+    InvalidRewrittenPattern {
+        detailed_report: String,
+        error: String,
+        span: Span,
+        note: Option<String>,
+    },
+
     #[error("mixing greedy and non-greedy quantifiers in regular expression")]
     #[label("this is {quantifier1_greediness}", quantifier1_span)]
     #[label("this is {quantifier2_greediness}", quantifier2_span)]
@@ -217,4 +316,57 @@ pub enum CompileError {
         span: Span,
         note: Option<String>,
     },
+
+    #[error("undefined fragment `{fragment}`")]
+    #[label("this fragment has not been declared", span)]
+    UndefinedFragment { detailed_report: String, fragment: String, span: Span },
+
+    #[error("cyclic fragment `{fragment}`")]
+    #[label("`{fragment}` declared here", fragment_span, style = "note")]
+    #[label("`{fragment}` is used while expanding itself", span)]
+    CyclicFragment {
+        detailed_report: String,
+        fragment: String,
+        span: Span,
+        fragment_span: Span,
+    },
+
+    #[error("duplicate fragment `{new_fragment}`")]
+    #[label(
+        "`{new_fragment}` declared here for the first time",
+        existing_fragment_span,
+        style = "note"
+    )]
+    #[label("duplicate declaration of `{new_fragment}`", new_fragment_span)]
+    DuplicateFragment {
+        detailed_report: String,
+        new_fragment: String,
+        new_fragment_span: Span,
+        existing_fragment_span: Span,
+    },
+
+    #[error("invalid xor range for `{pattern_ident}`")]
+    #[label(
+        "the range set by the xor range hook ({start}-{end}) is not within this range",
+        span
+    )]
+    InvalidXorRange {
+        detailed_report: String,
+        pattern_ident: String,
+        start: u8,
+        end: u8,
+        span: Span,
+    },
+
+    #[error("fragment `{fragment}` is too large")]
+    #[label(
+        "expanding this fragment produces more than {max_tokens} tokens",
+        span
+    )]
+    FragmentTooLarge {
+        detailed_report: String,
+        fragment: String,
+        max_tokens: usize,
+        span: Span,
+    },
 }
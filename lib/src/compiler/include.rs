@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A file included with an `include` statement, as returned by
+/// [`IncludeResolver::resolve`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncludedSource {
+    /// Identifies the included file, like its full path. This is used as the
+    /// origin of the source code in error reports, it's passed back to the
+    /// resolver when the included file contains `include` statements, and
+    /// it's used for detecting circular includes.
+    pub origin: String,
+    /// The source code of the included file.
+    pub code: String,
+}
+
+/// Finds the source code for the files included with `include` statements.
+///
+/// Includes are disabled by default, and the compiler doesn't have any
+/// resolver until one is set with [`crate::Compiler::include_resolver`].
+/// [`FsIncludeResolver`] reads the included files from a directory in the
+/// local filesystem, but resolvers can obtain the included files from
+/// somewhere else, like an in-memory map, an archive or a database.
+///
+/// This trait is implemented for `HashMap<String, String>`, where keys are
+/// the names used in `include` statements, and values are the source code
+/// of the included files.
+pub trait IncludeResolver {
+    /// Returns the source code of the file included as `name`.
+    ///
+    /// `includer` is the origin of the source code that contains the
+    /// `include` statement, if any. For sources passed to
+    /// [`crate::Compiler::add_source`] this is the origin set with
+    /// [`crate::SourceCode::with_origin`], and for included files it's
+    /// [`IncludedSource::origin`].
+    ///
+    /// In case of error the result is a message describing the reason.
+    fn resolve(
+        &self,
+        name: &str,
+        includer: Option<&str>,
+    ) -> Result<IncludedSource, String>;
+}
+
+/// Resolves included files from a directory in the local filesystem.
+///
+/// Only files within the base directory passed to [`FsIncludeResolver::new`]
+/// can be included, including files in its subdirectories. Paths are relative
+/// to the directory of the file that contains the `include` statement, or to
+/// the base directory if the source code doesn't have an origin. Paths that
+/// point outside the base directory, either because they are absolute, they
+/// contain `..` components, or they go through symbolic links, produce an
+/// error.
+#[derive(Debug)]
+pub struct FsIncludeResolver {
+    base_dir: PathBuf,
+}
+
+impl FsIncludeResolver {
+    /// Creates a resolver that includes files from `base_dir`.
+    ///
+    /// Returns an error if `base_dir` doesn't exist.
+    pub fn new<P: AsRef<Path>>(base_dir: P) -> io::Result<Self> {
+        Ok(Self { base_dir: base_dir.as_ref().canonicalize()? })
+    }
+}
+
+impl IncludeResolver for FsIncludeResolver {
+    fn resolve(
+        &self,
+        name: &str,
+        includer: Option<&str>,
+    ) -> Result<IncludedSource, String> {
+        // An origin without directory (e.g: `main.yar`) has an empty parent,
+        // which is treated like a source without origin.
+        let path = match includer
+            .and_then(|path| Path::new(path).parent())
+            .filter(|dir| !dir.as_os_str().is_empty())
+        {
+            Some(dir) => dir.join(name),
+            None => self.base_dir.join(name),
+        };
+
+        // The canonical path has no `..` components nor symbolic links, so
+        // it can be compared with the base directory.
+        let path = path.canonicalize().map_err(|err| err.to_string())?;
+
+        if !path.starts_with(&self.base_dir) {
+            return Err("file is outside the include directory".to_string());
+        }
+
+        let code = fs::read_to_string(&path).map_err(|err| err.to_string())?;
+
+        Ok(IncludedSource {
+            origin: path.to_string_lossy().into_owned(),
+            code,
+        })
+    }
+}
+
+impl IncludeResolver for HashMap<String, String> {
+    fn resolve(
+        &self,
+        name: &str,
+        _includer: Option<&str>,
+    ) -> Result<IncludedSource, String> {
+        match self.get(name) {
+            Some(code) => Ok(IncludedSource {
+                origin: name.to_string(),
+                code: code.clone(),
+            }),
+            None => Err("file not found".to_string()),
+        }
+    }
+}
@@ -7,9 +7,10 @@ use std::rc::Rc;
 
 use bstr::ByteSlice;
 use itertools::Itertools;
+use rustc_hash::FxHashMap;
 use yara_x_parser::ast::{HasSpan, Span};
 use yara_x_parser::report::ReportBuilder;
-use yara_x_parser::{ast, ErrorInfo, Warning};
+use yara_x_parser::{ast, ErrorInfo, Parser, Warning};
 
 use crate::compiler::ir::hex2hir::hex_pattern_hir_from_ast;
 use crate::compiler::ir::{
@@ -17,7 +18,9 @@ use crate::compiler::ir::{
     MatchAnchor, Of, OfItems, Pattern, PatternFlagSet, PatternFlags,
     PatternIdx, PatternInRule, Quantifier, Range, RegexpPattern,
 };
-use crate::compiler::{CompileContext, CompileError};
+use crate::compiler::{
+    CompileContext, CompileError, PatternRewriter, PatternSource,
+};
 use crate::modules::BUILTIN_MODULES;
 use crate::re;
 use crate::re::parser::Error;
@@ -51,7 +54,7 @@ fn pattern_from_ast<'src>(
 }
 
 pub(in crate::compiler) fn text_pattern_from_ast<'src>(
-    _ctx: &mut CompileContext,
+    ctx: &mut CompileContext,
     pattern: &ast::TextPattern<'src>,
 ) -> Result<PatternInRule<'src>, Box<CompileError>> {
     let mut flags = PatternFlagSet::none();
@@ -75,9 +78,29 @@ pub(in crate::compiler) fn text_pattern_from_ast<'src>(
     }
 
     let xor_range = match pattern.modifiers.xor() {
-        Some(ast::PatternModifier::Xor { start, end, .. }) => {
+        Some(ast::PatternModifier::Xor { start, end, span }) => {
             flags.set(PatternFlags::Xor);
-            Some(*start..=*end)
+            match ctx.xor_range_hook {
+                Some(hook) => {
+                    let range = hook(pattern.identifier.name, *start..=*end);
+                    if range.start() < start
+                        || range.end() > end
+                        || range.start() > range.end()
+                    {
+                        return Err(Box::new(
+                            CompileError::invalid_xor_range(
+                                ctx.report_builder,
+                                pattern.identifier.name.to_string(),
+                                *range.start(),
+                                *range.end(),
+                                *span,
+                            ),
+                        ));
+                    }
+                    Some(range)
+                }
+                None => Some(*start..=*end),
+            }
         }
         _ => None,
     };
@@ -98,33 +121,258 @@ pub(in crate::compiler) fn text_pattern_from_ast<'src>(
         _ => None,
     };
 
+    let rewritten = ctx.pattern_rewriter.and_then(|rewriter| {
+        match rewriter(&PatternSource::Text(pattern.text.to_vec())) {
+            PatternSource::Text(text) => Some(text),
+            _ => None,
+        }
+    });
+
+    // The rewritten pattern must satisfy the same length requirements that
+    // the parser checks for the original one.
+    if let Some(text) = &rewritten {
+        let min_len = if flags.contains(PatternFlags::Base64)
+            || flags.contains(PatternFlags::Base64Wide)
+        {
+            3
+        } else {
+            1
+        };
+
+        if text.len() < min_len {
+            return Err(invalid_rewritten_pattern(
+                ctx.report_builder,
+                "this pattern is too short".to_string(),
+                pattern.span(),
+                format!("{:?}", text.as_bstr()),
+            ));
+        }
+    }
+
     Ok(PatternInRule {
         identifier: pattern.identifier.name,
+        weight: pattern_weight(&pattern.modifiers),
         pattern: Pattern::Literal(LiteralPattern {
             flags,
             xor_range,
             base64_alphabet: base64_alphabet.map(String::from),
             base64wide_alphabet: base64wide_alphabet.map(String::from),
             anchored_at: None,
-            text: pattern.text.as_ref().into(),
+            text: match rewritten {
+                Some(text) => text.into(),
+                None => pattern.text.as_ref().into(),
+            },
         }),
     })
 }
 
+/// Returns the weight indicated by the `weight` modifier, or 1 if the
+/// pattern doesn't have this modifier.
+fn pattern_weight(modifiers: &ast::PatternModifiers) -> i64 {
+    match modifiers.weight() {
+        Some(ast::PatternModifier::Weight { weight, .. }) => *weight,
+        _ => 1,
+    }
+}
+
 pub(in crate::compiler) fn hex_pattern_from_ast<'src>(
-    _ctx: &mut CompileContext,
+    ctx: &mut CompileContext,
     pattern: &ast::HexPattern<'src>,
 ) -> Result<PatternInRule<'src>, Box<CompileError>> {
+    let rewritten = match ctx.pattern_rewriter {
+        Some(rewriter) => rewritten_hex_tokens(ctx, rewriter, pattern)?,
+        None => None,
+    };
+
+    let tokens = expand_fragments(
+        ctx,
+        rewritten.as_ref().unwrap_or(&pattern.tokens),
+        &mut Vec::new(),
+        &mut 0,
+    )?;
+
+    let hir = re::hir::Hir::from(hex_pattern_hir_from_ast(&tokens));
+
+    check_regexp_size(ctx, &hir, pattern.span())?;
+
     Ok(PatternInRule {
         identifier: pattern.identifier.name,
+        weight: pattern_weight(&pattern.modifiers),
         pattern: Pattern::Regexp(RegexpPattern {
             flags: PatternFlagSet::from(PatternFlags::Ascii),
-            hir: re::hir::Hir::from(hex_pattern_hir_from_ast(pattern)),
+            hir,
             anchored_at: None,
         }),
     })
 }
 
+/// Passes the source code of a hex pattern to the pattern rewriter, and
+/// returns the tokens of the rewritten pattern, or `None` if the pattern was
+/// not changed.
+///
+/// The rewritten pattern is parsed again, if it is not a valid hex pattern
+/// the parser's error is returned as a [`CompileError`] that refers to the
+/// original pattern.
+fn rewritten_hex_tokens(
+    ctx: &CompileContext,
+    rewriter: &PatternRewriter,
+    pattern: &ast::HexPattern,
+) -> Result<Option<ast::HexTokens>, Box<CompileError>> {
+    let span = pattern.span();
+
+    let src = match ctx.report_builder.source_code(span.source_id()) {
+        Some(code) => code[span.start()..span.end()].to_string(),
+        None => return Ok(None),
+    };
+
+    let rewritten = match rewriter(&PatternSource::Hex(src.clone())) {
+        PatternSource::Hex(rewritten) if rewritten != src => rewritten,
+        _ => return Ok(None),
+    };
+
+    // The rewritten pattern is parsed as the only pattern in a rule. The
+    // pattern must not include anything else, like modifiers or tokens that
+    // close the pattern and start something else.
+    let rule = format!(
+        "rule rewritten {{ strings: $a = {} condition: $a }}",
+        rewritten
+    );
+
+    let error = match Parser::new().build_ast(rule.as_str()) {
+        Ok(mut ast) => match ast.rules.pop().and_then(|rule| rule.patterns) {
+            Some(mut patterns)
+                if ast.rules.is_empty() && patterns.len() == 1 =>
+            {
+                match patterns.pop() {
+                    Some(ast::Pattern::Hex(hex))
+                        if hex.modifiers.iter().next().is_none() =>
+                    {
+                        return Ok(Some(hex.tokens));
+                    }
+                    _ => "this is not a hex pattern".to_string(),
+                }
+            }
+            _ => "this is not a hex pattern".to_string(),
+        },
+        Err(err) => match err.info() {
+            ErrorInfo::SyntaxError { error_msg, .. }
+            | ErrorInfo::InvalidPattern { error_msg, .. } => error_msg.clone(),
+            info => info.title(),
+        },
+    };
+
+    Err(invalid_rewritten_pattern(ctx.report_builder, error, span, rewritten))
+}
+
+/// Returns the error produced when the pattern at `span` was replaced by the
+/// pattern rewriter with `rewritten`, which is not a valid pattern.
+fn invalid_rewritten_pattern(
+    report_builder: &ReportBuilder,
+    error: String,
+    span: Span,
+    rewritten: String,
+) -> Box<CompileError> {
+    Box::new(CompileError::invalid_rewritten_pattern(
+        report_builder,
+        error,
+        span,
+        Some(format!(
+            "the pattern rewriter replaced this pattern with `{}`",
+            rewritten
+        )),
+    ))
+}
+
+/// Maximum number of tokens that the expansion of fragments can add to a
+/// hex pattern. Each fragment can reference other fragments more than once,
+/// so the size of the expanded pattern can grow exponentially with the
+/// number of fragments.
+const MAX_EXPANDED_TOKENS: usize = 10_000;
+
+/// Returns a copy of `tokens` where the references to fragments are
+/// replaced with the fragment's tokens, recursively.
+///
+/// `expanding` contains the fragments being expanded, together with the
+/// span of the reference that caused their expansion. It is used for
+/// detecting fragments that reference themselves, directly or indirectly.
+/// `num_tokens` is the number of tokens produced by fragments so far, which
+/// can't exceed [`MAX_EXPANDED_TOKENS`].
+fn expand_fragments(
+    ctx: &CompileContext,
+    tokens: &ast::HexTokens,
+    expanding: &mut Vec<(String, Span)>,
+    num_tokens: &mut usize,
+) -> Result<ast::HexTokens, Box<CompileError>> {
+    let mut expanded = Vec::with_capacity(tokens.tokens.len());
+
+    for token in &tokens.tokens {
+        // Only the tokens that come from fragments are counted, the size of
+        // the pattern itself is already limited by the size of the source.
+        // When the limit is exceeded, the error points to the reference in
+        // the pattern, not to the reference within some other fragment.
+        if let Some((name, span)) = expanding.first() {
+            *num_tokens += 1;
+            if *num_tokens > MAX_EXPANDED_TOKENS {
+                return Err(Box::new(CompileError::fragment_too_large(
+                    ctx.report_builder,
+                    name.clone(),
+                    MAX_EXPANDED_TOKENS,
+                    *span,
+                )));
+            }
+        }
+        match token {
+            ast::HexToken::Fragment(fragment) => {
+                let (fragment_span, fragment_tokens) =
+                    ctx.fragments.get(&fragment.name).ok_or_else(|| {
+                        Box::new(CompileError::undefined_fragment(
+                            ctx.report_builder,
+                            fragment.name.clone(),
+                            fragment.span,
+                        ))
+                    })?;
+
+                if expanding.iter().any(|(name, _)| name == &fragment.name) {
+                    return Err(Box::new(CompileError::cyclic_fragment(
+                        ctx.report_builder,
+                        fragment.name.clone(),
+                        fragment.span,
+                        *fragment_span,
+                    )));
+                }
+
+                expanding.push((fragment.name.clone(), fragment.span));
+                expanded.extend(
+                    expand_fragments(
+                        ctx,
+                        fragment_tokens,
+                        expanding,
+                        num_tokens,
+                    )?
+                    .tokens,
+                );
+                expanding.pop();
+            }
+            ast::HexToken::Alternative(alt) => {
+                let alternatives = alt
+                    .alternatives
+                    .iter()
+                    .map(|alt| {
+                        expand_fragments(ctx, alt, expanding, num_tokens)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                expanded.push(ast::HexToken::Alternative(Box::new(
+                    ast::HexAlternative { alternatives },
+                )));
+            }
+            token => expanded.push(token.clone()),
+        }
+    }
+
+    Ok(ast::HexTokens { tokens: expanded })
+}
+
 pub(in crate::compiler) fn regexp_pattern_from_ast<'src>(
     ctx: &mut CompileContext,
     pattern: &ast::RegexpPattern<'src>,
@@ -193,20 +441,61 @@ pub(in crate::compiler) fn regexp_pattern_from_ast<'src>(
     // matches that start at the same offset are found while scanning backwards
     // (right-to-left). However, if the regexp contains a mix of greedy and
     // non-greedy repetitions the decision becomes impossible.
+    let rewritten_src =
+        ctx.pattern_rewriter.and_then(|rewriter| {
+            match rewriter(&PatternSource::Regexp(
+                pattern.regexp.src.to_string(),
+            )) {
+                PatternSource::Regexp(src) if src != pattern.regexp.src => {
+                    Some(src)
+                }
+                _ => None,
+            }
+        });
+
+    let rewritten = rewritten_src.as_deref().map(|src| ast::Regexp {
+        span: pattern.regexp.span,
+        literal: pattern.regexp.literal,
+        src,
+        case_insensitive: pattern.regexp.case_insensitive,
+        dot_matches_new_line: pattern.regexp.dot_matches_new_line,
+    });
+
     let hir = re::parser::Parser::new()
         .force_case_insensitive(flags.contains(PatternFlags::Nocase))
         .allow_mixed_greediness(false)
         .relaxed_re_syntax(ctx.relaxed_re_syntax)
-        .parse(&pattern.regexp)
-        .map_err(|err| {
-            re_error_to_compile_error(ctx.report_builder, &pattern.regexp, err)
+        .parse(rewritten.as_ref().unwrap_or(&pattern.regexp))
+        .map_err(|err| match &rewritten {
+            // The spans in errors produced by the rewritten regexp don't
+            // correspond to the source code, so the error refers to the
+            // original regexp as a whole.
+            Some(rewritten) => invalid_rewritten_pattern(
+                ctx.report_builder,
+                match err {
+                    Error::SyntaxError { msg, .. } => msg,
+                    Error::MixedGreediness { .. } => {
+                        "mixing greedy and non-greedy quantifiers".to_string()
+                    }
+                },
+                pattern.regexp.span,
+                format!("/{}/", rewritten.src),
+            ),
+            None => Box::new(re_error_to_compile_error(
+                ctx.report_builder,
+                &pattern.regexp,
+                err,
+            )),
         })?;
 
+    check_regexp_size(ctx, &hir, pattern.regexp.span)?;
+
     // TODO: raise warning when .* used, propose using the non-greedy
     // variant .*?
 
     Ok(PatternInRule {
         identifier: pattern.identifier.name,
+        weight: pattern_weight(&pattern.modifiers),
         pattern: Pattern::Regexp(RegexpPattern {
             flags,
             hir,
@@ -219,6 +508,27 @@ pub(in crate::compiler) fn regexp_pattern_from_ast<'src>(
 pub(in crate::compiler) fn expr_from_ast(
     ctx: &mut CompileContext,
     expr: &ast::Expr,
+) -> Result<Expr, Box<CompileError>> {
+    if let Some(max_depth) = ctx.max_condition_depth {
+        if ctx.depth >= max_depth {
+            return Err(Box::new(CompileError::condition_too_deep(
+                ctx.report_builder,
+                max_depth,
+                expr.span(),
+            )));
+        }
+    }
+
+    ctx.depth += 1;
+    let result = expr_from_ast_impl(ctx, expr);
+    ctx.depth -= 1;
+
+    result
+}
+
+fn expr_from_ast_impl(
+    ctx: &mut CompileContext,
+    expr: &ast::Expr,
 ) -> Result<Expr, Box<CompileError>> {
     match expr {
         ast::Expr::Entrypoint { span } => {
@@ -248,12 +558,14 @@ pub(in crate::compiler) fn expr_from_ast(
             TypeValue::const_string_from(literal.value.as_bytes()))),
 
         ast::Expr::Regexp(regexp) => {
-            re::parser::Parser::new()
+            let hir = re::parser::Parser::new()
                 .relaxed_re_syntax(ctx.relaxed_re_syntax)
                 .parse(regexp.as_ref())
                 .map_err(|err| { re_error_to_compile_error(ctx.report_builder, regexp, err)
             })?;
 
+            check_regexp_size(ctx, &hir, regexp.span)?;
+
             Ok(Expr::Const(TypeValue::Regexp(Some(Regexp::new(
                     regexp.literal,
                 ))),
@@ -386,6 +698,19 @@ pub(in crate::compiler) fn expr_from_ast(
 
             let symbol = symbol.unwrap();
 
+            // Identifiers that refer to a field in the root structure, and
+            // are not inside some other structure, are either module names
+            // or global variables.
+            if current_symbol_table.is_none()
+                && matches!(symbol.kind(), SymbolKind::Field(_, true))
+                && BUILTIN_MODULES.contains_key(ident.name)
+            {
+                let module = ctx.ident_pool.get_or_intern(ident.name);
+                if !ctx.used_modules.contains(&module) {
+                    ctx.used_modules.push(module);
+                }
+            }
+
             // Return error if a global rule depends on a non-global rule. This
             // is an error because global rules are evaluated before non-global
             // rules, even if the global rule appears after the non-global one
@@ -395,6 +720,9 @@ pub(in crate::compiler) fn expr_from_ast(
             // A global rule can depend on another global rule. And non-global
             // rules can depend both on global rules and non-global ones.
             if let SymbolKind::Rule(rule_id) = symbol.kind() {
+                if !ctx.used_rules.contains(rule_id) {
+                    ctx.used_rules.push(*rule_id);
+                }
                 let current_rule = ctx.get_current_rule();
                 let used_rule = ctx.get_rule(*rule_id);
                 if current_rule.is_global && !used_rule.is_global {
@@ -431,9 +759,11 @@ pub(in crate::compiler) fn expr_from_ast(
             // know which is the PatternId because `$` refers to a different
             // pattern on each iteration. In those cases the symbol table must
             // contain an entry for `$`, corresponding to the variable that
-            // holds the current PatternId for the loop.
+            // holds the current PatternId for the loop. Outside of a loop `$`
+            // refers to the only anonymous pattern declared by the rule, and
+            // it's handled like any other pattern identifier.
             match p.identifier.name {
-                "$" => Ok(Expr::PatternMatchVar {
+                "$" if ctx.symbol_table.lookup("$").is_some() => Ok(Expr::PatternMatchVar {
                     symbol: ctx.symbol_table.lookup("$").unwrap(),
                     anchor,
                 }),
@@ -488,6 +818,16 @@ pub(in crate::compiler) fn expr_from_ast(
             }
         }
 
+        ast::Expr::WeightSum(w) => {
+            let patterns = pattern_set_from_ast(ctx, &w.pattern_set)?
+                .into_iter()
+                .map(|pattern| {
+                    (pattern, ctx.current_rule_patterns[pattern.as_usize()].weight())
+                })
+                .collect();
+            Ok(Expr::WeightSum { patterns })
+        }
+
         ast::Expr::PatternOffset(p) => {
             // If the identifier is just `@` we are inside a loop and we don't
             // know which is the PatternId because `@` refers to a different
@@ -511,13 +851,11 @@ pub(in crate::compiler) fn expr_from_ast(
                 // Cases where the identifier is not `@`.
                 (_, Some(index)) => {
                     ctx.get_pattern_mut(p.name).make_non_anchorable();
+                    let index = integer_in_range_from_ast(ctx, index, 1..=i64::MAX)?;
+                    warn_if_suspicious_match_index(ctx, p, &index);
                     Ok(Expr::PatternOffset {
                         pattern: ctx.get_pattern_index(p.name),
-                        index: Some(Box::new(integer_in_range_from_ast(
-                            ctx,
-                            index,
-                            1..=i64::MAX,
-                        )?)),
+                        index: Some(Box::new(index)),
                     })
                 }
                 (_, None) => {
@@ -553,13 +891,11 @@ pub(in crate::compiler) fn expr_from_ast(
                 // Cases where the identifier is not `!`.
                 (_, Some(index)) => {
                     ctx.get_pattern_mut(p.name).make_non_anchorable();
+                    let index = integer_in_range_from_ast(ctx, index, 1..=i64::MAX)?;
+                    warn_if_suspicious_match_index(ctx, p, &index);
                     Ok(Expr::PatternLength {
                         pattern: ctx.get_pattern_index(p.name),
-                        index: Some(Box::new(integer_in_range_from_ast(
-                            ctx,
-                            index,
-                            1..=i64::MAX,
-                        )?)),
+                        index: Some(Box::new(index)),
                     })
                 }
                 (_, None) => {
@@ -638,6 +974,16 @@ pub(in crate::compiler) fn bool_expr_from_ast(
     ast: &ast::Expr,
 ) -> Result<Expr, Box<CompileError>> {
     let expr = expr_from_ast(ctx, ast)?;
+    // Ranges can't be casted to boolean, they are valid only as the right
+    // operand of `in`.
+    if matches!(expr.ty(), Type::Range) {
+        check_type(
+            ctx,
+            expr.ty(),
+            ast.span(),
+            &[Type::Bool, Type::Integer, Type::Float, Type::String],
+        )?;
+    }
     warn_if_not_bool(ctx, expr.ty(), ast.span());
     Ok(expr)
 }
@@ -647,10 +993,10 @@ fn of_expr_from_ast(
     of: &ast::Of,
 ) -> Result<Expr, Box<CompileError>> {
     let quantifier = quantifier_from_ast(ctx, &of.quantifier)?;
-    // Create new stack frame with 5 slots:
+    // Create new stack frame with 6 slots:
     //   1 slot for the loop variable, a bool in this case.
-    //   4 up to slots used for loop control variables (see: emit::emit_for)
-    let stack_frame = ctx.vars.new_frame(5);
+    //   5 up to slots used for loop control variables (see: emit::emit_for)
+    let stack_frame = ctx.vars.new_frame(6);
 
     let (items, num_items) = match &of.items {
         // `x of (<boolean expr>, <boolean expr>, ...)`
@@ -675,9 +1021,10 @@ fn of_expr_from_ast(
         }
     };
 
-    // If the quantifier expression is greater than the number of items,
-    // the `of` expression is always false.
-    if let Quantifier::Expr(expr) = &quantifier {
+    // If the quantifier expression (or the lower bound of a quantifier like
+    // `2 to 4`) is greater than the number of items, the `of` expression is
+    // always false.
+    if let Quantifier::Expr(expr) | Quantifier::Range(expr, _) = &quantifier {
         if let TypeValue::Integer(Value::Const(value)) = expr.type_value() {
             if value > num_items.try_into().unwrap() {
                 ctx.warnings.add(|| Warning::invariant_boolean_expression(
@@ -715,11 +1062,14 @@ fn of_expr_from_ast(
             // warning.
             Quantifier::All { .. } => num_items > 1,
             // `<expr> of <items> at <expr>: the warning is raised if <expr> is
-            // 2 or more.
-            Quantifier::Expr(expr) => match expr.type_value() {
-                TypeValue::Integer(Value::Const(value)) => value >= 2,
-                _ => false,
-            },
+            // 2 or more. The same applies to the lower bound in
+            // `<expr> to <expr> of <items> at <expr>`.
+            Quantifier::Expr(expr) | Quantifier::Range(expr, _) => {
+                match expr.type_value() {
+                    TypeValue::Integer(Value::Const(value)) => value >= 2,
+                    _ => false,
+                }
+            }
             // `<expr>% of <items> at <expr>: the warning is raised if the
             // <expr> percent of the items is 2 or more.
             Quantifier::Percentage(expr) => match expr.type_value() {
@@ -755,10 +1105,10 @@ fn for_of_expr_from_ast(
 ) -> Result<Expr, Box<CompileError>> {
     let quantifier = quantifier_from_ast(ctx, &for_of.quantifier)?;
     let pattern_set = pattern_set_from_ast(ctx, &for_of.pattern_set)?;
-    // Create new stack frame with 5 slots:
+    // Create new stack frame with 6 slots:
     //   1 slot for the loop variable, a pattern ID in this case
-    //   4 up to slots used for loop control variables (see: emit::emit_for)
-    let mut stack_frame = ctx.vars.new_frame(5);
+    //   5 up to slots used for loop control variables (see: emit::emit_for)
+    let mut stack_frame = ctx.vars.new_frame(6);
     let next_pattern_id = stack_frame.new_var(Type::Integer);
     let mut loop_vars = SymbolTable::new();
 
@@ -793,6 +1143,38 @@ fn for_in_expr_from_ast(
     let quantifier = quantifier_from_ast(ctx, &for_in.quantifier)?;
     let iterable = iterable_from_ast(ctx, &for_in.iterable)?;
 
+    // The number of iterations can be checked only when the bounds of the
+    // range are known at compile time. In nested loops the number of
+    // iterations is multiplied by the number of iterations of the loops
+    // that contain this one.
+    let loop_iterations = ctx.loop_iterations;
+
+    if let Iterable::Range(range) = &iterable {
+        if let (
+            TypeValue::Integer(Value::Const(lower_bound)),
+            TypeValue::Integer(Value::Const(upper_bound)),
+        ) = (range.lower_bound.type_value(), range.upper_bound.type_value())
+        {
+            let iterations = upper_bound
+                .abs_diff(lower_bound)
+                .saturating_add(1)
+                .saturating_mul(loop_iterations);
+
+            if let Some(max_iterations) = ctx.max_loop_iterations {
+                if iterations > max_iterations as u64 {
+                    return Err(Box::new(CompileError::too_many_iterations(
+                        ctx.report_builder,
+                        iterations,
+                        max_iterations,
+                        for_in.iterable.span(),
+                    )));
+                }
+            }
+
+            ctx.loop_iterations = iterations;
+        }
+    }
+
     let expected_vars = match &iterable {
         Iterable::Range(_) => vec![TypeValue::Integer(Value::Unknown)],
         Iterable::ExprTuple(expressions) => {
@@ -841,11 +1223,11 @@ fn for_in_expr_from_ast(
         )));
     }
 
-    // Create stack frame with capacity for the loop variables, plus 4
+    // Create stack frame with capacity for the loop variables, plus 5
     // temporary variables used for controlling the loop (see emit_for),
     // plus one additional variable used in loops over arrays and maps
     // (see emit_for_in_array and emit_for_in_map).
-    let mut stack_frame = ctx.vars.new_frame(loop_vars.len() as i32 + 5);
+    let mut stack_frame = ctx.vars.new_frame(loop_vars.len() as i32 + 6);
     let mut symbols = SymbolTable::new();
     let mut variables = Vec::new();
 
@@ -863,7 +1245,28 @@ fn for_in_expr_from_ast(
     // Put the loop variables into scope.
     ctx.symbol_table.push(Rc::new(symbols));
 
-    let condition = bool_expr_from_ast(ctx, &for_in.condition)?;
+    let data_reads = ctx.data_reads;
+    let condition = bool_expr_from_ast(ctx, &for_in.condition);
+
+    ctx.loop_iterations = loop_iterations;
+
+    let condition = condition?;
+
+    // A loop over a range that grows with the file size, that reads from
+    // the scanned data in each iteration, can be very slow with large files.
+    if let ast::Iterable::Range(range) = &for_in.iterable {
+        if ctx.data_reads > data_reads
+            && depends_on_filesize(&range.upper_bound)
+        {
+            ctx.warnings.add(|| {
+                Warning::expensive_loop(
+                    ctx.report_builder,
+                    range.span,
+                    Some("the loop reads from the scanned data in every iteration, which can be very slow with large files".to_string()),
+                )
+            });
+        }
+    }
 
     // Leaving the condition's scope. Remove loop variables.
     ctx.symbol_table.pop();
@@ -944,6 +1347,11 @@ fn anchor_from_ast(
         Some(ast::MatchAnchor::In(in_)) => {
             Ok(MatchAnchor::In(range_from_ast(ctx, &in_.range)?))
         }
+        Some(ast::MatchAnchor::InExpr(in_)) => {
+            let expr = expr_from_ast(ctx, &in_.expr)?;
+            check_type(ctx, expr.ty(), in_.expr.span(), &[Type::Range])?;
+            Ok(MatchAnchor::InExpr(Box::new(expr)))
+        }
         None => Ok(MatchAnchor::None),
     }
 }
@@ -1046,6 +1454,27 @@ fn quantifier_from_ast(
         ast::Quantifier::Expr(expr) => {
             Ok(Quantifier::Expr(non_negative_integer_from_ast(ctx, expr)?))
         }
+        ast::Quantifier::Range { span, min, max } => {
+            let min = non_negative_integer_from_ast(ctx, min)?;
+            let max = non_negative_integer_from_ast(ctx, max)?;
+
+            // If both bounds are known at compile time, make sure that
+            // min <= max.
+            if let (
+                TypeValue::Integer(Value::Const(min)),
+                TypeValue::Integer(Value::Const(max)),
+            ) = (min.type_value(), max.type_value())
+            {
+                if min > max {
+                    return Err(Box::new(CompileError::invalid_range(
+                        ctx.report_builder,
+                        *span,
+                    )));
+                }
+            }
+
+            Ok(Quantifier::Range(min, max))
+        }
     }
 }
 
@@ -1117,6 +1546,22 @@ fn func_call_from_ast(
     ctx: &mut CompileContext,
     func_call: &ast::FuncCall,
 ) -> Result<Expr, Box<CompileError>> {
+    // `content_icontains` is not an ordinary function, unless some other
+    // symbol with the same name exists.
+    if let ast::Expr::Ident(ident) = &func_call.callable {
+        if ident.name == "content_icontains"
+            && ctx.current_symbol_table.is_none()
+            && ctx.symbol_table.lookup(ident.name).is_none()
+        {
+            return content_icontains_from_ast(ctx, func_call);
+        }
+        if ctx.current_symbol_table.is_none()
+            && is_data_read_function(ident.name)
+        {
+            ctx.data_reads += 1;
+        }
+    }
+
     let callable = expr_from_ast(ctx, &func_call.callable)?;
     let type_value = callable.type_value();
 
@@ -1187,6 +1632,45 @@ fn func_call_from_ast(
 
     let (signature_index, type_value) = matching_signature.unwrap();
 
+    // The default value passed to functions like `uint16(offset, default)`
+    // must be representable with the type returned by the function.
+    if let (ast::Expr::Ident(ident), [_, default]) =
+        (&func_call.callable, args.as_slice())
+    {
+        if let Some(range) = data_read_function_range(ident.name) {
+            if let TypeValue::Integer(Value::Const(value)) =
+                default.type_value()
+            {
+                if !range.contains(&value) {
+                    return Err(Box::new(CompileError::number_out_of_range(
+                        ctx.report_builder,
+                        *range.start(),
+                        *range.end(),
+                        func_call.args[1].span(),
+                    )));
+                }
+            }
+        }
+    }
+
+    // The name of the function is the mangled name of any of its
+    // signatures, without the part that describes the arguments and
+    // result.
+    let func_name = func.signatures()[signature_index]
+        .mangled_name
+        .as_str()
+        .split('@')
+        .next()
+        .unwrap();
+
+    if ctx.disabled_functions.iter().any(|f| f == func_name) {
+        return Err(Box::new(CompileError::function_disabled(
+            ctx.report_builder,
+            func_name.to_string(),
+            func_call.span(),
+        )));
+    }
+
     Ok(Expr::FuncCall(Box::new(FuncCall {
         callable,
         type_value,
@@ -1195,6 +1679,63 @@ fn func_call_from_ast(
     })))
 }
 
+/// Converts a call to `content_icontains("...")` into a match of a
+/// synthetic `nocase` pattern, which is searched for in the scanned data
+/// like any other pattern.
+fn content_icontains_from_ast(
+    ctx: &mut CompileContext,
+    func_call: &ast::FuncCall,
+) -> Result<Expr, Box<CompileError>> {
+    let wrong_arguments = |ctx: &mut CompileContext| {
+        Box::new(CompileError::wrong_arguments(
+            ctx.report_builder,
+            func_call.args_span,
+            Some(
+                "`content_icontains` accepts a single constant string"
+                    .to_string(),
+            ),
+        ))
+    };
+
+    let [arg] = func_call.args.as_slice() else {
+        return Err(wrong_arguments(ctx));
+    };
+
+    let text = match expr_from_ast(ctx, arg)?.type_value() {
+        TypeValue::String(Value::Const(text)) => text,
+        _ => return Err(wrong_arguments(ctx)),
+    };
+
+    // Every file contains the empty string.
+    if text.is_empty() {
+        return Ok(Expr::Const(TypeValue::const_bool_from(true)));
+    }
+
+    let mut flags = PatternFlagSet::none();
+
+    flags.set(PatternFlags::Ascii);
+    flags.set(PatternFlags::Nocase);
+
+    let pattern = PatternInRule {
+        identifier: "$",
+        weight: 1,
+        pattern: Pattern::Literal(LiteralPattern {
+            flags,
+            xor_range: None,
+            base64_alphabet: None,
+            base64wide_alphabet: None,
+            anchored_at: None,
+            text: text.as_ref().clone(),
+        }),
+    };
+
+    let index = ctx.current_rule_patterns.len() + ctx.synthetic_patterns.len();
+
+    ctx.synthetic_patterns.push(pattern);
+
+    Ok(Expr::PatternMatch { pattern: index.into(), anchor: MatchAnchor::None })
+}
+
 fn matches_expr_from_ast(
     ctx: &mut CompileContext,
     expr: &ast::BinaryExpr,
@@ -1276,7 +1817,34 @@ fn check_operands(
     Ok(())
 }
 
-fn re_error_to_compile_error(
+/// Returns an error if the regexp or hex pattern represented by `hir` is
+/// larger than the maximum size set with
+/// [`crate::Compiler::max_regexp_size`]. See [`re::hir::Hir::expanded_size`]
+/// for details about how the size is computed.
+fn check_regexp_size(
+    ctx: &CompileContext,
+    hir: &re::hir::Hir,
+    span: Span,
+) -> Result<(), Box<CompileError>> {
+    let Some(max_size) = ctx.max_regexp_size else {
+        return Ok(());
+    };
+
+    let size = hir.expanded_size();
+
+    if size > max_size {
+        return Err(Box::new(CompileError::regexp_too_large(
+            ctx.report_builder,
+            size,
+            max_size,
+            span,
+        )));
+    }
+
+    Ok(())
+}
+
+pub(in crate::compiler) fn re_error_to_compile_error(
     report_builder: &ReportBuilder,
     regexp: &ast::Regexp,
     err: re::parser::Error,
@@ -1308,6 +1876,120 @@ fn re_error_to_compile_error(
     }
 }
 
+/// Returns the minimum number of matches that `condition` guarantees for
+/// each pattern.
+///
+/// Only the top-level conjuncts of the condition (i.e: the operands of the
+/// outermost `and` operators) with the form `#a >= N`, `#a > N` or
+/// `#a == N` are taken into account. Keys in the returned map are pattern
+/// identifiers without the prefix (e.g: `a` for `#a`).
+pub(in crate::compiler) fn min_matches<'src>(
+    condition: &ast::Expr<'src>,
+) -> FxHashMap<&'src str, i64> {
+    let mut result = FxHashMap::default();
+
+    let conjuncts = match condition {
+        ast::Expr::And(operands) => operands.operands.iter().collect(),
+        expr => vec![expr],
+    };
+
+    for conjunct in conjuncts {
+        let (expr, min) = match conjunct {
+            ast::Expr::Ge(expr) => (expr, 0),
+            ast::Expr::Gt(expr) => (expr, 1),
+            ast::Expr::Eq(expr) => (expr, 0),
+            _ => continue,
+        };
+        if let (
+            ast::Expr::PatternCount(count),
+            ast::Expr::LiteralInteger(value),
+        ) = (&expr.lhs, &expr.rhs)
+        {
+            if count.range.is_none() && count.name != "#" {
+                let min = value.value.saturating_add(min);
+                let entry = result.entry(&count.name[1..]).or_insert(min);
+                *entry = (*entry).max(min);
+            }
+        }
+    }
+
+    result
+}
+
+/// Returns true if `name` is one of the functions that read an integer
+/// from the scanned data (e.g: `uint8`, `int32be`).
+fn is_data_read_function(name: &str) -> bool {
+    matches!(
+        name.strip_prefix('u').unwrap_or(name),
+        "int8" | "int16" | "int32" | "int8be" | "int16be" | "int32be"
+    )
+}
+
+/// If `name` is one of the functions that read an integer from the scanned
+/// data, returns the range of the values returned by the function.
+fn data_read_function_range(name: &str) -> Option<RangeInclusive<i64>> {
+    match name {
+        "uint8" | "uint8be" => Some(u8::MIN.into()..=u8::MAX.into()),
+        "uint16" | "uint16be" => Some(u16::MIN.into()..=u16::MAX.into()),
+        "uint32" | "uint32be" => Some(u32::MIN.into()..=u32::MAX.into()),
+        "int8" | "int8be" => Some(i8::MIN.into()..=i8::MAX.into()),
+        "int16" | "int16be" => Some(i16::MIN.into()..=i16::MAX.into()),
+        "int32" | "int32be" => Some(i32::MIN.into()..=i32::MAX.into()),
+        _ => None,
+    }
+}
+
+/// Returns true if `expr` is `filesize`, or an arithmetic expression where
+/// `filesize` is one of the operands.
+fn depends_on_filesize(expr: &ast::Expr) -> bool {
+    match expr {
+        ast::Expr::Filesize { .. } => true,
+        ast::Expr::Add(expr)
+        | ast::Expr::Sub(expr)
+        | ast::Expr::Mul(expr)
+        | ast::Expr::Div(expr) => {
+            expr.operands.iter().any(depends_on_filesize)
+        }
+        _ => false,
+    }
+}
+
+/// Raises a warning if `index` is a constant larger than the number of
+/// matches that the condition guarantees for the pattern in `@a[index]`
+/// or `!a[index]`.
+///
+/// Index 1 is not reported even if the pattern is not guaranteed to match,
+/// as `@a[1] < 100` is a common idiom for "`$a` matches at some offset
+/// lower than 100".
+fn warn_if_suspicious_match_index(
+    ctx: &mut CompileContext,
+    ident: &ast::IdentWithIndex,
+    index: &Expr,
+) {
+    let index = match index.type_value() {
+        TypeValue::Integer(Value::Const(index)) if index > 1 => index,
+        _ => return,
+    };
+
+    let pattern = &ident.name[1..];
+    let min = ctx.min_matches.get(pattern).cloned().unwrap_or(0);
+
+    if index > min {
+        ctx.warnings.add(|| {
+            Warning::suspicious_match_index(
+                ctx.report_builder,
+                format!("${}", pattern),
+                index,
+                ident.span,
+                Some(format!(
+                    "use `#{} >= {}` for making sure that there are enough matches",
+                    pattern, index
+                )),
+            )
+        });
+    }
+}
+
 /// Produce a warning if the expression is not boolean.
 pub(in crate::compiler) fn warn_if_not_bool(
     ctx: &mut CompileContext,
@@ -1400,11 +2082,11 @@ macro_rules! gen_binary_op {
             )?;
 
             let check_fn:
-                Option<fn(&mut CompileContext, &Expr, &Expr, Span, Span) -> Result<(), Box<CompileError>>>
+                Option<fn(&mut CompileContext, &Expr, &Expr, &ast::Expr, &ast::Expr) -> Result<(), Box<CompileError>>>
                 = $check_fn;
 
             if let Some(check_fn) = check_fn {
-                check_fn(ctx, &lhs, &rhs, lhs_span, rhs_span)?;
+                check_fn(ctx, &lhs, &rhs, &expr.lhs, &expr.rhs)?;
             }
 
             let expr = Expr::$variant { lhs, rhs };
@@ -1618,19 +2300,9 @@ gen_binary_op!(
     Shl,
     Type::Integer,
     Type::Integer,
-    Some(|ctx, _lhs, rhs, _lhs_span, rhs_span| {
-        if let TypeValue::Integer(Value::Const(value)) = rhs.type_value() {
-            if value < 0 {
-                return Err(Box::new(
-                    CompileError::unexpected_negative_number(
-                        ctx.report_builder,
-                        rhs_span,
-                    ),
-                ));
-            }
-        }
-        Ok(())
-    })
+    Some(|ctx, _lhs, rhs, _lhs_ast, rhs_ast| check_shift_amount(
+        ctx, rhs, rhs_ast
+    ))
 );
 
 gen_binary_op!(
@@ -1638,20 +2310,42 @@ gen_binary_op!(
     Shr,
     Type::Integer,
     Type::Integer,
-    Some(|ctx, _lhs, rhs, _lhs_span, rhs_span| {
-        if let TypeValue::Integer(Value::Const(value)) = rhs.type_value() {
-            if value < 0 {
-                return Err(Box::new(
-                    CompileError::unexpected_negative_number(
+    Some(|ctx, _lhs, rhs, _lhs_ast, rhs_ast| check_shift_amount(
+        ctx, rhs, rhs_ast
+    ))
+);
+
+/// Makes sure that the number of bits in a shift operation is not negative.
+///
+/// Hex, octal and binary literals larger than `i64::MAX`, like
+/// `0xFFFFFFFFFFFFFFFF`, are negative numbers in two's complement. When used
+/// as the number of bits to shift they are reported as out of range instead
+/// of negative, as that's how they were written.
+fn check_shift_amount(
+    ctx: &mut CompileContext,
+    rhs: &Expr,
+    rhs_ast: &ast::Expr,
+) -> Result<(), Box<CompileError>> {
+    if let TypeValue::Integer(Value::Const(value)) = rhs.type_value() {
+        if value < 0 {
+            if let ast::Expr::LiteralInteger(literal) = rhs_ast {
+                if !literal.literal.starts_with('-') {
+                    return Err(Box::new(CompileError::number_out_of_range(
                         ctx.report_builder,
-                        rhs_span,
-                    ),
-                ));
+                        0,
+                        i64::MAX,
+                        rhs_ast.span(),
+                    )));
+                }
             }
+            return Err(Box::new(CompileError::unexpected_negative_number(
+                ctx.report_builder,
+                rhs_ast.span(),
+            )));
         }
-        Ok(())
-    })
-);
+    }
+    Ok(())
+}
 
 gen_unary_op!(bitwise_not_expr_from_ast, BitwiseNot, Type::Integer, None);
 
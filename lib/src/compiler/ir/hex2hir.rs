@@ -5,10 +5,12 @@ use yara_x_parser::ast;
 
 use crate::compiler::ByteMaskCombinator;
 
+/// Converts the tokens of a hex pattern into a HIR. References to fragments
+/// must be expanded before calling this function.
 pub(in crate::compiler) fn hex_pattern_hir_from_ast(
-    pattern: &ast::HexPattern,
+    tokens: &ast::HexTokens,
 ) -> hir::Hir {
-    hex_tokens_hir_from_ast(&pattern.tokens)
+    hex_tokens_hir_from_ast(tokens)
 }
 
 fn hex_tokens_hir_from_ast(tokens: &ast::HexTokens) -> hir::Hir {
@@ -87,6 +89,9 @@ fn hex_tokens_hir_from_ast(tokens: &ast::HexTokens) -> hir::Hir {
                     sub: Box::new(hir::Hir::dot(hir::Dot::AnyByte)),
                 }))
             }
+            ast::HexToken::Fragment(_) => {
+                unreachable!("fragments must be expanded before")
+            }
         }
     }
 
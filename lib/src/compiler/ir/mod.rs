@@ -29,11 +29,12 @@ allows using the same regexp engine for matching both types of patterns.
 [Hir]: regex_syntax::hir::Hir
 */
 
+use std::cmp::Ordering;
 use std::hash::Hash;
 use std::ops::RangeInclusive;
 
 use bitmask::bitmask;
-use bstr::BString;
+use bstr::{BString, ByteSlice};
 use serde::{Deserialize, Serialize};
 
 use crate::compiler::context::{CompileContext, Var, VarStackFrame};
@@ -41,7 +42,9 @@ use crate::symbols::Symbol;
 use crate::types::{Type, TypeValue, Value};
 
 pub(in crate::compiler) use ast2ir::bool_expr_from_ast;
+pub(in crate::compiler) use ast2ir::min_matches;
 pub(in crate::compiler) use ast2ir::patterns_from_ast;
+pub(in crate::compiler) use ast2ir::re_error_to_compile_error;
 use yara_x_parser::ast::Span;
 
 use crate::{re, CompileError};
@@ -87,6 +90,7 @@ bitmask! {
 pub(in crate::compiler) struct PatternInRule<'src> {
     identifier: &'src str,
     pattern: Pattern,
+    weight: i64,
 }
 
 impl<'src> PatternInRule<'src> {
@@ -95,6 +99,13 @@ impl<'src> PatternInRule<'src> {
         self.identifier
     }
 
+    /// Weight of the pattern, as indicated by the `weight` modifier. The
+    /// default weight is 1.
+    #[inline]
+    pub fn weight(&self) -> i64 {
+        self.weight
+    }
+
     #[inline]
     pub fn into_pattern(self) -> Pattern {
         self.pattern
@@ -509,6 +520,12 @@ pub(in crate::compiler) enum Expr {
         index: Option<Box<Expr>>,
     },
 
+    /// Sum of the weights of the matching patterns in a set (e.g.
+    /// `weightsum of them`). Each item contains the pattern and its weight.
+    WeightSum {
+        patterns: Vec<(PatternIdx, i64)>,
+    },
+
     /// Function call.
     FuncCall(Box<FuncCall>),
 
@@ -587,10 +604,11 @@ pub(in crate::compiler) enum Quantifier {
     Any,
     Percentage(Expr),
     Expr(Expr),
+    Range(Expr, Expr),
 }
 
 /// In expressions like `$a at 0` and `$b in (0..10)`, this type represents the
-/// anchor (e.g. `at <expr>`, `in <range>`).
+/// anchor (e.g. `at <expr>`, `in <range>`, `in <expr>`).
 ///
 /// The anchor is the part of the expression that restricts the offset range
 /// where the match can occur.
@@ -600,6 +618,9 @@ pub(in crate::compiler) enum MatchAnchor {
     None,
     At(Box<Expr>),
     In(Range),
+    /// The range is given by an expression of type range, like a call to
+    /// `pe.section_range(0)`.
+    InExpr(Box<Expr>),
 }
 
 impl MatchAnchor {
@@ -695,6 +716,7 @@ impl Expr {
             | Expr::PatternOffsetVar { .. }
             | Expr::PatternLength { .. }
             | Expr::PatternLengthVar { .. }
+            | Expr::WeightSum { .. }
             | Expr::Mod { .. }
             | Expr::BitwiseNot { .. }
             | Expr::BitwiseAnd { .. }
@@ -765,6 +787,7 @@ impl Expr {
             | Expr::PatternOffsetVar { .. }
             | Expr::PatternLength { .. }
             | Expr::PatternLengthVar { .. }
+            | Expr::WeightSum { .. }
             | Expr::Mod { .. }
             | Expr::BitwiseNot { .. }
             | Expr::BitwiseAnd { .. }
@@ -863,10 +886,89 @@ impl Expr {
 
                 Self::fold_arithmetic(ctx, span, operands, |acc, x| acc * x)
             }
+            Expr::Eq { lhs, rhs } => Ok(Self::fold_comparison(
+                *lhs,
+                *rhs,
+                Ordering::is_eq,
+                |lhs, rhs| Expr::Eq { lhs, rhs },
+            )),
+            Expr::Ne { lhs, rhs } => Ok(Self::fold_comparison(
+                *lhs,
+                *rhs,
+                Ordering::is_ne,
+                |lhs, rhs| Expr::Ne { lhs, rhs },
+            )),
+            Expr::Lt { lhs, rhs } => Ok(Self::fold_comparison(
+                *lhs,
+                *rhs,
+                Ordering::is_lt,
+                |lhs, rhs| Expr::Lt { lhs, rhs },
+            )),
+            Expr::Gt { lhs, rhs } => Ok(Self::fold_comparison(
+                *lhs,
+                *rhs,
+                Ordering::is_gt,
+                |lhs, rhs| Expr::Gt { lhs, rhs },
+            )),
+            Expr::Le { lhs, rhs } => Ok(Self::fold_comparison(
+                *lhs,
+                *rhs,
+                Ordering::is_le,
+                |lhs, rhs| Expr::Le { lhs, rhs },
+            )),
+            Expr::Ge { lhs, rhs } => Ok(Self::fold_comparison(
+                *lhs,
+                *rhs,
+                Ordering::is_ge,
+                |lhs, rhs| Expr::Ge { lhs, rhs },
+            )),
             _ => Ok(self),
         }
     }
 
+    /// Folds a comparison between two expressions.
+    ///
+    /// If both operands are constant, they are compared and `f` is used for
+    /// translating the resulting [`Ordering`] into the constant boolean
+    /// the comparison is replaced with. Otherwise, the comparison is rebuilt
+    /// with `rebuild`.
+    fn fold_comparison<F, R>(lhs: Expr, rhs: Expr, f: F, rebuild: R) -> Self
+    where
+        F: FnOnce(Ordering) -> bool,
+        R: FnOnce(Box<Expr>, Box<Expr>) -> Expr,
+    {
+        let ordering = match (lhs.type_value(), rhs.type_value()) {
+            (
+                TypeValue::Integer(Value::Const(lhs)),
+                TypeValue::Integer(Value::Const(rhs)),
+            ) => Some(lhs.cmp(&rhs)),
+            (
+                TypeValue::Integer(Value::Const(lhs)),
+                TypeValue::Float(Value::Const(rhs)),
+            ) => (lhs as f64).partial_cmp(&rhs),
+            (
+                TypeValue::Float(Value::Const(lhs)),
+                TypeValue::Integer(Value::Const(rhs)),
+            ) => lhs.partial_cmp(&(rhs as f64)),
+            (
+                TypeValue::Float(Value::Const(lhs)),
+                TypeValue::Float(Value::Const(rhs)),
+            ) => lhs.partial_cmp(&rhs),
+            (
+                TypeValue::String(Value::Const(lhs)),
+                TypeValue::String(Value::Const(rhs)),
+            ) => Some(lhs.cmp(&rhs)),
+            _ => None,
+        };
+
+        match ordering {
+            Some(ordering) => {
+                Expr::Const(TypeValue::const_bool_from(f(ordering)))
+            }
+            None => rebuild(Box::new(lhs), Box::new(rhs)),
+        }
+    }
+
     pub fn fold_arithmetic<F>(
         ctx: &mut CompileContext,
         span: Span,
@@ -909,3 +1011,96 @@ impl Expr {
         }
     }
 }
+
+/// Finds patterns that are redundant in a rule's condition.
+///
+/// A pattern is redundant when the condition is a disjunction of patterns,
+/// like `$a or $b` or `any of them`, and every match of the pattern implies
+/// a match of some other pattern in the disjunction. For example, if `$a` is
+/// `"malware.exe"` and `$b` is `"malware"`, `$a` can't match without `$b`
+/// also matching, which means that `$a` doesn't change the result of
+/// `$a or $b`.
+///
+/// Only literal patterns with the same modifiers are considered, and the
+/// modifiers must not be `xor`, `base64`, `base64wide` or `fullword`, as
+/// these modifiers break the relationship between a pattern and the
+/// patterns it contains. Returns a vector of `(redundant, dominant)`
+/// tuples, where `dominant` is the pattern that makes `redundant`
+/// unnecessary.
+///
+/// The analysis is limited to the patterns of a single rule. Rules are
+/// evaluated independently, so a pattern declared in some other rule of the
+/// same namespace never makes a pattern redundant, even if it's contained
+/// in it. Redundant patterns are reported with a warning, but they are
+/// still searched for, as their matches are part of the scan results
+/// regardless of whether they change the result of the condition. Patterns
+/// that are identical across rules don't need any special treatment, as
+/// they already share the same atoms.
+pub(in crate::compiler) fn redundant_patterns(
+    condition: &Expr,
+    patterns: &[PatternInRule],
+) -> Vec<(PatternIdx, PatternIdx)> {
+    let alternatives: Vec<PatternIdx> = match condition {
+        Expr::Or { operands } => operands
+            .iter()
+            .map(|operand| match operand {
+                Expr::PatternMatch { pattern, anchor: MatchAnchor::None } => {
+                    Some(*pattern)
+                }
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default(),
+        Expr::Of(of)
+            if matches!(of.quantifier, Quantifier::Any)
+                && matches!(of.anchor, MatchAnchor::None) =>
+        {
+            match &of.items {
+                OfItems::PatternSet(set) => set.clone(),
+                OfItems::BoolExprTuple(_) => vec![],
+            }
+        }
+        _ => vec![],
+    };
+
+    let incompatible_flags = PatternFlags::Xor
+        | PatternFlags::Base64
+        | PatternFlags::Base64Wide
+        | PatternFlags::Fullword;
+
+    let literal = |idx: &PatternIdx| match patterns[idx.as_usize()].pattern() {
+        Pattern::Literal(lit) if !lit.flags.intersects(incompatible_flags) => {
+            Some(lit)
+        }
+        _ => None,
+    };
+
+    let mut result = Vec::new();
+
+    for redundant in &alternatives {
+        let Some(long) = literal(redundant) else {
+            continue;
+        };
+        let dominant = alternatives.iter().find(|dominant| {
+            let Some(short) = literal(dominant) else {
+                return false;
+            };
+            if short.flags != long.flags || short.text.len() >= long.text.len()
+            {
+                return false;
+            }
+            if long.flags.contains(PatternFlags::Nocase) {
+                long.text
+                    .to_ascii_lowercase()
+                    .contains_str(short.text.to_ascii_lowercase())
+            } else {
+                long.text.contains_str(&short.text)
+            }
+        });
+        if let Some(dominant) = dominant {
+            result.push((*redundant, *dominant));
+        }
+    }
+
+    result
+}
@@ -10,9 +10,9 @@ use std::collections::HashSet;
 use std::ops::RangeInclusive;
 use std::path::Path;
 use std::rc::Rc;
-#[cfg(feature = "logging")]
-use std::time::Instant;
-use std::{fmt, iter, u32};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{fmt, iter, mem, u32};
 
 use bincode::Options;
 use bitmask::bitmask;
@@ -26,7 +26,7 @@ use serde::{Deserialize, Serialize};
 use walrus::FunctionId;
 
 use yara_x_parser::ast;
-use yara_x_parser::ast::{HasSpan, Ident, Import, RuleFlag, Span};
+use yara_x_parser::ast::{HasSpan, Ident, Import, Include, RuleFlag, Span};
 use yara_x_parser::report::ReportBuilder;
 use yara_x_parser::warnings::{Warning, Warnings};
 use yara_x_parser::{Parser, SourceCode};
@@ -39,19 +39,29 @@ use crate::string_pool::{BStringPool, StringPool};
 use crate::symbols::{
     StackedSymbolTable, Symbol, SymbolKind, SymbolLookup, SymbolTable,
 };
-use crate::types::{Func, Struct, TypeValue, Value};
+use crate::types::{Func, Struct, Type, TypeValue, Value};
 use crate::utils::cast;
 use crate::variables::{is_valid_identifier, Variable, VariableError};
 use crate::wasm::builder::WasmModuleBuilder;
-use crate::wasm::{WasmExport, WasmSymbols, WASM_EXPORTS};
+use crate::wasm::host::{FuncResult, FuncValue, HostFunction};
+use crate::wasm::{WasmExport, WasmSymbols};
 
 pub(crate) use crate::compiler::atoms::*;
 pub(crate) use crate::compiler::context::*;
 pub(crate) use crate::compiler::ir::*;
 
+#[doc(inline)]
+pub use crate::compiler::atoms::{
+    AtomTableBuilder, AtomsExtractor, AtomsSetQuality, ExtractedAtom,
+    ExtractedAtoms,
+};
+
 #[doc(inline)]
 pub use crate::compiler::errors::*;
 
+#[doc(inline)]
+pub use crate::compiler::include::*;
+
 #[doc(inline)]
 pub use crate::compiler::rules::*;
 use crate::re;
@@ -61,6 +71,7 @@ mod atoms;
 mod context;
 mod emit;
 mod errors;
+mod include;
 mod ir;
 mod rules;
 
@@ -138,6 +149,70 @@ pub struct Compiler<'a> {
     /// escape sequences.
     relaxed_re_syntax: bool,
 
+    /// If true, the rules signal when an undefined value is found while
+    /// evaluating their conditions. See [`Compiler::strict_undefined`].
+    strict_undefined: bool,
+
+    /// If true, the source code of each rule is stored in the compiled
+    /// rules. See [`Compiler::embed_sources`].
+    embed_sources: bool,
+
+    /// If true, the doc comment of each rule is stored in the compiled
+    /// rules. See [`Compiler::embed_doc_comments`].
+    embed_doc_comments: bool,
+
+    /// If true, the WASM module is serialized in binary form instead of
+    /// native code. See [`Compiler::reproducible_serialization`].
+    reproducible_serialization: bool,
+
+    /// If true, private rules that are not used by any other rule are
+    /// kept in the compiled rules. See [`Compiler::keep_dead_rules`].
+    keep_dead_rules: bool,
+
+    /// Conditions of the private rules whose code was not emitted yet,
+    /// because no other rule has used them so far.
+    pending_rules: FxHashMap<RuleId, Expr>,
+
+    /// Private rules whose code was never emitted, because they were not
+    /// used by any other rule in their namespace.
+    dead_rules: Vec<RuleId>,
+
+    /// If true, errors don't abort the compilation, they are stored in
+    /// `errors` instead. See [`Compiler::error_recovery`].
+    error_recovery: bool,
+
+    /// Errors found while compiling the rules, only populated when
+    /// `error_recovery` is true.
+    errors: Vec<Error>,
+
+    /// If true, the time spent compiling the patterns of each rule is
+    /// recorded. See [`Compiler::enable_compile_timing`].
+    compile_timing: bool,
+
+    /// Time spent compiling the patterns of each rule, only populated when
+    /// `compile_timing` is true.
+    compile_timings: Vec<(RuleId, Duration)>,
+
+    /// Atom length favored when choosing the atoms for a pattern. See
+    /// [`Compiler::preferred_atom_length`].
+    preferred_atom_length: Option<usize>,
+
+    /// Maximum nesting depth of rule conditions. See
+    /// [`Compiler::max_condition_depth`].
+    max_condition_depth: Option<usize>,
+
+    /// Maximum number of patterns in a rule. See
+    /// [`Compiler::max_patterns_per_rule`].
+    max_patterns_per_rule: Option<usize>,
+
+    /// Maximum size of regular expressions. See
+    /// [`Compiler::max_regexp_size`].
+    max_regexp_size: Option<usize>,
+
+    /// Maximum number of iterations in loops. See
+    /// [`Compiler::max_loop_iterations`].
+    max_loop_iterations: Option<usize>,
+
     /// Used for generating error and warning reports.
     report_builder: ReportBuilder,
 
@@ -184,10 +259,10 @@ pub struct Compiler<'a> {
     /// variables, etc.
     wasm_symbols: WasmSymbols,
 
-    /// Map that contains the functions that are callable from WASM code. These
-    /// are the same functions in [`static@WASM_EXPORTS`]. This map allows to
-    /// retrieve the WASM [`FunctionId`] from the fully qualified mangled
-    /// function name (e.g: `my_module.my_struct.my_func@ii@i`)
+    /// Map that contains the functions that are callable from WASM code.
+    /// These are the same functions in [`static@crate::wasm::WASM_EXPORTS`].
+    /// This map allows to retrieve the WASM [`FunctionId`] from the fully
+    /// qualified mangled function name (e.g: `my_module.my_struct.my_func@ii@i`)
     wasm_exports: FxHashMap<String, FunctionId>,
 
     /// A vector with all the rules that has been compiled. A [`RuleId`] is
@@ -239,6 +314,27 @@ pub struct Compiler<'a> {
     /// module is ignored.
     ignored_modules: Vec<String>,
 
+    /// Names of modules that can't be imported. Unlike ignored modules, an
+    /// `import` statement with one of these modules is an error, even if the
+    /// module actually exists.
+    disabled_modules: Vec<String>,
+
+    /// Names of functions that can't be called (e.g. `hash.md5`). Calling
+    /// one of these functions is an error.
+    disabled_functions: Vec<String>,
+
+    /// If false, `include` statements produce an error. See
+    /// [`Compiler::enable_includes`].
+    includes_enabled: bool,
+
+    /// Resolver used for finding the files included with `include`
+    /// statements, if any. See [`Compiler::include_resolver`].
+    include_resolver: Option<Box<dyn IncludeResolver + 'a>>,
+
+    /// Origins of the files being included. The last item is the file
+    /// currently being compiled. Used for detecting circular includes.
+    include_stack: Vec<String>,
+
     /// Keys in this map are the name of rules that will be ignored because they
     /// depend on unsupported modules, either directly or indirectly. Values are
     /// the names of the unsupported modules they depend on.
@@ -249,8 +345,41 @@ pub struct Compiler<'a> {
     /// the structure that describes the module.
     root_struct: Struct,
 
+    /// Statistics about the last source code added with
+    /// [`Compiler::add_source`], if it was successfully compiled.
+    last_source_stats: Option<SourceStats>,
+
     /// Warnings generated while compiling the rules.
     warnings: Warnings,
+
+    /// Function that rewrites the patterns before they are compiled. See
+    /// [`Compiler::set_pattern_rewriter`].
+    pattern_rewriter: Option<Box<PatternRewriter<'a>>>,
+
+    /// Function that narrows the key range of patterns with the `xor`
+    /// modifier. See [`Compiler::set_xor_range_hook`].
+    xor_range_hook: Option<Box<XorRangeHook<'a>>>,
+
+    /// Metadata added to every rule that doesn't declare metadata with the
+    /// same identifier. See [`Compiler::add_default_metadata`].
+    default_metadata: Vec<(IdentId, MetaValue)>,
+
+    /// Functions implemented by the host application. See
+    /// [`Compiler::add_function`].
+    host_functions: Vec<HostFunction>,
+
+    /// Fragments declared so far, indexed by name. Each fragment is
+    /// accompanied by the span of its identifier. Fragments declared in a
+    /// source file can be used by any source file added afterwards,
+    /// regardless of the namespace.
+    fragments: FxHashMap<String, (Span, ast::HexTokens)>,
+
+    /// Maps the logic of each rule to the [`RuleId`] of the first rule with
+    /// that logic. The logic covers the rule's patterns, including their
+    /// modifiers, and its condition, but not the metadata or tags. It is used
+    /// for detecting differently named rules that are duplicates of each
+    /// other.
+    rule_logic: FxHashMap<RuleLogic, RuleId>,
 }
 
 impl<'a> Compiler<'a> {
@@ -261,20 +390,20 @@ impl<'a> Compiler<'a> {
 
         let global_symbols = symbol_table.push_new();
 
-        // Add symbols for built-in functions like uint8, uint16, etc.
-        for export in WASM_EXPORTS
-            .iter()
-            // Get only the public exports not belonging to a YARA module.
-            .filter(|e| e.public && e.builtin())
+        // Add symbols for built-in functions like uint8, uint16, etc. Get
+        // only the public exports not belonging to a YARA module. Overloaded
+        // functions have a single symbol with multiple signatures.
+        for (name, func) in
+            WasmExport::get_functions(|e| e.public && e.builtin())
         {
-            let func = Rc::new(Func::from_mangled_name(export.mangled_name));
+            let func = Rc::new(func);
 
             let symbol = Symbol::new(
                 TypeValue::Func(func.clone()),
                 SymbolKind::Func(func),
             );
 
-            global_symbols.borrow_mut().insert(export.name, symbol);
+            global_symbols.borrow_mut().insert(name, symbol);
         }
 
         // Create the default namespace. Rule identifiers will be added to this
@@ -310,10 +439,33 @@ impl<'a> Compiler<'a> {
             wasm_symbols,
             wasm_exports,
             relaxed_re_syntax: false,
+            strict_undefined: false,
+            embed_sources: false,
+            embed_doc_comments: false,
+            reproducible_serialization: false,
+            keep_dead_rules: false,
+            pending_rules: FxHashMap::default(),
+            dead_rules: Vec::new(),
+            error_recovery: false,
+            errors: Vec::new(),
+            compile_timing: false,
+            compile_timings: Vec::new(),
+            preferred_atom_length: None,
+            max_condition_depth: None,
+            max_patterns_per_rule: None,
+            max_regexp_size: None,
+            max_loop_iterations: None,
             next_pattern_id: PatternId(0),
             current_pattern_id: PatternId(0),
             current_namespace: default_namespace,
             warnings: Warnings::default(),
+            last_source_stats: None,
+            pattern_rewriter: None,
+            xor_range_hook: None,
+            default_metadata: Vec::new(),
+            host_functions: Vec::new(),
+            fragments: FxHashMap::default(),
+            rule_logic: FxHashMap::default(),
             rules: Vec::new(),
             sub_patterns: Vec::new(),
             anchored_sub_patterns: Vec::new(),
@@ -321,6 +473,11 @@ impl<'a> Compiler<'a> {
             re_code: Vec::new(),
             imported_modules: Vec::new(),
             ignored_modules: Vec::new(),
+            disabled_modules: Vec::new(),
+            disabled_functions: Vec::new(),
+            includes_enabled: false,
+            include_resolver: None,
+            include_stack: Vec::new(),
             ignored_rules: FxHashMap::default(),
             root_struct: Struct::new().make_root(),
             report_builder: ReportBuilder::new(),
@@ -330,6 +487,73 @@ impl<'a> Compiler<'a> {
         }
     }
 
+    /// Creates a new YARA compiler configured with the given
+    /// [`CompileOptions`].
+    ///
+    /// This is equivalent to calling [`Compiler::new`] followed by the
+    /// builder methods corresponding to each option. Returns an error if
+    /// [`CompileOptions::disabled_warnings`] or
+    /// [`CompileOptions::warnings_as_errors`] contain a code that doesn't
+    /// correspond to any warning.
+    pub fn with_options(
+        options: CompileOptions,
+    ) -> Result<Self, InvalidWarningCode> {
+        let mut compiler = Self::new();
+
+        compiler
+            .relaxed_re_syntax(options.relaxed_re_syntax)
+            .strict_undefined(options.strict_undefined)
+            .embed_sources(options.embed_sources)
+            .embed_doc_comments(options.embed_doc_comments)
+            .reproducible_serialization(options.reproducible_serialization)
+            .keep_dead_rules(options.keep_dead_rules)
+            .error_recovery(options.error_recovery)
+            .enable_compile_timing(options.enable_compile_timing)
+            .colorize_errors(options.colorize_errors);
+
+        if let Some(len) = options.preferred_atom_length {
+            compiler.preferred_atom_length(len);
+        }
+
+        if let Some(depth) = options.max_condition_depth {
+            compiler.max_condition_depth(depth);
+        }
+
+        if let Some(n) = options.max_patterns_per_rule {
+            compiler.max_patterns_per_rule(n);
+        }
+
+        if let Some(size) = options.max_regexp_size {
+            compiler.max_regexp_size(size);
+        }
+
+        if let Some(n) = options.max_loop_iterations {
+            compiler.max_loop_iterations(n);
+        }
+
+        for module in options.ignored_modules {
+            compiler.ignore_module(module);
+        }
+
+        for module in options.disabled_modules {
+            compiler.disable_module(module);
+        }
+
+        for function in options.disabled_functions {
+            compiler.disable_function(function);
+        }
+
+        for code in options.disabled_warnings.iter() {
+            compiler.switch_warning(code, false)?;
+        }
+
+        for code in options.warnings_as_errors.iter() {
+            compiler.warning_as_error(code, true)?;
+        }
+
+        Ok(compiler)
+    }
+
     /// Adds a YARA source code to be compiled.
     ///
     /// This function can be called multiple times.
@@ -340,11 +564,46 @@ impl<'a> Compiler<'a> {
         // Convert `src` into an instance of `SourceCode` if it is something
         // else, like a &str.
         let src = src.into();
+        let src_bytes = src.as_bytes();
+        let origin = src.origin().map(String::from);
+
+        // Stats for the previous source are not valid anymore, and they
+        // won't be replaced if this source fails to compile.
+        self.last_source_stats = None;
+
+        let num_rules = self.rules.len();
+        let num_warnings = self.warnings.len();
+
+        // Parse the source code and build the Abstract Syntax Tree. Syntax
+        // errors can't be recovered within the same source, the whole source
+        // is skipped.
+        let mut parser = Parser::new();
+
+        parser.set_report_builder(&self.report_builder);
+
+        // The parser's own limit protects against stack overflows, the
+        // condition depth can lower it but never raise it.
+        if let Some(max_depth) = self.max_condition_depth {
+            parser.max_nesting_depth(
+                max_depth.min(Parser::DEFAULT_MAX_NESTING_DEPTH),
+            );
+        }
+
+        let mut ast = match parser.build_ast(src) {
+            Ok(ast) => ast,
+            Err(err) => {
+                self.recover_from(err)?;
+                return Ok(self);
+            }
+        };
 
-        // Parse the source code and build the Abstract Syntax Tree.
-        let ast = Parser::new()
-            .set_report_builder(&self.report_builder)
-            .build_ast(src)?;
+        // Included files are compiled before anything else in the source
+        // code, regardless of the position of the `include` statements.
+        for include in &ast.includes {
+            if let Err(err) = self.c_include(include, origin.as_deref()) {
+                self.recover_from(err)?;
+            }
+        }
 
         let mut already_imported = FxHashMap::default();
 
@@ -368,22 +627,214 @@ impl<'a> Compiler<'a> {
 
             // Import the module. This updates `self.root_struct` if
             // necessary.
-            self.c_import(import)?;
+            if let Err(err) = self.c_import(import) {
+                self.recover_from(err)?;
+            }
+        }
+
+        // Fragments are processed before rules, so that rules can use
+        // fragments declared after them in the same source file.
+        for fragment in &ast.fragments {
+            if let Err(err) = self.c_fragment(fragment) {
+                self.recover_from(err)?;
+            }
         }
 
+        // Warnings treated as errors raised so far don't belong to any rule.
+        self.report_promoted_warnings()?;
+
+        let mut parser_warnings: Vec<Warning> =
+            mem::take(&mut ast.warnings).into();
+
         // Iterate over the list of declared rules and verify that their
         // conditions are semantically valid. For each rule add a symbol
         // to the current namespace.
         for rule in &ast.rules {
-            self.c_rule(rule)?;
+            let rules_len = self.rules.len();
+
+            // Warnings raised by the parser within the rule that must be
+            // treated as errors make the rule fail, like the ones raised
+            // while compiling it.
+            let (promoted, rest): (Vec<_>, Vec<_>) =
+                parser_warnings.into_iter().partition(|warning| {
+                    let span = warning.span();
+                    self.warnings.is_error(warning.code())
+                        && span.source_id() == rule.span.source_id()
+                        && span.start() >= rule.span.start()
+                        && span.end() <= rule.span.end()
+                });
+
+            parser_warnings = rest;
+            self.warnings.extend(promoted);
+
+            // In error recovery mode the failed rule is skipped. Rules that
+            // use it will fail too, as it doesn't exist.
+            if let Err(err) = self.c_rule(rule) {
+                self.recover_from(err)?;
+                self.report_promoted_warnings()?;
+                continue;
+            }
+
+            // Store the rule's source code if it was actually added, rules
+            // that depend on ignored modules are not.
+            if self.embed_sources && self.rules.len() > rules_len {
+                let source = &src_bytes[rule.span.start()..rule.span.end()];
+                self.rules.last_mut().unwrap().source =
+                    Some(String::from_utf8_lossy(source).into_owned());
+            }
+
+            // Same for the rule's doc comment.
+            if self.embed_doc_comments && self.rules.len() > rules_len {
+                self.rules.last_mut().unwrap().doc_comment =
+                    rule.doc_comment.map(String::from);
+            }
         }
 
-        // Transfer the warnings generated by the parser to the compiler
-        self.warnings.append(ast.warnings);
+        // Transfer the remaining warnings generated by the parser to the
+        // compiler, and report the ones that must be treated as errors.
+        self.warnings.extend(parser_warnings);
+        self.report_promoted_warnings()?;
+
+        self.last_source_stats = Some(SourceStats {
+            num_rules: self.rules.len() - num_rules,
+            num_warnings: self.warnings.len() - num_warnings,
+        });
 
         Ok(self)
     }
 
+    /// Returns statistics about the last source code added with
+    /// [`Compiler::add_source`].
+    ///
+    /// The result is `None` if no source code has been added yet, or if the
+    /// last call to [`Compiler::add_source`] failed.
+    pub fn last_source_stats(&self) -> Option<SourceStats> {
+        self.last_source_stats
+    }
+
+    /// Returns the symbols defined in the global scope.
+    ///
+    /// This includes the global variables defined with
+    /// [`Compiler::define_global`], the functions added with
+    /// [`Compiler::add_function`], and the fields of the modules imported
+    /// and the rules compiled so far in the current namespace, which are
+    /// the only ones that can be used by new rules. Module fields are
+    /// identified by their full path, where `[]` indicates a field inside
+    /// the items of an array or map (e.g: `pe.sections[].name`). This is
+    /// useful for tools like editors, which can use the symbols for
+    /// completing identifiers.
+    ///
+    /// ```
+    /// # use yara_x::{Compiler, GlobalSymbolKind};
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler
+    ///     .define_global("debug", false)
+    ///     .unwrap()
+    ///     .add_source(r#"rule test { condition: debug }"#)
+    ///     .unwrap();
+    ///
+    /// let symbols = compiler.symbols();
+    ///
+    /// assert!(symbols.iter().any(|s| s.name == "debug"
+    ///     && s.kind == GlobalSymbolKind::Variable
+    ///     && s.ty == "boolean"));
+    ///
+    /// assert!(symbols.iter().any(|s| s.name == "test"
+    ///     && s.kind == GlobalSymbolKind::Rule));
+    /// ```
+    pub fn symbols(&self) -> Vec<GlobalSymbol> {
+        let mut symbols = Vec::new();
+
+        for (name, field) in self.root_struct.fields() {
+            let is_module = self
+                .imported_modules
+                .iter()
+                .any(|id| self.ident_pool.get(*id) == Some(name));
+
+            if is_module {
+                // Modules must be imported in each namespace that uses them.
+                if !self.current_namespace.symbols.borrow().contains(name) {
+                    continue;
+                }
+                symbols.push(GlobalSymbol {
+                    name: name.to_string(),
+                    kind: GlobalSymbolKind::ModuleField,
+                    ty: field.type_value.ty().to_string(),
+                });
+                if let TypeValue::Struct(s) = &field.type_value {
+                    Self::module_symbols(
+                        name,
+                        &rules::field_descriptors(s),
+                        &mut symbols,
+                    );
+                }
+            } else {
+                symbols.push(GlobalSymbol {
+                    name: name.to_string(),
+                    kind: GlobalSymbolKind::Variable,
+                    ty: field.type_value.ty().to_string(),
+                });
+            }
+        }
+
+        for host_func in &self.host_functions {
+            // The function's name is the part of the mangled name that goes
+            // before the first `@`.
+            let name = host_func.mangled_name.split('@').next().unwrap();
+            symbols.push(GlobalSymbol {
+                name: name.to_string(),
+                kind: GlobalSymbolKind::Function,
+                ty: Type::Func.to_string(),
+            });
+        }
+
+        for rule in self
+            .rules
+            .iter()
+            .filter(|rule| rule.namespace_id == self.current_namespace.id)
+        {
+            symbols.push(GlobalSymbol {
+                name: self.ident_pool.get(rule.ident_id).unwrap().to_string(),
+                kind: GlobalSymbolKind::Rule,
+                ty: Type::Bool.to_string(),
+            });
+        }
+
+        symbols
+    }
+
+    /// Adds to `symbols` the module fields described by `fields`, and all
+    /// the fields nested under them. `path` is the path of the structure
+    /// that contains the fields.
+    fn module_symbols(
+        path: &str,
+        fields: &[FieldDescriptor],
+        symbols: &mut Vec<GlobalSymbol>,
+    ) {
+        for field in fields {
+            let field_path = format!("{path}.{}", field.name);
+
+            // Fields nested inside the items of an array or map have `[]`
+            // after the name of the array or map in their paths.
+            let nested_path = if field.ty == Type::Array.to_string()
+                || field.ty == Type::Map.to_string()
+            {
+                format!("{field_path}[]")
+            } else {
+                field_path.clone()
+            };
+
+            symbols.push(GlobalSymbol {
+                name: field_path,
+                kind: GlobalSymbolKind::ModuleField,
+                ty: field.ty.clone(),
+            });
+
+            Self::module_symbols(&nested_path, &field.fields, symbols);
+        }
+    }
+
     /// Defines a global variable and sets its initial value.    
     ///   
     /// Global variables must be defined before using [`Compiler::add_source`]
@@ -433,6 +884,100 @@ impl<'a> Compiler<'a> {
         Ok(self)
     }
 
+    /// Adds a function implemented by the host application.
+    ///
+    /// The function can be called from rule conditions as `name(...)`, and
+    /// `func` is invoked each time the function is called while scanning.
+    /// `signature` has the form `<arguments>@<return type>`, where the
+    /// arguments are a sequence of characters, one per argument, describing
+    /// its type, and the return type is a single character. The allowed
+    /// types are `i` (integer), `f` (float), `b` (bool) and `s` (string).
+    /// For instance, `s@i` is a function that receives a string and returns
+    /// an integer.
+    ///
+    /// Calls to the function are type-checked against the signature, and
+    /// `func` receives its arguments as [`FuncValue`]s of the declared
+    /// types. The type returned by `func` must correspond to the return
+    /// type in the signature (see [`FuncResult`]). If `func` returns `None`
+    /// the result of the call is undefined.
+    ///
+    /// Rules that use host functions can't be serialized, as closures
+    /// can't be serialized with them.
+    ///
+    /// ```
+    /// # use yara_x::{Compiler, FuncValue, Scanner};
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler.add_function("reputation", "s@i", |args| {
+    ///     match &args[0] {
+    ///         FuncValue::String(hash) if hash == "d41d8cd9" => Some(100),
+    ///         _ => None,
+    ///     }
+    /// })?;
+    ///
+    /// compiler.add_source(
+    ///     r#"rule test { condition: reputation("d41d8cd9") == 100 }"#,
+    /// )?;
+    ///
+    /// let rules = compiler.build();
+    ///
+    /// assert_eq!(
+    ///     Scanner::new(&rules).scan(b"").unwrap().matching_rules().len(),
+    ///     1
+    /// );
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// Returns [`Error::InvalidFunctionSignature`] if `signature` is not
+    /// valid or its return type is not the one returned by `func`.
+    pub fn add_function<F, R>(
+        &mut self,
+        name: &str,
+        signature: &str,
+        func: F,
+    ) -> Result<&mut Self, Error>
+    where
+        F: Fn(&[FuncValue]) -> Option<R> + Send + Sync + 'static,
+        R: FuncResult,
+    {
+        if !is_valid_identifier(name) {
+            return Err(
+                VariableError::InvalidIdentifier(name.to_string()).into()
+            );
+        }
+
+        if self.global_symbols.borrow().contains(name) {
+            return Err(VariableError::AlreadyExists(name.to_string()).into());
+        }
+
+        let host_func = HostFunction::new(
+            name,
+            signature,
+            Arc::new(move |args| func(args).map(R::into)),
+        )
+        .filter(|host_func| host_func.result == R::TYPE)
+        .ok_or_else(|| Error::InvalidFunctionSignature {
+            name: name.to_string(),
+            signature: signature.to_string(),
+        })?;
+
+        let func_id = self.wasm_mod.add_host_function(&host_func);
+
+        self.wasm_exports.insert(host_func.mangled_name.clone(), func_id);
+
+        let func =
+            Rc::new(Func::from_mangled_name(host_func.mangled_name.as_str()));
+
+        self.global_symbols.borrow_mut().insert(
+            name,
+            Symbol::new(TypeValue::Func(func.clone()), SymbolKind::Func(func)),
+        );
+
+        self.host_functions.push(host_func);
+
+        Ok(self)
+    }
+
     /// Creates a new namespace.
     ///
     /// Further calls to [`Compiler::add_source`] will put the rules under the
@@ -470,6 +1015,9 @@ impl<'a> Compiler<'a> {
     pub fn new_namespace(&mut self, namespace: &str) -> &mut Self {
         // Remove the symbol table corresponding to the previous namespace.
         self.symbol_table.pop().expect("expecting a namespace");
+        // Private rules that were not used in the previous namespace can't
+        // be used anymore.
+        self.dead_rules.extend(self.pending_rules.drain().map(|(id, _)| id));
         // Create a new namespace. The NamespaceId is simply the ID of the
         // previous namespace + 1.
         self.current_namespace = Namespace {
@@ -487,6 +1035,43 @@ impl<'a> Compiler<'a> {
     /// This function consumes the compiler and returns an instance of
     /// [`Rules`].
     pub fn build(self) -> Rules {
+        let mut rules = self.into_rules();
+
+        rules.build_ac_automaton();
+        rules.build_metadata_index();
+
+        rules
+    }
+
+    /// Builds the source code previously added to the compiler, reusing the
+    /// atom table from rules previously built with the same
+    /// [`AtomTableBuilder`].
+    ///
+    /// This is like [`Compiler::build`], but instead of creating the
+    /// Aho-Corasick automaton for all the atoms from scratch, only the atoms
+    /// that were not present in the previous rules are added to the table.
+    /// See [`AtomTableBuilder`] for details.
+    pub fn build_with_atom_table(
+        self,
+        atom_table: &mut AtomTableBuilder,
+    ) -> Rules {
+        let mut rules = self.into_rules();
+
+        rules.ac = Some(atom_table.build(rules.atoms()));
+        rules.build_metadata_index();
+
+        rules
+    }
+
+    /// Creates the [`Rules`] from the compiler's state, without building the
+    /// atom table nor the metadata index.
+    fn into_rules(mut self) -> Rules {
+        // Private rules that are still pending were not used by any other
+        // rule in the last namespace.
+        self.dead_rules.extend(self.pending_rules.drain().map(|(id, _)| id));
+        self.dead_rules.sort_by_key(|rule_id| usize::from(*rule_id));
+        self.strip_dead_rules();
+
         // Finish building the WASM module.
         let wasm_mod = self.wasm_mod.build().emit_wasm();
 
@@ -521,12 +1106,21 @@ impl<'a> Compiler<'a> {
             .serialize(&self.root_struct)
             .expect("failed to serialize global variables");
 
-        let mut rules = Rules {
+        Rules {
             serialized_globals,
             relaxed_re_syntax: self.relaxed_re_syntax,
-            wasm_mod: compiled_wasm_mod,
+            wasm_mod: WasmModule::new(
+                compiled_wasm_mod,
+                self.reproducible_serialization.then(|| wasm_mod.clone()),
+            ),
             ac: None,
+            metadata_index: FxHashMap::default(),
             num_patterns: self.next_pattern_id.0 as usize,
+            num_non_private_rules: self
+                .rules
+                .iter()
+                .filter(|rule| !rule.is_private)
+                .count(),
             ident_pool: self.ident_pool,
             regexp_pool: self.regexp_pool,
             lit_pool: self.lit_pool,
@@ -537,11 +1131,12 @@ impl<'a> Compiler<'a> {
             atoms: self.atoms,
             re_code: self.re_code,
             warnings: self.warnings.into(),
-        };
-
-        rules.build_ac_automaton();
-
-        rules
+            host_functions: self.host_functions,
+            compile_timings: self.compile_timings,
+            dead_rules: self.dead_rules,
+            #[cfg(feature = "wasm-wat")]
+            wasm_bytes: wasm_mod,
+        }
     }
 
     /// Tell the compiler that a YARA module is not supported.
@@ -555,6 +1150,353 @@ impl<'a> Compiler<'a> {
         self
     }
 
+    /// Enables or disables the strict handling of undefined values.
+    ///
+    /// By default, when the condition of a rule finds an undefined value in
+    /// a boolean context, like in `pe.number_of_sections > 5` when the
+    /// scanned file is not a PE, the expression is silently considered
+    /// false. When this setting is enabled the result is still false, but
+    /// the rule is flagged as having an evaluation error, which can be
+    /// checked with [`crate::Rule::has_evaluation_error`] after the scan.
+    ///
+    /// This helps in finding conditions that don't get evaluated at all.
+    /// The default setting is `false`.
+    pub fn strict_undefined(&mut self, yes: bool) -> &mut Self {
+        self.strict_undefined = yes;
+        self
+    }
+
+    /// Enables or disables the error recovery mode.
+    ///
+    /// By default, [`Compiler::add_source`] returns the first error found in
+    /// the source code. In error recovery mode the compiler keeps going
+    /// instead: rules, imports and fragments that fail to compile are
+    /// skipped, and their errors are collected. [`Compiler::add_source`]
+    /// always succeeds, use [`Compiler::errors`] for retrieving the errors
+    /// found so far. Errors found while parsing the source code can't be
+    /// recovered, they make the compiler skip the whole source code, but not
+    /// the sources added after it.
+    ///
+    /// This is useful for finding all the errors in a large set of rules in
+    /// a single pass. The [`Rules`] produced by [`Compiler::build`] contain
+    /// only the rules that were successfully compiled.
+    ///
+    /// ```
+    /// # use yara_x::Compiler;
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler
+    ///     .error_recovery(true)
+    ///     .add_source("rule foo { condition: bar }")?
+    ///     .add_source("rule baz { condition: qux }")?
+    ///     .add_source("rule ok { condition: true }")?;
+    ///
+    /// assert_eq!(compiler.errors().len(), 2);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// The default setting is `false`.
+    pub fn error_recovery(&mut self, yes: bool) -> &mut Self {
+        self.error_recovery = yes;
+        self
+    }
+
+    /// Enables or disables the embedding of rule sources.
+    ///
+    /// When enabled, the source code of each rule is stored in the compiled
+    /// rules, and can be retrieved with [`crate::Rule::source`] or
+    /// [`crate::ScanResults::matching_rule_source`]. Embedded sources are
+    /// serialized together with the rules. The default setting is `false`.
+    pub fn embed_sources(&mut self, yes: bool) -> &mut Self {
+        self.embed_sources = yes;
+        self
+    }
+
+    /// Enables or disables the embedding of rule doc comments.
+    ///
+    /// The doc comment of a rule is the block comment (`/* ... */`) that
+    /// appears right before the rule, with nothing but whitespaces in
+    /// between. When enabled, doc comments are stored in the compiled rules
+    /// and serialized together with them, and can be retrieved with
+    /// [`crate::Rule::doc_comment`] or [`crate::RuleRef::doc_comment`].
+    /// The default setting is `false`, which keeps comments out of the
+    /// compiled rules.
+    pub fn embed_doc_comments(&mut self, yes: bool) -> &mut Self {
+        self.embed_doc_comments = yes;
+        self
+    }
+
+    /// Makes the output of [`Rules::serialize`] reproducible.
+    ///
+    /// By default, serialized rules contain the code for the conditions
+    /// translated into native code for the current platform. The native
+    /// code depends on the features of the CPU where the rules were
+    /// compiled, so the same rules compiled in different hosts don't
+    /// produce the same bytes. When this setting is enabled, the code is
+    /// serialized in WASM binary form instead, and the same sources compiled
+    /// with the same settings and the same version of YARA-X always produce
+    /// byte-identical serialized rules. This is useful for content-addressing
+    /// or signing compiled rules.
+    ///
+    /// The downside is that the code must be translated into native code
+    /// every time the rules are deserialized, which makes deserialization
+    /// slower. The default setting is `false`.
+    ///
+    /// ```
+    /// # use yara_x::Compiler;
+    /// let compile = || {
+    ///     let mut compiler = Compiler::new();
+    ///     compiler
+    ///         .reproducible_serialization(true)
+    ///         .add_source(r#"rule test { strings: $a = "foo" condition: $a }"#)
+    ///         .unwrap();
+    ///     compiler.build().serialize().unwrap()
+    /// };
+    ///
+    /// assert_eq!(compile(), compile());
+    /// ```
+    pub fn reproducible_serialization(&mut self, yes: bool) -> &mut Self {
+        self.reproducible_serialization = yes;
+        self
+    }
+
+    /// Keeps private rules that are not used by any other rule.
+    ///
+    /// A private rule is dead when no non-private rule uses it, either
+    /// directly or through other private rules. Dead rules can't affect the
+    /// scan results, so by default their conditions are not compiled into
+    /// the rules, and the atoms of their patterns are removed from the
+    /// Aho-Corasick automaton, unless some other rule shares the same
+    /// pattern. Global rules are never considered dead, as they affect
+    /// every other rule in their namespace. Dead rules are still present in
+    /// the compiled rules, but they never match. The rules removed this way
+    /// are listed by [`Rules::dead_rules`].
+    ///
+    /// As the condition of a dead rule is never evaluated, any side effect
+    /// it may have doesn't happen either. For instance, calls to
+    /// `console.log` in the condition of a dead rule don't produce any
+    /// output. Enable this setting if you rely on those side effects, for
+    /// example while debugging a private rule.
+    ///
+    /// When this setting is enabled, dead rules are compiled as any other
+    /// rule. The default setting is `false`.
+    pub fn keep_dead_rules(&mut self, yes: bool) -> &mut Self {
+        self.keep_dead_rules = yes;
+        self
+    }
+
+    /// Enables or disables the measurement of the time spent compiling
+    /// the patterns of each rule.
+    ///
+    /// This is useful for finding out which rules are slow to compile,
+    /// usually because of patterns that produce a large number of atoms,
+    /// like regular expressions with many alternatives, or patterns with
+    /// the `xor` modifier. The timings can be retrieved with
+    /// [`crate::Rules::compile_timings`]. When disabled no time
+    /// measurements are taken at all. The default setting is `false`.
+    pub fn enable_compile_timing(&mut self, yes: bool) -> &mut Self {
+        self.compile_timing = yes;
+        self
+    }
+
+    /// Sets the atom length that the compiler should favor when choosing
+    /// the atoms for a pattern.
+    ///
+    /// Atoms are the short substrings that the scanner searches for before
+    /// verifying whether a pattern actually matches. Short atoms are more
+    /// likely to appear in the scanned data, which means more verifications,
+    /// while longer atoms are more selective. When choosing between sets of
+    /// atoms with similar quality, the compiler favors the set whose atoms
+    /// length is closer to `len`. Atoms are never longer than 4 bytes, so
+    /// values larger than 4 are equivalent to 4.
+    ///
+    /// This setting only applies to regular expressions and hex patterns
+    /// that are compiled for the Thompson/PikeVM engine, which are the ones
+    /// where the compiler must choose among multiple sets of atoms. Text
+    /// patterns, and regexps or hex patterns simple enough to be handled by
+    /// the faster engine or split into literal pieces, always use the atoms
+    /// with the best quality, regardless of their length.
+    ///
+    /// This affects only the performance of the scan, not the results. By
+    /// default, no length is favored.
+    pub fn preferred_atom_length(&mut self, len: usize) -> &mut Self {
+        self.preferred_atom_length = Some(len);
+        self
+    }
+
+    /// Sets the maximum nesting depth of rule conditions.
+    ///
+    /// Each expression in a condition is one level deeper than the
+    /// expression that contains it. For instance, in `not ($a and $b)` the
+    /// whole condition has depth 1, `$a and $b` has depth 2, and `$a` and
+    /// `$b` have depth 3. Conditions deeper than `depth` produce a
+    /// [`CompileError::ConditionTooDeep`] error. By default, there's no
+    /// limit.
+    ///
+    /// The parser also rejects code where parenthesis, brackets and unary
+    /// operators like `not` or `-` are nested more than `depth` levels,
+    /// with a syntax error. Its own limit (see
+    /// [`yara_x_parser::Parser::DEFAULT_MAX_NESTING_DEPTH`]) still applies
+    /// when `depth` is larger.
+    ///
+    /// This limit, like [`Compiler::max_patterns_per_rule`],
+    /// [`Compiler::max_regexp_size`] and [`Compiler::max_loop_iterations`],
+    /// is useful when compiling rules from untrusted sources.
+    pub fn max_condition_depth(&mut self, depth: usize) -> &mut Self {
+        self.max_condition_depth = Some(depth);
+        self
+    }
+
+    /// Sets the maximum number of patterns in a rule.
+    ///
+    /// Rules that declare more than `n` patterns produce a
+    /// [`CompileError::TooManyPatterns`] error. By default, there's no
+    /// limit.
+    pub fn max_patterns_per_rule(&mut self, n: usize) -> &mut Self {
+        self.max_patterns_per_rule = Some(n);
+        self
+    }
+
+    /// Sets the maximum size of regular expressions.
+    ///
+    /// The size is computed after expanding repetitions, as the compiled
+    /// code for `a{1000}` contains the code for `a` 1000 times. Each byte in
+    /// a literal, each character class and each assertion count as one, and
+    /// a repetition counts as many times as its maximum number of
+    /// repetitions, or its minimum if it's unbounded. For instance, the size
+    /// of `/ab{2,5}c/` is 7, and the size of `/a{1000}{1000}/` is 1000000.
+    ///
+    /// The limit applies to regexp patterns, to hex patterns, where jumps
+    /// are repetitions of any byte, and to regular expressions used in
+    /// conditions. Larger ones produce a [`CompileError::RegexpTooLarge`]
+    /// error. By default, there's no limit.
+    pub fn max_regexp_size(&mut self, size: usize) -> &mut Self {
+        self.max_regexp_size = Some(size);
+        self
+    }
+
+    /// Sets the maximum number of iterations in `for` loops over ranges.
+    ///
+    /// Loops like `for any i in (0..100000) : (...)` where the range has
+    /// more than `n` items produce a [`CompileError::TooManyIterations`]
+    /// error. In nested loops the limit applies to the total number of
+    /// iterations of the innermost loop, which is the product of the number
+    /// of items in the ranges of all the enclosing loops. Only ranges whose
+    /// bounds are known at compile time are checked, loops over ranges like
+    /// `(0..filesize)` are accepted, as the number of iterations is not
+    /// known until the scan. By default, there's no limit.
+    ///
+    /// ```
+    /// # use yara_x::Compiler;
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler.max_loop_iterations(1000);
+    ///
+    /// assert!(compiler
+    ///     .add_source("rule test { condition: for any i in (1..1000) : (i == 1) }")
+    ///     .is_ok());
+    ///
+    /// assert!(compiler
+    ///     .add_source("rule test { condition: for any i in (0..1000) : (i == 1) }")
+    ///     .is_err());
+    /// ```
+    pub fn max_loop_iterations(&mut self, n: usize) -> &mut Self {
+        self.max_loop_iterations = Some(n);
+        self
+    }
+
+    /// Tell the compiler that a YARA module can't be used.
+    ///
+    /// Import statements for disabled modules will produce a
+    /// [`CompileError::ModuleDisabled`] error. This is useful for imposing
+    /// restrictions on which modules can be used by the rules, regardless of
+    /// whether the modules are supported or not.
+    pub fn disable_module<M: Into<String>>(&mut self, module: M) -> &mut Self {
+        self.disabled_modules.push(module.into());
+        self
+    }
+
+    /// Tell the compiler that a function can't be used.
+    ///
+    /// Functions are identified by their fully qualified name, like
+    /// `hash.md5` or `uint32`. Calling a disabled function in a rule
+    /// condition produces a [`CompileError::FunctionDisabled`] error, but
+    /// the module the function belongs to can still be imported and used.
+    /// This applies to all the overloads of the function.
+    ///
+    /// ```
+    /// # use yara_x::Compiler;
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler.disable_function("hash.md5");
+    ///
+    /// assert!(compiler
+    ///     .add_source(r#"
+    ///         import "hash"
+    ///         rule test { condition: hash.md5(0, filesize) == "" }"#)
+    ///     .is_err());
+    ///
+    /// assert!(compiler
+    ///     .add_source(r#"
+    ///         import "hash"
+    ///         rule test { condition: hash.sha1(0, filesize) == "" }"#)
+    ///     .is_ok());
+    /// ```
+    pub fn disable_function<F: Into<String>>(
+        &mut self,
+        function: F,
+    ) -> &mut Self {
+        self.disabled_functions.push(function.into());
+        self
+    }
+
+    /// Enables or disables `include` statements.
+    ///
+    /// When includes are disabled, `include` statements produce a
+    /// [`CompileError::IncludeNotAllowed`] error. Includes are disabled by
+    /// default, as they allow the source code to read files from the host.
+    /// When enabled, a resolver must be set with
+    /// [`Compiler::include_resolver`] too.
+    pub fn enable_includes(&mut self, yes: bool) -> &mut Self {
+        self.includes_enabled = yes;
+        self
+    }
+
+    /// Sets the resolver used for finding the files included with `include`
+    /// statements.
+    ///
+    /// There's no resolver by default, use [`FsIncludeResolver`] for reading
+    /// the included files from a directory in the local filesystem. The
+    /// resolver is used only if includes are enabled with
+    /// [`Compiler::enable_includes`]. The included files are compiled before
+    /// the rest of the source code that contains the `include` statements,
+    /// in the same namespace.
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use yara_x::Compiler;
+    /// let mut files = HashMap::new();
+    ///
+    /// files.insert(
+    ///     "foo.yar".to_string(),
+    ///     "rule foo { condition: true }".to_string(),
+    /// );
+    ///
+    /// assert!(Compiler::new()
+    ///     .enable_includes(true)
+    ///     .include_resolver(files)
+    ///     .add_source(r#"include "foo.yar" rule bar { condition: foo }"#)
+    ///     .is_ok());
+    /// ```
+    pub fn include_resolver<R: IncludeResolver + 'a>(
+        &mut self,
+        resolver: R,
+    ) -> &mut Self {
+        self.include_resolver = Some(Box::new(resolver));
+        self
+    }
+
     /// Specifies whether the compiler should produce colorful error messages.
     ///
     /// Colorized error messages contain ANSI escape sequences that make them
@@ -590,12 +1532,214 @@ impl<'a> Compiler<'a> {
         self
     }
 
+    /// Sets a function that rewrites the patterns before they are compiled.
+    ///
+    /// The function is called after the source code is parsed, with the
+    /// source of every text, regexp and hex pattern, and the pattern
+    /// returned by the function is compiled instead of the original one.
+    /// This allows tools that transform rules in bulk to apply changes like
+    /// escaping or normalizing patterns without modifying the source code.
+    ///
+    /// The function must return a pattern of the same kind it received. If
+    /// a pattern is rewritten as a pattern of some other kind, the original
+    /// pattern is compiled unchanged.
+    ///
+    /// Rewritten patterns are validated just like the original ones. When a
+    /// rewritten pattern is invalid, the compiler returns
+    /// [`CompileError::InvalidRewrittenPattern`], which describes the problem
+    /// found in the rewritten pattern and points to the original one in the
+    /// source code, as the rewritten pattern doesn't appear in it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use yara_x::{Compiler, PatternSource};
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler.set_pattern_rewriter(|pattern| match pattern {
+    ///     PatternSource::Text(text) => {
+    ///         PatternSource::Text(text.to_ascii_lowercase())
+    ///     }
+    ///     other => other.clone(),
+    /// });
+    /// ```
+    pub fn set_pattern_rewriter<F>(&mut self, rewriter: F) -> &mut Self
+    where
+        F: Fn(&PatternSource) -> PatternSource + 'a,
+    {
+        self.pattern_rewriter = Some(Box::new(rewriter));
+        self
+    }
+
+    /// Sets a function that can narrow the range of keys tried for patterns
+    /// with the `xor` modifier.
+    ///
+    /// The function is called for every text pattern with the `xor`
+    /// modifier, with the pattern's identifier (e.g: `$a`) and the range of
+    /// keys declared in the source code, which is `0..=255` for a plain
+    /// `xor`. The range returned by the function is the one used while
+    /// scanning. Returning the received range leaves the pattern unchanged.
+    ///
+    /// The returned range must be within the declared one, otherwise the
+    /// rule fails to compile with [`CompileError::InvalidXorRange`]. Keys
+    /// can be removed from the range, but never added.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use yara_x::Compiler;
+    /// let mut compiler = Compiler::new();
+    ///
+    /// // Skip key 0, which matches the pattern without any xor applied.
+    /// compiler.set_xor_range_hook(|_ident, range| {
+    ///     (*range.start()).max(1)..=*range.end()
+    /// });
+    /// ```
+    pub fn set_xor_range_hook<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(&str, RangeInclusive<u8>) -> RangeInclusive<u8> + 'a,
+    {
+        self.xor_range_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Adds metadata to every rule compiled after this call.
+    ///
+    /// The metadata is added to rules in any namespace, but it is not added
+    /// to rules that already declare some metadata with the same identifier,
+    /// the value declared in the rule prevails. Default metadata appears
+    /// after the metadata declared by the rule. If this is called more than
+    /// once with the same `key`, the last value is used.
+    ///
+    /// ```
+    /// # use yara_x::{Compiler, MetaValue};
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler
+    ///     .add_default_metadata("engine", MetaValue::String("yara-x"))
+    ///     .add_source("rule test { condition: true }")
+    ///     .unwrap();
+    ///
+    /// let rules = compiler.build();
+    /// let mut scanner = yara_x::Scanner::new(&rules);
+    /// let results = scanner.scan(&[]).unwrap();
+    /// let rule = results.matching_rules().next().unwrap();
+    ///
+    /// assert_eq!(
+    ///     rule.metadata().collect::<Vec<_>>(),
+    ///     [("engine", MetaValue::String("yara-x"))]
+    /// );
+    /// ```
+    pub fn add_default_metadata(
+        &mut self,
+        key: &str,
+        value: crate::MetaValue,
+    ) -> &mut Self {
+        let ident_id = self.ident_pool.get_or_intern(key);
+
+        let value = match value {
+            crate::MetaValue::Integer(i) => MetaValue::Integer(i),
+            crate::MetaValue::Float(f) => MetaValue::Float(f),
+            crate::MetaValue::Bool(b) => MetaValue::Bool(b),
+            crate::MetaValue::String(s) => {
+                MetaValue::String(self.lit_pool.get_or_intern(s))
+            }
+            crate::MetaValue::Bytes(b) => {
+                MetaValue::Bytes(self.lit_pool.get_or_intern(b))
+            }
+        };
+
+        self.default_metadata.retain(|(id, _)| *id != ident_id);
+        self.default_metadata.push((ident_id, value));
+        self
+    }
+
     /// Returns the warnings emitted by the compiler.
     #[inline]
     pub fn warnings(&self) -> &[Warning] {
         self.warnings.as_slice()
     }
 
+    /// Enables or disables the warning with the given code.
+    ///
+    /// Codes are the ones returned by [`Warning::code`] (e.g.
+    /// `slow_pattern`, `non_boolean_as_boolean`). All warnings are
+    /// enabled by default. Returns an error if the code doesn't correspond
+    /// to any warning.
+    ///
+    /// ```
+    /// # use yara_x::Compiler;
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler
+    ///     .switch_warning("non_boolean_as_boolean", false)?
+    ///     .add_source("rule test { condition: 2 }")?;
+    ///
+    /// assert!(compiler
+    ///     .warnings()
+    ///     .iter()
+    ///     .all(|w| w.code() != "non_boolean_as_boolean"));
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn switch_warning(
+        &mut self,
+        code: &str,
+        enabled: bool,
+    ) -> Result<&mut Self, InvalidWarningCode> {
+        if !self.warnings.switch_warning(code, enabled) {
+            return Err(InvalidWarningCode(code.to_string()));
+        }
+        Ok(self)
+    }
+
+    /// Enables or disables all warnings.
+    ///
+    /// This can be combined with [`Compiler::switch_warning`] for enabling
+    /// only some specific warnings.
+    pub fn switch_all_warnings(&mut self, enabled: bool) -> &mut Self {
+        self.warnings.switch_all_warnings(enabled);
+        self
+    }
+
+    /// Treats the warning with the given code as an error, or as a normal
+    /// warning again if `yes` is false.
+    ///
+    /// A warning treated as an error makes the rule that raised it fail, as
+    /// any other error does: [`Compiler::add_source`] returns
+    /// [`Error::WarningAsError`], or the error is collected and the rule is
+    /// skipped if the compiler is in error recovery mode (see
+    /// [`Compiler::error_recovery`]). Disabled warnings are not treated as
+    /// errors. Returns an error if the code doesn't correspond to any
+    /// warning.
+    pub fn warning_as_error(
+        &mut self,
+        code: &str,
+        yes: bool,
+    ) -> Result<&mut Self, InvalidWarningCode> {
+        if !self.warnings.warning_as_error(code, yes) {
+            return Err(InvalidWarningCode(code.to_string()));
+        }
+        Ok(self)
+    }
+
+    /// Returns the errors found while compiling the rules.
+    ///
+    /// Errors are collected only in error recovery mode, otherwise they
+    /// are returned by [`Compiler::add_source`] and this is always empty.
+    /// See [`Compiler::error_recovery`].
+    #[inline]
+    pub fn errors(&self) -> &[Error] {
+        self.errors.as_slice()
+    }
+
+    /// Returns the [`ReportBuilder`] that holds every source file seen by
+    /// the compiler, including the ones included with `include`.
+    #[inline]
+    pub(crate) fn report_builder(&self) -> &ReportBuilder {
+        &self.report_builder
+    }
+
     /// Emits a `.wasm` file with the WASM module generated by the compiler.
     ///
     /// This file can be inspected and converted to WASM text format by using
@@ -637,6 +1781,31 @@ impl<'a> Compiler<'a> {
         sub_pattern_id
     }
 
+    /// Adds a fragment to the set of known fragments. Fails if another
+    /// fragment with the same name already exists.
+    fn c_fragment(
+        &mut self,
+        fragment: &ast::Fragment,
+    ) -> Result<(), Box<CompileError>> {
+        let name = fragment.identifier.name;
+
+        if let Some((existing_span, _)) = self.fragments.get(name) {
+            return Err(Box::new(CompileError::duplicate_fragment(
+                &self.report_builder,
+                name.to_string(),
+                fragment.identifier.span,
+                *existing_span,
+            )));
+        }
+
+        self.fragments.insert(
+            name.to_string(),
+            (fragment.identifier.span, fragment.tokens.clone()),
+        );
+
+        Ok(())
+    }
+
     /// Check if another rule, module or variable has the given identifier and
     /// return an error in that case.
     fn check_for_existing_identifier(
@@ -698,6 +1867,30 @@ impl<'a> Compiler<'a> {
         }
     }
 
+    /// Handles an error found while compiling a source code.
+    ///
+    /// In error recovery mode the error is stored and the result is `Ok`,
+    /// so that the compilation can continue. Otherwise the error is
+    /// returned as is.
+    fn recover_from<E: Into<Error>>(&mut self, err: E) -> Result<(), Error> {
+        if self.error_recovery {
+            self.errors.push(err.into());
+            Ok(())
+        } else {
+            Err(err.into())
+        }
+    }
+
+    /// Reports the warnings that must be treated as errors (see
+    /// [`Compiler::warning_as_error`]) raised since the last call to this
+    /// function.
+    fn report_promoted_warnings(&mut self) -> Result<(), Error> {
+        for warning in self.warnings.take_errors() {
+            self.recover_from(Error::WarningAsError(Box::new(warning)))?;
+        }
+        Ok(())
+    }
+
     /// Restores the compiler's to a previous state.
     ///
     /// Use [`Compiler::take_snapshot`] for taking a snapshot of the compiler's
@@ -713,11 +1906,26 @@ impl<'a> Compiler<'a> {
 }
 
 impl<'a> Compiler<'a> {
-    fn c_rule(&mut self, rule: &ast::Rule) -> Result<(), Box<CompileError>> {
+    fn c_rule(&mut self, rule: &ast::Rule) -> Result<(), Error> {
         // Check if another rule, module or variable has the same identifier
         // and return an error in that case.
         self.check_for_existing_identifier(&rule.identifier)?;
 
+        let num_patterns = rule.patterns.as_ref().map_or(0, |p| p.len());
+
+        if let Some(max_patterns) = self.max_patterns_per_rule {
+            if num_patterns > max_patterns {
+                return Err(Box::new(CompileError::too_many_patterns(
+                    &self.report_builder,
+                    rule.identifier.name.to_string(),
+                    num_patterns,
+                    max_patterns,
+                    rule.identifier.span,
+                ))
+                .into());
+            }
+        }
+
         // Take snapshot of the current compiler state. In case of error
         // compiling the current rule this snapshot allows restoring the
         // compiler to the state it had before starting compiling the rule.
@@ -733,7 +1941,7 @@ impl<'a> Compiler<'a> {
 
         // Build a vector of pairs (IdentId, MetaValue) for every meta defined
         // in the rule.
-        let meta = rule
+        let mut meta: Vec<(IdentId, MetaValue)> = rule
             .meta
             .iter()
             .flatten()
@@ -755,6 +1963,21 @@ impl<'a> Compiler<'a> {
             })
             .collect();
 
+        for (ident_id, value) in &self.default_metadata {
+            if !meta.iter().any(|(id, _)| id == ident_id) {
+                meta.push((*ident_id, value.clone()));
+            }
+        }
+
+        // Tags are kept in the order in which they were declared. The
+        // parser rejects duplicate tags, so they are unique.
+        let tags = rule
+            .tags
+            .iter()
+            .flatten()
+            .map(|tag| self.ident_pool.get_or_intern(tag))
+            .collect();
+
         // Add the new rule to `self.rules`. The only information about the
         // rule that we don't have right now is the PatternId corresponding to
         // each pattern, that's why the `pattern` fields is initialized as
@@ -772,10 +1995,20 @@ impl<'a> Compiler<'a> {
             is_global: rule.flags.contains(RuleFlag::Global),
             is_private: rule.flags.contains(RuleFlag::Private),
             metadata: meta,
+            tags,
+            candidate_predicate: CandidatePredicate::default(),
+            dependencies: Vec::new(),
+            modules: Vec::new(),
+            source: None,
+            doc_comment: None,
         });
 
         let mut rule_patterns = Vec::new();
 
+        // Time spent processing the patterns is measured only if the user
+        // asked for it.
+        let start = self.compile_timing.then(Instant::now);
+
         let mut ctx = CompileContext {
             relaxed_re_syntax: self.relaxed_re_syntax,
             current_symbol_table: None,
@@ -784,8 +2017,22 @@ impl<'a> Compiler<'a> {
             report_builder: &self.report_builder,
             rules: &self.rules,
             current_rule_patterns: &mut rule_patterns,
+            synthetic_patterns: Vec::new(),
             warnings: &mut self.warnings,
             vars: VarStack::new(),
+            min_matches: min_matches(&rule.condition),
+            pattern_rewriter: self.pattern_rewriter.as_deref(),
+            xor_range_hook: self.xor_range_hook.as_deref(),
+            fragments: &self.fragments,
+            disabled_functions: &self.disabled_functions,
+            data_reads: 0,
+            used_rules: Vec::new(),
+            used_modules: Vec::new(),
+            max_condition_depth: self.max_condition_depth,
+            max_regexp_size: self.max_regexp_size,
+            max_loop_iterations: self.max_loop_iterations,
+            depth: 0,
+            loop_iterations: 1,
         };
 
         // Convert the patterns from AST to IR. Populates `patterns_in_rule`
@@ -793,21 +2040,28 @@ impl<'a> Compiler<'a> {
         if let Err(err) = patterns_from_ast(&mut ctx, rule.patterns.as_ref()) {
             drop(ctx);
             self.restore_snapshot(snapshot);
-            return Err(Box::new(*err));
+            return Err(err.into());
         };
 
         // Convert the rule condition's AST to the intermediate representation
         // (IR). Also updates the patterns with information about whether they
         // are anchored or not.
+        let num_warnings = ctx.warnings.len();
         let condition = bool_expr_from_ast(&mut ctx, &rule.condition);
+        let condition_has_warnings = ctx.warnings.len() > num_warnings;
+        let mut synthetic_patterns = mem::take(&mut ctx.synthetic_patterns);
+        let used_rules = mem::take(&mut ctx.used_rules);
+        let used_modules = mem::take(&mut ctx.used_modules);
 
         drop(ctx);
 
+        rule_patterns.append(&mut synthetic_patterns);
+
         // In case of error, restore the compiler to the state it was before
         // entering this function. Also, if the error is due to an unknown
         // identifier, but the identifier is one of the unsupported modules,
         // the error is tolerated and a warning is issued instead.
-        let mut condition = match condition.map_err(|err| *err) {
+        let condition = match condition.map_err(|err| *err) {
             Ok(condition) => condition,
             Err(CompileError::UnknownIdentifier {
                 identifier, span, ..
@@ -847,10 +2101,68 @@ impl<'a> Compiler<'a> {
             }
             Err(err) => {
                 self.restore_snapshot(snapshot);
-                return Err(Box::new(err));
+                return Err(Box::new(err).into());
             }
         };
 
+        // Raise a warning for each pattern that doesn't affect the result of
+        // the condition because some other pattern always matches when it
+        // does.
+        for (redundant, dominant) in
+            redundant_patterns(&condition, rule_patterns.as_slice())
+        {
+            // Patterns in `rule_patterns` are in the same order as they
+            // appear in the AST, followed by the synthetic ones, which are
+            // not taken into account.
+            let ast_patterns = rule.patterns.as_deref().unwrap_or_default();
+            if redundant.as_usize() >= ast_patterns.len()
+                || dominant.as_usize() >= ast_patterns.len()
+            {
+                continue;
+            }
+            let redundant = ast_patterns[redundant.as_usize()].identifier();
+            let dominant = ast_patterns[dominant.as_usize()].identifier();
+
+            self.warnings.add(|| {
+                Warning::redundant_pattern(
+                    &self.report_builder,
+                    redundant.name.to_string(),
+                    dominant.name.to_string(),
+                    redundant.span,
+                    dominant.span,
+                    Some(format!(
+                        "the condition is true whenever `{}` matches, so `{}` doesn't change the result",
+                        dominant.name, redundant.name
+                    )),
+                )
+            });
+        }
+
+        // Raise a warning if the condition is statically true, as the rule
+        // will match every scanned file. When the rule is global this also
+        // means that it doesn't impose any restriction on other rules.
+        // Conditions for which some other warning was raised are not
+        // reported, as the other warning most likely explains why the
+        // condition is always true.
+        let is_global = rule.flags.contains(RuleFlag::Global);
+
+        if matches!(
+            condition.type_value(),
+            TypeValue::Bool(Value::Const(true))
+        ) && !condition_has_warnings
+        {
+            self.warnings.add(|| {
+                Warning::always_matches(
+                    &self.report_builder,
+                    rule.identifier.name.to_string(),
+                    rule.condition.span(),
+                    is_global.then(|| {
+                        "this global rule has no effect on the other rules in the namespace".to_string()
+                    }),
+                )
+            });
+        }
+
         // Create a new symbol of bool type for the rule.
         let new_symbol = Symbol::new(
             TypeValue::Bool(Value::Unknown),
@@ -874,6 +2186,11 @@ impl<'a> Compiler<'a> {
 
         let current_rule = self.rules.last_mut().unwrap();
 
+        current_rule.candidate_predicate =
+            CandidatePredicate::from_condition(&condition);
+        current_rule.dependencies = used_rules;
+        current_rule.modules = used_modules;
+
         for pattern in &rule_patterns {
             // Check if this pattern has been declared before, in this rule or
             // in some other rule. In such cases the pattern ID is re-used, and
@@ -908,24 +2225,47 @@ impl<'a> Compiler<'a> {
         // from each pattern, adding them to the `self.atoms` vector, it
         // also creates one or more sub-patterns per pattern and add them
         // to `self.sub_patterns`
+        //
+        // Synthetic patterns don't have a span of their own, the condition's
+        // span is used for them.
         for (pattern_id, pattern, span) in izip!(
             pattern_ids.iter(),
             rule_patterns.into_iter(),
-            rule.patterns.iter().flatten().map(|p| p.span())
+            rule.patterns
+                .iter()
+                .flatten()
+                .map(|p| p.span())
+                .chain(iter::repeat(rule.condition.span()))
         ) {
             if pending_patterns.contains(pattern_id) {
                 self.current_pattern_id = *pattern_id;
                 let anchored_at = pattern.anchored_at();
+                let identifier = pattern.identifier();
                 match pattern.into_pattern() {
                     Pattern::Literal(pattern) => {
                         self.c_literal_pattern(pattern, anchored_at);
                     }
                     Pattern::Regexp(pattern) => {
+                        if pattern.hir.has_nested_unbounded_repetitions() {
+                            self.warnings.add(|| {
+                                Warning::potentially_expensive_regex(
+                                    &self.report_builder,
+                                    identifier.to_string(),
+                                    span,
+                                    Some(
+                                        "an unbounded quantifier applied to \
+                                         an expression that already has one \
+                                         can cause excessive backtracking"
+                                            .to_string(),
+                                    ),
+                                )
+                            });
+                        }
                         if let Err(err) =
                             self.c_regexp_pattern(pattern, anchored_at, span)
                         {
                             self.restore_snapshot(snapshot);
-                            return Err(err);
+                            return Err(err.into());
                         }
                     }
                 };
@@ -933,14 +2273,98 @@ impl<'a> Compiler<'a> {
             }
         }
 
+        if let Some(start) = start {
+            self.compile_timings.push((rule_id, start.elapsed()));
+        }
+
+        // Two rules have the same logic if their patterns are the same, and
+        // their conditions are equal. Pattern identifiers don't matter, as
+        // the condition refers to patterns by their position in the rule.
+        let logic = RuleLogic {
+            global: rule.flags.contains(RuleFlag::Global),
+            private: rule.flags.contains(RuleFlag::Private),
+            pattern_ids,
+            condition: format!("{:?}", condition),
+        };
+
+        match self.rule_logic.entry(logic) {
+            Entry::Occupied(entry) => {
+                let first = &self.rules[usize::from(*entry.get())];
+                let first_ident = self.ident_pool.get(first.ident_id).unwrap();
+                if first_ident != rule.identifier.name {
+                    self.warnings.add(|| {
+                        Warning::duplicate_rule_logic(
+                            &self.report_builder,
+                            first_ident.to_string(),
+                            rule.identifier.name.to_string(),
+                            first.ident_span,
+                            rule.identifier.span,
+                        )
+                    });
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(rule_id);
+            }
+        }
+
+        // Warnings treated as errors make the rule fail like any other error.
+        // Besides restoring the snapshot, the symbol, patterns and logic hash
+        // registered for the rule are removed.
+        if self.warnings.has_errors() {
+            self.current_namespace
+                .symbols
+                .as_ref()
+                .borrow_mut()
+                .remove(rule.identifier.name);
+            self.patterns.retain(|_, id| id.0 < snapshot.next_pattern_id.0);
+            self.rule_logic.retain(|_, id| *id != rule_id);
+            self.compile_timings.retain(|(id, _)| *id != rule_id);
+            self.restore_snapshot(snapshot);
+            return self.report_promoted_warnings();
+        }
+
         // The last step is emitting the WASM code corresponding to the rule's
         // condition. This is done after every fallible function has been called
         // because once the code is emitted it cannot be undone, which means
         // that if this function fails after emitting the code, some code debris
         // will remain in the WASM module.
+        //
+        // The code for private rules is not emitted until some other rule uses
+        // them, so the code for dead rules is never emitted at all. Global
+        // rules are emitted right away, as they affect every other rule in the
+        // namespace.
+        let current_rule = self.rules.last().unwrap();
+
+        if current_rule.is_private
+            && !current_rule.is_global
+            && !self.keep_dead_rules
+        {
+            self.pending_rules.insert(rule_id, condition);
+        } else {
+            self.emit_rule(rule_id, condition);
+        }
+
+        Ok(())
+    }
+
+    /// Emits the WASM code for the condition of a rule, preceded by the code
+    /// for the pending private rules it depends on.
+    fn emit_rule(&mut self, rule_id: RuleId, mut condition: Expr) {
+        // Dependencies must be emitted first, as the condition of this rule
+        // checks whether they matched or not.
+        for dependency in self.rules[usize::from(rule_id)].dependencies.clone()
+        {
+            if let Some(condition) = self.pending_rules.remove(&dependency) {
+                self.emit_rule(dependency, condition);
+            }
+        }
+
         let mut ctx = EmitContext {
-            current_rule: self.rules.last_mut().unwrap(),
+            current_rule: &self.rules[usize::from(rule_id)],
+            current_rule_id: rule_id,
             current_signature: None,
+            strict_undefined: self.strict_undefined,
             lit_pool: &mut self.lit_pool,
             regexp_pool: &mut self.regexp_pool,
             wasm_symbols: &self.wasm_symbols,
@@ -960,14 +2384,56 @@ impl<'a> Compiler<'a> {
         // After emitting the whole condition, the stack of variables should
         // be empty.
         assert_eq!(ctx.vars.used, 0);
+    }
 
-        Ok(())
+    /// Removes the atoms of the patterns that are used only by dead rules,
+    /// so that they are not searched for while scanning.
+    fn strip_dead_rules(&mut self) {
+        if self.dead_rules.is_empty() {
+            return;
+        }
+
+        let mut dead_rules = vec![false; self.rules.len()];
+
+        for rule_id in &self.dead_rules {
+            dead_rules[usize::from(*rule_id)] = true;
+        }
+
+        let mut live_patterns = vec![false; self.next_pattern_id.into()];
+
+        for (rule, _) in
+            self.rules.iter().zip(dead_rules).filter(|(_, dead)| !dead)
+        {
+            for (_, pattern_id) in &rule.patterns {
+                live_patterns[usize::from(*pattern_id)] = true;
+            }
+        }
+
+        let sub_patterns = &self.sub_patterns;
+
+        let is_live = |sub_pattern_id: SubPatternId| {
+            let (pattern_id, _) = &sub_patterns[sub_pattern_id.0 as usize];
+            live_patterns[usize::from(*pattern_id)]
+        };
+
+        self.atoms.retain(|atom| is_live(atom.sub_pattern_id()));
+        self.anchored_sub_patterns.retain(|id| is_live(*id));
     }
 
     fn c_import(&mut self, import: &Import) -> Result<(), Box<CompileError>> {
         let module_name = import.module_name.as_str();
         let module = BUILTIN_MODULES.get(module_name);
 
+        // Importing a disabled module is an error, no matter if the module
+        // exists or not.
+        if self.disabled_modules.iter().any(|m| m == module_name) {
+            return Err(Box::new(CompileError::module_disabled(
+                &self.report_builder,
+                module_name.to_string(),
+                import.span(),
+            )));
+        }
+
         // Does a module with the given name actually exist? ...
         if module.is_none() {
             // The module does not exist, but it is included in the list
@@ -1060,6 +2526,59 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
+    fn c_include(
+        &mut self,
+        include: &Include,
+        includer: Option<&str>,
+    ) -> Result<(), Error> {
+        if !self.includes_enabled {
+            return Err(Box::new(CompileError::include_not_allowed(
+                &self.report_builder,
+                include.span,
+            ))
+            .into());
+        }
+
+        let included = self
+            .include_resolver
+            .as_ref()
+            .ok_or_else(|| "no include resolver has been set".to_string())
+            .and_then(|resolver| {
+                resolver.resolve(include.file_name.as_str(), includer)
+            })
+            .map_err(|reason| {
+                Box::new(CompileError::include_error(
+                    &self.report_builder,
+                    include.file_name.clone(),
+                    reason,
+                    include.span,
+                ))
+            })?;
+
+        if self.include_stack.contains(&included.origin) {
+            return Err(Box::new(CompileError::include_error(
+                &self.report_builder,
+                include.file_name.clone(),
+                "circular include".to_string(),
+                include.span,
+            ))
+            .into());
+        }
+
+        self.include_stack.push(included.origin.clone());
+
+        let result = self
+            .add_source(
+                SourceCode::from(included.code.as_str())
+                    .with_origin(included.origin.as_str()),
+            )
+            .map(|_| ());
+
+        self.include_stack.pop();
+
+        result
+    }
+
     fn c_literal_pattern(
         &mut self,
         pattern: LiteralPattern,
@@ -1555,19 +3074,22 @@ impl<'a> Compiler<'a> {
         // regexp is not compatible for `FastVM` and `PikeVM` must be used
         // instead.
         #[cfg(feature = "fast-regexp")]
-        let (result, is_fast_regexp) = match re::fast::Compiler::new()
-            .compile(hir, &mut self.re_code)
-        {
-            Err(re::Error::FastIncompatible) => (
-                re::thompson::Compiler::new().compile(hir, &mut self.re_code),
-                false,
-            ),
-            result => (result, true),
-        };
+        let (result, is_fast_regexp) =
+            match re::fast::Compiler::new().compile(hir, &mut self.re_code) {
+                Err(re::Error::FastIncompatible) => (
+                    re::thompson::Compiler::new()
+                        .preferred_atom_len(self.preferred_atom_length)
+                        .compile(hir, &mut self.re_code),
+                    false,
+                ),
+                result => (result, true),
+            };
 
         #[cfg(not(feature = "fast-regexp"))]
         let (result, is_fast_regexp) = (
-            re::thompson::Compiler::new().compile(hir, &mut self.re_code),
+            re::thompson::Compiler::new()
+                .preferred_atom_len(self.preferred_atom_length)
+                .compile(hir, &mut self.re_code),
             false,
         );
 
@@ -1717,12 +3239,23 @@ impl From<LiteralId> for u64 {
 }
 
 /// ID associated to each namespace.
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
 #[serde(transparent)]
 pub(crate) struct NamespaceId(i32);
 
 /// ID associated to each rule.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct RuleId(i32);
 
 impl From<i32> for RuleId {
@@ -1857,6 +3390,121 @@ impl From<PatternId> for usize {
 #[serde(transparent)]
 pub(crate) struct SubPatternId(u32);
 
+/// Statistics about a source code added to the compiler.
+///
+/// See [`Compiler::last_source_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourceStats {
+    /// Number of rules compiled from the source code. Rules that were
+    /// ignored because they depend on unsupported modules are not counted.
+    pub num_rules: usize,
+    /// Number of warnings raised while compiling the source code.
+    pub num_warnings: usize,
+}
+
+/// A symbol defined in the global scope, as returned by
+/// [`Compiler::symbols`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalSymbol {
+    /// The symbol's name. For module fields this is the full path of the
+    /// field (e.g: `pe.number_of_sections`).
+    pub name: String,
+    /// The kind of symbol.
+    pub kind: GlobalSymbolKind,
+    /// The symbol's type (e.g: `integer`, `string`, `struct`, `function`).
+    pub ty: String,
+}
+
+/// Kinds of symbols returned by [`Compiler::symbols`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlobalSymbolKind {
+    /// A rule.
+    Rule,
+    /// A global variable defined with [`Compiler::define_global`].
+    Variable,
+    /// A function added with [`Compiler::add_function`].
+    Function,
+    /// A field in an imported module, including module functions.
+    ModuleField,
+}
+
+/// Options that control the behaviour of the compiler.
+///
+/// Each option corresponds to one of the builder methods in [`Compiler`],
+/// see the documentation of those methods for details. The options can be
+/// serialized and deserialized, which allows storing them in a config file.
+/// Missing fields take their default values when deserializing.
+///
+/// See [`Compiler::with_options`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompileOptions {
+    /// See [`Compiler::relaxed_re_syntax`].
+    pub relaxed_re_syntax: bool,
+    /// See [`Compiler::strict_undefined`].
+    pub strict_undefined: bool,
+    /// See [`Compiler::colorize_errors`].
+    pub colorize_errors: bool,
+    /// See [`Compiler::embed_sources`].
+    pub embed_sources: bool,
+    /// See [`Compiler::embed_doc_comments`].
+    pub embed_doc_comments: bool,
+    /// See [`Compiler::reproducible_serialization`].
+    pub reproducible_serialization: bool,
+    /// See [`Compiler::keep_dead_rules`].
+    pub keep_dead_rules: bool,
+    /// See [`Compiler::error_recovery`].
+    pub error_recovery: bool,
+    /// See [`Compiler::enable_compile_timing`].
+    pub enable_compile_timing: bool,
+    /// See [`Compiler::preferred_atom_length`].
+    pub preferred_atom_length: Option<usize>,
+    /// See [`Compiler::max_condition_depth`].
+    pub max_condition_depth: Option<usize>,
+    /// See [`Compiler::max_patterns_per_rule`].
+    pub max_patterns_per_rule: Option<usize>,
+    /// See [`Compiler::max_regexp_size`].
+    pub max_regexp_size: Option<usize>,
+    /// See [`Compiler::max_loop_iterations`].
+    pub max_loop_iterations: Option<usize>,
+    /// See [`Compiler::ignore_module`].
+    pub ignored_modules: Vec<String>,
+    /// See [`Compiler::disable_module`].
+    pub disabled_modules: Vec<String>,
+    /// See [`Compiler::disable_function`].
+    pub disabled_functions: Vec<String>,
+    /// Codes of the warnings that are disabled. See
+    /// [`Compiler::switch_warning`].
+    pub disabled_warnings: Vec<String>,
+    /// Codes of the warnings that are treated as errors. See
+    /// [`Compiler::warning_as_error`].
+    pub warnings_as_errors: Vec<String>,
+}
+
+/// Source of a pattern, as received and returned by the function set with
+/// [`Compiler::set_pattern_rewriter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternSource {
+    /// A text pattern (e.g: `"foo"`). Escape sequences are already
+    /// replaced with the bytes they represent.
+    Text(Vec<u8>),
+    /// A regexp pattern (e.g: `/foo/`), without the enclosing slashes and
+    /// the modifiers that follow them.
+    Regexp(String),
+    /// A hex pattern (e.g: `{ 01 02 ?? 03 }`), including the enclosing
+    /// braces, exactly as it appears in the source code.
+    Hex(String),
+}
+
+/// Function that rewrites patterns. See [`Compiler::set_pattern_rewriter`].
+pub(crate) type PatternRewriter<'a> =
+    dyn Fn(&PatternSource) -> PatternSource + 'a;
+
+/// Function that narrows the key range of patterns with the `xor` modifier.
+/// See [`Compiler::set_xor_range_hook`].
+pub(crate) type XorRangeHook<'a> =
+    dyn Fn(&str, RangeInclusive<u8>) -> RangeInclusive<u8> + 'a;
+
 /// Iterator that yields the names of the modules imported by the rules.
 pub struct Imports<'a> {
     iter: std::slice::Iter<'a, IdentId>,
@@ -1987,3 +3635,16 @@ struct Snapshot {
     sub_patterns_len: usize,
     symbol_table_len: usize,
 }
+
+/// The logic of a rule, used for detecting rules that are duplicates of each
+/// other. The condition is stored in its textual form, which reflects the
+/// whole structure of the IR tree, so two rules are considered duplicates
+/// only when their conditions are structurally equal, not merely when their
+/// hashes collide.
+#[derive(PartialEq, Eq, Hash)]
+struct RuleLogic {
+    global: bool,
+    private: bool,
+    pattern_ids: Vec<PatternId>,
+    condition: String,
+}
@@ -1,30 +1,52 @@
+use std::collections::{BTreeMap, VecDeque};
 use std::fmt;
 use std::io::{BufWriter, Read, Write};
+use std::time::Duration;
 #[cfg(feature = "logging")]
 use std::time::Instant;
 
-use aho_corasick::AhoCorasick;
 use bincode::Options;
+#[cfg(feature = "signed-rules")]
+use hmac::{Hmac, Mac};
 #[cfg(feature = "logging")]
 use log::*;
 use regex_automata::meta::Regex;
+use rustc_hash::FxHashMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "signed-rules")]
+use sha2::Sha256;
 
 use yara_x_parser::ast::Span;
 use yara_x_parser::Warning;
 
-use crate::compiler::atoms::Atom;
+use crate::compiler::atoms::{Atom, AtomTable};
+use crate::compiler::ir::Expr;
 use crate::compiler::{
     IdentId, Imports, LiteralId, NamespaceId, PatternId, RegexpId, RuleId,
-    SubPattern, SubPatternId,
+    SubPattern, SubPatternFlags, SubPatternId,
 };
-use crate::re::{BckCodeLoc, FwdCodeLoc, RegexpAtom};
+use crate::modules::BUILTIN_MODULES;
+use crate::re::{BckCodeLoc, CodeLoc, FwdCodeLoc, RegexpAtom};
+use crate::scanner::{MetaValue as ScannedMetaValue, Metadata};
 use crate::string_pool::{BStringPool, StringPool};
+use crate::symbols::SymbolKind;
+use crate::types::TypeValue;
+use crate::wasm::host::HostFunction;
 use crate::{re, types, SerializationError};
 
 /// A set of YARA rules in compiled form.
 ///
 /// This is the result from [`crate::Compiler::build`].
+///
+/// Compiled rules can't be merged with other compiled rules. The conditions
+/// of all the rules are compiled into a single WASM module that is then
+/// translated to native code, and rule, pattern and atom identifiers are
+/// indexes that are resolved at compile time, so combining two sets of
+/// rules would require re-generating the code for both. If you need to
+/// scan with rules that come from multiple sources, add all the sources
+/// to the same [`crate::Compiler`], using a different namespace for each of
+/// them with [`crate::Compiler::new_namespace`] if necessary. Alternatively,
+/// each set of rules can be used with its own [`crate::Scanner`].
 #[derive(Serialize, Deserialize)]
 pub struct Rules {
     /// Pool with identifiers used in the rules. Each identifier has its
@@ -48,11 +70,7 @@ pub struct Rules {
     pub(in crate::compiler) lit_pool: BStringPool<LiteralId>,
 
     /// WASM module already compiled into native code for the current platform.
-    #[serde(
-        serialize_with = "serialize_wasm_mod",
-        deserialize_with = "deserialize_wasm_mod"
-    )]
-    pub(in crate::compiler) wasm_mod: wasmtime::Module,
+    pub(in crate::compiler) wasm_mod: WasmModule,
 
     /// Vector with the names of all the imported modules. The vector contains
     /// the [`IdentId`] corresponding to the module's identifier.
@@ -62,6 +80,9 @@ pub struct Rules {
     /// in this vector.
     pub(in crate::compiler) rules: Vec<RuleInfo>,
 
+    /// Number of rules in `rules` that are not private.
+    pub(in crate::compiler) num_non_private_rules: usize,
+
     /// Total number of patterns across all rules. This is equal to the last
     /// [`PatternId`] +  1.
     pub(in crate::compiler) num_patterns: usize,
@@ -100,20 +121,52 @@ pub struct Rules {
     /// defined at compile time using [`crate::compiler::Compiler`].
     pub(in crate::compiler) serialized_globals: Vec<u8>,
 
-    /// Aho-Corasick automaton containing the atoms extracted from the patterns.
-    /// This allows to search for all the atoms in the scanned data at the same
-    /// time in an efficient manner. The automaton is not serialized during when
-    /// [`Rules::serialize`] is called, it needs to be wrapped in [`Option`] so
-    /// that we can use `#[serde(skip)]` on it because [`AhoCorasick`] doesn't
-    /// implement the [`Default`] trait.
+    /// Table with Aho-Corasick automata containing the atoms extracted from
+    /// the patterns. This allows to search for all the atoms in the scanned
+    /// data at the same time in an efficient manner. The table is not
+    /// serialized when [`Rules::serialize`] is called, it needs to be wrapped
+    /// in [`Option`] so that we can use `#[serde(skip)]` on it because
+    /// [`AtomTable`] doesn't implement the [`Default`] trait.
     #[serde(skip)]
-    pub(in crate::compiler) ac: Option<AhoCorasick>,
+    pub(in crate::compiler) ac: Option<AtomTable>,
+
+    /// Index that maps each metadata identifier to the rules that have
+    /// metadata with that identifier. Each entry contains the [`RuleId`]
+    /// and the position of the metadata within [`RuleInfo::metadata`]. The
+    /// index is not serialized, it is built when the rules are created or
+    /// deserialized.
+    #[serde(skip)]
+    pub(in crate::compiler) metadata_index:
+        FxHashMap<String, Vec<(RuleId, usize)>>,
 
     /// Warnings that were produced while compiling these rules. These warnings
     /// are not serialized, rules that are obtained by deserializing previously
     /// serialized rules won't have any warnings.
     #[serde(skip)]
     pub(in crate::compiler) warnings: Vec<Warning>,
+
+    /// Functions implemented by the host application, added with
+    /// [`crate::Compiler::add_function`]. These functions are not
+    /// serialized, and rules that have them can't be serialized at all.
+    #[serde(skip)]
+    pub(in crate::compiler) host_functions: Vec<HostFunction>,
+
+    /// Time spent compiling the patterns of each rule. Only populated when
+    /// [`crate::Compiler::enable_compile_timing`] is used. Timings are not
+    /// serialized.
+    #[serde(skip)]
+    pub(in crate::compiler) compile_timings: Vec<(RuleId, Duration)>,
+
+    /// Private rules that were not compiled because no other rule used them.
+    /// See [`crate::Compiler::keep_dead_rules`]. They are not serialized.
+    #[serde(skip)]
+    pub(in crate::compiler) dead_rules: Vec<RuleId>,
+
+    /// The WASM module in binary form, before being compiled for the current
+    /// platform. It is not serialized.
+    #[cfg(feature = "wasm-wat")]
+    #[serde(skip)]
+    pub(in crate::compiler) wasm_bytes: Vec<u8>,
 }
 
 impl Rules {
@@ -131,10 +184,100 @@ impl Rules {
         self.warnings.as_slice()
     }
 
+    /// Returns the time spent compiling the patterns of each rule.
+    ///
+    /// Each item in the result is a pair with the rule's name, in the
+    /// form `namespace:identifier`, and the time spent extracting atoms
+    /// and building the sub-patterns for that rule. Rules are returned in
+    /// the order in which they were compiled.
+    ///
+    /// The result is empty unless the rules were compiled with
+    /// [`crate::Compiler::enable_compile_timing`]. Timings are not
+    /// serialized, so rules obtained by deserializing previously
+    /// serialized rules don't have them either.
+    pub fn compile_timings(&self) -> Vec<(String, Duration)> {
+        self.compile_timings
+            .iter()
+            .map(|(rule_id, duration)| {
+                let rule = self.get(*rule_id);
+                let name = format!(
+                    "{}:{}",
+                    self.ident_pool.get(rule.namespace_ident_id).unwrap(),
+                    self.ident_pool.get(rule.ident_id).unwrap()
+                );
+                (name, *duration)
+            })
+            .collect()
+    }
+
+    /// Returns the WASM code that evaluates the conditions of the rules, in
+    /// WAT (WebAssembly Text) format.
+    ///
+    /// Rules are grouped in functions, and functions are grouped by namespace,
+    /// so a single function can contain the code for multiple rules. The
+    /// result is an empty string for rules obtained by deserializing
+    /// previously serialized rules, as the WASM module is not serialized in
+    /// binary form.
+    ///
+    /// This is intended for debugging the code generated for the rules, and
+    /// it is available only when the `wasm-wat` feature is enabled.
+    #[cfg(feature = "wasm-wat")]
+    pub fn condition_wasm_wat(&self) -> String {
+        if self.wasm_bytes.is_empty() {
+            return String::new();
+        }
+        wasmprinter::print_bytes(self.wasm_bytes.as_slice())
+            .expect("WASM module is not valid")
+    }
+
+    /// Returns the descriptors of the modules imported by the rules.
+    ///
+    /// Each descriptor contains the tree of fields exposed by the module,
+    /// with their names and types, as they are derived from the module's
+    /// protobuf definition. Functions exported by the modules are not
+    /// included.
+    ///
+    /// ```
+    /// # use yara_x::compile;
+    /// let rules = compile(r#"import "pe" rule test { condition: pe.is_pe }"#).unwrap();
+    /// let descriptors = rules.module_descriptors();
+    ///
+    /// assert_eq!(descriptors[0].name, "pe");
+    /// assert!(descriptors[0].fields.iter().any(|f| f.name == "is_pe"));
+    /// ```
+    pub fn module_descriptors(&self) -> Vec<ModuleDescriptor> {
+        self.imports()
+            .filter_map(|name| {
+                let module = BUILTIN_MODULES.get(name)?;
+                let module_struct =
+                    types::Struct::from_proto_descriptor_and_msg(
+                        &module.root_struct_descriptor,
+                        None,
+                        true,
+                    );
+                Some(ModuleDescriptor {
+                    name: name.to_string(),
+                    fields: field_descriptors(&module_struct),
+                })
+            })
+            .collect()
+    }
+
     /// Serializes the rules as a sequence of bytes.
     ///
     /// The [`Rules`] can be restored back by passing the bytes to
     /// [`Rules::deserialize`].
+    ///
+    /// The serialized rules include native code generated by the version
+    /// of YARA-X that compiled them, and the layout of the data changes
+    /// between versions, so they can be deserialized only by the same
+    /// version. There's no way of producing serialized rules for older
+    /// versions, the rules must be compiled with the version that will
+    /// use them.
+    ///
+    /// The native code depends on the host where the rules were compiled,
+    /// use [`crate::Compiler::reproducible_serialization`] if the same
+    /// rules must produce the same bytes regardless of the host.
     pub fn serialize(&self) -> Result<Vec<u8>, SerializationError> {
         let mut bytes = Vec::new();
         self.serialize_into(&mut bytes)?;
@@ -143,11 +286,25 @@ impl Rules {
 
     /// Deserializes the rules from a sequence of bytes produced by
     /// [`Rules::serialize`].
+    ///
+    /// Malformed or truncated data results in an error, not a panic. The
+    /// identifiers and offsets that link the different parts of the rules
+    /// together are verified, and no allocation exceeds the size of the
+    /// input. However, these checks are not a security boundary: the
+    /// serialized rules can contain native code, and the code generated by
+    /// the compiler trusts the data it works with. This is true also for
+    /// rules compiled with [`crate::Compiler::reproducible_serialization`].
+    /// Only deserialize data that comes from a trusted source.
+    ///
+    /// Rules serialized with `Rules::serialize_signed` are accepted too,
+    /// but their signature is ignored. Use `Rules::deserialize_verified`
+    /// for verifying it. Both functions require the `signed-rules` feature.
     pub fn deserialize<B>(bytes: B) -> Result<Self, SerializationError>
     where
         B: AsRef<[u8]>,
     {
         let bytes = bytes.as_ref();
+        let bytes = split_signature(bytes).map_or(bytes, |(data, _)| data);
         let magic = b"YARA-X";
 
         if bytes.len() < magic.len() || &bytes[0..magic.len()] != magic {
@@ -158,14 +315,24 @@ impl Rules {
         let start = Instant::now();
 
         // Skip the magic and deserialize the remaining data.
+        // The limit prevents length fields in malformed data from causing
+        // allocations larger than the data itself.
         let mut rules = bincode::DefaultOptions::new()
             .with_varint_encoding()
+            .with_limit(bytes.len() as u64)
             .deserialize::<Self>(&bytes[magic.len()..])?;
 
+        rules.validate().map_err(|msg| {
+            SerializationError::InvalidEncoding(Box::new(
+                bincode::ErrorKind::Custom(msg),
+            ))
+        })?;
+
         #[cfg(feature = "logging")]
         info!("Deserialization time: {:?}", Instant::elapsed(&start));
 
         rules.build_ac_automaton();
+        rules.build_metadata_index();
 
         Ok(rules)
     }
@@ -178,6 +345,10 @@ impl Rules {
     where
         W: Write,
     {
+        if !self.host_functions.is_empty() {
+            return Err(SerializationError::HostFunctions);
+        }
+
         let mut writer = BufWriter::new(writer);
 
         // Write file header.
@@ -189,6 +360,53 @@ impl Rules {
             .serialize_into(writer, self)?)
     }
 
+    /// Serializes the rules as a sequence of bytes that carries a signature.
+    ///
+    /// The signature is a HMAC-SHA256 computed with `key` over the output
+    /// of [`Rules::serialize`], and is appended at the end of it. The result
+    /// can be passed to [`Rules::deserialize_verified`] with the same key,
+    /// which fails if the data was modified. It can also be passed to
+    /// [`Rules::deserialize`], which ignores the signature.
+    ///
+    /// This function is available only with the `signed-rules` feature.
+    #[cfg(feature = "signed-rules")]
+    pub fn serialize_signed(
+        &self,
+        key: &[u8],
+    ) -> Result<Vec<u8>, SerializationError> {
+        let mut bytes = self.serialize()?;
+        let signature = signature_mac(key, &bytes).finalize().into_bytes();
+        bytes.extend_from_slice(SIGNATURE_MAGIC);
+        bytes.extend_from_slice(&signature);
+        Ok(bytes)
+    }
+
+    /// Deserializes the rules from a sequence of bytes produced by
+    /// [`Rules::serialize_signed`], verifying their signature.
+    ///
+    /// Returns [`SerializationError::SignatureMismatch`] if the data was not
+    /// signed with `key`, if it was modified after being signed, or if it is
+    /// not signed at all.
+    ///
+    /// This function is available only with the `signed-rules` feature.
+    #[cfg(feature = "signed-rules")]
+    pub fn deserialize_verified<B>(
+        bytes: B,
+        key: &[u8],
+    ) -> Result<Self, SerializationError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let (data, signature) = split_signature(bytes.as_ref())
+            .ok_or(SerializationError::SignatureMismatch)?;
+
+        signature_mac(key, data)
+            .verify_slice(signature)
+            .map_err(|_| SerializationError::SignatureMismatch)?;
+
+        Self::deserialize(data)
+    }
+
     /// Deserializes the rules from a `reader`.
     pub fn deserialize_from<R>(
         mut reader: R,
@@ -201,6 +419,370 @@ impl Rules {
         Self::deserialize(bytes)
     }
 
+    /// Returns the [`CandidatePredicate`] for the rule with the given
+    /// namespace and identifier, or `None` if no such rule exists.
+    ///
+    /// Rules that were not declared inside an explicit namespace belong to
+    /// the `default` namespace.
+    pub fn candidate_predicate(
+        &self,
+        namespace: &str,
+        identifier: &str,
+    ) -> Option<&CandidatePredicate> {
+        self.rules
+            .iter()
+            .find(|rule| {
+                self.ident_pool.get(rule.namespace_ident_id) == Some(namespace)
+                    && self.ident_pool.get(rule.ident_id) == Some(identifier)
+            })
+            .map(|rule| &rule.candidate_predicate)
+    }
+
+    /// Returns the rules that have some metadata with identifier `key` and
+    /// a value for which `predicate` returns `true`.
+    ///
+    /// Rules are returned in the same order they were declared, and each
+    /// rule is returned only once even if it has multiple metadata entries
+    /// that satisfy the predicate.
+    ///
+    /// ```
+    /// # use yara_x::MetaValue;
+    /// let rules = yara_x::compile(r#"
+    ///     rule test { meta: author = "foo" condition: true }
+    /// "#).unwrap();
+    ///
+    /// let found = rules.find_by_meta("author", |value| {
+    ///     *value == MetaValue::String("foo")
+    /// });
+    ///
+    /// assert_eq!(found.len(), 1);
+    /// assert_eq!(found[0].identifier(), "test");
+    /// ```
+    pub fn find_by_meta<P>(&self, key: &str, predicate: P) -> Vec<RuleRef<'_>>
+    where
+        P: Fn(&ScannedMetaValue) -> bool,
+    {
+        let mut result: Vec<RuleRef> = Vec::new();
+
+        let entries = match self.metadata_index.get(key) {
+            Some(entries) => entries,
+            None => return result,
+        };
+
+        for (rule_id, i) in entries {
+            // Entries are sorted by rule, if the last rule in the result
+            // is this same rule, it was already found.
+            if result.last().is_some_and(|r| r.rule_id == *rule_id) {
+                continue;
+            }
+            let rule_info = self.get(*rule_id);
+            let (_, value) = &rule_info.metadata[*i];
+            if predicate(&ScannedMetaValue::from_compiled(self, value)) {
+                result.push(RuleRef {
+                    rules: self,
+                    rule_id: *rule_id,
+                    rule_info,
+                })
+            }
+        }
+
+        result
+    }
+
+    /// Returns the dependency graph between rules.
+    ///
+    /// Each item in the result is an edge `(rule, dependency)`, meaning that
+    /// the condition of `rule` uses `dependency`. Edges are sorted by `rule`,
+    /// in the order in which rules were declared, and then by the order in
+    /// which dependencies appear for the first time in the condition. Only
+    /// direct dependencies are included, the transitive ones can be found by
+    /// following the edges. Both ends of the edge carry their namespace, so
+    /// rules with the same identifier in different namespaces can be told
+    /// apart.
+    ///
+    /// Every non-global rule also depends on the global rules in its
+    /// namespace, as it can't match unless all of them match, even if its
+    /// condition doesn't use them. These implicit dependencies are included
+    /// after the ones that appear in the condition, in the order in which
+    /// the global rules were declared.
+    ///
+    /// Both ends of an edge are always in the same namespace, as rules
+    /// can't use rules declared in other namespaces (see
+    /// [`crate::Compiler::new_namespace`]).
+    ///
+    /// ```
+    /// let rules = yara_x::compile(r#"
+    ///     private rule foo { condition: true }
+    ///     rule bar { condition: foo }
+    /// "#).unwrap();
+    ///
+    /// let graph = rules.dependency_graph();
+    ///
+    /// assert_eq!(graph.len(), 1);
+    /// assert_eq!(graph[0].0.identifier(), "bar");
+    /// assert_eq!(graph[0].1.identifier(), "foo");
+    /// ```
+    pub fn dependency_graph(&self) -> Vec<(RuleRef<'_>, RuleRef<'_>)> {
+        // Global rules in each namespace, in the order in which they were
+        // declared.
+        let mut globals: FxHashMap<NamespaceId, Vec<RuleRef<'_>>> =
+            FxHashMap::default();
+
+        for (rule_id, rule_info) in self.rules.iter().enumerate() {
+            if rule_info.is_global {
+                let rule = RuleRef {
+                    rules: self,
+                    rule_id: rule_id.into(),
+                    rule_info,
+                };
+                globals.entry(rule_info.namespace_id).or_default().push(rule);
+            }
+        }
+
+        let mut graph = Vec::new();
+
+        for (rule_id, rule_info) in self.rules.iter().enumerate() {
+            let rule =
+                RuleRef { rules: self, rule_id: rule_id.into(), rule_info };
+
+            graph.extend(
+                rule.dependencies().map(|dependency| (rule, dependency)),
+            );
+
+            if rule_info.is_global {
+                continue;
+            }
+
+            if let Some(globals) = globals.get(&rule_info.namespace_id) {
+                graph.extend(
+                    globals
+                        .iter()
+                        .filter(|global| {
+                            !rule_info.dependencies.contains(&global.rule_id)
+                        })
+                        .map(|global| (rule, *global)),
+                );
+            }
+        }
+
+        graph
+    }
+
+    /// Returns the private rules that were not compiled because no other
+    /// rule used them.
+    ///
+    /// These rules are still present, but they never match. The result is
+    /// empty if the rules were compiled with
+    /// [`crate::Compiler::keep_dead_rules`] enabled. Dead rules are not
+    /// serialized, so rules obtained by deserializing previously serialized
+    /// rules don't have them either.
+    ///
+    /// ```
+    /// let rules = yara_x::compile(r#"
+    ///     private rule foo { condition: true }
+    ///     private rule bar { condition: true }
+    ///     rule baz { condition: foo }
+    /// "#).unwrap();
+    ///
+    /// let dead_rules = rules.dead_rules();
+    ///
+    /// assert_eq!(dead_rules.len(), 1);
+    /// assert_eq!(dead_rules[0].identifier(), "bar");
+    /// ```
+    pub fn dead_rules(&self) -> Vec<RuleRef<'_>> {
+        self.dead_rules
+            .iter()
+            .map(|rule_id| RuleRef {
+                rules: self,
+                rule_id: *rule_id,
+                rule_info: self.get(*rule_id),
+            })
+            .collect()
+    }
+
+    /// Returns a graph in [DOT][1] format that represents the Aho-Corasick
+    /// automaton built from the atoms extracted from the patterns.
+    ///
+    /// Each node in the graph corresponds to a prefix of some atom, and
+    /// edges are labelled with the byte that leads from one prefix to the
+    /// next one. Dashed edges are the automaton's failure transitions, only
+    /// the ones that don't go back to the root node are shown. Nodes where
+    /// some atom ends are drawn with a double circle, and labelled with the
+    /// atom and the patterns it belongs to, like `test:$a`.
+    ///
+    /// The graph is meant for debugging purposes, and includes up to
+    /// [`Rules::MAX_DOT_ATOMS`] atoms. If there are more atoms, the graph
+    /// is incomplete. Use [`Rules::rule_prefilter_to_dot`] for obtaining the
+    /// graph for a single rule.
+    ///
+    /// ```
+    /// let rules = yara_x::compile(r#"
+    ///     rule test { strings: $a = "foobar" condition: $a }
+    /// "#).unwrap();
+    ///
+    /// assert!(rules.prefilter_to_dot().starts_with("digraph prefilter {"));
+    /// ```
+    ///
+    /// [1]: https://graphviz.org/doc/info/lang.html
+    pub fn prefilter_to_dot(&self) -> String {
+        self.atoms_to_dot(|_| true)
+    }
+
+    /// Like [`Rules::prefilter_to_dot`], but the graph contains only the
+    /// atoms extracted from the patterns in the rule with the given namespace
+    /// and identifier. Returns `None` if no such rule exists.
+    pub fn rule_prefilter_to_dot(
+        &self,
+        namespace: &str,
+        identifier: &str,
+    ) -> Option<String> {
+        let rule = self.rules.iter().find(|rule| {
+            self.ident_pool.get(rule.namespace_ident_id) == Some(namespace)
+                && self.ident_pool.get(rule.ident_id) == Some(identifier)
+        })?;
+
+        let patterns: Vec<PatternId> =
+            rule.patterns.iter().map(|(_, pattern_id)| *pattern_id).collect();
+
+        Some(self.atoms_to_dot(|pattern_id| patterns.contains(&pattern_id)))
+    }
+
+    /// Maximum number of atoms included in the graphs returned by
+    /// [`Rules::prefilter_to_dot`] and [`Rules::rule_prefilter_to_dot`].
+    pub const MAX_DOT_ATOMS: usize = 2048;
+
+    fn atoms_to_dot<F>(&self, filter: F) -> String
+    where
+        F: Fn(PatternId) -> bool,
+    {
+        // Names of the patterns, in the form `rule:$ident`. The same
+        // pattern can be shared by multiple rules.
+        let mut pattern_names: FxHashMap<PatternId, Vec<String>> =
+            FxHashMap::default();
+
+        for rule in &self.rules {
+            let rule_ident = self.ident_pool.get(rule.ident_id).unwrap();
+            for (ident_id, pattern_id) in &rule.patterns {
+                pattern_names.entry(*pattern_id).or_default().push(format!(
+                    "{}:{}",
+                    rule_ident,
+                    self.ident_pool.get(*ident_id).unwrap()
+                ));
+            }
+        }
+
+        let atoms = self.atoms.iter().filter(|atom| {
+            filter(self.get_sub_pattern(atom.sub_pattern_id).0)
+        });
+
+        let num_atoms = atoms.clone().count();
+
+        // Build a trie with the selected atoms, `children` contains the
+        // transitions for each node, and `outputs` the patterns for the
+        // atoms that end at each node. Node 0 is the root.
+        let mut children: Vec<BTreeMap<u8, usize>> = vec![BTreeMap::new()];
+        let mut outputs: Vec<Vec<PatternId>> = vec![Vec::new()];
+
+        for atom in atoms.take(Self::MAX_DOT_ATOMS) {
+            let mut node = 0;
+            for byte in atom.as_slice() {
+                node = match children[node].get(byte) {
+                    Some(next) => *next,
+                    None => {
+                        children.push(BTreeMap::new());
+                        outputs.push(Vec::new());
+                        let next = children.len() - 1;
+                        children[node].insert(*byte, next);
+                        next
+                    }
+                };
+            }
+            let pattern_id = self.get_sub_pattern(atom.sub_pattern_id).0;
+            if !outputs[node].contains(&pattern_id) {
+                outputs[node].push(pattern_id);
+            }
+        }
+
+        // Compute the failure transitions by traversing the trie in
+        // breadth-first order, and the bytes that lead to each node.
+        let mut failure = vec![0; children.len()];
+        let mut prefixes: Vec<Vec<u8>> = vec![Vec::new(); children.len()];
+        let mut queue = VecDeque::from([0]);
+
+        while let Some(node) = queue.pop_front() {
+            for (byte, child) in &children[node] {
+                let mut prefix = prefixes[node].clone();
+                prefix.push(*byte);
+                prefixes[*child] = prefix;
+
+                if node != 0 {
+                    let mut f = failure[node];
+                    loop {
+                        if let Some(next) = children[f].get(byte) {
+                            failure[*child] = *next;
+                            break;
+                        }
+                        if f == 0 {
+                            break;
+                        }
+                        f = failure[f];
+                    }
+                }
+
+                queue.push_back(*child);
+            }
+        }
+
+        let mut dot = String::new();
+
+        dot.push_str("digraph prefilter {\n");
+        dot.push_str("  rankdir=LR;\n");
+        dot.push_str("  node [shape=circle, label=\"\"];\n");
+
+        if num_atoms > Self::MAX_DOT_ATOMS {
+            dot.push_str(&format!(
+                "  // {} of {} atoms omitted\n",
+                num_atoms - Self::MAX_DOT_ATOMS,
+                num_atoms
+            ));
+        }
+
+        for (node, patterns) in outputs.iter().enumerate() {
+            if patterns.is_empty() {
+                continue;
+            }
+            let mut label = escape_dot(&prefixes[node]);
+            for pattern_id in patterns {
+                for name in pattern_names.get(pattern_id).into_iter().flatten()
+                {
+                    label.push_str("\\n");
+                    label.push_str(&name.replace('\\', "\\\\"));
+                }
+            }
+            dot.push_str(&format!(
+                "  n{node} [shape=doublecircle, label=\"{label}\"];\n"
+            ));
+        }
+
+        for (node, transitions) in children.iter().enumerate() {
+            for (byte, child) in transitions {
+                dot.push_str(&format!(
+                    "  n{node} -> n{child} [label=\"{}\"];\n",
+                    escape_dot(&[*byte])
+                ));
+            }
+        }
+
+        for (node, f) in failure.iter().enumerate() {
+            if *f != 0 {
+                dot.push_str(&format!("  n{node} -> n{f} [style=dashed];\n"));
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
     /// Returns a [`RuleInfo`] given its [`RuleId`].
     ///
     /// # Panics
@@ -269,7 +851,6 @@ impl Rules {
         None
     }
 
-    #[cfg(feature = "rules-profiling")]
     #[inline]
     pub(crate) fn rules(&self) -> &[RuleInfo] {
         self.rules.as_slice()
@@ -295,15 +876,20 @@ impl Rules {
         self.rules.len()
     }
 
+    #[inline]
+    pub(crate) fn num_non_private_rules(&self) -> usize {
+        self.num_non_private_rules
+    }
+
     #[inline]
     pub(crate) fn num_patterns(&self) -> usize {
         self.num_patterns
     }
 
-    /// Returns the Aho-Corasick automaton that allows to search for pattern
-    /// atoms.
+    /// Returns the table of Aho-Corasick automata that allows to search for
+    /// pattern atoms.
     #[inline]
-    pub(crate) fn ac_automaton(&self) -> &AhoCorasick {
+    pub(crate) fn ac_automaton(&self) -> &AtomTable {
         self.ac.as_ref().expect("Aho-Corasick automaton not compiled")
     }
 
@@ -318,42 +904,33 @@ impl Rules {
         #[cfg(feature = "logging")]
         let mut num_atoms = [0_usize; 6];
 
-        let atoms = self.atoms.iter().map(|x| {
-            #[cfg(feature = "logging")]
-            {
-                match x.atom.len() {
-                    atom_len @ 0..=4 => num_atoms[atom_len] += 1,
-                    _ => num_atoms[num_atoms.len() - 1] += 1,
-                }
-
-                if x.atom.len() < 2 {
-                    let (rule_id, pattern_ident_id) = self
-                        .get_rule_and_pattern_by_sub_pattern_id(
-                            x.sub_pattern_id,
-                        )
-                        .unwrap();
-
-                    let rule = self.get(rule_id);
-
-                    info!(
-                            "Very short atom in pattern `{}` in rule `{}:{}` (length: {})",
-                            self.ident_pool.get(pattern_ident_id).unwrap(),
-                            self.ident_pool
-                                .get(rule.namespace_ident_id)
-                                .unwrap(),
-                            self.ident_pool.get(rule.ident_id).unwrap(),
-                            x.atom.len()
-                        );
-                }
+        #[cfg(feature = "logging")]
+        for x in self.atoms.iter() {
+            match x.atom.len() {
+                atom_len @ 0..=4 => num_atoms[atom_len] += 1,
+                _ => num_atoms[num_atoms.len() - 1] += 1,
             }
 
-            x.atom.as_ref()
-        });
+            if x.atom.len() < 2 {
+                let (rule_id, pattern_ident_id) = self
+                    .get_rule_and_pattern_by_sub_pattern_id(x.sub_pattern_id)
+                    .unwrap();
+
+                let rule = self.get(rule_id);
+
+                info!(
+                        "Very short atom in pattern `{}` in rule `{}:{}` (length: {})",
+                        self.ident_pool.get(pattern_ident_id).unwrap(),
+                        self.ident_pool
+                            .get(rule.namespace_ident_id)
+                            .unwrap(),
+                        self.ident_pool.get(rule.ident_id).unwrap(),
+                        x.atom.len()
+                    );
+            }
+        }
 
-        self.ac = Some(
-            AhoCorasick::new(atoms)
-                .expect("failed to build Aho-Corasick automaton"),
-        );
+        self.ac = Some(AtomTable::new(self.atoms.as_slice()));
 
         #[cfg(feature = "logging")]
         {
@@ -378,6 +955,192 @@ impl Rules {
         }
     }
 
+    pub(crate) fn build_metadata_index(&mut self) {
+        let mut index: FxHashMap<String, Vec<(RuleId, usize)>> =
+            FxHashMap::default();
+
+        for (rule_id, rule_info) in self.rules.iter().enumerate() {
+            for (i, (ident_id, _)) in rule_info.metadata.iter().enumerate() {
+                let ident = self.ident_pool.get(*ident_id).unwrap();
+                index
+                    .entry(ident.to_string())
+                    .or_default()
+                    .push((rule_id.into(), i));
+            }
+        }
+
+        self.metadata_index = index;
+    }
+
+    /// Checks that every identifier and offset in deserialized rules
+    /// refers to an existing item, returning a description of the first
+    /// inconsistency found.
+    fn validate(&self) -> Result<(), String> {
+        let check_ident = |id: IdentId| {
+            self.ident_pool
+                .get(id)
+                .ok_or_else(|| format!("unknown identifier {:?}", id))
+        };
+
+        let check_literal = |id: LiteralId| {
+            self.lit_pool
+                .get(id)
+                .map(|_| ())
+                .ok_or_else(|| format!("unknown literal {:?}", id))
+        };
+
+        let check_pattern = |id: PatternId| {
+            if usize::from(id) < self.num_patterns {
+                Ok(())
+            } else {
+                Err(format!("unknown pattern {:?}", id))
+            }
+        };
+
+        let check_sub_pattern = |id: SubPatternId| {
+            if (id.0 as usize) < self.sub_patterns.len() {
+                Ok(())
+            } else {
+                Err(format!("unknown sub-pattern {:?}", id))
+            }
+        };
+
+        for module_ident_id in &self.imported_modules {
+            let module = check_ident(*module_ident_id)?;
+            if !crate::modules::BUILTIN_MODULES.contains_key(module) {
+                return Err(format!("unknown module `{}`", module));
+            }
+        }
+
+        let mut num_rule_patterns = 0;
+
+        for rule in &self.rules {
+            check_ident(rule.namespace_ident_id)?;
+            check_ident(rule.ident_id)?;
+            for (ident_id, value) in &rule.metadata {
+                check_ident(*ident_id)?;
+                if let MetaValue::String(id) | MetaValue::Bytes(id) = value {
+                    check_literal(*id)?;
+                }
+            }
+            for tag in &rule.tags {
+                check_ident(*tag)?;
+            }
+            for (ident_id, pattern_id) in &rule.patterns {
+                check_ident(*ident_id)?;
+                check_pattern(*pattern_id)?;
+            }
+            for rule_id in &rule.dependencies {
+                if usize::from(*rule_id) >= self.rules.len() {
+                    return Err(format!("unknown rule {:?}", rule_id));
+                }
+            }
+            for module_ident_id in &rule.modules {
+                check_ident(*module_ident_id)?;
+            }
+            num_rule_patterns += rule.patterns.len();
+        }
+
+        if self.num_non_private_rules
+            != self.rules.iter().filter(|rule| !rule.is_private).count()
+        {
+            return Err(format!(
+                "invalid number of non-private rules: {}",
+                self.num_non_private_rules
+            ));
+        }
+
+        // Every pattern belongs to at least one rule. Without this check
+        // a bogus `num_patterns` could make the scanner allocate a huge
+        // amount of memory.
+        if self.num_patterns > num_rule_patterns {
+            return Err(format!(
+                "invalid number of patterns: {}",
+                self.num_patterns
+            ));
+        }
+
+        for (pattern_id, sub_pattern) in &self.sub_patterns {
+            check_pattern(*pattern_id)?;
+            match sub_pattern {
+                SubPattern::Literal { pattern, .. }
+                | SubPattern::LiteralChainHead { pattern, .. }
+                | SubPattern::Xor { pattern, .. }
+                | SubPattern::Base64 { pattern, .. }
+                | SubPattern::Base64Wide { pattern, .. } => {
+                    check_literal(*pattern)?
+                }
+                SubPattern::LiteralChainTail {
+                    pattern, chained_to, ..
+                } => {
+                    check_literal(*pattern)?;
+                    check_sub_pattern(*chained_to)?;
+                }
+                SubPattern::RegexpChainTail { chained_to, .. } => {
+                    check_sub_pattern(*chained_to)?
+                }
+                SubPattern::CustomBase64 { pattern, alphabet, .. }
+                | SubPattern::CustomBase64Wide { pattern, alphabet, .. } => {
+                    check_literal(*pattern)?;
+                    check_literal(*alphabet)?;
+                }
+                SubPattern::Regexp { .. }
+                | SubPattern::RegexpChainHead { .. } => {}
+            }
+        }
+
+        for sub_pattern_id in &self.anchored_sub_patterns {
+            check_sub_pattern(*sub_pattern_id)?;
+        }
+
+        // Locations in `re_code` where the code for FastVM and PikeVM
+        // starts, the code is validated after collecting all of them.
+        let mut fast_vm_code = Vec::new();
+        let mut pike_vm_code = Vec::new();
+
+        for atom in &self.atoms {
+            check_sub_pattern(atom.sub_pattern_id)?;
+            let fast_regexp = match self.get_sub_pattern(atom.sub_pattern_id) {
+                (_, SubPattern::Regexp { flags })
+                | (_, SubPattern::RegexpChainHead { flags })
+                | (_, SubPattern::RegexpChainTail { flags, .. }) => {
+                    flags.contains(SubPatternFlags::FastRegexp)
+                }
+                _ => false,
+            };
+            let fwd = atom.fwd_code.as_ref().map(|loc| loc.location());
+            let bck = atom.bck_code.as_ref().map(|loc| loc.location());
+            for location in fwd.into_iter().chain(bck) {
+                if location >= self.re_code.len() {
+                    return Err(format!("invalid code location {}", location));
+                }
+                if fast_regexp {
+                    fast_vm_code.push(location);
+                } else {
+                    pike_vm_code.push(location);
+                }
+            }
+        }
+
+        re::fast::validate_code(self.re_code.as_slice(), fast_vm_code)
+            .and_then(|_| {
+                re::thompson::validate_code(
+                    self.re_code.as_slice(),
+                    pike_vm_code,
+                )
+            })
+            .map_err(|location| {
+                format!("invalid regexp code at location {}", location)
+            })?;
+
+        bincode::DefaultOptions::new()
+            .with_limit(self.serialized_globals.len() as u64)
+            .deserialize::<types::Struct>(self.serialized_globals.as_slice())
+            .map_err(|err| format!("invalid global variables: {}", err))?;
+
+        Ok(())
+    }
+
     #[inline]
     pub(crate) fn lit_pool(&self) -> &BStringPool<LiteralId> {
         &self.lit_pool
@@ -397,35 +1160,125 @@ impl Rules {
 
     #[inline]
     pub(crate) fn wasm_mod(&self) -> &wasmtime::Module {
-        &self.wasm_mod
+        &self.wasm_mod.compiled
+    }
+
+    #[inline]
+    pub(crate) fn host_functions(&self) -> &[HostFunction] {
+        self.host_functions.as_slice()
+    }
+}
+
+/// Marker that precedes the signature in rules serialized with
+/// [`Rules::serialize_signed`].
+const SIGNATURE_MAGIC: &[u8] = b"YARA-X-HMAC-SHA256";
+
+/// Length of the signature that follows [`SIGNATURE_MAGIC`].
+const SIGNATURE_LEN: usize = 32;
+
+/// Splits signed serialized rules into the serialized data and the signature.
+/// Returns `None` if the rules are not signed.
+fn split_signature(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let data_len =
+        bytes.len().checked_sub(SIGNATURE_MAGIC.len() + SIGNATURE_LEN)?;
+
+    let (data, trailer) = bytes.split_at(data_len);
+    let (magic, signature) = trailer.split_at(SIGNATURE_MAGIC.len());
+
+    (magic == SIGNATURE_MAGIC).then_some((data, signature))
+}
+
+/// Returns the HMAC-SHA256 for `data`, computed with `key`.
+#[cfg(feature = "signed-rules")]
+fn signature_mac(key: &[u8], data: &[u8]) -> Hmac<Sha256> {
+    // HMAC accepts keys of any length, this can't fail.
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(data);
+    mac
+}
+
+/// Magic number at the start of any module in WASM binary format.
+const WASM_MAGIC: &[u8] = b"\0asm";
+
+/// The WASM module containing the code for the conditions of [`Rules`].
+///
+/// By default, the module is serialized as native code for the current
+/// platform, which is fast to deserialize but depends on the host where the
+/// rules were compiled. When the rules are compiled with
+/// [`crate::Compiler::reproducible_serialization`], the module is serialized
+/// in WASM binary form instead, which is the same regardless of the host,
+/// and it's translated into native code while being deserialized.
+pub(in crate::compiler) struct WasmModule {
+    /// The module compiled into native code for the current platform.
+    compiled: wasmtime::Module,
+    /// The module in WASM binary form, only present when it must be
+    /// serialized in that form.
+    portable: Option<Vec<u8>>,
+}
+
+impl WasmModule {
+    /// Creates a [`WasmModule`] from its native code. `portable` contains
+    /// the module in WASM binary form, if it must be serialized that way.
+    pub(in crate::compiler) fn new(
+        compiled: wasmtime::Module,
+        portable: Option<Vec<u8>>,
+    ) -> Self {
+        Self { compiled, portable }
     }
 }
 
-fn serialize_wasm_mod<S>(
-    wasm_mod: &wasmtime::Module,
-    serializer: S,
-) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    let bytes = wasm_mod
-        .serialize()
-        .map_err(|err| serde::ser::Error::custom(err.to_string()))?;
-
-    serializer.serialize_bytes(bytes.as_slice())
+impl Serialize for WasmModule {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if let Some(portable) = &self.portable {
+            return serializer.serialize_bytes(portable.as_slice());
+        }
+
+        let bytes = self
+            .compiled
+            .serialize()
+            .map_err(|err| serde::ser::Error::custom(err.to_string()))?;
+
+        serializer.serialize_bytes(bytes.as_slice())
+    }
 }
 
-pub fn deserialize_wasm_mod<'de, D>(
-    deserializer: D,
-) -> Result<wasmtime::Module, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let bytes: &[u8] = Deserialize::deserialize(deserializer)?;
+impl<'de> Deserialize<'de> for WasmModule {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes: &[u8] = Deserialize::deserialize(deserializer)?;
+
+        // Modules serialized in WASM binary form are validated and compiled
+        // again. They are kept in that form, so that serializing the rules
+        // again produces the same output.
+        if bytes.starts_with(WASM_MAGIC) {
+            let compiled =
+                wasmtime::Module::from_binary(&crate::wasm::ENGINE, bytes)
+                    .map_err(|err| {
+                        serde::de::Error::custom(err.to_string())
+                    })?;
+
+            return Ok(Self::new(compiled, Some(bytes.to_vec())));
+        }
+
+        // Native code is loaded without any validation, arbitrary data
+        // produced by the fuzzer must never reach this point.
+        if cfg!(fuzzing) {
+            return Err(serde::de::Error::custom(
+                "native code is not accepted while fuzzing",
+            ));
+        }
 
-    unsafe {
-        wasmtime::Module::deserialize(&crate::wasm::ENGINE, bytes)
-            .map_err(|err| serde::de::Error::custom(err.to_string()))
+        let compiled = unsafe {
+            wasmtime::Module::deserialize(&crate::wasm::ENGINE, bytes)
+                .map_err(|err| serde::de::Error::custom(err.to_string()))?
+        };
+
+        Ok(Self::new(compiled, None))
     }
 }
 
@@ -454,7 +1307,7 @@ impl fmt::Debug for Rules {
 }
 
 /// Metadata values.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub(crate) enum MetaValue {
     Bool(bool),
     Integer(i64),
@@ -463,6 +1316,115 @@ pub(crate) enum MetaValue {
     Bytes(LiteralId),
 }
 
+/// A reference to a rule in [`Rules`], as returned by
+/// [`Rules::find_by_meta`], [`Rules::dependency_graph`] and
+/// [`Rules::dead_rules`].
+#[derive(Clone, Copy)]
+pub struct RuleRef<'r> {
+    rules: &'r Rules,
+    rule_id: RuleId,
+    rule_info: &'r RuleInfo,
+}
+
+impl<'r> RuleRef<'r> {
+    /// Returns the rule's name.
+    pub fn identifier(&self) -> &'r str {
+        self.rules.ident_pool.get(self.rule_info.ident_id).unwrap()
+    }
+
+    /// Returns the rule's namespace.
+    pub fn namespace(&self) -> &'r str {
+        self.rules.ident_pool.get(self.rule_info.namespace_ident_id).unwrap()
+    }
+
+    /// Returns the metadata associated to this rule.
+    pub fn metadata(&self) -> Metadata<'r, 'r> {
+        Metadata::new(self.rules, self.rule_info)
+    }
+
+    /// Returns the rule's doc comment.
+    ///
+    /// The result is `None` if the rule doesn't have a doc comment, or if
+    /// the rules were not compiled with
+    /// [`crate::Compiler::embed_doc_comments`] enabled.
+    pub fn doc_comment(&self) -> Option<&'r str> {
+        self.rule_info.doc_comment.as_deref()
+    }
+
+    /// Returns true if the rule is private.
+    pub fn is_private(&self) -> bool {
+        self.rule_info.is_private
+    }
+
+    /// Returns true if the rule is global.
+    pub fn is_global(&self) -> bool {
+        self.rule_info.is_global
+    }
+
+    /// Returns the rules used in this rule's condition, in the order in
+    /// which they appear for the first time.
+    pub fn dependencies(&self) -> impl Iterator<Item = RuleRef<'r>> + 'r {
+        let rules = self.rules;
+        self.rule_info.dependencies.iter().map(move |rule_id| RuleRef {
+            rules,
+            rule_id: *rule_id,
+            rule_info: rules.get(*rule_id),
+        })
+    }
+}
+
+/// Describes the fields exposed by a module, as returned by
+/// [`Rules::module_descriptors`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleDescriptor {
+    /// The module's name (e.g: `pe`).
+    pub name: String,
+    /// The fields at the top level of the module.
+    pub fields: Vec<FieldDescriptor>,
+}
+
+/// Describes a field in a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDescriptor {
+    /// The field's name.
+    pub name: String,
+    /// The field's type (e.g: `integer`, `string`, `struct`, `array`).
+    pub ty: String,
+    /// If the field is a structure, its fields. For arrays and maps of
+    /// structures these are the fields of the structures stored in the
+    /// array or map. Empty for every other type.
+    pub fields: Vec<FieldDescriptor>,
+}
+
+/// Returns the descriptors for the fields in `s`.
+pub(in crate::compiler) fn field_descriptors(
+    s: &types::Struct,
+) -> Vec<FieldDescriptor> {
+    s.fields()
+        .map(|(name, field)| {
+            let nested = match &field.type_value {
+                TypeValue::Struct(s) => Some(s.clone()),
+                TypeValue::Array(a) => match a.deputy() {
+                    TypeValue::Struct(s) => Some(s),
+                    _ => None,
+                },
+                TypeValue::Map(m) => match m.deputy() {
+                    TypeValue::Struct(s) => Some(s),
+                    _ => None,
+                },
+                _ => None,
+            };
+            FieldDescriptor {
+                name: name.to_string(),
+                ty: field.type_value.ty().to_string(),
+                fields: nested
+                    .map(|s| field_descriptors(&s))
+                    .unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
 /// Information about each of the individual rules included in [`Rules`].
 #[derive(Serialize, Deserialize)]
 pub(crate) struct RuleInfo {
@@ -479,12 +1441,271 @@ pub(crate) struct RuleInfo {
     pub(crate) ident_span: Span,
     /// Metadata associated to the rule.
     pub(crate) metadata: Vec<(IdentId, MetaValue)>,
+    /// Tags associated to the rule, in the order they were declared.
+    pub(crate) tags: Vec<IdentId>,
     /// Vector with all the patterns defined by this rule.
     pub(crate) patterns: Vec<(IdentId, PatternId)>,
     /// True if the rule is global.
     pub(crate) is_global: bool,
     /// True if the rule is private.
     pub(crate) is_private: bool,
+    /// Necessary conditions for the rule to match, derived from its
+    /// condition.
+    pub(crate) candidate_predicate: CandidatePredicate,
+    /// Rules used in the condition of this rule, in the order in which
+    /// they appear for the first time.
+    pub(crate) dependencies: Vec<RuleId>,
+    /// Modules used in the condition of this rule, in the order in which
+    /// they appear for the first time.
+    pub(crate) modules: Vec<IdentId>,
+    /// The rule's source code, if the rules were compiled with
+    /// [`crate::Compiler::embed_sources`] enabled.
+    pub(crate) source: Option<String>,
+    /// The rule's doc comment, if the rules were compiled with
+    /// [`crate::Compiler::embed_doc_comments`] enabled.
+    pub(crate) doc_comment: Option<String>,
+}
+
+/// Necessary conditions for a rule to match, derived at compile time from
+/// the rule's condition.
+///
+/// The predicate is built from the top-level conjuncts of the condition
+/// (i.e: the operands of the outermost `and` operators) that compare
+/// `filesize` against a constant, or that check that an integer read at a
+/// constant offset with functions like `uint16` or `uint32be` is equal to
+/// a constant. For instance, for the condition
+/// `uint16(0) == 0x5A4D and filesize < 1MB and $a` the predicate says that
+/// the file must start with `MZ` and that its size must be lower than 1MB.
+///
+/// Any other part of the condition is ignored, which means that a rule may
+/// be a candidate and yet not match. The opposite is not possible: if a
+/// file is not a candidate for some rule, the rule won't match that file.
+/// Rules where no necessary condition could be determined are candidates
+/// for every file. Notice that conditions on module fields like `pe.is_pe`
+/// are not taken into account, use `uint16(0) == 0x5A4D` instead if you
+/// want PE rules to be discarded by this predicate.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CandidatePredicate {
+    min_filesize: Option<u64>,
+    max_filesize: Option<u64>,
+    header: Vec<(usize, Vec<u8>)>,
+}
+
+impl CandidatePredicate {
+    /// Returns true if the rule is a candidate for every file, which happens
+    /// when no necessary condition could be derived from its condition.
+    pub fn is_always_candidate(&self) -> bool {
+        self.min_filesize.is_none()
+            && self.max_filesize.is_none()
+            && self.header.is_empty()
+    }
+
+    /// Minimum file size required by the rule, if any.
+    pub fn min_filesize(&self) -> Option<u64> {
+        self.min_filesize
+    }
+
+    /// Maximum file size allowed by the rule, if any.
+    pub fn max_filesize(&self) -> Option<u64> {
+        self.max_filesize
+    }
+
+    /// An iterator that yields `(offset, bytes)` pairs, meaning that the
+    /// data must contain `bytes` at `offset` for the rule to match.
+    pub fn header_bytes(&self) -> impl Iterator<Item = (usize, &[u8])> {
+        self.header.iter().map(|(offset, bytes)| (*offset, bytes.as_slice()))
+    }
+
+    /// Returns true if a file with the given size, and starting with the
+    /// bytes in `header`, is a candidate for the rule.
+    ///
+    /// `header` can be a prefix of the file of any length. Required bytes
+    /// that lay beyond the end of `header` are considered unknown, and
+    /// don't prevent the file from being a candidate.
+    pub fn is_candidate(&self, filesize: u64, header: &[u8]) -> bool {
+        if self.min_filesize.is_some_and(|min| filesize < min) {
+            return false;
+        }
+        if self.max_filesize.is_some_and(|max| filesize > max) {
+            return false;
+        }
+        self.header.iter().all(|(offset, bytes)| {
+            let end = (*offset as u64).saturating_add(bytes.len() as u64);
+            // Reading beyond the end of the file produces an undefined
+            // value, so the condition can't be true.
+            if end > filesize {
+                return false;
+            }
+            match header.get(*offset..*offset + bytes.len()) {
+                Some(actual) => actual == bytes.as_slice(),
+                None => true,
+            }
+        })
+    }
+
+    /// Builds the predicate for a rule's condition.
+    pub(in crate::compiler) fn from_condition(condition: &Expr) -> Self {
+        let mut predicate = Self::default();
+        predicate.add_conjunct(condition);
+        predicate
+    }
+
+    fn add_conjunct(&mut self, expr: &Expr) {
+        match expr {
+            Expr::And { operands } => {
+                for operand in operands {
+                    self.add_conjunct(operand);
+                }
+            }
+            Expr::Eq { lhs, rhs } => {
+                if let Some(size) = filesize_cmp(lhs, rhs) {
+                    self.set_min_filesize(size);
+                    self.set_max_filesize(size);
+                } else if let Some(header) =
+                    header_eq(lhs, rhs).or_else(|| header_eq(rhs, lhs))
+                {
+                    self.header.push(header);
+                }
+            }
+            Expr::Ge { lhs, rhs } => {
+                if let Some(size) = filesize_cmp(lhs, rhs) {
+                    self.set_min_filesize(size);
+                } else if let Some(size) = filesize_cmp(rhs, lhs) {
+                    self.set_max_filesize(size);
+                }
+            }
+            Expr::Gt { lhs, rhs } => {
+                if let Some(size) = filesize_cmp(lhs, rhs) {
+                    self.set_min_filesize(size.saturating_add(1));
+                } else if let Some(size) = filesize_cmp(rhs, lhs) {
+                    self.set_max_filesize(size.saturating_sub(1));
+                }
+            }
+            Expr::Le { lhs, rhs } => {
+                if let Some(size) = filesize_cmp(lhs, rhs) {
+                    self.set_max_filesize(size);
+                } else if let Some(size) = filesize_cmp(rhs, lhs) {
+                    self.set_min_filesize(size);
+                }
+            }
+            Expr::Lt { lhs, rhs } => {
+                if let Some(size) = filesize_cmp(lhs, rhs) {
+                    self.set_max_filesize(size.saturating_sub(1));
+                } else if let Some(size) = filesize_cmp(rhs, lhs) {
+                    self.set_min_filesize(size.saturating_add(1));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn set_min_filesize(&mut self, size: u64) {
+        self.min_filesize =
+            Some(self.min_filesize.map_or(size, |min| min.max(size)));
+    }
+
+    fn set_max_filesize(&mut self, size: u64) {
+        self.max_filesize =
+            Some(self.max_filesize.map_or(size, |max| max.min(size)));
+    }
+}
+
+/// If `lhs` is `filesize` and `rhs` is a non-negative integer constant,
+/// returns the constant.
+fn filesize_cmp(lhs: &Expr, rhs: &Expr) -> Option<u64> {
+    match lhs {
+        Expr::Filesize => const_integer(rhs)?.try_into().ok(),
+        _ => None,
+    }
+}
+
+/// If `lhs` is a call to one of the `uintXX`/`intXX` functions with a
+/// constant offset, and `rhs` is an integer constant, returns the offset
+/// and the bytes that must be present at that offset.
+fn header_eq(lhs: &Expr, rhs: &Expr) -> Option<(usize, Vec<u8>)> {
+    let Expr::FuncCall(fn_call) = lhs else {
+        return None;
+    };
+    // Only the overloads that receive just the offset read from the scanned
+    // data. The ones with a default value return it when the offset is out
+    // of bounds, which means that the file can match without having those
+    // bytes.
+    if fn_call.args.len() != 1 {
+        return None;
+    }
+    let Expr::Ident { symbol } = &fn_call.callable else {
+        return None;
+    };
+    let SymbolKind::Func(func) = symbol.kind() else {
+        return None;
+    };
+
+    let signature = func.signatures().get(fn_call.signature_index)?;
+    let name = signature.mangled_name.as_str().split('@').next()?;
+
+    let (signed, rest) = match name.strip_prefix("uint") {
+        Some(rest) => (false, rest),
+        None => (true, name.strip_prefix("int")?),
+    };
+
+    let (bits, big_endian) = match rest.strip_suffix("be") {
+        Some(bits) => (bits, true),
+        None => (rest, false),
+    };
+
+    let width: usize = match bits {
+        "8" => 1,
+        "16" => 2,
+        "32" => 4,
+        _ => return None,
+    };
+
+    let offset: usize =
+        const_integer(fn_call.args.first()?)?.try_into().ok()?;
+    let value = const_integer(rhs)?;
+
+    // If the value doesn't fit in the integer type the comparison is always
+    // false, this is not handled here.
+    let bits = 8 * width as u32;
+    let (min, max) = if signed {
+        (-(1_i64 << (bits - 1)), (1_i64 << (bits - 1)) - 1)
+    } else {
+        (0, (1_i64 << bits) - 1)
+    };
+
+    if value < min || value > max {
+        return None;
+    }
+
+    let bytes = if big_endian {
+        value.to_be_bytes()[8 - width..].to_vec()
+    } else {
+        value.to_le_bytes()[..width].to_vec()
+    };
+
+    Some((offset, bytes))
+}
+
+fn const_integer(expr: &Expr) -> Option<i64> {
+    match expr {
+        Expr::Const(TypeValue::Integer(value)) => value.extract().cloned(),
+        _ => None,
+    }
+}
+
+/// Escapes `bytes` so that they can be used in a DOT label. Non-printable
+/// bytes are represented as `\xNN`.
+fn escape_dot(bytes: &[u8]) -> String {
+    let mut result = String::new();
+    for b in bytes {
+        match b {
+            b'"' => result.push_str("\\\""),
+            b'\\' => result.push_str("\\\\"),
+            b' '..=b'~' => result.push(*b as char),
+            _ => result.push_str(&format!("\\\\x{:02x}", b)),
+        }
+    }
+    result
 }
 
 /// Represents an atom extracted from a pattern and added to the Aho-Corasick
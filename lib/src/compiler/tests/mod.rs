@@ -1,15 +1,22 @@
 use pretty_assertions::assert_eq;
 use serde_json::json;
+use std::collections::HashMap;
 use std::fs;
 use std::io::Write;
 use std::mem::size_of;
-use yara_x_parser::Parser;
+use yara_x_parser::warnings::Warning;
+use yara_x_parser::{ErrorInfo, Parser, SourceCode};
 
 use crate::compiler::{
-    SerializationError, SubPattern, Var, VarStack, VariableError,
+    CompileError, SerializationError, SubPattern, Var, VarStack, VariableError,
 };
 use crate::types::Type;
-use crate::{compile, Compiler, Error, Rules, Scanner};
+use crate::{
+    compile, AtomTableBuilder, AtomsExtractor, CandidatePredicate,
+    CompileOptions, Compiler, Error, FsIncludeResolver, FuncValue,
+    GlobalSymbol, GlobalSymbolKind, InvalidWarningCode, MetaValue,
+    PatternSource, Rules, Scanner, SourceStats,
+};
 
 #[test]
 fn serialization() {
@@ -43,6 +50,153 @@ fn serialization() {
     assert_eq!(size_of::<SubPattern>(), 24);
 }
 
+#[cfg(feature = "signed-rules")]
+#[test]
+fn serialization_signed() {
+    let rules =
+        compile(r#"rule test { strings: $a = "foo" condition: $a }"#).unwrap();
+
+    let signed = rules.serialize_signed(b"secret").unwrap();
+
+    // Round-trip with the right key.
+    let rules = Rules::deserialize_verified(&signed, b"secret").unwrap();
+    let mut scanner = Scanner::new(&rules);
+    assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 1);
+
+    // The signature is ignored by `deserialize`.
+    let rules = Rules::deserialize(&signed).unwrap();
+    let mut scanner = Scanner::new(&rules);
+    assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 1);
+
+    // Wrong key.
+    assert!(matches!(
+        Rules::deserialize_verified(&signed, b"wrong").err().unwrap(),
+        SerializationError::SignatureMismatch
+    ));
+
+    // Tampered data.
+    let mut tampered = signed.clone();
+    let offset = tampered.len() / 2;
+    tampered[offset] ^= 0xff;
+
+    assert!(matches!(
+        Rules::deserialize_verified(&tampered, b"secret").err().unwrap(),
+        SerializationError::SignatureMismatch
+    ));
+
+    // Tampered signature.
+    let mut tampered = signed.clone();
+    *tampered.last_mut().unwrap() ^= 0xff;
+
+    assert!(matches!(
+        Rules::deserialize_verified(&tampered, b"secret").err().unwrap(),
+        SerializationError::SignatureMismatch
+    ));
+
+    // Rules that are not signed.
+    let unsigned = rules.serialize().unwrap();
+
+    assert!(matches!(
+        Rules::deserialize_verified(&unsigned, b"secret").err().unwrap(),
+        SerializationError::SignatureMismatch
+    ));
+}
+
+#[test]
+fn serialization_reproducible() {
+    let serialize = || {
+        let mut compiler = Compiler::new();
+
+        compiler
+            .reproducible_serialization(true)
+            .define_global("foo", "foo")
+            .unwrap()
+            .add_source(
+                r#"
+                rule test_1 : bar baz {
+                  meta:
+                    author = "foo"
+                  strings:
+                    $a = "foo"
+                    $b = /ba[rz]+/
+                    $c = { 01 02 ?? 04 }
+                  condition:
+                    any of them and foo == "foo"
+                }"#,
+            )
+            .unwrap()
+            .new_namespace("qux")
+            .add_source(r#"rule test_2 { condition: filesize < 10 }"#)
+            .unwrap();
+
+        compiler.build().serialize().unwrap()
+    };
+
+    let serialized = serialize();
+
+    assert_eq!(serialized, serialize());
+
+    // Serializing deserialized rules produces the same bytes.
+    let rules = Rules::deserialize(&serialized).unwrap();
+    assert_eq!(rules.serialize().unwrap(), serialized);
+
+    let mut scanner = Scanner::new(&rules);
+    assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 2);
+}
+
+#[test]
+fn deserialize_malformed() {
+    let mut compiler = Compiler::new();
+
+    // Native code is trusted when deserializing, the data must contain the
+    // WASM code instead, which is validated.
+    compiler
+        .reproducible_serialization(true)
+        .add_source(
+            r#"
+        import "test_proto2"
+        rule test : foo bar {
+          meta:
+            author = "foo"
+            hash = { 0a 1b 2c }
+          strings:
+            $a = "foo" xor
+            $b = { 01 02 [0-10] 03 04 }
+            $c = /ba[rz]+/
+            $d = "qux" base64
+          condition:
+            test_proto2.int32_one == 1 and any of them
+        }"#,
+        )
+        .unwrap();
+
+    let rules = compiler.build().serialize().unwrap();
+
+    // Truncated data must produce an error.
+    for len in (0..rules.len()).step_by(rules.len() / 200 + 1) {
+        assert!(Rules::deserialize(&rules[..len]).is_err());
+    }
+
+    // Corrupted data must not cause a panic, deserialization either fails
+    // or produces some rules.
+    for i in (6..rules.len()).step_by(rules.len() / 200 + 1) {
+        let mut corrupted = rules.clone();
+        corrupted[i] ^= 0xff;
+        let _ = Rules::deserialize(corrupted);
+    }
+
+    // A length field claiming a huge number of strings in the identifiers
+    // pool must not cause an allocation of that size.
+    let mut huge_len = b"YARA-X".to_vec();
+    huge_len.push(0xfd);
+    huge_len.extend_from_slice(&u64::MAX.to_le_bytes());
+
+    assert!(matches!(
+        Rules::deserialize(huge_len).err().unwrap(),
+        SerializationError::InvalidEncoding(_)
+    ));
+}
+
 #[test]
 fn namespaces() {
     // `foo` and `bar` are both in the default namespace, this compiles
@@ -534,248 +688,2106 @@ fn relaxed_re_syntax() {
 }
 
 #[test]
-fn unsupported_modules() {
+fn candidate_predicate() {
+    let rules = compile(
+        r#"
+        rule pe {
+          strings:
+            $a = "foo"
+          condition:
+            uint16(0) == 0x5A4D and 1KB <= filesize and filesize < 1MB and $a
+        }
+        rule ungated {
+          condition:
+            filesize > 100 or uint16(0) == 0x5A4D
+        }
+        rule elf {
+          condition:
+            0x7F454C46 == uint32be(0)
+        }
+        "#,
+    )
+    .unwrap();
+
+    let pe = rules.candidate_predicate("default", "pe").unwrap();
+
+    assert!(!pe.is_always_candidate());
+    assert_eq!(pe.min_filesize(), Some(1024));
+    assert_eq!(pe.max_filesize(), Some(1024 * 1024 - 1));
+    assert_eq!(pe.header_bytes().collect::<Vec<_>>(), [(0, b"MZ".as_slice())]);
+
+    assert!(pe.is_candidate(2048, b"MZ\x90\x00"));
+    assert!(!pe.is_candidate(2048, b"\x7fELF"));
+    assert!(!pe.is_candidate(100, b"MZ"));
+    assert!(!pe.is_candidate(2 * 1024 * 1024, b"MZ"));
+    // The header is unknown, only the file size is checked.
+    assert!(pe.is_candidate(2048, b""));
+
+    let ungated = rules.candidate_predicate("default", "ungated").unwrap();
+
+    assert!(ungated.is_always_candidate());
+    assert_eq!(ungated, &CandidatePredicate::default());
+    assert!(ungated.is_candidate(0, b""));
+
+    // Constants can appear at either side of the comparison.
+    let elf = rules.candidate_predicate("default", "elf").unwrap();
+
+    assert!(elf.is_candidate(10, b"\x7fELF"));
+    assert!(!elf.is_candidate(10, b"MZ\x90\x00"));
+    assert!(!elf.is_candidate(2, b""));
+
+    assert!(rules.candidate_predicate("default", "unknown").is_none());
+    assert!(rules.candidate_predicate("foo", "pe").is_none());
+}
+
+#[test]
+fn candidate_predicate_default_value() {
+    // The overloads with a default value return it when the offset is out
+    // of bounds, so they don't impose any requirement on the header.
+    let rules = compile(
+        r#"
+        rule test {
+          condition:
+            uint16(0, 0x5A4D) == 0x5A4D and uint32(100, 0) == 0
+        }
+        "#,
+    )
+    .unwrap();
+
+    let test = rules.candidate_predicate("default", "test").unwrap();
+
+    assert!(test.is_always_candidate());
+    assert!(test.is_candidate(0, b""));
+
+    let mut scanner = Scanner::new(&rules);
+    assert_eq!(scanner.scan(b"").unwrap().matching_rules().len(), 1);
+}
+
+#[test]
+fn prefilter_to_dot() {
+    let rules = crate::compile(
+        r#"
+        rule a {
+          strings:
+            $a = "abcd"
+          condition:
+            $a
+        }
+        rule b {
+          strings:
+            $b = "abxy"
+            $c = { 00 01 02 03 }
+          condition:
+            $b and $c
+        }
+        "#,
+    )
+    .unwrap();
+
+    let dot = rules.prefilter_to_dot();
+
+    assert!(dot.starts_with("digraph prefilter {"));
+    assert!(dot.contains(r#"label="abcd\na:$a""#));
+    assert!(dot.contains(r#"label="abxy\nb:$b""#));
+    assert!(dot.contains(r#"label="\\x00\\x01\\x02\\x03\nb:$c""#));
+
+    // Atoms `abcd` and `abxy` share the `ab` prefix.
+    assert_eq!(dot.matches(r#"[label="a"]"#).count(), 1);
+    assert_eq!(dot.matches(r#"[label="b"]"#).count(), 1);
+
+    let dot = rules.rule_prefilter_to_dot("default", "a").unwrap();
+
+    assert!(dot.contains(r#"label="abcd\na:$a""#));
+    assert!(!dot.contains("abxy"));
+
+    assert!(rules.rule_prefilter_to_dot("default", "unknown").is_none());
+}
+
+#[test]
+fn compile_timings() {
+    let src = r#"
+        rule literal {
+          strings:
+            $a = "foo"
+          condition:
+            $a
+        }
+        rule broad_xor {
+          strings:
+            $a = "this program cannot be run in DOS mode" xor(0-255) ascii wide
+          condition:
+            $a
+        }
+        "#;
+
+    let rules = crate::compile(src).unwrap();
+
+    // Timings are not recorded by default.
+    assert!(rules.compile_timings().is_empty());
+
+    let mut compiler = Compiler::new();
+    compiler.enable_compile_timing(true);
+    compiler.add_source(src).unwrap();
+
+    let rules = compiler.build();
+    let timings = rules.compile_timings();
+
+    assert_eq!(timings.len(), 2);
+    assert_eq!(timings[0].0, "default:literal");
+    assert_eq!(timings[1].0, "default:broad_xor");
+    assert!(timings[1].1 > timings[0].1);
+}
+
+#[cfg(feature = "wasm-wat")]
+#[test]
+fn condition_wasm_wat() {
+    let rules = crate::compile(
+        r#"
+        rule test {
+          condition:
+            uint16(0) == 0x5A4D
+        }
+        "#,
+    )
+    .unwrap();
+
+    let wat = rules.condition_wasm_wat();
+
+    assert!(wat.starts_with("(module"));
+    assert!(wat.contains(r#"(export "main" (func"#));
+    assert!(wat.contains(r#""uint16@i@iu""#));
+    assert!(wat.contains("i64.const 23117"));
+
+    // The WAT text can be parsed back into a valid WASM module.
+    assert!(wasmtime::Module::new(&crate::wasm::ENGINE, wat).is_ok());
+
+    // Deserialized rules don't have the WASM module in binary form.
+    let rules = Rules::deserialize(rules.serialize().unwrap()).unwrap();
+    assert!(rules.condition_wasm_wat().is_empty());
+}
+
+#[test]
+fn find_by_meta() {
     let mut compiler = Compiler::new();
 
     compiler
-        .ignore_module("foo_module")
         .add_source(
             r#"
-            import "foo_module"
-            rule ignored { condition: foo_module.some_field == 1 }
-            // This rule should match even if the previous one was ignored.
-            rule always_true { condition: true }
+            rule a {
+              meta:
+                author = "alice"
+                severity = 5
+                tlp = "white"
+              condition:
+                true
+            }
+            rule b {
+              meta:
+                author = "bob"
+                severity = 8
+                severity = 9
+                draft = true
+              condition:
+                true
+            }
+            "#,
+        )
+        .unwrap()
+        .new_namespace("other")
+        .add_source(
+            r#"
+            rule c {
+              meta:
+                author = "alice"
+              condition:
+                true
+            }
             "#,
         )
         .unwrap();
 
     let rules = compiler.build();
 
+    let names = |rules: &Rules, key: &str, p: &dyn Fn(&MetaValue) -> bool| {
+        rules
+            .find_by_meta(key, p)
+            .iter()
+            .map(|r| format!("{}:{}", r.namespace(), r.identifier()))
+            .collect::<Vec<_>>()
+    };
+
     assert_eq!(
-        Scanner::new(&rules)
-            .scan(&[])
-            .expect("scan should not fail")
-            .matching_rules()
-            .len(),
-        1
+        names(&rules, "author", &|v| *v == MetaValue::String("alice")),
+        ["default:a", "other:c"]
+    );
+
+    // Rule `b` has two values for `severity`, but it's returned only once.
+    assert_eq!(
+        names(
+            &rules,
+            "severity",
+            &|v| matches!(v, MetaValue::Integer(i) if *i > 4)
+        ),
+        ["default:a", "default:b"]
+    );
+
+    assert_eq!(
+        names(&rules, "severity", &|v| *v == MetaValue::Integer(9)),
+        ["default:b"]
+    );
+
+    assert_eq!(
+        names(&rules, "draft", &|v| *v == MetaValue::Bool(true)),
+        ["default:b"]
+    );
+
+    assert!(names(&rules, "unknown", &|_| true).is_empty());
+
+    let found = rules.find_by_meta("tlp", |_| true);
+    assert_eq!(
+        found[0].metadata().collect::<Vec<_>>(),
+        [
+            ("author", MetaValue::String("alice")),
+            ("severity", MetaValue::Integer(5)),
+            ("tlp", MetaValue::String("white")),
+        ]
+    );
+
+    // The index must be rebuilt after deserializing the rules.
+    let rules = Rules::deserialize(rules.serialize().unwrap()).unwrap();
+
+    assert_eq!(
+        names(&rules, "author", &|v| *v == MetaValue::String("bob")),
+        ["default:b"]
     );
 }
 
-#[cfg(feature = "test_proto2-module")]
 #[test]
-fn import_modules() {
+fn dependency_graph() {
     let mut compiler = Compiler::new();
-    assert!(compiler
-        .add_source(
-            r#"
-            import "test_proto2" 
-            rule foo {condition: test_proto2.int32_zero == 0}"#
-        )
-        .unwrap()
-        .add_source(
-            r#"
-            import "test_proto2" 
-            rule bar {condition: test_proto2.int32_zero == 0}"#
-        )
-        .is_ok());
 
-    let mut compiler = Compiler::new();
-    assert!(compiler
+    compiler
         .add_source(
             r#"
-            import "test_proto2" 
-            rule foo {condition: test_proto2.int32_zero == 0}"#
+            private rule a { condition: true }
+            rule b { condition: a }
+            rule c { condition: b and a and b }
+            "#,
         )
         .unwrap()
-        .new_namespace("namespace1")
-        .add_source(
-            r#"
-            import "test_proto2" 
-            rule bar {condition: test_proto2.int32_zero == 0}"#
-        )
-        .is_ok());
-}
-
-#[test]
-fn continue_after_error() {
-    let mut compiler = Compiler::new();
-
-    // This rule won't compile because we are using `contains` with an integer.
-    assert!(compiler
+        .new_namespace("other")
         .add_source(
             r#"
-            rule test { 
-                condition: 
-                    for any x in (1,2,3) : ( x contains "foo") 
-            }"#
+            private rule a { condition: false }
+            rule d { condition: a or filesize > 0 }
+            global rule e { condition: filesize > 1 }
+            global rule f { condition: e }
+            rule g { condition: f }
+            "#,
         )
-        .is_err());
+        .unwrap();
 
-    // Adding a rule with the same name after the previous one failed should
-    // be ok.
-    assert!(compiler.add_source(r#"rule test { condition: true }"#).is_ok());
+    let rules = compiler.build();
 
-    // Now do the same test, but with each rule in a different namespace.
-    let mut compiler = Compiler::new();
-    compiler.new_namespace("namespace1");
+    let edges = |rules: &Rules| {
+        rules
+            .dependency_graph()
+            .iter()
+            .map(|(rule, dependency)| {
+                format!(
+                    "{}:{} -> {}:{}",
+                    rule.namespace(),
+                    rule.identifier(),
+                    dependency.namespace(),
+                    dependency.identifier()
+                )
+            })
+            .collect::<Vec<_>>()
+    };
 
-    assert!(compiler
-        .add_source(
-            r#"
-            rule test { 
-                condition: 
-                    for any x in (1,2,3) : ( x contains "foo") 
-            }"#
-        )
-        .is_err());
+    assert_eq!(
+        edges(&rules),
+        [
+            "default:b -> default:a",
+            "default:c -> default:b",
+            "default:c -> default:a",
+            "other:a -> other:e",
+            "other:a -> other:f",
+            "other:d -> other:a",
+            "other:d -> other:e",
+            "other:d -> other:f",
+            "other:f -> other:e",
+            "other:g -> other:f",
+            "other:g -> other:e",
+        ]
+    );
 
-    compiler.new_namespace("namespace2");
+    let graph = rules.dependency_graph();
+    assert!(graph[0].1.is_private());
+    assert!(!graph[0].0.is_private());
 
-    assert!(compiler.add_source(r#"rule test { condition: true }"#).is_ok());
+    // Dependencies are preserved by serialization.
+    let rules = Rules::deserialize(rules.serialize().unwrap()).unwrap();
+    assert_eq!(edges(&rules).len(), 11);
 }
 
 #[test]
-fn errors_2() {
-    assert_eq!(
-        Compiler::new()
-            .define_global("foo", 1)
+fn dead_rules() {
+    let compile = |keep_dead_rules: bool| {
+        let mut compiler = Compiler::new();
+
+        compiler
+            .keep_dead_rules(keep_dead_rules)
+            .add_source(
+                r#"
+                private rule a { strings: $a = "foo" condition: $a }
+                private rule b { condition: a }
+                private rule c { strings: $c = "bar" condition: $c }
+                private rule d { condition: c }
+                global private rule e { condition: filesize > 0 }
+                rule f { condition: b }
+                "#,
+            )
             .unwrap()
-            .add_source("rule foo  {condition: true}")
-            .unwrap_err()
-            .to_string(),
-        "error: rule `foo` conflicts with an existing identifier
- --> line:1:6
-  |
-1 | rule foo  {condition: true}
-  |      ^^^ identifier already in use by a module or global variable
-  |"
-    );
+            .new_namespace("other")
+            .add_source(
+                r#"
+                private rule g { strings: $g = "foo" condition: $g }
+                rule h { condition: true }
+                "#,
+            )
+            .unwrap();
+
+        compiler.build()
+    };
+
+    let rules = compile(false);
 
     assert_eq!(
-        Compiler::new()
-            .add_source("rule foo : first {condition: true}")
-            .unwrap()
-            .add_source("rule foo : second {condition: true}")
-            .unwrap_err()
-            .to_string(),
-        "error: duplicate rule `foo`
- --> line:1:6
-  |
-1 | rule foo : first {condition: true}
-  |      --- note: `foo` declared here for the first time
-  |
- ::: line:1:6
-  |
-1 | rule foo : second {condition: true}
-  |      ^^^ duplicate declaration of `foo`
-  |"
+        rules
+            .dead_rules()
+            .iter()
+            .map(|r| format!("{}:{}", r.namespace(), r.identifier()))
+            .collect::<Vec<_>>(),
+        ["default:c", "default:d", "other:g"]
+    );
+
+    // The atoms for `$c` are removed, but not the ones for `$g`, as `$g`
+    // shares the pattern with `$a`.
+    let all_rules = compile(true);
+
+    assert!(all_rules.dead_rules().is_empty());
+    assert!(rules.atoms().len() < all_rules.atoms().len());
+
+    let mut scanner = Scanner::new(&rules);
+    assert_eq!(scanner.scan(b"foobar").unwrap().matching_rules().len(), 2);
+
+    let mut scanner = Scanner::new(&all_rules);
+    assert_eq!(scanner.scan(b"foobar").unwrap().matching_rules().len(), 2);
+
+    let rules = Rules::deserialize(rules.serialize().unwrap()).unwrap();
+    let mut scanner = Scanner::new(&rules);
+    assert_eq!(scanner.scan(b"foobar").unwrap().matching_rules().len(), 2);
+}
+
+#[test]
+fn dead_rules_side_effects() {
+    let log_messages = |keep_dead_rules: bool| {
+        let mut compiler = Compiler::new();
+
+        compiler
+            .keep_dead_rules(keep_dead_rules)
+            .add_source(
+                r#"
+                import "console"
+                private rule dead { condition: console.log("dead") }
+                rule live { condition: console.log("live") }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut messages = vec![];
+        let mut scanner = Scanner::new(&rules);
+
+        scanner.console_log(|message| messages.push(message));
+        scanner.scan(b"").unwrap();
+        drop(scanner);
+
+        messages
+    };
+
+    // The condition of a dead rule is never evaluated, so it doesn't log
+    // anything.
+    assert_eq!(log_messages(false), ["live"]);
+    assert_eq!(log_messages(true), ["dead", "live"]);
+}
+
+#[test]
+fn meta_bytes() {
+    // SHA-256 of the empty string.
+    let hash: [u8; 32] = [
+        0xe3, 0xb0, 0xc4, 0x42, 0x98, 0xfc, 0x1c, 0x14, 0x9a, 0xfb, 0xf4,
+        0xc8, 0x99, 0x6f, 0xb9, 0x24, 0x27, 0xae, 0x41, 0xe4, 0x64, 0x9b,
+        0x93, 0x4c, 0xa4, 0x95, 0x99, 0x1b, 0x78, 0x52, 0xb8, 0x55,
+    ];
+
+    let rules = compile(
+        r#"
+        rule test {
+          meta:
+            sha256 = {
+              e3 b0 c4 42 98 fc 1c 14 9a fb f4 c8 99 6f b9 24
+              27 ae 41 e4 64 9b 93 4c a4 95 99 1b 78 52 b8 55
+            }
+          condition:
+            true
+        }
+        "#,
+    )
+    .unwrap();
+
+    let rules = Rules::deserialize(rules.serialize().unwrap()).unwrap();
+    let mut scanner = Scanner::new(&rules);
+    let scan_results = scanner.scan(&[]).unwrap();
+    let rule = scan_results.matching_rules().next().unwrap();
+
+    assert_eq!(
+        rule.metadata().collect::<Vec<_>>(),
+        [("sha256", MetaValue::Bytes(hash.as_slice().into()))]
+    );
+}
+
+#[test]
+fn host_functions() {
+    let mut compiler = Compiler::new();
+
+    compiler
+        .add_function("reputation", "s@i", |args| match &args[0] {
+            FuncValue::String(hash) if hash == "bad" => Some(0),
+            FuncValue::String(_) => Some(100),
+            _ => unreachable!(),
+        })
+        .unwrap()
+        .add_function("unknown", "@b", |_| None::<bool>)
+        .unwrap();
+
+    assert_eq!(
+        compiler.add_function("uint8", "i@i", |_| None::<i64>).err(),
+        Some(Error::VariableError(VariableError::AlreadyExists(
+            "uint8".to_string()
+        )))
+    );
+
+    assert_eq!(
+        compiler.add_function("my.func", "i@i", |_| None::<i64>).err(),
+        Some(Error::VariableError(VariableError::InvalidIdentifier(
+            "my.func".to_string()
+        )))
+    );
+
+    // Invalid signatures are an error.
+    for signature in ["i", "i@", "i@ii", "x@i", "i@x"] {
+        assert_eq!(
+            compiler.add_function("func", signature, |_| None::<i64>).err(),
+            Some(Error::InvalidFunctionSignature {
+                name: "func".to_string(),
+                signature: signature.to_string(),
+            })
+        );
+    }
+
+    // The return type in the signature must match the function's one.
+    assert_eq!(
+        compiler.add_function("func", "i@s", |_| Some(1)).err(),
+        Some(Error::InvalidFunctionSignature {
+            name: "func".to_string(),
+            signature: "i@s".to_string(),
+        })
+    );
+
+    // Arguments are type-checked against the signature.
+    assert!(compiler
+        .add_source("rule bad { condition: reputation(1) == 100 }")
+        .is_err());
+
+    compiler
+        .add_source(
+            r#"
+            rule good { condition: reputation("good") == 100 }
+            rule bad { condition: reputation("bad") == 100 }
+            rule undefined { condition: not unknown() }
+            "#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+
+    assert!(matches!(
+        rules.serialize().err().unwrap(),
+        SerializationError::HostFunctions
+    ));
+
+    let mut scanner = Scanner::new(&rules);
+    let scan_results = scanner.scan(&[]).unwrap();
+
+    assert_eq!(
+        scan_results
+            .matching_rules()
+            .map(|rule| rule.identifier())
+            .collect::<Vec<_>>(),
+        ["good"]
+    );
+}
+
+#[test]
+fn rule_tags() {
+    let rules =
+        compile("rule test : foo bar baz { condition: true }").unwrap();
+    let mut scanner = Scanner::new(&rules);
+    let scan_results = scanner.scan(&[]).unwrap();
+    let rule = scan_results.matching_rules().next().unwrap();
+
+    // Tags are reported in the order they were declared.
+    assert_eq!(rule.tags().collect::<Vec<_>>(), ["foo", "bar", "baz"]);
+
+    // Duplicate tags are an error, as in YARA.
+    assert!(compile("rule test : foo bar foo { condition: true }").is_err());
+}
+
+#[test]
+fn default_metadata() {
+    let mut compiler = Compiler::new();
+
+    compiler
+        .add_default_metadata("engine", MetaValue::String("yara-x"))
+        .add_default_metadata("compiled_at", MetaValue::Integer(1))
+        .add_default_metadata("compiled_at", MetaValue::Integer(1700000000))
+        .add_source(
+            r#"
+            rule foo {
+              meta:
+                author = "foo"
+              condition:
+                true
+            }
+            rule bar {
+              meta:
+                engine = "yara"
+              condition:
+                true
+            }
+            "#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+    let scan_results = scanner.scan(&[]).unwrap();
+    let mut matching_rules = scan_results.matching_rules();
+
+    let foo = matching_rules.next().unwrap();
+    assert_eq!(foo.identifier(), "foo");
+    assert_eq!(
+        foo.metadata().collect::<Vec<_>>(),
+        [
+            ("author", MetaValue::String("foo")),
+            ("engine", MetaValue::String("yara-x")),
+            ("compiled_at", MetaValue::Integer(1700000000)),
+        ]
+    );
+
+    // The value declared in the rule is not overridden.
+    let bar = matching_rules.next().unwrap();
+    assert_eq!(bar.identifier(), "bar");
+    assert_eq!(
+        bar.metadata().collect::<Vec<_>>(),
+        [
+            ("engine", MetaValue::String("yara")),
+            ("compiled_at", MetaValue::Integer(1700000000)),
+        ]
+    );
+}
+
+#[test]
+fn source_stats() {
+    let mut compiler = Compiler::new();
+
+    assert_eq!(compiler.last_source_stats(), None);
+
+    compiler
+        .add_source(
+            r#"
+            rule foo { condition: true }
+            rule bar { condition: true }
+            rule baz { condition: 2 }
+            "#,
+        )
+        .unwrap();
+
+    assert_eq!(
+        compiler.last_source_stats(),
+        Some(SourceStats { num_rules: 3, num_warnings: 4 })
+    );
+
+    compiler
+        .add_source(
+            r#"
+            // This source only contains comments.
+            /* rule qux { condition: true } */
+            "#,
+        )
+        .unwrap();
+
+    assert_eq!(
+        compiler.last_source_stats(),
+        Some(SourceStats { num_rules: 0, num_warnings: 0 })
+    );
+
+    assert!(compiler.add_source("rule foo { condition: true }").is_err());
+    assert_eq!(compiler.last_source_stats(), None);
+}
+
+#[test]
+fn duplicate_rule_logic() {
+    let mut compiler = Compiler::new();
+
+    compiler
+        .add_source(
+            r#"
+            rule foo { strings: $a = "foo" condition: $a and filesize > 10 }
+            rule bar { strings: $b = "foo" condition: $b and filesize > 10 }
+            rule baz { strings: $a = "foo" condition: $a and filesize > 11 }
+            rule qux { strings: $a = "foo" condition: $a or filesize > 10 }
+            rule quux { strings: $a = "bar" condition: $a and filesize > 10 }
+            "#,
+        )
+        .unwrap();
+
+    // Only `bar` is a duplicate of `foo`, the rest of the rules have
+    // conditions or patterns that differ from those in `foo`.
+    let warnings = compiler.warnings();
+
+    assert_eq!(warnings.len(), 1);
+    assert!(matches!(
+        &warnings[0],
+        Warning::DuplicateRuleLogic { first, second, .. }
+            if first == "foo" && second == "bar"
+    ));
+}
+
+#[test]
+fn unsupported_modules() {
+    let mut compiler = Compiler::new();
+
+    compiler
+        .ignore_module("foo_module")
+        .add_source(
+            r#"
+            import "foo_module"
+            rule ignored { condition: foo_module.some_field == 1 }
+            // This rule should match even if the previous one was ignored.
+            rule always_true { condition: true }
+            "#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+
+    assert_eq!(
+        Scanner::new(&rules)
+            .scan(&[])
+            .expect("scan should not fail")
+            .matching_rules()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn compile_options() {
+    let options = CompileOptions {
+        relaxed_re_syntax: true,
+        enable_compile_timing: true,
+        ignored_modules: vec!["unsupported".to_string()],
+        disabled_warnings: vec!["ignored_module".to_string()],
+        warnings_as_errors: vec!["non_boolean_as_boolean".to_string()],
+        ..Default::default()
+    };
+
+    let json = serde_json::to_string(&options).unwrap();
+    let deserialized: CompileOptions = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(deserialized, options);
+
+    // Missing fields take their default values.
+    let partial: CompileOptions =
+        serde_json::from_str(r#"{"strict_undefined": true}"#).unwrap();
+
+    assert_eq!(
+        partial,
+        CompileOptions { strict_undefined: true, ..Default::default() }
+    );
+
+    let mut compiler = Compiler::with_options(deserialized).unwrap();
+
+    // `\R` is accepted only with relaxed regexp syntax, and the import
+    // statement is accepted because the module is ignored. The warning
+    // about the ignored module is disabled.
+    compiler
+        .add_source(
+            r#"
+            import "unsupported"
+            rule foo { condition: "R" matches /\R/ }
+            "#,
+        )
+        .unwrap();
+
+    // The `non_boolean_as_boolean` warning is treated as an error.
+    assert!(matches!(
+        compiler.add_source("rule bar { condition: 1 }"),
+        Err(Error::WarningAsError(_))
+    ));
+
+    let rules = compiler.build();
+
+    assert_eq!(rules.warnings().len(), 0);
+    assert_eq!(rules.compile_timings().len(), 1);
+
+    // Unknown warning codes are rejected.
+    assert_eq!(
+        Compiler::with_options(CompileOptions {
+            disabled_warnings: vec!["foo".to_string()],
+            ..Default::default()
+        })
+        .err(),
+        Some(InvalidWarningCode("foo".to_string()))
+    );
+
+    // Without the options the same source code produces an error.
+    assert!(Compiler::new()
+        .add_source(r#"rule foo { condition: "R" matches /\R/ }"#)
+        .is_err());
+}
+
+#[cfg(feature = "test_proto2-module")]
+#[test]
+fn disabled_modules() {
+    let mut compiler = Compiler::new();
+
+    compiler.disable_module("test_proto2");
+
+    assert!(matches!(
+        compiler
+            .add_source(
+                r#"
+                import "test_proto2"
+                rule foo { condition: test_proto2.int32_zero == 0 }"#
+            )
+            .unwrap_err(),
+        Error::CompileError(err)
+            if matches!(*err, CompileError::ModuleDisabled { ref module, .. }
+                if module == "test_proto2")
+    ));
+
+    // Modules that are not disabled can still be imported.
+    let mut compiler = Compiler::new();
+
+    compiler.disable_module("test_proto3");
+
+    assert!(compiler
+        .add_source(
+            r#"
+            import "test_proto2"
+            rule bar { condition: test_proto2.int32_zero == 0 }"#
+        )
+        .is_ok());
+}
+
+#[cfg(feature = "test_proto2-module")]
+#[test]
+fn disabled_functions() {
+    let mut compiler = Compiler::new();
+
+    compiler.disable_function("test_proto2.add").disable_function("uint8");
+
+    assert!(matches!(
+        compiler
+            .add_source(
+                r#"
+                import "test_proto2"
+                rule foo { condition: test_proto2.add(1, 2) == 3 }"#
+            )
+            .unwrap_err(),
+        Error::CompileError(err)
+            if matches!(*err, CompileError::FunctionDisabled { ref function, .. }
+                if function == "test_proto2.add")
+    ));
+
+    // All the overloads of the function are disabled.
+    assert!(matches!(
+        compiler
+            .add_source(
+                r#"
+                import "test_proto2"
+                rule foo { condition: test_proto2.add(1.0, 2.0) == 3.0 }"#
+            )
+            .unwrap_err(),
+        Error::CompileError(err)
+            if matches!(*err, CompileError::FunctionDisabled { ref function, .. }
+                if function == "test_proto2.add")
+    ));
+
+    assert!(matches!(
+        compiler
+            .add_source(r#"rule foo { condition: uint8(0) == 0 }"#)
+            .unwrap_err(),
+        Error::CompileError(err)
+            if matches!(*err, CompileError::FunctionDisabled { ref function, .. }
+                if function == "uint8")
+    ));
+
+    // Other functions in the same module can still be used.
+    assert!(compiler
+        .add_source(
+            r#"
+            import "test_proto2"
+            rule bar {
+              condition:
+                test_proto2.uppercase("foo") == "FOO" and uint16(0) == 0
+            }"#
+        )
+        .is_ok());
+}
+
+#[test]
+fn compile_limits() {
+    let mut compiler = Compiler::new();
+
+    compiler
+        .max_condition_depth(3)
+        .max_patterns_per_rule(2)
+        .max_regexp_size(5)
+        .max_loop_iterations(10);
+
+    // `not`, `and` and `true`/`false` are 3 levels deep.
+    assert!(compiler
+        .add_source("rule a { condition: not (true and false) }")
+        .is_ok());
+
+    assert!(matches!(
+        compiler
+            .add_source("rule b { condition: not (true and not false) }")
+            .unwrap_err(),
+        Error::CompileError(err)
+            if matches!(*err, CompileError::ConditionTooDeep { max_depth: 3, .. })
+    ));
+
+    // Parenthesis are not expressions by themselves, but the parser doesn't
+    // accept them nested deeper than the limit either.
+    assert!(matches!(
+        compiler
+            .add_source("rule b { condition: ((((true)))) }")
+            .unwrap_err(),
+        Error::ParseError(err)
+            if matches!(err.info(), ErrorInfo::TooDeeplyNested { limit: 3, .. })
+    ));
+
+    assert!(matches!(
+        compiler
+            .add_source(
+                r#"rule c {
+                  strings:
+                    $a = "foo"
+                    $b = "bar"
+                    $c = "baz"
+                  condition:
+                    any of them
+                }"#
+            )
+            .unwrap_err(),
+        Error::CompileError(err)
+            if matches!(*err, CompileError::TooManyPatterns {
+                ref rule, num_patterns: 3, max_patterns: 2, ..
+            } if rule == "c")
+    ));
+
+    assert!(compiler
+        .add_source(r#"rule d { strings: $a = /fo+ba[rz]/ condition: $a }"#)
+        .is_ok());
+
+    // Repetitions are expanded when computing the size.
+    assert!(matches!(
+        compiler
+            .add_source(r#"rule e1 { strings: $a = /fo{2}bar/ condition: $a }"#)
+            .unwrap_err(),
+        Error::CompileError(err)
+            if matches!(*err, CompileError::RegexpTooLarge {
+                size: 6, max_size: 5, ..
+            })
+    ));
+
+    assert!(matches!(
+        compiler
+            .add_source(
+                r#"rule e2 { strings: $a = /a{1000}{1000}/ condition: $a }"#
+            )
+            .unwrap_err(),
+        Error::CompileError(err)
+            if matches!(*err, CompileError::RegexpTooLarge {
+                size: 1000000, max_size: 5, ..
+            })
+    ));
+
+    // Hex patterns are limited too.
+    assert!(compiler
+        .add_source(r#"rule e3 { strings: $a = { 00 [3] 01 } condition: $a }"#)
+        .is_ok());
+
+    assert!(matches!(
+        compiler
+            .add_source(
+                r#"rule e4 { strings: $a = { 00 [2-4] 01 } condition: $a }"#
+            )
+            .unwrap_err(),
+        Error::CompileError(err)
+            if matches!(*err, CompileError::RegexpTooLarge {
+                size: 6, max_size: 5, ..
+            })
+    ));
+
+    assert!(matches!(
+        compiler
+            .add_source(r#"rule f { condition: "foo" matches /fo{5}/ }"#)
+            .unwrap_err(),
+        Error::CompileError(err)
+            if matches!(*err, CompileError::RegexpTooLarge { .. })
+    ));
+
+    assert!(compiler
+        .add_source("rule g { condition: for any i in (1..10) : (i) }")
+        .is_ok());
+
+    assert!(matches!(
+        compiler
+            .add_source("rule h { condition: for any i in (0..10) : (i) }")
+            .unwrap_err(),
+        Error::CompileError(err)
+            if matches!(*err, CompileError::TooManyIterations {
+                iterations: 11, max_iterations: 10, ..
+            })
+    ));
+
+    // Ranges that are not known at compile time are not checked.
+    assert!(compiler
+        .add_source("rule i { condition: for any i in (0..filesize) : (i) }")
+        .is_ok());
+
+    // In nested loops the number of iterations is multiplied.
+    let mut compiler = Compiler::new();
+
+    compiler.max_loop_iterations(10);
+
+    assert!(matches!(
+        compiler
+            .add_source(
+                "rule j { condition: for any i in (1..5) : (for any j in (1..3) : (i == j)) }"
+            )
+            .unwrap_err(),
+        Error::CompileError(err)
+            if matches!(*err, CompileError::TooManyIterations {
+                iterations: 15, max_iterations: 10, ..
+            })
+    ));
+
+    assert!(compiler
+        .add_source(
+            "rule k { condition: for any i in (1..5) : (for any j in (1..2) : (i == j)) }"
+        )
+        .is_ok());
+
+    // A condition depth larger than the parser's limit doesn't raise it.
+    let mut compiler = Compiler::new();
+
+    compiler.max_condition_depth(usize::MAX);
+
+    let condition = format!("{}true{}", "(".repeat(200), ")".repeat(200));
+
+    assert!(matches!(
+        compiler
+            .add_source(format!("rule l {{ condition: {} }}", condition).as_str())
+            .unwrap_err(),
+        Error::ParseError(err)
+            if matches!(err.info(), ErrorInfo::TooDeeplyNested {
+                limit: Parser::DEFAULT_MAX_NESTING_DEPTH, ..
+            })
+    ));
+}
+
+#[cfg(feature = "test_proto2-module")]
+#[test]
+fn import_modules() {
+    let mut compiler = Compiler::new();
+    assert!(compiler
+        .add_source(
+            r#"
+            import "test_proto2" 
+            rule foo {condition: test_proto2.int32_zero == 0}"#
+        )
+        .unwrap()
+        .add_source(
+            r#"
+            import "test_proto2" 
+            rule bar {condition: test_proto2.int32_zero == 0}"#
+        )
+        .is_ok());
+
+    let mut compiler = Compiler::new();
+    assert!(compiler
+        .add_source(
+            r#"
+            import "test_proto2" 
+            rule foo {condition: test_proto2.int32_zero == 0}"#
+        )
+        .unwrap()
+        .new_namespace("namespace1")
+        .add_source(
+            r#"
+            import "test_proto2" 
+            rule bar {condition: test_proto2.int32_zero == 0}"#
+        )
+        .is_ok());
+}
+
+#[test]
+fn continue_after_error() {
+    let mut compiler = Compiler::new();
+
+    // This rule won't compile because we are using `contains` with an integer.
+    assert!(compiler
+        .add_source(
+            r#"
+            rule test { 
+                condition: 
+                    for any x in (1,2,3) : ( x contains "foo") 
+            }"#
+        )
+        .is_err());
+
+    // Adding a rule with the same name after the previous one failed should
+    // be ok.
+    assert!(compiler.add_source(r#"rule test { condition: true }"#).is_ok());
+
+    // Now do the same test, but with each rule in a different namespace.
+    let mut compiler = Compiler::new();
+    compiler.new_namespace("namespace1");
+
+    assert!(compiler
+        .add_source(
+            r#"
+            rule test { 
+                condition: 
+                    for any x in (1,2,3) : ( x contains "foo") 
+            }"#
+        )
+        .is_err());
+
+    compiler.new_namespace("namespace2");
+
+    assert!(compiler.add_source(r#"rule test { condition: true }"#).is_ok());
+}
+
+#[test]
+fn errors_2() {
+    assert_eq!(
+        Compiler::new()
+            .define_global("foo", 1)
+            .unwrap()
+            .add_source("rule foo  {condition: true}")
+            .unwrap_err()
+            .to_string(),
+        "error: rule `foo` conflicts with an existing identifier
+ --> line:1:6
+  |
+1 | rule foo  {condition: true}
+  |      ^^^ identifier already in use by a module or global variable
+  |"
+    );
+
+    assert_eq!(
+        Compiler::new()
+            .add_source("rule foo : first {condition: true}")
+            .unwrap()
+            .add_source("rule foo : second {condition: true}")
+            .unwrap_err()
+            .to_string(),
+        "error: duplicate rule `foo`
+ --> line:1:6
+  |
+1 | rule foo : first {condition: true}
+  |      --- note: `foo` declared here for the first time
+  |
+ ::: line:1:6
+  |
+1 | rule foo : second {condition: true}
+  |      ^^^ duplicate declaration of `foo`
+  |"
     );
 
     #[cfg(feature = "constant-folding")]
     assert_eq!(
-        Compiler::new()
+        Compiler::new()
+            .add_source(
+                "rule test {
+condition:
+  	9223372036854775807 + 1000000000 == 0
+}"
+            )
+            .unwrap_err()
+            .to_string(),
+        "error: number out of range
+ --> line:3:4
+  |
+3 |    9223372036854775807 + 1000000000 == 0
+  |    ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^ this number is out of the allowed range [-9223372036854775808-9223372036854775807]
+  |"
+    );
+}
+
+#[test]
+fn utf8_errors() {
+    let mut src =
+        "rule test {condition: true}".to_string().as_bytes().to_vec();
+
+    // Insert invalid UTF-8 in the code.
+    src.insert(4, 0xff);
+
+    assert_eq!(
+        Parser::new()
+            .build_ast(src.as_slice())
+            .expect_err("expected error")
+            .to_string(),
+        "error: invalid UTF-8
+ --> line:1:5
+  |
+1 | rule� test {condition: true}
+  |     ^ invalid UTF-8 character
+  |"
+    );
+}
+
+#[test]
+fn atoms_extractor() {
+    let http = AtomsExtractor::new().extract("http://").unwrap();
+    let a = AtomsExtractor::new().extract("a").unwrap();
+
+    assert_eq!(http.atoms.len(), 1);
+    assert_eq!(http.atoms[0].bytes.len(), 4);
+    assert!(http.atoms[0].quality > a.atoms[0].quality);
+    assert!(http.quality > a.quality);
+    assert_eq!(http.quality.min_atom_len(), 4);
+
+    let extracted = AtomsExtractor::new().extract("a|b|c").unwrap();
+
+    assert_eq!(
+        extracted
+            .atoms
+            .iter()
+            .map(|atom| atom.bytes.as_slice())
+            .collect::<Vec<_>>(),
+        vec![b"a", b"b", b"c"]
+    );
+
+    let extracted = AtomsExtractor::new().wide(true).extract("ab").unwrap();
+
+    assert_eq!(extracted.atoms.len(), 1);
+    assert_eq!(extracted.atoms[0].bytes, b"a\x00b\x00");
+
+    let extracted =
+        AtomsExtractor::new().wide(true).ascii(true).extract("ab").unwrap();
+
+    assert_eq!(
+        extracted
+            .atoms
+            .iter()
+            .map(|atom| atom.bytes.as_slice())
+            .collect::<Vec<_>>(),
+        vec![b"a\x00b\x00".as_slice(), b"ab".as_slice()]
+    );
+
+    assert!(matches!(
+        AtomsExtractor::new().extract("a(b"),
+        Err(Error::CompileError(_))
+    ));
+}
+
+#[test]
+fn preferred_atom_length() {
+    let atom_bytes = |preferred_len: Option<usize>| {
+        let mut extractor = AtomsExtractor::new();
+        if let Some(len) = preferred_len {
+            extractor.preferred_atom_length(len);
+        }
+        extractor
+            .extract("(a|b)cde")
+            .unwrap()
+            .atoms
+            .into_iter()
+            .map(|atom| atom.bytes)
+            .collect::<Vec<_>>()
+    };
+
+    // By default the longer atoms are chosen, and the same happens when
+    // the preferred atom length is 4.
+    assert_eq!(atom_bytes(None), vec![b"acde".to_vec(), b"bcde".to_vec()]);
+    assert_eq!(atom_bytes(Some(4)), atom_bytes(None));
+
+    // With a preferred length of 3 the single, shorter atom is chosen.
+    assert_eq!(atom_bytes(Some(3)), vec![b"cde".to_vec()]);
+
+    // The results of the scan are the same regardless of the atoms chosen.
+    let src = r#"rule test { strings: $a = /(a|b)cde/ condition: #a == 2 }"#;
+
+    for len in [1, 3, 4, 8] {
+        let mut compiler = Compiler::new();
+        compiler.preferred_atom_length(len).add_source(src).unwrap();
+        let rules = compiler.build();
+        let mut scanner = Scanner::new(&rules);
+
+        assert_eq!(
+            scanner.scan(b"xxacdexxbcdexxcde").unwrap().matching_rules().len(),
+            1
+        );
+    }
+}
+
+#[test]
+fn symbols() {
+    let mut compiler = Compiler::new();
+
+    compiler
+        .define_global("threshold", 1)
+        .unwrap()
+        .add_function("reputation", "s@i", |_| Some(0))
+        .unwrap()
+        .add_source(
+            r#"
+            import "pe"
+            rule foo { condition: pe.number_of_sections > threshold }
+            rule bar { condition: foo }
+            "#,
+        )
+        .unwrap();
+
+    let symbols = compiler.symbols();
+
+    let symbol = |name: &str| symbols.iter().find(|s| s.name == name).cloned();
+
+    assert_eq!(
+        symbol("threshold"),
+        Some(GlobalSymbol {
+            name: "threshold".to_string(),
+            kind: GlobalSymbolKind::Variable,
+            ty: "integer".to_string(),
+        })
+    );
+
+    assert_eq!(
+        symbol("pe.number_of_sections"),
+        Some(GlobalSymbol {
+            name: "pe.number_of_sections".to_string(),
+            kind: GlobalSymbolKind::ModuleField,
+            ty: "integer".to_string(),
+        })
+    );
+
+    assert_eq!(symbol("pe").unwrap().ty, "struct");
+    assert_eq!(symbol("pe.sections").unwrap().ty, "array");
+    assert_eq!(symbol("pe.sections[].name").unwrap().ty, "string");
+    assert_eq!(symbol("pe.imphash").unwrap().ty, "function");
+
+    assert_eq!(
+        symbols
+            .iter()
+            .filter(|s| s.kind == GlobalSymbolKind::Rule)
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["foo", "bar"]
+    );
+
+    assert_eq!(
+        symbol("reputation"),
+        Some(GlobalSymbol {
+            name: "reputation".to_string(),
+            kind: GlobalSymbolKind::Function,
+            ty: "function".to_string(),
+        })
+    );
+
+    // Modules that were not imported don't appear.
+    assert!(symbol("elf").is_none());
+
+    compiler
+        .new_namespace("other")
+        .add_source("rule baz { condition: true }")
+        .unwrap();
+
+    // Only the modules and rules in the current namespace appear.
+    assert!(compiler.symbols().iter().all(|s| !s.name.starts_with("pe")));
+    assert_eq!(
+        compiler
+            .symbols()
+            .iter()
+            .filter(|s| s.kind == GlobalSymbolKind::Rule)
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["baz"]
+    );
+}
+
+#[test]
+fn module_descriptors() {
+    let rules = crate::compile(
+        r#"
+        import "pe"
+        rule test { condition: pe.number_of_sections > 0 }
+        "#,
+    )
+    .unwrap();
+
+    let descriptors = rules.module_descriptors();
+
+    assert_eq!(descriptors.len(), 1);
+    assert_eq!(descriptors[0].name, "pe");
+
+    let field = |fields: &[crate::FieldDescriptor], name: &str| {
+        fields.iter().find(|f| f.name == name).cloned().unwrap()
+    };
+
+    let pe = &descriptors[0].fields;
+
+    assert_eq!(field(pe, "number_of_sections").ty, "integer");
+
+    let sections = field(pe, "sections");
+
+    assert_eq!(sections.ty, "array");
+    assert_eq!(field(&sections.fields, "name").ty, "string");
+
+    // Rules that don't import modules have no descriptors.
+    let rules = crate::compile("rule test { condition: true }").unwrap();
+    assert!(rules.module_descriptors().is_empty());
+}
+
+#[test]
+fn test_errors() {
+    let mut mint = goldenfile::Mint::new(".");
+
+    for entry in globwalk::glob("src/compiler/tests/testdata/errors/*.in")
+        .unwrap()
+        .flatten()
+    {
+        // Path to the .in file.
+        let in_path = entry.into_path();
+
+        // Path to the .out file.
+        let out_path = in_path.with_extension("out");
+
+        let mut src = String::new();
+
+        let rules = fs::read_to_string(&in_path).expect("unable to read");
+
+        src.push_str(rules.as_str());
+
+        let err = compile(src.as_str()).expect_err(
+            format!("file {:?} should have failed with error", in_path)
+                .as_str(),
+        );
+
+        let mut output_file = mint.new_goldenfile(out_path).unwrap();
+
+        output_file
+            .write_all(err.to_string().as_bytes())
+            .expect("unable to write")
+    }
+}
+
+#[test]
+fn test_warnings() {
+    let mut mint = goldenfile::Mint::new(".");
+
+    for entry in globwalk::glob("src/compiler/tests/testdata/warnings/*.in")
+        .unwrap()
+        .flatten()
+    {
+        // Path to the .in file.
+        let in_path = entry.into_path();
+
+        // Path to the .out file.
+        let out_path = in_path.with_extension("out");
+
+        let mut src = String::new();
+        let rules = fs::read_to_string(&in_path).expect("unable to read");
+
+        src.push_str(rules.as_str());
+
+        let mut compiler = Compiler::new();
+
+        compiler.ignore_module("unsupported_module");
+        compiler.add_source(src.as_str()).unwrap();
+
+        let mut output_file = mint.new_goldenfile(out_path).unwrap();
+
+        for w in compiler.warnings() {
+            output_file
+                .write_all(w.to_string().as_bytes())
+                .expect("unable to write");
+        }
+    }
+}
+
+#[test]
+fn pattern_rewriter() {
+    let mut compiler = Compiler::new();
+
+    compiler.set_pattern_rewriter(|pattern| match pattern {
+        PatternSource::Text(text) => {
+            PatternSource::Text(text.to_ascii_lowercase())
+        }
+        other => other.clone(),
+    });
+
+    compiler
+        .add_source(
+            r#"
+            rule test {
+              strings:
+                $a = "FOO"
+              condition:
+                $a
+            }"#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+
+    assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 1);
+    assert_eq!(scanner.scan(b"FOO").unwrap().matching_rules().len(), 0);
+
+    let mut compiler = Compiler::new();
+
+    // Make every regexp invalid by adding an unclosed group.
+    compiler.set_pattern_rewriter(|pattern| match pattern {
+        PatternSource::Regexp(src) => {
+            PatternSource::Regexp(format!("{}(", src))
+        }
+        other => other.clone(),
+    });
+
+    assert_eq!(
+        compiler
+            .add_source(
+                r#"rule test {
+  strings:
+    $a = /foo/
+  condition:
+    $a
+}"#
+            )
+            .unwrap_err()
+            .to_string(),
+        r#"error: invalid rewritten pattern
+ --> line:3:11
+  |
+3 |     $a = /foo/
+  |           ^^^ unclosed group
+  |
+  = note: the pattern rewriter replaced this pattern with `/foo(/`"#
+    );
+
+    let mut compiler = Compiler::new();
+
+    // Replace the first byte in hex patterns with a wildcard.
+    compiler.set_pattern_rewriter(|pattern| match pattern {
+        PatternSource::Hex(src) => {
+            PatternSource::Hex(src.replacen("61", "??", 1))
+        }
+        other => other.clone(),
+    });
+
+    compiler
+        .add_source(
+            r#"
+            rule test {
+              strings:
+                $a = { 61 62 63 }
+              condition:
+                $a
+            }"#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+
+    assert_eq!(scanner.scan(b"xbc").unwrap().matching_rules().len(), 1);
+
+    let mut compiler = Compiler::new();
+
+    // Rewritten hex patterns are parsed again, and they can't contain
+    // anything but the pattern itself.
+    compiler.set_pattern_rewriter(|pattern| match pattern {
+        PatternSource::Hex(src) => PatternSource::Hex(format!("{} wide", src)),
+        other => other.clone(),
+    });
+
+    assert_eq!(
+        compiler
             .add_source(
-                "rule test {
-condition:
-  	9223372036854775807 + 1000000000 == 0
-}"
+                r#"rule test {
+  strings:
+    $a = { 61 62 63 }
+  condition:
+    $a
+}"#
             )
             .unwrap_err()
             .to_string(),
-        "error: number out of range
- --> line:3:4
+        r#"error: invalid rewritten pattern
+ --> line:3:10
   |
-3 |    9223372036854775807 + 1000000000 == 0
-  |    ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^ this number is out of the allowed range [-9223372036854775808-9223372036854775807]
-  |"
+3 |     $a = { 61 62 63 }
+  |          ^^^^^^^^^^^^ invalid pattern modifier
+  |
+  = note: the pattern rewriter replaced this pattern with `{ 61 62 63 } wide`"#
+    );
+
+    let mut compiler = Compiler::new();
+
+    compiler.set_pattern_rewriter(|pattern| match pattern {
+        PatternSource::Hex(_) => PatternSource::Hex(r#""abc""#.to_string()),
+        other => other.clone(),
+    });
+
+    assert_eq!(
+        compiler
+            .add_source(
+                r#"rule test {
+  strings:
+    $a = { 61 62 63 }
+  condition:
+    $a
+}"#
+            )
+            .unwrap_err()
+            .to_string(),
+        r#"error: invalid rewritten pattern
+ --> line:3:10
+  |
+3 |     $a = { 61 62 63 }
+  |          ^^^^^^^^^^^^ this is not a hex pattern
+  |
+  = note: the pattern rewriter replaced this pattern with `"abc"`"#
+    );
+
+    let mut compiler = Compiler::new();
+
+    compiler.set_pattern_rewriter(|pattern| match pattern {
+        PatternSource::Hex(src) => PatternSource::Hex(src.replace("63", "6X")),
+        PatternSource::Text(_) => PatternSource::Text(vec![]),
+        other => other.clone(),
+    });
+
+    assert_eq!(
+        compiler
+            .add_source(
+                r#"rule test {
+  strings:
+    $a = { 61 62 63 }
+  condition:
+    $a
+}"#
+            )
+            .unwrap_err()
+            .to_string(),
+        r#"error: invalid rewritten pattern
+ --> line:3:10
+  |
+3 |     $a = { 61 62 63 }
+  |          ^^^^^^^^^^^^ uneven number of nibbles
+  |
+  = note: the pattern rewriter replaced this pattern with `{ 61 62 6X }`"#
+    );
+
+    assert_eq!(
+        compiler
+            .add_source(
+                r#"rule test {
+  strings:
+    $a = "foo"
+  condition:
+    $a
+}"#
+            )
+            .unwrap_err()
+            .to_string(),
+        r#"error: invalid rewritten pattern
+ --> line:3:10
+  |
+3 |     $a = "foo"
+  |          ^^^^^ this pattern is too short
+  |
+  = note: the pattern rewriter replaced this pattern with `""`"#
     );
 }
 
 #[test]
-fn utf8_errors() {
-    let mut src =
-        "rule test {condition: true}".to_string().as_bytes().to_vec();
+fn xor_range_hook() {
+    let mut compiler = Compiler::new();
 
-    // Insert invalid UTF-8 in the code.
-    src.insert(4, 0xff);
+    compiler.set_xor_range_hook(|ident, range| {
+        assert_eq!(ident, "$a");
+        assert_eq!(range, 0x00..=0xff);
+        0x01..=0x1f
+    });
+
+    compiler
+        .add_source(
+            r#"
+            rule test {
+              strings:
+                $a = "foobar" xor(0x00-0xff)
+              condition:
+                $a
+            }"#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+
+    let xored =
+        |key: u8| b"foobar".iter().map(|b| b ^ key).collect::<Vec<_>>();
+
+    assert_eq!(scanner.scan(&xored(0x01)).unwrap().matching_rules().len(), 1);
+    assert_eq!(scanner.scan(&xored(0x05)).unwrap().matching_rules().len(), 1);
+    assert_eq!(scanner.scan(&xored(0x1f)).unwrap().matching_rules().len(), 1);
+    assert_eq!(scanner.scan(&xored(0x00)).unwrap().matching_rules().len(), 0);
+    assert_eq!(scanner.scan(&xored(0x20)).unwrap().matching_rules().len(), 0);
+    assert_eq!(scanner.scan(&xored(0x40)).unwrap().matching_rules().len(), 0);
+
+    let mut compiler = Compiler::new();
+
+    // The hook can't widen the range declared in the source code.
+    compiler.set_xor_range_hook(|_, _| 0x00..=0xff);
 
     assert_eq!(
-        Parser::new()
-            .build_ast(src.as_slice())
-            .expect_err("expected error")
+        compiler
+            .add_source(
+                r#"rule test {
+  strings:
+    $a = "foobar" xor(0x01-0x1f)
+  condition:
+    $a
+}"#
+            )
+            .unwrap_err()
             .to_string(),
-        "error: invalid UTF-8
- --> line:1:5
+        r#"error: invalid xor range for `$a`
+ --> line:3:19
   |
-1 | rule� test {condition: true}
-  |     ^ invalid UTF-8 character
-  |"
+3 |     $a = "foobar" xor(0x01-0x1f)
+  |                   ^^^ the range set by the xor range hook (0-255) is not within this range
+  |"#
     );
 }
 
 #[test]
-fn test_errors() {
-    let mut mint = goldenfile::Mint::new(".");
+fn rule_references_are_not_reevaluated() {
+    let rules = crate::compile(
+        r#"
+        import "console"
+        private rule small { condition: console.log("small") and filesize > 0 }
+        rule foo { condition: small }
+        rule bar { condition: small and not foo }
+        rule baz { condition: small or bar }
+        "#,
+    )
+    .unwrap();
+
+    let mut messages = vec![];
+    let mut scanner = Scanner::new(&rules);
 
-    for entry in globwalk::glob("src/compiler/tests/testdata/errors/*.in")
+    scanner.console_log(|message| messages.push(message));
+
+    let matching: Vec<_> = scanner
+        .scan(b"x")
         .unwrap()
-        .flatten()
-    {
-        // Path to the .in file.
-        let in_path = entry.into_path();
+        .matching_rules()
+        .map(|rule| rule.identifier())
+        .collect();
 
-        // Path to the .out file.
-        let out_path = in_path.with_extension("out");
+    assert_eq!(matching, ["foo", "baz"]);
+    drop(scanner);
 
-        let mut src = String::new();
+    // The condition of `small` is evaluated once, no matter how many rules
+    // reference it.
+    assert_eq!(messages, ["small"]);
+}
 
-        let rules = fs::read_to_string(&in_path).expect("unable to read");
+#[test]
+fn atom_table_builder() {
+    let sources = [
+        r#"rule a { strings: $a = "foo" condition: $a }"#,
+        r#"rule b { strings: $b = /ba[rz]{1,3}/ condition: #b == 2 }"#,
+        r#"rule c { strings: $c = { 66 6F [0-300] 71 75 78 } condition: $c }"#,
+        r#"rule d { strings: $d = "qux" wide nocase condition: $d and $d }"#,
+    ];
+
+    let mut data = b"foo bar".to_vec();
+    data.extend(b"-".repeat(200));
+    data.extend(b"qux baz q\x00U\x00x\x00");
+
+    let scan = |rules: &Rules| {
+        let mut scanner = Scanner::new(rules);
+        let results = scanner.scan(data.as_slice()).unwrap();
+        results
+            .matching_rules()
+            .map(|rule| {
+                let matches: Vec<_> = rule
+                    .patterns()
+                    .flat_map(|pattern| pattern.matches())
+                    .map(|m| m.range())
+                    .collect();
+                (rule.identifier().to_string(), matches)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let build = |sources: &[&str]| {
+        let mut compiler = Compiler::new();
+        for src in sources {
+            compiler.add_source(*src).unwrap();
+        }
+        compiler
+    };
 
-        src.push_str(rules.as_str());
+    let mut atom_table = AtomTableBuilder::new();
 
-        let err = compile(src.as_str()).expect_err(
-            format!("file {:?} should have failed with error", in_path)
-                .as_str(),
-        );
+    // Each new rule adds a new segment to the atom table, and the results
+    // are the same as with rules built from scratch.
+    for i in 1..=sources.len() {
+        let incremental =
+            build(&sources[..i]).build_with_atom_table(&mut atom_table);
+        let batch = build(&sources[..i]).build();
 
-        let mut output_file = mint.new_goldenfile(out_path).unwrap();
+        assert_eq!(atom_table.num_segments(), i);
+        assert_eq!(scan(&incremental), scan(&batch));
+    }
 
-        output_file
-            .write_all(err.to_string().as_bytes())
-            .expect("unable to write")
+    assert_eq!(scan(&build(&sources).build()).len(), 4);
+
+    // The whole table is rebuilt when the previous atoms are not a prefix
+    // of the new ones.
+    let incremental =
+        build(&sources[1..]).build_with_atom_table(&mut atom_table);
+
+    assert_eq!(atom_table.num_segments(), 1);
+    assert_eq!(scan(&incremental), scan(&build(&sources[1..]).build()));
+
+    // The whole table is also rebuilt when the maximum number of segments
+    // is reached.
+    let mut atom_table = AtomTableBuilder::new();
+    atom_table.max_segments(2);
+
+    for (i, expected_segments) in [(1, 1), (2, 2), (3, 1), (4, 2)] {
+        let incremental =
+            build(&sources[..i]).build_with_atom_table(&mut atom_table);
+
+        assert_eq!(atom_table.num_segments(), expected_segments);
+        assert_eq!(scan(&incremental), scan(&build(&sources[..i]).build()));
     }
 }
 
 #[test]
-fn test_warnings() {
-    let mut mint = goldenfile::Mint::new(".");
+fn error_recovery() {
+    let mut compiler = Compiler::new();
 
-    for entry in globwalk::glob("src/compiler/tests/testdata/warnings/*.in")
+    compiler
+        .error_recovery(true)
+        .add_source(
+            r#"
+            import "unknown"
+            rule a { condition: true }
+            rule b { condition: 1 + "foo" }
+            rule c { condition: b }
+            rule d { condition: a }
+            "#,
+        )
         .unwrap()
-        .flatten()
-    {
-        // Path to the .in file.
-        let in_path = entry.into_path();
+        // Syntax errors make the compiler skip the whole source.
+        .add_source("rule e { condition: true } rule f {")
+        .unwrap()
+        .add_source("rule g { condition: true }")
+        .unwrap();
 
-        // Path to the .out file.
-        let out_path = in_path.with_extension("out");
+    let codes: Vec<_> = compiler
+        .errors()
+        .iter()
+        .map(|err| match err {
+            Error::ParseError(err) => err.info().code(),
+            Error::CompileError(err) => err.code(),
+            Error::WarningAsError(warning) => warning.code(),
+            Error::VariableError(_)
+            | Error::InvalidFunctionSignature { .. } => unreachable!(),
+        })
+        .collect();
 
-        let mut src = String::new();
-        let rules = fs::read_to_string(&in_path).expect("unable to read");
+    assert_eq!(
+        codes,
+        ["unknown_module", "wrong_type", "unknown_identifier", "syntax_error"]
+    );
 
-        src.push_str(rules.as_str());
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+    let results = scanner.scan(&[]).unwrap();
 
-        let mut compiler = Compiler::new();
+    let matching: Vec<_> =
+        results.matching_rules().map(|rule| rule.identifier()).collect();
 
-        compiler.ignore_module("unsupported_module");
-        compiler.add_source(src.as_str()).unwrap();
+    assert_eq!(matching, ["a", "d", "g"]);
 
-        let mut output_file = mint.new_goldenfile(out_path).unwrap();
+    // Without error recovery the first error is returned.
+    let mut compiler = Compiler::new();
 
-        for w in compiler.warnings() {
-            output_file
-                .write_all(w.to_string().as_bytes())
-                .expect("unable to write");
-        }
+    assert!(compiler.add_source("rule b { condition: 1 + \"foo\" }").is_err());
+    assert!(compiler.errors().is_empty());
+}
+
+#[test]
+fn warnings_config() {
+    let src = r#"
+        rule a { condition: 2 }
+        rule b { strings: $b = /a.*b/ condition: $b }
+        rule c { condition: 1 == 1 }
+    "#;
+
+    let codes = |compiler: &Compiler| {
+        compiler.warnings().iter().map(|w| w.code()).collect::<Vec<_>>()
+    };
+
+    let rule_names = |compiler: Compiler| {
+        let rules = compiler.build();
+        let mut scanner = Scanner::new(&rules);
+        let results = scanner.scan(&[]).unwrap();
+        let mut names = results
+            .matching_rules()
+            .chain(results.non_matching_rules())
+            .map(|rule| rule.identifier().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+
+    let mut compiler = Compiler::new();
+    compiler.add_source(src).unwrap();
+
+    assert_eq!(
+        codes(&compiler),
+        ["non_boolean_as_boolean", "slow_pattern", "always_matches"]
+    );
+
+    let mut compiler = Compiler::new();
+
+    compiler
+        .switch_warning("non_boolean_as_boolean", false)
+        .unwrap()
+        .add_source(src)
+        .unwrap();
+
+    assert_eq!(codes(&compiler), ["slow_pattern", "always_matches"]);
+
+    let mut compiler = Compiler::new();
+
+    compiler
+        .switch_all_warnings(false)
+        .switch_warning("slow_pattern", true)
+        .unwrap()
+        .add_source(src)
+        .unwrap();
+
+    assert_eq!(codes(&compiler), ["slow_pattern"]);
+
+    assert_eq!(
+        Compiler::new().switch_warning("foo", false).err(),
+        Some(InvalidWarningCode("foo".to_string()))
+    );
+
+    // Warnings treated as errors.
+    let mut compiler = Compiler::new();
+
+    let err = compiler
+        .warning_as_error("slow_pattern", true)
+        .unwrap()
+        .add_source(src)
+        .unwrap_err();
+
+    assert!(
+        matches!(err, Error::WarningAsError(warning) if warning.code() == "slow_pattern")
+    );
+
+    // The rule that raised the warning fails like with any other error, and
+    // the rules after it are not compiled.
+    assert_eq!(codes(&compiler), ["non_boolean_as_boolean"]);
+    assert!(compiler.last_source_stats().is_none());
+
+    assert_eq!(rule_names(compiler), ["a"]);
+
+    // In error recovery mode the warnings are collected as errors.
+    let mut compiler = Compiler::new();
+
+    compiler
+        .error_recovery(true)
+        .warning_as_error("slow_pattern", true)
+        .unwrap()
+        .warning_as_error("always_matches", true)
+        .unwrap()
+        .switch_warning("always_matches", false)
+        .unwrap()
+        .add_source(src)
+        .unwrap();
+
+    assert_eq!(codes(&compiler), ["non_boolean_as_boolean"]);
+    assert_eq!(compiler.errors().len(), 1);
+    assert_eq!(compiler.last_source_stats().unwrap().num_rules, 2);
+
+    assert_eq!(rule_names(compiler), ["a", "c"]);
+
+    // Warnings raised by the parser make the rule fail too.
+    let mut compiler = Compiler::new();
+
+    compiler
+        .error_recovery(true)
+        .warning_as_error("consecutive_jumps", true)
+        .unwrap()
+        .add_source(
+            r#"
+            rule a { condition: true }
+            rule b { strings: $b = { 00 [1] [2] 00 } condition: $b }
+            "#,
+        )
+        .unwrap();
+
+    assert_eq!(compiler.errors().len(), 1);
+
+    assert_eq!(rule_names(compiler), ["a"]);
+}
+
+#[test]
+fn includes() {
+    let matching_rules = |compiler: Compiler| {
+        let rules = compiler.build();
+        let mut scanner = Scanner::new(&rules);
+        let results = scanner.scan(&[]).unwrap();
+        results
+            .matching_rules()
+            .map(|rule| rule.identifier().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // Included files are relative to the directory of the including file.
+    let base_dir = "src/compiler/tests/testdata/includes";
+    let path = "src/compiler/tests/testdata/includes/main.yar";
+    let src = fs::read_to_string(path).unwrap();
+
+    let mut compiler = Compiler::new();
+    compiler
+        .enable_includes(true)
+        .include_resolver(FsIncludeResolver::new(base_dir).unwrap())
+        .add_source(SourceCode::from(src.as_str()).with_origin(path))
+        .unwrap();
+
+    assert_eq!(matching_rules(compiler), ["bar", "foo", "main"]);
+
+    // Files outside the base directory can't be included.
+    let mut compiler = Compiler::new();
+    compiler
+        .enable_includes(true)
+        .include_resolver(FsIncludeResolver::new(base_dir).unwrap());
+
+    for file_name in ["../mod.rs", "nested/../../mod.rs", "/etc/passwd"] {
+        assert!(matches!(
+            compiler
+                .add_source(format!(r#"include "{}""#, file_name).as_str())
+                .unwrap_err(),
+            Error::CompileError(err) if err.code() == "include_error"
+        ));
     }
+
+    assert!(compiler.add_source(r#"include "nested/bar.yar""#).is_ok());
+
+    // An origin without directory is relative to the base directory.
+    let mut compiler = Compiler::new();
+    compiler
+        .enable_includes(true)
+        .include_resolver(FsIncludeResolver::new(base_dir).unwrap())
+        .add_source(SourceCode::from(src.as_str()).with_origin("main.yar"))
+        .unwrap();
+
+    assert_eq!(matching_rules(compiler), ["bar", "foo", "main"]);
+
+    let files = HashMap::from([
+        (
+            "a".to_string(),
+            r#"include "b" rule a { condition: b }"#.to_string(),
+        ),
+        ("b".to_string(), "rule b { condition: true }".to_string()),
+        ("c".to_string(), r#"include "d""#.to_string()),
+        ("d".to_string(), r#"include "c""#.to_string()),
+    ]);
+
+    let mut compiler = Compiler::new();
+
+    compiler
+        .enable_includes(true)
+        .include_resolver(files.clone())
+        .add_source(r#"include "a" rule main { condition: a and b }"#)
+        .unwrap();
+
+    assert_eq!(matching_rules(compiler), ["b", "a", "main"]);
+
+    let mut compiler = Compiler::new();
+    compiler.enable_includes(true).include_resolver(files.clone());
+
+    assert!(matches!(
+        compiler.add_source(r#"include "e""#).unwrap_err(),
+        Error::CompileError(err) if err.title() == "can not include `e`"
+    ));
+
+    assert!(matches!(
+        compiler.add_source(r#"include "c""#).unwrap_err(),
+        Error::CompileError(err)
+            if err.code() == "include_error"
+                && err.to_string().contains("circular include")
+    ));
+
+    // Includes are disabled by default.
+    let mut compiler = Compiler::new();
+    compiler.include_resolver(files);
+
+    assert!(matches!(
+        compiler.add_source(r#"include "a""#).unwrap_err(),
+        Error::CompileError(err) if err.code() == "include_not_allowed"
+    ));
+
+    // Enabled, but without a resolver.
+    let mut compiler = Compiler::new();
+
+    assert!(matches!(
+        compiler
+            .enable_includes(true)
+            .add_source(r#"include "a""#)
+            .unwrap_err(),
+        Error::CompileError(err) if err.code() == "include_error"
+    ));
 }
@@ -44,12 +44,32 @@ assert_eq!(results.matching_rules().len(), 1);
 #![deny(missing_docs)]
 
 pub use compiler::compile;
+pub use compiler::AtomTableBuilder;
+pub use compiler::AtomsExtractor;
+pub use compiler::AtomsSetQuality;
+pub use compiler::CandidatePredicate;
 pub use compiler::CompileError;
+pub use compiler::CompileOptions;
 pub use compiler::Compiler;
 pub use compiler::Error;
+pub use compiler::ExtractedAtom;
+pub use compiler::ExtractedAtoms;
+pub use compiler::FieldDescriptor;
+pub use compiler::FsIncludeResolver;
+pub use compiler::GlobalSymbol;
+pub use compiler::GlobalSymbolKind;
+pub use compiler::IncludeResolver;
+pub use compiler::IncludedSource;
+pub use compiler::InvalidWarningCode;
+pub use compiler::ModuleDescriptor;
+pub use compiler::PatternSource;
+pub use compiler::RuleRef;
 pub use compiler::Rules;
 pub use compiler::SerializationError;
+pub use compiler::SourceStats;
 
+pub use scanner::CapturedScanContext;
+pub use scanner::EntropyCacheStats;
 pub use scanner::Match;
 pub use scanner::Matches;
 pub use scanner::MatchingRules;
@@ -58,20 +78,35 @@ pub use scanner::Metadata;
 pub use scanner::ModuleOutputs;
 pub use scanner::NonMatchingRules;
 pub use scanner::Pattern;
+pub use scanner::PatternDiagnostics;
 pub use scanner::Patterns;
+pub use scanner::PrefilterHit;
+pub use scanner::PrefilterHits;
 pub use scanner::Rule;
+pub use scanner::RuleStatus;
+pub use scanner::RuleStatuses;
 pub use scanner::ScanError;
 pub use scanner::ScanResults;
 pub use scanner::Scanner;
+pub use scanner::SkipReason;
+pub use scanner::StreamMatch;
+pub use scanner::StreamScanner;
+pub use scanner::WindowScanner;
 
 pub use modules::mods;
+pub use modules::ModuleError;
+pub use modules::ModuleLimits;
 
 pub use variables::Variable;
 pub use variables::VariableError;
 
+pub use wasm::host::FuncResult;
+pub use wasm::host::FuncValue;
+
 mod compiler;
 mod modules;
 mod re;
+pub mod report;
 mod scanner;
 mod string_pool;
 mod symbols;
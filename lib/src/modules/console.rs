@@ -109,4 +109,28 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn log_formatted_number() {
+        let rules = crate::compile(
+            r#"
+            import "console"
+            import "math"
+            rule test {
+                condition:
+                    console.log("value: ", math.to_string(uint16be(0), 16))
+            }
+            "#,
+        )
+        .unwrap();
+
+        let mut messages = vec![];
+
+        crate::scanner::Scanner::new(&rules)
+            .console_log(|message| messages.push(message))
+            .scan(b"\xca\xfe")
+            .expect("scan should not fail");
+
+        assert_eq!(messages, vec!["value: cafe"]);
+    }
 }
@@ -8,10 +8,27 @@ use crate::modules::protos::dotnet::*;
 
 pub mod parser;
 
+#[cfg(test)]
+mod tests;
+
 #[module_main]
-fn main(input: &[u8]) -> Dotnet {
-    match parser::Dotnet::parse(input) {
-        Ok(dotnet) => dotnet.into(),
+fn main(
+    input: &[u8],
+    limits: &ModuleLimits,
+    errors: &mut Vec<ModuleError>,
+) -> Dotnet {
+    match parser::Dotnet::parse_with_limits(input, *limits) {
+        Ok(dotnet) => {
+            let dotnet: Dotnet = dotnet.into();
+            // Resources that exceed the module limits are not included in
+            // the output.
+            if dotnet.resources_truncated() {
+                errors.push(ModuleError::PartialOutput {
+                    reason: "too many resources".to_string(),
+                });
+            }
+            dotnet
+        }
         Err(_) => {
             let mut dotnet = Dotnet::new();
             dotnet.is_dotnet = Some(false);
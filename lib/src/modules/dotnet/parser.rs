@@ -21,6 +21,7 @@ use uuid::Uuid;
 
 use crate::modules::pe::parser::{DirEntry, PE};
 use crate::modules::protos;
+use crate::modules::ModuleLimits;
 
 type NomError<'a> = nom::Err<nom::error::Error<&'a [u8]>>;
 
@@ -115,6 +116,9 @@ pub struct Dotnet<'a> {
     assembly_refs: Vec<AssemblyRef<'a>>,
     /// Resource table.
     resources: Vec<Resource<'a>>,
+    /// True if the resource table had more than `limits.max_resources`
+    /// entries and only the first ones are in `resources`.
+    resources_truncated: bool,
     /// NestedClass table.
     nested_classes: Vec<NestedClass>,
     /// GenericParam table.
@@ -129,7 +133,16 @@ impl<'a> Dotnet<'a> {
     /// Parses a .NET file and produces a [`Dotnet`] structure containing
     /// metadata extracted from the file.
     pub fn parse(data: &'a [u8]) -> Result<Self, Error<'a>> {
-        let pe = PE::parse(data)?;
+        Self::parse_with_limits(data, ModuleLimits::default())
+    }
+
+    /// Similar to [`Dotnet::parse`], but the number of resources is limited
+    /// according to `limits`.
+    pub fn parse_with_limits(
+        data: &'a [u8],
+        limits: ModuleLimits,
+    ) -> Result<Self, Error<'a>> {
+        let pe = PE::parse_with_limits(data, limits)?;
 
         let (_, _, cli_header) = pe
             .get_dir_entry_data(
@@ -215,6 +228,11 @@ impl<'a> Dotnet<'a> {
             .filter_map(|rva| pe.rva_to_offset(*rva))
             .collect();
 
+        if dotnet.resources.len() > limits.max_resources {
+            dotnet.resources.truncate(limits.max_resources);
+            dotnet.resources_truncated = true;
+        }
+
         Ok(dotnet)
     }
 
@@ -2732,6 +2750,8 @@ impl From<Dotnet<'_>> for protos::dotnet::Dotnet {
             dotnet.resources.iter().map(protos::dotnet::Resource::from),
         );
 
+        result.set_resources_truncated(dotnet.resources_truncated);
+
         result
             .classes
             .extend(dotnet.get_user_types().map(protos::dotnet::Class::from));
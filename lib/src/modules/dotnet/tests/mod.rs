@@ -0,0 +1,50 @@
+use pretty_assertions::assert_eq;
+
+use crate::modules::tests::create_binary_from_zipped_ihex;
+
+#[test]
+fn module_limits() {
+    let dotnet = create_binary_from_zipped_ihex(
+        "src/modules/dotnet/tests/testdata/0885cff80dd6e14559c6429a89d724d9f5553a34e0716a660376728b8987c61b.in.zip",
+    );
+
+    let rules = crate::compile(
+        r#"
+        import "dotnet"
+        rule test {
+          condition:
+            dotnet.resources_truncated and dotnet.number_of_resources == 1
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut errors = Vec::new();
+    let mut scanner = crate::Scanner::new(&rules);
+
+    scanner.on_module_error(|module, error| {
+        errors.push((module.to_string(), error.clone()))
+    });
+
+    // With the default limits the file has 2 resources.
+    assert_eq!(scanner.scan(&dotnet).unwrap().matching_rules().len(), 0);
+
+    scanner.set_module_limits(crate::ModuleLimits {
+        max_resources: 1,
+        ..Default::default()
+    });
+
+    assert_eq!(scanner.scan(&dotnet).unwrap().matching_rules().len(), 1);
+
+    drop(scanner);
+
+    assert_eq!(
+        errors,
+        [(
+            "dotnet".to_string(),
+            crate::ModuleError::PartialOutput {
+                reason: "too many resources".to_string()
+            }
+        )]
+    );
+}
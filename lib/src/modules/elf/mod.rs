@@ -4,6 +4,8 @@ This allows creating YARA rules based on ELF metadata, including segments
 and sections information, exported symbols, target platform, etc.
  */
 
+use std::ops::Range;
+
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use md5::{Digest, Md5};
@@ -19,9 +21,28 @@ pub mod parser;
 mod tests;
 
 #[module_main]
-fn main(data: &[u8]) -> ELF {
-    match parser::ElfParser::new().parse(data) {
-        Ok(elf) => elf,
+fn main(
+    data: &[u8],
+    limits: &ModuleLimits,
+    errors: &mut Vec<ModuleError>,
+) -> ELF {
+    match parser::ElfParser::new().limits(*limits).parse(data) {
+        Ok(elf) => {
+            // Parts of the file that exceed the module limits are not
+            // included in the output.
+            for (truncated, what) in [
+                (elf.symtab_truncated(), "symbols in symtab"),
+                (elf.dynsym_truncated(), "symbols in dynsym"),
+                (elf.notes_truncated(), "notes"),
+            ] {
+                if truncated {
+                    errors.push(ModuleError::PartialOutput {
+                        reason: format!("too many {what}"),
+                    });
+                }
+            }
+            elf
+        }
         Err(_) => ELF::new(),
     }
 }
@@ -54,6 +75,22 @@ fn import_md5(ctx: &mut ScanContext) -> Option<RuntimeString> {
     Some(RuntimeString::new(digest))
 }
 
+/// Returns the range of file offsets occupied by the section with the given
+/// index in the section table.
+///
+/// The result can be used with `in`, as in `$a in elf.section_range(1)`,
+/// which is equivalent to `$a in (offset..offset + size - 1)`, where
+/// `offset` and `size` are the section's `offset` and `size`.
+#[module_export]
+fn section_range(ctx: &ScanContext, index: i64) -> Option<Range<i64>> {
+    let elf = ctx.module_output::<ELF>()?;
+    let section = elf.sections.get(usize::try_from(index).ok()?)?;
+    let start: i64 = section.offset?.try_into().ok()?;
+    let size: i64 = section.size?.try_into().ok()?;
+
+    Some(start..start.checked_add(size)?)
+}
+
 lazy_static! {
     /// Function names excluded while computing the telfhash. These exclusions
     /// are based on the original implementation:
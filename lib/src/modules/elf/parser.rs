@@ -3,7 +3,7 @@ use std::ops::Range;
 
 use nom::bytes::complete::{take, take_till};
 use nom::combinator::{map, map_res, verify};
-use nom::multi::{count, many0};
+use nom::multi::{count, many0, many_m_n};
 use nom::number::complete::{le_u32, u16, u32, u64, u8};
 use nom::number::Endianness;
 use nom::sequence::tuple;
@@ -11,6 +11,7 @@ use nom::{Err, IResult, Parser};
 use protobuf::EnumOrUnknown;
 
 use crate::modules::protos::elf;
+use crate::modules::ModuleLimits;
 
 #[repr(u8)]
 enum Class {
@@ -23,6 +24,7 @@ pub struct ElfParser {
     result: elf::ELF,
     endianness: Endianness,
     class: Class,
+    limits: ModuleLimits,
 }
 
 impl ElfParser {
@@ -32,9 +34,17 @@ impl ElfParser {
             result: elf::ELF::default(),
             endianness: Endianness::Native,
             class: Class::Elf32,
+            limits: ModuleLimits::default(),
         }
     }
 
+    /// Sets the limits on the number of symbols parsed from each symbol
+    /// table, and on the number of notes.
+    pub fn limits(&mut self, limits: ModuleLimits) -> &mut Self {
+        self.limits = limits;
+        self
+    }
+
     /// Parses an ELF file and produces a [`elf::ELF`] protobuf containing
     /// metadata extracted from the file.
     pub fn parse<'a>(
@@ -115,8 +125,18 @@ impl ElfParser {
             if s.type_ == Self::ELF_PT_DYNAMIC {
                 self.result.dynamic.extend(self.parse_dyn_entries(elf, s));
             }
+
+            if s.type_ == Self::ELF_PT_NOTE {
+                if let Some(notes) =
+                    s.offset_range().and_then(|range| elf.get(range))
+                {
+                    self.add_notes(notes, s.alignment);
+                }
+            }
         }
 
+        self.set_notes_info();
+
         self.result.dynamic_section_entries =
             Some(self.result.dynamic.len().try_into().unwrap());
 
@@ -147,6 +167,24 @@ impl ElfParser {
         // index for that section in the section table.
         let shstrtab = sections.get(ehdr.sh_str_tab_index as usize);
 
+        // Relocatable objects don't have segments, in such cases the notes
+        // are taken from the note sections. When the file has `PT_NOTE`
+        // segments the sections are ignored, as they contain the same notes.
+        if self.result.notes.is_empty() {
+            for s in sections.iter().filter(|s| s.type_ == Self::ELF_SHT_NOTE)
+            {
+                if let Some(notes) = s.offset_range().and_then(|r| elf.get(r))
+                {
+                    self.add_notes(notes, s.alignment);
+                }
+            }
+            self.set_notes_info();
+        }
+
+        // The file is considered stripped if it has neither a symbol table
+        // nor debugging information.
+        let mut is_stripped = true;
+
         for s in sections.iter() {
             let mut section = elf::Section::new();
 
@@ -161,25 +199,37 @@ impl ElfParser {
                 .ok()
                 .map(EnumOrUnknown::<elf::SectionType>::from_i32);
 
+            if s.type_ == Self::ELF_SHT_SYMTAB
+                || section.name().starts_with(".debug")
+            {
+                is_stripped = false;
+            }
+
             self.result.sections.push(section);
         }
 
+        self.result.is_stripped = Some(is_stripped);
+
         // Find the `.symtab` section and parse the symbol table.
-        self.result.symtab.extend(self.parse_sym_table(
-            elf,
-            sections.as_slice(),
-            |section| section.type_ == Self::ELF_SHT_SYMTAB,
-        ));
+        let (symtab, symtab_truncated) =
+            self.parse_sym_table(elf, sections.as_slice(), |section| {
+                section.type_ == Self::ELF_SHT_SYMTAB
+            });
+
+        self.result.symtab.extend(symtab);
+        self.result.symtab_truncated = Some(symtab_truncated);
 
         self.result.symtab_entries =
             Some(self.result.symtab.len().try_into().unwrap());
 
         // Find the `.dynsym` section and parse the dynamic linking symbols.
-        self.result.dynsym.extend(self.parse_sym_table(
-            elf,
-            sections.as_slice(),
-            |section| section.type_ == Self::ELF_SHT_DYNSYM,
-        ));
+        let (dynsym, dynsym_truncated) =
+            self.parse_sym_table(elf, sections.as_slice(), |section| {
+                section.type_ == Self::ELF_SHT_DYNSYM
+            });
+
+        self.result.dynsym.extend(dynsym);
+        self.result.dynsym_truncated = Some(dynsym_truncated);
 
         self.result.dynsym_entries =
             Some(self.result.dynsym.len().try_into().unwrap());
@@ -196,12 +246,15 @@ impl ElfParser {
     const ELF_DATA_2LSB: u8 = 0x01;
     const ELF_DATA_2MSB: u8 = 0x02;
     const ELF_PT_DYNAMIC: u32 = 0x02;
+    const ELF_PT_NOTE: u32 = 0x04;
     const ELF_SHN_LORESERVE: u16 = 0xFF00;
     const ELF_DT_NULL: u64 = 0;
     const ELF_SHT_NULL: u32 = 0;
     const ELF_SHT_SYMTAB: u32 = 2;
+    const ELF_SHT_NOTE: u32 = 7;
     const ELF_SHT_NOBITS: u32 = 8;
     const ELF_SHT_DYNSYM: u32 = 11;
+    const ELF_NT_GNU_BUILD_ID: u32 = 3;
 
     /// Parses an offset or address.
     ///
@@ -326,7 +379,7 @@ impl ElfParser {
                     shdr.size,
                     shdr.link,
                     shdr.info,
-                    _,
+                    shdr.alignment,
                     shdr.entry_size,
                 ),
             ) = tuple((
@@ -421,25 +474,31 @@ impl ElfParser {
     /// structures that describe the sections in the ELF. The first section for
     /// which the predicate functions returns true is considered as symbol
     /// table and parsed accordingly. The result is a vector of [`elf::Sym`]
-    /// structures.
+    /// structures, together with a boolean that is true if the table has
+    /// more than `limits.max_symbols` entries and was truncated.
     fn parse_sym_table<P>(
         &self,
         elf: &[u8],
         sections: &[Shdr],
         predicate: P,
-    ) -> Vec<elf::Sym>
+    ) -> (Vec<elf::Sym>, bool)
     where
         P: FnMut(&&Shdr) -> bool,
     {
         let mut result = vec![];
+        let mut truncated = false;
 
         if let Some(symtab) = sections.iter().find(predicate) {
             if let Some(range) = symtab.offset_range() {
                 if let Some(data) = elf.get(range) {
-                    let syms = many0(self.parse_sym())
-                        .parse(data)
-                        .map(|(_, syms)| syms)
-                        .ok();
+                    let syms =
+                        many_m_n(0, self.limits.max_symbols, self.parse_sym())
+                            .parse(data)
+                            .map(|(rest, syms)| {
+                                truncated = self.parse_sym()(rest).is_ok();
+                                syms
+                            })
+                            .ok();
 
                     let symtabstr = sections.get(symtab.link as usize);
 
@@ -472,7 +531,7 @@ impl ElfParser {
             }
         }
 
-        result
+        (result, truncated)
     }
 
     fn parse_sym(&self) -> impl FnMut(&[u8]) -> IResult<&[u8], Sym> + '_ {
@@ -558,6 +617,123 @@ impl ElfParser {
         Some(String::from_utf8_lossy(str_bytes).to_string())
     }
 
+    /// Parses the notes contained in a `PT_NOTE` segment or `SHT_NOTE`
+    /// section.
+    ///
+    /// `alignment` is the alignment of the segment or section. Notes in
+    /// segments and sections aligned to 8 bytes have their name and
+    /// descriptor padded to an 8-bytes boundary, in all other cases they
+    /// are padded to a 4-bytes boundary.
+    ///
+    /// At most `max_notes` notes are returned, together with a boolean that
+    /// is true if there were more notes that were not parsed.
+    fn parse_notes(
+        &self,
+        notes: &[u8],
+        alignment: u64,
+        max_notes: usize,
+    ) -> (Vec<elf::Note>, bool) {
+        let alignment = if alignment == 8 { 8 } else { 4 };
+        let mut result = Vec::new();
+        let mut remainder = notes;
+
+        while let Ok((rest, note)) = self.parse_note(remainder, alignment) {
+            if result.len() == max_notes {
+                return (result, true);
+            }
+            let mut n = elf::Note::new();
+            // The name includes the terminating null character, which is
+            // not part of the name exposed to rules.
+            n.name = Some(
+                String::from_utf8_lossy(
+                    note.name.strip_suffix(b"\0").unwrap_or(note.name),
+                )
+                .to_string(),
+            );
+            n.type_ = Some(note.type_);
+            n.descriptor = Some(note.desc.to_vec());
+            result.push(n);
+            remainder = rest;
+        }
+
+        (result, false)
+    }
+
+    /// Parses the notes contained in a `PT_NOTE` segment or `SHT_NOTE`
+    /// section and adds them to the result, up to `limits.max_notes` notes
+    /// in total.
+    fn add_notes(&mut self, notes: &[u8], alignment: u64) {
+        let (notes, truncated) = self.parse_notes(
+            notes,
+            alignment,
+            self.limits.max_notes.saturating_sub(self.result.notes.len()),
+        );
+
+        self.result.notes.extend(notes);
+
+        if truncated {
+            self.result.notes_truncated = Some(true);
+        }
+    }
+
+    /// Updates `number_of_notes`, `notes_truncated` and `build_id` according
+    /// to the notes parsed so far.
+    fn set_notes_info(&mut self) {
+        self.result.number_of_notes =
+            Some(self.result.notes.len().try_into().unwrap());
+
+        self.result.notes_truncated.get_or_insert(false);
+
+        self.result.build_id = self
+            .result
+            .notes
+            .iter()
+            .find(|note| {
+                note.type_ == Some(Self::ELF_NT_GNU_BUILD_ID)
+                    && note.name() == "GNU"
+            })
+            .map(|note| {
+                note.descriptor()
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect()
+            });
+    }
+
+    /// Parses a single note.
+    ///
+    /// Each note consists in a header with the size of the name, the size
+    /// of the descriptor and the type of the note, followed by the name and
+    /// the descriptor. Both the name and the descriptor are padded so that
+    /// the next field starts at an offset that is multiple of `alignment`,
+    /// relative to the start of the note.
+    fn parse_note<'a>(
+        &self,
+        input: &'a [u8],
+        alignment: usize,
+    ) -> IResult<&'a [u8], Note<'a>> {
+        let padding = |remainder: &[u8]| {
+            let offset = input.len() - remainder.len();
+            (alignment - offset % alignment) % alignment
+        };
+
+        let (remainder, (name_size, desc_size, type_)) = tuple((
+            u32(self.endianness), // name size
+            u32(self.endianness), // descriptor size
+            u32(self.endianness), // type
+        ))(input)?;
+
+        let (remainder, name) = take(name_size)(remainder)?;
+        let (remainder, _) = take(padding(remainder))(remainder)?;
+        let (remainder, desc) = take(desc_size)(remainder)?;
+
+        // The padding after the last descriptor may be missing.
+        let remainder =
+            remainder.get(padding(remainder)..).unwrap_or_default();
+
+        Ok((remainder, Note { type_, name, desc }))
+    }
+
     fn parse_dyn_entries(&self, elf: &[u8], s: &Phdr) -> Vec<elf::Dyn> {
         let mut result = vec![];
 
@@ -623,6 +799,7 @@ struct Shdr {
     size: u64,
     link: u32,
     info: u32,
+    alignment: u64,
     entry_size: u64,
 }
 
@@ -678,3 +855,10 @@ struct Sym {
     other: u8,
     shndx: u16,
 }
+
+/// ELF note
+struct Note<'a> {
+    type_: u32,
+    name: &'a [u8],
+    desc: &'a [u8],
+}
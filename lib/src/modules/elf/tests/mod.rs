@@ -1,6 +1,7 @@
 use pretty_assertions::assert_eq;
 
 use crate::modules::tests::create_binary_from_zipped_ihex;
+use crate::tests::rule_false;
 use crate::tests::rule_true;
 use crate::tests::test_rule;
 
@@ -39,3 +40,212 @@ fn telfhash() {
         &elf
     );
 }
+
+#[test]
+fn section_range() {
+    let elf = create_binary_from_zipped_ihex(
+        "src/modules/elf/tests/testdata/8bfe885838b4d1fba194b761ca900a0425aa892e4b358bf5a9bf4304e571df1b.in.zip",
+    );
+
+    // The note in the `.note.gnu.property` section has the name "GNU".
+    rule_true!(
+        r#"
+        import "elf"
+        rule test {
+          strings:
+            $a = "GNU\x00"
+          condition:
+            $a in elf.section_range(1)
+        }
+        "#,
+        &elf
+    );
+
+    // The first section is empty.
+    rule_false!(
+        r#"
+        import "elf"
+        rule test {
+          strings:
+            $a = "GNU\x00"
+          condition:
+            $a in elf.section_range(0)
+        }
+        "#,
+        &elf
+    );
+
+    // Ranges for non-existing sections are undefined.
+    rule_false!(
+        r#"
+        import "elf"
+        rule test {
+          strings:
+            $a = "GNU\x00"
+          condition:
+            $a in elf.section_range(100)
+        }
+        "#,
+        &elf
+    );
+
+    rule_true!(
+        r#"
+        import "elf"
+        rule test {
+          strings:
+            $a = "GNU\x00"
+          condition:
+            for any i in (0..elf.number_of_sections - 1) : (
+              $a in elf.section_range(i) and
+              elf.sections[i].name == ".note.gnu.build-id"
+            )
+        }
+        "#,
+        &elf
+    );
+}
+
+#[test]
+fn build_id() {
+    let elf = create_binary_from_zipped_ihex(
+        "src/modules/elf/tests/testdata/8bfe885838b4d1fba194b761ca900a0425aa892e4b358bf5a9bf4304e571df1b.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "elf"
+        rule test {
+          condition:
+            elf.build_id == "f8552717600e7b9764eb8b07252b4556be3d8a7a" and
+            not elf.is_stripped
+        }
+        "#,
+        &elf
+    );
+
+    let elf = create_binary_from_zipped_ihex(
+        "src/modules/elf/tests/testdata/f2ae0c22691d4f0364eecaaa826bdc82b8c32324637fe92b65e743d0c47d9ff6.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "elf"
+        rule test {
+          condition:
+            elf.build_id == "ca69e7e890afb94d190b60f854db7436cf1aefbb" and
+            elf.is_stripped
+        }
+        "#,
+        &elf
+    );
+
+    let elf = create_binary_from_zipped_ihex(
+        "src/modules/elf/tests/testdata/5ddb04cb08755e692fa540c51cdb8c93818264222518c11107aae7d586fabd9e.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "elf"
+        rule test {
+          condition:
+            not defined elf.build_id
+        }
+        "#,
+        &elf
+    );
+}
+
+#[test]
+fn notes() {
+    let elf = create_binary_from_zipped_ihex(
+        "src/modules/elf/tests/testdata/830bce561b53a1d28da96fbe1be28856567204853b2dcd9a73d9add9296c6fc0.in.zip",
+    );
+
+    // The first note is in a segment aligned to 8 bytes, the remaining
+    // ones are in a segment aligned to 4 bytes.
+    rule_true!(
+        r#"
+        import "elf"
+        rule test {
+          condition:
+            elf.number_of_notes == 3 and
+            elf.notes[0].name == "GNU" and
+            elf.notes[0].type == 5 and
+            elf.notes[1].type == 3 and
+            elf.notes[2].name == "GNU" and
+            elf.notes[2].type == 1 and
+            elf.notes[2].descriptor == "\x00\x00\x00\x00\x03\x00\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00" and
+            elf.build_id == "e3ffbb5a4ab58c5001020666b67e5b2ba20736d0"
+        }
+        "#,
+        &elf
+    );
+}
+
+#[test]
+fn module_limits() {
+    let elf = create_binary_from_zipped_ihex(
+        "src/modules/elf/tests/testdata/8bfe885838b4d1fba194b761ca900a0425aa892e4b358bf5a9bf4304e571df1b.in.zip",
+    );
+
+    let rules = crate::compile(
+        r#"
+        import "elf"
+        rule test {
+          condition:
+            elf.symtab_truncated and elf.symtab_entries == 30 and
+            not elf.dynsym_truncated and elf.dynsym_entries == 21
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = crate::Scanner::new(&rules);
+
+    assert_eq!(scanner.scan(&elf).unwrap().matching_rules().len(), 0);
+
+    scanner.set_module_limits(crate::ModuleLimits {
+        max_symbols: 30,
+        ..Default::default()
+    });
+
+    assert_eq!(scanner.scan(&elf).unwrap().matching_rules().len(), 1);
+
+    let rules = crate::compile(
+        r#"
+        import "elf"
+        rule test {
+          condition:
+            elf.notes_truncated and elf.number_of_notes == 1
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut errors = Vec::new();
+    let mut scanner = crate::Scanner::new(&rules);
+
+    scanner
+        .set_module_limits(crate::ModuleLimits {
+            max_notes: 1,
+            ..Default::default()
+        })
+        .on_module_error(|module, error| {
+            errors.push((module.to_string(), error.clone()))
+        });
+
+    assert_eq!(scanner.scan(&elf).unwrap().matching_rules().len(), 1);
+
+    drop(scanner);
+
+    assert_eq!(
+        errors,
+        [(
+            "elf".to_string(),
+            crate::ModuleError::PartialOutput {
+                reason: "too many notes".to_string()
+            }
+        )]
+    );
+}
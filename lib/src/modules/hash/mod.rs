@@ -8,6 +8,8 @@ use sha2::{Digest, Sha256};
 use crate::modules::prelude::*;
 use crate::modules::protos::hash::*;
 
+mod ssdeep;
+
 #[cfg(test)]
 mod tests;
 
@@ -26,6 +28,9 @@ thread_local!(
 
     static CHECKSUM32_CACHE: RefCell<FxHashMap<(i64, i64), i64>> =
         RefCell::new(FxHashMap::default());
+
+    static SSDEEP_CACHE: RefCell<FxHashMap<(i64, i64), String>> =
+        RefCell::new(FxHashMap::default());
 );
 
 #[module_main]
@@ -36,6 +41,7 @@ fn main(_data: &[u8]) -> Hash {
     MD5_CACHE.with(|cache| cache.borrow_mut().clear());
     CRC32_CACHE.with(|cache| cache.borrow_mut().clear());
     CHECKSUM32_CACHE.with(|cache| cache.borrow_mut().clear());
+    SSDEEP_CACHE.with(|cache| cache.borrow_mut().clear());
 
     Hash::new()
 }
@@ -223,3 +229,62 @@ fn checksum_str(ctx: &ScanContext, s: RuntimeString) -> Option<i64> {
     }
     Some(checksum.into())
 }
+
+#[module_export(name = "ssdeep")]
+fn ssdeep_data(
+    ctx: &mut ScanContext,
+    offset: i64,
+    size: i64,
+) -> Option<RuntimeString> {
+    // The ssdeep digest of empty data is not meaningful.
+    if size <= 0 {
+        return None;
+    }
+
+    let cached = SSDEEP_CACHE.with(|cache| -> Option<RuntimeString> {
+        Some(RuntimeString::from_slice(
+            ctx,
+            cache.borrow().get(&(offset, size))?.as_bytes(),
+        ))
+    });
+
+    if cached.is_some() {
+        return cached;
+    }
+
+    let range = offset.try_into().ok()?..(offset + size).try_into().ok()?;
+    let data = ctx.scanned_data().get(range)?;
+    let digest = ssdeep::digest(data);
+
+    SSDEEP_CACHE.with(|cache| {
+        cache.borrow_mut().insert((offset, size), digest.clone());
+    });
+
+    Some(RuntimeString::new(digest))
+}
+
+#[module_export(name = "ssdeep")]
+fn ssdeep_str(
+    ctx: &mut ScanContext,
+    s: RuntimeString,
+) -> Option<RuntimeString> {
+    let s = s.as_bstr(ctx);
+
+    if s.is_empty() {
+        return None;
+    }
+
+    Some(RuntimeString::new(ssdeep::digest(s)))
+}
+
+#[module_export]
+fn ssdeep_compare(
+    ctx: &ScanContext,
+    a: RuntimeString,
+    b: RuntimeString,
+) -> Option<i64> {
+    let a = a.as_bstr(ctx).to_str().ok()?;
+    let b = b.as_bstr(ctx).to_str().ok()?;
+
+    ssdeep::compare(a, b).map(|score| score.into())
+}
@@ -0,0 +1,389 @@
+/*! Implementation of ssdeep, a context-triggered piecewise hash (CTPH).
+
+This is a port of the algorithm implemented by [ssdeep][1], which in turn
+is based on [spamsum][2]. The digests produced are identical to the ones
+produced by ssdeep, and similarity scores are computed in the same way.
+
+[1]: https://ssdeep-project.github.io/ssdeep/
+[2]: https://www.samba.org/ftp/unpacked/junkcode/spamsum/README
+ */
+
+const ROLLING_WINDOW: usize = 7;
+const MIN_BLOCK_SIZE: u64 = 3;
+const SPAMSUM_LENGTH: usize = 64;
+const NUM_BLOCKHASHES: usize = 31;
+const HASH_PRIME: u32 = 0x01000193;
+const HASH_INIT: u32 = 0x28021967;
+
+const B64: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Returns the ssdeep digest for `data`.
+pub(crate) fn digest(data: &[u8]) -> String {
+    let mut state = State::new(data.len() as u64);
+
+    for c in data {
+        state.update(*c);
+    }
+
+    state.digest()
+}
+
+/// Compares two ssdeep digests and returns a score between 0 and 100,
+/// where 0 means that the digests are not similar, and 100 means that
+/// they are identical, or very similar.
+///
+/// Returns `None` if any of the digests is not valid.
+pub(crate) fn compare(a: &str, b: &str) -> Option<u32> {
+    let (block_size1, a1, a2) = parse(a)?;
+    let (block_size2, b1, b2) = parse(b)?;
+
+    // Digests can be compared only if their block sizes are equal, or one
+    // is twice the other.
+    if block_size1 != block_size2
+        && block_size1.checked_mul(2) != Some(block_size2)
+        && (block_size1 % 2 == 1 || block_size1 / 2 != block_size2)
+    {
+        return Some(0);
+    }
+
+    let a1 = eliminate_sequences(a1);
+    let a2 = eliminate_sequences(a2);
+    let b1 = eliminate_sequences(b1);
+    let b2 = eliminate_sequences(b2);
+
+    if block_size1 == block_size2 && a1 == b1 && a2 == b2 {
+        return Some(100);
+    }
+
+    let score = if block_size1 == block_size2 {
+        score_strings(&a1, &b1, block_size1)
+            .max(score_strings(&a2, &b2, block_size1 * 2))
+    } else if block_size1 * 2 == block_size2 {
+        score_strings(&b1, &a2, block_size2)
+    } else {
+        score_strings(&a1, &b2, block_size1)
+    };
+
+    Some(score)
+}
+
+/// Rolling hash computed over the last [`ROLLING_WINDOW`] bytes.
+#[derive(Default)]
+struct RollingHash {
+    window: [u8; ROLLING_WINDOW],
+    h1: u32,
+    h2: u32,
+    h3: u32,
+    n: usize,
+}
+
+impl RollingHash {
+    fn update(&mut self, c: u8) {
+        self.h2 = self.h2.wrapping_sub(self.h1);
+        self.h2 = self.h2.wrapping_add(ROLLING_WINDOW as u32 * c as u32);
+        self.h1 = self.h1.wrapping_add(c as u32);
+        self.h1 = self.h1.wrapping_sub(self.window[self.n] as u32);
+        self.window[self.n] = c;
+        self.n = (self.n + 1) % ROLLING_WINDOW;
+        self.h3 <<= 5;
+        self.h3 ^= c as u32;
+    }
+
+    fn sum(&self) -> u32 {
+        self.h1.wrapping_add(self.h2).wrapping_add(self.h3)
+    }
+}
+
+/// Digest computed for a given block size.
+#[derive(Clone)]
+struct BlockHash {
+    /// Characters in the digest. Only the first `len` characters are
+    /// final, the one at `len` (if non-zero) is overwritten every time
+    /// the block is triggered once the digest is full.
+    digest: [u8; SPAMSUM_LENGTH],
+    len: usize,
+    /// Hash for the current piece.
+    h: u32,
+    /// Hash for the current piece, for the digest truncated to half of
+    /// [`SPAMSUM_LENGTH`].
+    half_h: u32,
+    /// Last character of the digest truncated to half of
+    /// [`SPAMSUM_LENGTH`], or zero.
+    half_digest: u8,
+}
+
+impl BlockHash {
+    fn new() -> Self {
+        Self {
+            digest: [0; SPAMSUM_LENGTH],
+            len: 0,
+            h: HASH_INIT,
+            half_h: HASH_INIT,
+            half_digest: 0,
+        }
+    }
+}
+
+struct State {
+    roll: RollingHash,
+    /// Block hashes for block sizes `MIN_BLOCK_SIZE << i`. The ones
+    /// before `start` are not updated anymore, as they are too small
+    /// for the data's size.
+    blocks: Vec<BlockHash>,
+    start: usize,
+    total_size: u64,
+}
+
+impl State {
+    fn new(total_size: u64) -> Self {
+        Self {
+            roll: RollingHash::default(),
+            blocks: vec![BlockHash::new()],
+            start: 0,
+            total_size,
+        }
+    }
+
+    fn block_size(i: usize) -> u64 {
+        MIN_BLOCK_SIZE << i
+    }
+
+    fn update(&mut self, c: u8) {
+        self.roll.update(c);
+
+        let h = self.roll.sum() as u64;
+
+        for block in &mut self.blocks[self.start..] {
+            block.h = sum_hash(c, block.h);
+            block.half_h = sum_hash(c, block.half_h);
+        }
+
+        // `self.blocks` can grow while iterating, the new block must be
+        // checked too.
+        let mut i = self.start;
+
+        while i < self.blocks.len() {
+            let block_size = Self::block_size(i);
+
+            // Block sizes are powers of two of each other, if the current
+            // one is not triggered the next ones won't be triggered either.
+            if h % block_size != block_size - 1 {
+                break;
+            }
+
+            if self.blocks[i].len == 0 {
+                self.try_fork();
+            }
+
+            let block = &mut self.blocks[i];
+
+            block.digest[block.len] = B64[(block.h & 63) as usize];
+            block.half_digest = B64[(block.half_h & 63) as usize];
+
+            if block.len < SPAMSUM_LENGTH - 1 {
+                block.len += 1;
+                block.digest[block.len] = 0;
+                block.h = HASH_INIT;
+                if block.len < SPAMSUM_LENGTH / 2 {
+                    block.half_h = HASH_INIT;
+                    block.half_digest = 0;
+                }
+            } else {
+                self.try_reduce();
+            }
+
+            i += 1;
+        }
+    }
+
+    /// Adds a block hash for the next block size, which starts in the
+    /// same state as the current last one.
+    fn try_fork(&mut self) {
+        if self.blocks.len() >= NUM_BLOCKHASHES {
+            return;
+        }
+        let last = self.blocks.last().unwrap();
+        let mut block = BlockHash::new();
+        block.h = last.h;
+        block.half_h = last.half_h;
+        self.blocks.push(block);
+    }
+
+    /// Stops updating the block hash for the smallest block size if it
+    /// won't be used by the final digest.
+    fn try_reduce(&mut self) {
+        if self.blocks.len() - self.start < 2 {
+            return;
+        }
+        if Self::block_size(self.start) * SPAMSUM_LENGTH as u64
+            >= self.total_size
+        {
+            return;
+        }
+        if self.blocks[self.start + 1].len < SPAMSUM_LENGTH / 2 {
+            return;
+        }
+        self.start += 1;
+    }
+
+    fn digest(&self) -> String {
+        let h = self.roll.sum();
+        let mut i = self.start;
+
+        // Initial guess for the block size, which depends only on the size
+        // of the data.
+        while i < NUM_BLOCKHASHES - 1
+            && Self::block_size(i) * (SPAMSUM_LENGTH as u64) < self.total_size
+        {
+            i += 1;
+        }
+
+        // Adjust the guess to the actual length of the digests.
+        while i >= self.blocks.len() {
+            i -= 1;
+        }
+
+        while i > self.start && self.blocks[i].len < SPAMSUM_LENGTH / 2 {
+            i -= 1;
+        }
+
+        let block = &self.blocks[i];
+        let mut result = Vec::with_capacity(2 * SPAMSUM_LENGTH + 16);
+
+        result.extend_from_slice(&block.digest[..block.len]);
+
+        if h != 0 {
+            result.push(B64[(block.h & 63) as usize]);
+        } else if block.digest[block.len] != 0 {
+            result.push(block.digest[block.len]);
+        }
+
+        result.push(b':');
+
+        if i < self.blocks.len() - 1 {
+            let block = &self.blocks[i + 1];
+            let len = block.len.min(SPAMSUM_LENGTH / 2 - 1);
+
+            result.extend_from_slice(&block.digest[..len]);
+
+            if h != 0 {
+                result.push(B64[(block.half_h & 63) as usize]);
+            } else if block.half_digest != 0 {
+                result.push(block.half_digest);
+            }
+        } else if h != 0 {
+            // The block for twice the block size never diverged from the
+            // current one.
+            if i == 0 {
+                result.push(B64[(block.h & 63) as usize]);
+            } else {
+                result.push(B64[(block.half_h & 63) as usize]);
+            }
+        }
+
+        // All the characters in `result` are ASCII.
+        format!(
+            "{}:{}",
+            Self::block_size(i),
+            String::from_utf8(result).unwrap()
+        )
+    }
+}
+
+fn sum_hash(c: u8, h: u32) -> u32 {
+    h.wrapping_mul(HASH_PRIME) ^ c as u32
+}
+
+/// Splits a digest of the form `<block size>:<hash 1>:<hash 2>` into its
+/// parts. Anything after a comma in the second hash is ignored, as ssdeep
+/// uses it for appending the file name to the digest.
+fn parse(digest: &str) -> Option<(u64, &[u8], &[u8])> {
+    let (block_size, hashes) = digest.split_once(':')?;
+    let (hash1, hash2) = hashes.split_once(':')?;
+    let hash2 = hash2.split(',').next().unwrap();
+
+    let block_size = block_size.parse::<u64>().ok()?;
+
+    if hash1.len() > SPAMSUM_LENGTH || hash2.len() > SPAMSUM_LENGTH {
+        return None;
+    }
+
+    Some((block_size, hash1.as_bytes(), hash2.as_bytes()))
+}
+
+/// Removes any characters that are repeated more than three times in a
+/// row, as they don't add information to the digest.
+fn eliminate_sequences(s: &[u8]) -> Vec<u8> {
+    s.iter()
+        .enumerate()
+        .filter(|(i, c)| *i < 3 || s[i - 3..*i].iter().any(|p| p != *c))
+        .map(|(_, c)| *c)
+        .collect()
+}
+
+fn score_strings(s1: &[u8], s2: &[u8], block_size: u64) -> u32 {
+    if s1.len() > SPAMSUM_LENGTH || s2.len() > SPAMSUM_LENGTH {
+        return 0;
+    }
+
+    // Digests that don't share at least one substring of the size of the
+    // rolling window are considered completely different.
+    if !has_common_substring(s1, s2) {
+        return 0;
+    }
+
+    let mut score = edit_distance(s1, s2) as u64;
+
+    // Scale the edit distance to a 0-100 range, where 100 means that
+    // the strings are completely different.
+    score = score * SPAMSUM_LENGTH as u64 / (s1.len() + s2.len()) as u64;
+    score = 100 * score / SPAMSUM_LENGTH as u64;
+
+    if score >= 100 {
+        return 0;
+    }
+
+    score = 100 - score;
+
+    // For small block sizes the score is capped, so that small inputs
+    // don't produce exaggerated scores.
+    let cap_threshold = (99 + ROLLING_WINDOW as u64) / ROLLING_WINDOW as u64
+        * MIN_BLOCK_SIZE;
+
+    if block_size < cap_threshold {
+        let cap =
+            block_size / MIN_BLOCK_SIZE * s1.len().min(s2.len()) as u64;
+        score = score.min(cap);
+    }
+
+    score as u32
+}
+
+fn has_common_substring(s1: &[u8], s2: &[u8]) -> bool {
+    if s1.len() < ROLLING_WINDOW || s2.len() < ROLLING_WINDOW {
+        return false;
+    }
+    s1.windows(ROLLING_WINDOW)
+        .any(|w1| s2.windows(ROLLING_WINDOW).any(|w2| w1 == w2))
+}
+
+/// Edit distance where insertions and deletions cost 1, and replacements
+/// cost 2.
+fn edit_distance(s1: &[u8], s2: &[u8]) -> usize {
+    let mut prev: Vec<usize> = (0..=s2.len()).collect();
+    let mut curr = vec![0; s2.len() + 1];
+
+    for (i, c1) in s1.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, c2) in s2.iter().enumerate() {
+            let replace_cost = if c1 == c2 { 0 } else { 2 };
+            curr[j + 1] = (prev[j + 1] + 1)
+                .min(curr[j] + 1)
+                .min(prev[j] + replace_cost);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[s2.len()]
+}
@@ -82,3 +82,106 @@ fn test_hash_module() {
         b"TEST STRING"
     );
 }
+
+#[test]
+#[cfg(feature = "hash-module")]
+fn test_ssdeep() {
+    rule_true!(
+        r#"
+        import "hash"
+        rule test {
+          condition:
+            hash.ssdeep(0, filesize) == "3:Nx:X" and
+            hash.ssdeep(3, 3) == "3:e:e" and
+            hash.ssdeep(0, filesize) == hash.ssdeep("foobarbaz") and
+            hash.ssdeep(3, 3) == hash.ssdeep("bar")
+        }
+        "#,
+        b"foobarbaz"
+    );
+
+    // Empty ranges and strings don't have a ssdeep digest.
+    rule_true!(
+        r#"
+        import "hash"
+        rule test {
+          condition:
+            not defined hash.ssdeep(0, 0) and
+            not defined hash.ssdeep("") and
+            not defined hash.ssdeep(100, 3)
+        }
+        "#,
+        b"foobarbaz"
+    );
+
+    let data = "hello world, this is some text ".repeat(100);
+
+    rule_true!(
+        r#"
+        import "hash"
+        rule test {
+          condition:
+            hash.ssdeep(0, filesize) == "12:7qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqV:D"
+        }
+        "#,
+        data.as_bytes()
+    );
+
+    let data: String = (0..2000_u64)
+        .map(|i| format!("line {}: {}\n", i, i * i * 7919 % 100003))
+        .collect();
+
+    // Same data with a few lines modified.
+    let similar = data
+        .replace("line 1000:", "XXXX 1000:")
+        .replace("line 1001:", "XXXX 1001:")
+        .replace("line 1002:", "XXXX 1002:");
+
+    rule_true!(
+        r#"
+        import "hash"
+        rule test {
+          condition:
+            hash.ssdeep(0, filesize) == "768:5yGhT060DAtDjQSelKxzSZM029UnUQyd6oVz/LJ2me8wPGntYWQUygkLN:4UT0VDAtDjQSelKyTnQVVHBNtx+gkR"
+        }
+        "#,
+        data.as_bytes()
+    );
+
+    rule_true!(
+        r#"
+        import "hash"
+        rule test {
+          condition:
+            hash.ssdeep(0, filesize) == "768:5yGhT060DAtDjQSelKxzSZM029UnUQyd6onz/LJ2me8wPGntYWQUygkLN:4UT0VDAtDjQSelKyTnQrVHBNtx+gkR" and
+            hash.ssdeep_compare(
+              hash.ssdeep(0, filesize),
+              "768:5yGhT060DAtDjQSelKxzSZM029UnUQyd6oVz/LJ2me8wPGntYWQUygkLN:4UT0VDAtDjQSelKyTnQVVHBNtx+gkR"
+            ) == 99
+        }
+        "#,
+        similar.as_bytes()
+    );
+
+    rule_true!(
+        r#"
+        import "hash"
+        rule test {
+          condition:
+            // Identical digests.
+            hash.ssdeep_compare(
+              "768:5yGhT060DAtDjQSelKxzSZM029UnUQyd6oVz/LJ2me8wPGntYWQUygkLN:4UT0VDAtDjQSelKyTnQVVHBNtx+gkR",
+              "768:5yGhT060DAtDjQSelKxzSZM029UnUQyd6oVz/LJ2me8wPGntYWQUygkLN:4UT0VDAtDjQSelKyTnQVVHBNtx+gkR"
+            ) == 100 and
+            // Unrelated digests.
+            hash.ssdeep_compare(
+              "768:5yGhT060DAtDjQSelKxzSZM029UnUQyd6oVz/LJ2me8wPGntYWQUygkLN:4UT0VDAtDjQSelKyTnQVVHBNtx+gkR",
+              "12:7qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqV:D"
+            ) == 0 and
+            // Invalid digests.
+            not defined hash.ssdeep_compare("foo", "3:Nx:X")
+        }
+        "#,
+        b""
+    );
+}
@@ -1,4 +1,5 @@
 use crate::modules::protos;
+use crate::modules::utils::entropy;
 use bstr::{BStr, ByteSlice};
 use itertools::Itertools;
 #[cfg(feature = "logging")]
@@ -76,6 +77,12 @@ const CPU_TYPE_SPARC: u32 = 0x0000000e;
 const CPU_TYPE_POWERPC: u32 = 0x00000012;
 const CPU_TYPE_POWERPC64: u32 = 0x01000012;
 
+/// Mach-O section types
+const SECTION_TYPE: u32 = 0x000000ff;
+const S_ZEROFILL: u32 = 0x00000001;
+const S_GB_ZEROFILL: u32 = 0x0000000c;
+const S_THREAD_LOCAL_ZEROFILL: u32 = 0x00000012;
+
 /// Represents a Mach-O file. It can represent both a multi-architecture
 /// binary (a.k.a. FAT binary) or a single-architecture binary.
 pub struct MachO<'a> {
@@ -311,6 +318,29 @@ impl<'a> MachO<'a> {
             }
         }
 
+        // Compute the entropy for the content of each section. Sections that
+        // extend beyond the end of the file are truncated, and zero-fill
+        // sections don't have any content in the file.
+        for section in macho
+            .segments
+            .iter_mut()
+            .flat_map(|segment| segment.sections.iter_mut())
+        {
+            if matches!(
+                section.flags & SECTION_TYPE,
+                S_ZEROFILL | S_GB_ZEROFILL | S_THREAD_LOCAL_ZEROFILL
+            ) {
+                continue;
+            }
+            let start = section.offset as usize;
+            let end = start
+                .saturating_add(section.size as usize)
+                .min(data.len());
+            if let Some(content) = data.get(start..end) {
+                section.entropy = Some(entropy(content));
+            }
+        }
+
         if let Some(entry_point_rva) = macho.entry_point_rva {
             macho.entry_point_offset = macho.rva_to_offset(entry_point_rva);
         }
@@ -421,6 +451,7 @@ impl<'a> MachOFile<'a> {
                     reserved1,
                     reserved2,
                     reserved3,
+                    entropy: None,
                 }
             },
         )
@@ -1191,6 +1222,7 @@ struct Section<'a> {
     reserved1: u32,
     reserved2: u32,
     reserved3: Option<u32>, // Only set in 64-bits binaries
+    entropy: Option<f64>,
 }
 
 struct Dylib<'a> {
@@ -1536,6 +1568,7 @@ impl From<&Section<'_>> for protos::macho::Section {
         result.set_reserved1(sec.reserved1);
         result.set_reserved2(sec.reserved2);
         result.reserved3 = sec.reserved3;
+        result.entropy = sec.entropy;
         result
     }
 }
@@ -311,4 +311,99 @@ fn test_macho_module() {
         "#,
         &chess_macho_data
     );
+
+    rule_true!(
+        r#"
+        import "macho"
+        rule macho_test {
+            condition:
+                for any seg in macho.segments : (
+                    for any sec in seg.sections : (
+                        sec.segname == "__TEXT" and
+                        sec.sectname == "__text" and
+                        sec.entropy > 6.0 and sec.entropy < 7.0
+                    )
+                )
+        }
+        "#,
+        &chess_macho_data
+    );
+
+    rule_true!(
+        r#"
+        import "macho"
+        rule macho_test {
+            condition:
+                // Zero-fill sections don't have entropy.
+                for any seg in macho.segments : (
+                    for any sec in seg.sections : (
+                        sec.sectname == "__bss" and not defined sec.entropy
+                    )
+                )
+        }
+        "#,
+        &chess_macho_data
+    );
+
+    let tiny_macho_data = create_binary_from_zipped_ihex(
+        "src/modules/macho/tests/testdata/tiny_macho.in.zip",
+    );
+
+    let x86_object_data = create_binary_from_zipped_ihex(
+        "src/modules/macho/tests/testdata/macho_x86_object_file.in.zip",
+    );
+
+    // The entry point is the `entryoff` field in `LC_MAIN`, which is the
+    // same value reported by `otool -l` (0x1ee0), and the stack size is
+    // also taken from `LC_MAIN`.
+    rule_true!(
+        r#"
+        import "macho"
+        rule test {
+          condition:
+            macho.entry_point == 0x1ee0 and macho.stack_size == 0
+        }
+        "#,
+        &chess_macho_data
+    );
+
+    // With `LC_UNIXTHREAD` the entry point is the file offset corresponding
+    // to the initial instruction pointer, there's no stack size.
+    rule_true!(
+        r#"
+        import "macho"
+        rule test {
+          condition:
+            macho.entry_point == 116 and not defined macho.stack_size
+        }
+        "#,
+        &tiny_macho_data
+    );
+
+    // Object files have neither `LC_MAIN` nor `LC_UNIXTHREAD`.
+    rule_true!(
+        r#"
+        import "macho"
+        rule test {
+          condition:
+            not defined macho.entry_point and not defined macho.stack_size
+        }
+        "#,
+        &x86_object_data
+    );
+
+    // In fat binaries these fields are per architecture.
+    rule_true!(
+        r#"
+        import "macho"
+        rule test {
+          condition:
+            not defined macho.entry_point and
+            macho.file[0].entry_point == 0xee0 and
+            macho.file[0].stack_size == 0 and
+            macho.file[1].entry_point == 0xee0
+        }
+        "#,
+        &tiny_universal_macho_data
+    );
 }
@@ -6,6 +6,7 @@ use num_traits::Pow;
 
 use crate::modules::prelude::*;
 use crate::modules::protos::math::*;
+use crate::modules::utils::entropy;
 
 #[module_main]
 fn main(_data: &[u8]) -> Math {
@@ -53,6 +54,21 @@ fn to_string_base(
         8 => Some(RuntimeString::new(format!("{:o}", x))),
         10 => Some(RuntimeString::new(format!("{}", x))),
         16 => Some(RuntimeString::new(format!("{:x}", x))),
+        2..=36 => {
+            // Like with bases 8 and 16, negative numbers are represented
+            // in two's complement.
+            let mut x = x as u64;
+            let base = base as u64;
+            let mut digits = Vec::new();
+            loop {
+                digits.push(char::from_digit((x % base) as u32, base as u32)?);
+                x /= base;
+                if x == 0 {
+                    break;
+                }
+            }
+            Some(RuntimeString::new(digits.iter().rev().collect::<String>()))
+        }
         _ => None,
     }
 }
@@ -66,6 +82,28 @@ fn to_number(_ctx: &ScanContext, b: bool) -> i64 {
     }
 }
 
+#[module_export]
+fn count_bits(_ctx: &ScanContext, x: i64) -> i64 {
+    x.count_ones() as i64
+}
+
+#[module_export]
+fn count_trailing_zeros(_ctx: &ScanContext, x: i64) -> i64 {
+    x.trailing_zeros() as i64
+}
+
+#[module_export]
+fn gcd(_ctx: &ScanContext, a: i64, b: i64) -> Option<i64> {
+    let mut a = a.unsigned_abs();
+    let mut b = b.unsigned_abs();
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    // The result doesn't fit in an i64 when it is 2^63, which happens with
+    // gcd(i64::MIN, 0) and gcd(i64::MIN, i64::MIN).
+    a.try_into().ok()
+}
+
 #[module_export(name = "count")]
 fn count_range(
     ctx: &ScanContext,
@@ -135,7 +173,8 @@ fn entropy_data(ctx: &ScanContext, offset: i64, length: i64) -> Option<f64> {
     let length: usize = length.try_into().ok()?;
     let start: usize = offset.try_into().ok()?;
     let end = cmp::min(ctx.scanned_data().len(), start.saturating_add(length));
-    Some(entropy(ctx.scanned_data().get(start..end)?))
+    let data = ctx.scanned_data().get(start..end)?;
+    Some(ctx.cached_entropy(start..end, || entropy(data)))
 }
 
 #[module_export(name = "entropy")]
@@ -215,27 +254,6 @@ fn monte_carlo_pi_string(ctx: &ScanContext, s: RuntimeString) -> Option<f64> {
     monte_carlo_pi(s.as_bstr(ctx).as_bytes())
 }
 
-fn entropy(data: &[u8]) -> f64 {
-    if data.is_empty() {
-        return 0.0;
-    }
-
-    let mut distribution = [0u64; 256];
-    for byte in data {
-        distribution[*byte as usize] += 1;
-    }
-
-    let mut entropy: f64 = 0.0;
-    for value in &distribution {
-        if *value != 0 {
-            let x = *value as f64 / data.len() as f64;
-            entropy -= x * f64::log2(x);
-        }
-    }
-
-    entropy
-}
-
 fn deviation(data: &[u8], mean: f64) -> Option<f64> {
     if data.is_empty() {
         return None;
@@ -878,7 +896,21 @@ mod tests {
             import "math"
             rule test {
                 condition:
-                    not defined math.to_string(32, 7) 
+                    math.to_string(5, 2) == "101" and
+                    math.to_string(0, 2) == "0" and
+                    math.to_string(35, 36) == "z" and
+                    math.to_string(-1, 2) == "1111111111111111111111111111111111111111111111111111111111111111"
+            }"#,
+            b""
+        );
+
+        rule_true!(
+            r#"
+            import "math"
+            rule test {
+                condition:
+                    not defined math.to_string(32, 1) and
+                    not defined math.to_string(32, 37)
             }"#,
             b""
         );
@@ -897,4 +929,64 @@ mod tests {
             b""
         );
     }
+
+    #[test]
+    fn count_bits() {
+        rule_true!(
+            r#"
+            import "math"
+            rule test {
+                condition:
+                    math.count_bits(0) == 0 and
+                    math.count_bits(1) == 1 and
+                    math.count_bits(0xff) == 8 and
+                    math.count_bits(0x8001) == 2 and
+                    math.count_bits(-1) == 64
+            }"#,
+            b""
+        );
+    }
+
+    #[test]
+    fn count_trailing_zeros() {
+        rule_true!(
+            r#"
+            import "math"
+            rule test {
+                condition:
+                    math.count_trailing_zeros(1) == 0 and
+                    math.count_trailing_zeros(8) == 3 and
+                    math.count_trailing_zeros(0x1000) == 12 and
+                    math.count_trailing_zeros(0) == 64
+            }"#,
+            b""
+        );
+    }
+
+    #[test]
+    fn gcd() {
+        rule_true!(
+            r#"
+            import "math"
+            rule test {
+                condition:
+                    math.gcd(12, 18) == 6 and
+                    math.gcd(17, 5) == 1 and
+                    math.gcd(-12, 18) == 6 and
+                    math.gcd(0, 7) == 7 and
+                    math.gcd(0, 0) == 0
+            }"#,
+            b""
+        );
+
+        rule_true!(
+            r#"
+            import "math"
+            rule test {
+                condition:
+                    not defined math.gcd(-9223372036854775807 - 1, 0)
+            }"#,
+            b""
+        );
+    }
 }
@@ -2,6 +2,7 @@ use lazy_static::lazy_static;
 use protobuf::reflect::MessageDescriptor;
 use protobuf::MessageDyn;
 use rustc_hash::FxHashMap;
+use thiserror::Error;
 
 pub mod protos {
     include!(concat!(env!("OUT_DIR"), "/protos/mod.rs"));
@@ -10,8 +11,17 @@ pub mod protos {
 #[cfg(test)]
 mod tests;
 
+#[cfg(any(
+    feature = "macho-module",
+    feature = "math-module",
+    feature = "pe-module"
+))]
+pub(crate) mod utils;
+
 #[allow(unused_imports)]
 pub(crate) mod prelude {
+    pub(crate) use crate::modules::ModuleError;
+    pub(crate) use crate::modules::ModuleLimits;
     pub(crate) use crate::scanner::ScanContext;
     pub(crate) use crate::wasm::string::*;
     pub(crate) use crate::wasm::*;
@@ -24,7 +34,68 @@ pub(crate) mod prelude {
 include!("modules.rs");
 
 /// Type of module's main function.
-type MainFn = fn(&[u8]) -> Box<dyn MessageDyn>;
+type MainFn =
+    fn(&[u8], &ModuleLimits, &mut Vec<ModuleError>) -> Box<dyn MessageDyn>;
+
+/// Limits on the size of the output produced by modules.
+///
+/// Malformed or adversarial files can declare an absurd number of imports,
+/// exports, resources, symbols or notes. When some of these limits is
+/// reached the module stops parsing the corresponding structure, sets a
+/// flag in its output indicating that the information is incomplete (e.g:
+/// `pe.imports_truncated`), and reports a [`ModuleError::PartialOutput`]
+/// error. See [`crate::Scanner::set_module_limits`].
+///
+/// The default limits are the ones that modules always had: exports and
+/// resources are limited, while imports, symbols and notes are not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleLimits {
+    /// Maximum number of imported functions in the `pe` module. The limit
+    /// applies to ordinary and delayed imports separately.
+    pub max_imports: usize,
+    /// Maximum number of exported functions in the `pe` module.
+    pub max_exports: usize,
+    /// Maximum number of resources in the `pe` and `dotnet` modules.
+    pub max_resources: usize,
+    /// Maximum number of entries in each symbol table in the `elf` module.
+    pub max_symbols: usize,
+    /// Maximum number of notes in the `elf` module.
+    pub max_notes: usize,
+}
+
+impl Default for ModuleLimits {
+    fn default() -> Self {
+        Self {
+            max_imports: usize::MAX,
+            max_exports: 16384,
+            max_resources: 65536,
+            max_symbols: usize::MAX,
+            max_notes: usize::MAX,
+        }
+    }
+}
+
+/// An error found by a module while processing the scanned data.
+///
+/// These errors don't abort the scan, and the module still produces some
+/// output, which may be incomplete. See [`crate::Scanner::on_module_error`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ModuleError {
+    /// The data looks like a file of the type handled by the module, but
+    /// it is malformed and could not be parsed.
+    #[error("malformed data: {reason}")]
+    MalformedData {
+        /// Description of the problem.
+        reason: String,
+    },
+    /// The data was parsed only partially, and the module's output is
+    /// incomplete.
+    #[error("partial output: {reason}")]
+    PartialOutput {
+        /// Description of the problem.
+        reason: String,
+    },
+}
 
 /// Describes a YARA module.
 pub(crate) struct Module {
@@ -233,7 +304,11 @@ pub mod mods {
                 module.root_struct_descriptor.full_name() == proto_name
             })?;
 
-        Some(module.main_fn?(data))
+        Some(module.main_fn?(
+            data,
+            &super::ModuleLimits::default(),
+            &mut Vec::new(),
+        ))
     }
 
     /// Invoke all YARA modules and return the data produced by them.
@@ -4,6 +4,7 @@ This allows creating YARA rules based on PE metadata, including sections,
 imports and exports, resources, etc.
  */
 
+use std::ops::Range;
 use std::rc::Rc;
 use std::slice::Iter;
 
@@ -18,6 +19,7 @@ use nom::number::complete::{le_u16, le_u32};
 use crate::compiler::RegexpId;
 use crate::modules::prelude::*;
 use crate::modules::protos::pe::*;
+use crate::modules::utils::entropy;
 use crate::types::Struct;
 
 #[cfg(test)]
@@ -29,10 +31,46 @@ pub mod parser;
 mod rva2off;
 
 #[module_main]
-fn main(input: &[u8]) -> PE {
-    match parser::PE::parse(input) {
-        Ok(pe) => pe.into(),
-        Err(_) => {
+fn main(
+    input: &[u8],
+    limits: &ModuleLimits,
+    errors: &mut Vec<ModuleError>,
+) -> PE {
+    match parser::PE::parse_with_limits(input, *limits) {
+        Ok(pe) => {
+            let pe: PE = pe.into();
+            // Parts of the file that exceed the module limits are not
+            // included in the output.
+            for (truncated, what) in [
+                (pe.imports_truncated(), "imports"),
+                (pe.delayed_imports_truncated(), "delayed imports"),
+                (pe.exports_truncated(), "exports"),
+                (pe.resources_truncated(), "resources"),
+            ] {
+                if truncated {
+                    errors.push(ModuleError::PartialOutput {
+                        reason: format!("too many {what}"),
+                    });
+                }
+            }
+            pe
+        }
+        Err(err) => {
+            // Files that don't start with the MZ signature are not PE
+            // files at all, that's not an error.
+            if input.starts_with(b"MZ") {
+                let reason = match err {
+                    nom::Err::Incomplete(_) => {
+                        "unexpected end of data".to_string()
+                    }
+                    nom::Err::Error(err) | nom::Err::Failure(err) => format!(
+                        "{} at offset {}",
+                        err.code.description(),
+                        input.len() - err.input.len()
+                    ),
+                };
+                errors.push(ModuleError::MalformedData { reason });
+            }
             let mut pe = PE::new();
             pe.is_pe = Some(false);
             pe
@@ -61,6 +99,111 @@ fn is_dll(ctx: &ScanContext) -> Option<bool> {
     Some(characteristics & Characteristics::DLL as u32 != 0)
 }
 
+/// Returns true if the file is a kernel-mode driver.
+///
+/// A file is considered a driver if its subsystem is `SUBSYSTEM_NATIVE` and
+/// it imports functions from some of the modules that are available only
+/// in kernel mode, like `ntoskrnl.exe` or `hal.dll`. The latter condition
+/// excludes native user-mode applications, like `smss.exe`, which import
+/// from `ntdll.dll` instead.
+#[module_export]
+fn is_driver(ctx: &ScanContext) -> Option<bool> {
+    const KERNEL_MODULES: &[&str] = &[
+        "ntoskrnl.exe",
+        "ntkrnlpa.exe",
+        "ntkrnlmp.exe",
+        "ntkrpamp.exe",
+        "hal.dll",
+        "ndis.sys",
+        "fltmgr.sys",
+        "wdfldr.sys",
+        "ksecdd.sys",
+    ];
+
+    let pe = ctx.module_output::<PE>()?;
+
+    if pe.subsystem?.value() != Subsystem::SUBSYSTEM_NATIVE as i32 {
+        return Some(false);
+    }
+
+    Some(
+        pe.import_details
+            .iter()
+            .chain(pe.delayed_import_details.iter())
+            .filter_map(|import| import.library_name.as_deref())
+            .any(|name| {
+                KERNEL_MODULES
+                    .iter()
+                    .any(|module| module.eq_ignore_ascii_case(name))
+            }),
+    )
+}
+
+/// Returns a score between 0.0 and 1.0 that indicates how likely is that
+/// the PE file is packed.
+///
+/// The score is the average of three signals, each of them between 0.0
+/// and 1.0:
+///
+/// * The maximum entropy among all sections. Entropies lower than 6.0
+///   contribute 0.0, entropies equal or higher than 7.5 contribute 1.0,
+///   and anything in between contributes proportionally.
+/// * The number of imported functions. Files without imports contribute
+///   1.0, files with 32 or more imported functions contribute 0.0, and
+///   anything in between contributes proportionally.
+/// * Whether some executable section has a virtual size that is more
+///   than twice its raw data size, which is typical of sections that
+///   are filled by the unpacking stub at runtime. This contributes either
+///   0.0 or 1.0.
+///
+/// The score is computed only when a rule calls this function, as the
+/// entropy of every section is expensive to compute. The result is
+/// undefined if the file is not a PE, it doesn't have sections, or the
+/// data of some section goes beyond the end of the file.
+#[module_export]
+fn packer_score(ctx: &ScanContext) -> Option<f64> {
+    const SECTION_MEM_EXECUTE: u32 = 0x20000000;
+
+    let pe = ctx.module_output::<PE>()?;
+
+    if pe.sections.is_empty() {
+        return None;
+    }
+
+    let data = ctx.scanned_data();
+
+    let mut max_entropy: f64 = 0.0;
+    let mut size_mismatch = false;
+
+    for section in pe.sections.iter() {
+        let start = section.raw_data_offset() as usize;
+        let end = start.checked_add(section.raw_data_size() as usize)?;
+
+        max_entropy = max_entropy.max(entropy(data.get(start..end)?));
+
+        if section.characteristics() & SECTION_MEM_EXECUTE != 0
+            && section.virtual_size() as u64
+                > 2 * section.raw_data_size() as u64
+        {
+            size_mismatch = true;
+        }
+    }
+
+    let entropy_signal = ((max_entropy - 6.0) / 1.5).clamp(0.0, 1.0);
+    let imports_signal = (1.0
+        - pe.number_of_imported_functions() as f64 / 32.0)
+        .clamp(0.0, 1.0);
+    let size_signal = if size_mismatch { 1.0 } else { 0.0 };
+
+    Some((entropy_signal + imports_signal + size_signal) / 3.0)
+}
+
+/// Returns true if [`packer_score`] is 0.5 or higher.
+#[module_export]
+fn is_packed(ctx: &ScanContext) -> Option<bool> {
+    Some(packer_score(ctx)? >= 0.5)
+}
+
 /// Convert a relative virtual address (RVA) to a file offset.
 #[module_export]
 fn rva_to_offset(ctx: &ScanContext, rva: i64) -> Option<i64> {
@@ -196,6 +339,22 @@ fn section_index_offset(ctx: &ScanContext, offset: i64) -> Option<i64> {
         .map(|(index, _)| index as i64)
 }
 
+/// Returns the range of file offsets occupied by the raw data of the section
+/// with the given index in the section table.
+///
+/// The result can be used with `in`, as in `$a in pe.section_range(0)`,
+/// which is equivalent to `$a in (start..start + size - 1)`, where `start`
+/// and `size` are the section's `raw_data_offset` and `raw_data_size`.
+#[module_export]
+fn section_range(ctx: &ScanContext, index: i64) -> Option<Range<i64>> {
+    let pe = ctx.module_output::<PE>()?;
+    let section = pe.sections.get(usize::try_from(index).ok()?)?;
+    let start: i64 = section.raw_data_offset?.into();
+    let size: i64 = section.raw_data_size?.into();
+
+    Some(start..start + size)
+}
+
 /// Returns the PE import hash.
 ///
 /// The import hash represents the MD5 checksum of the PE's import table
@@ -1,6 +1,6 @@
-use std::cell::OnceCell;
+use std::cell::{Cell, OnceCell};
 use std::cmp::min;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::default::Default;
 use std::iter::zip;
 use std::mem;
@@ -22,12 +22,14 @@ use nom::number::complete::{le_u16, le_u32, le_u64, u8};
 use nom::sequence::tuple;
 use nom::{Err, IResult, Parser, ToUsize};
 use protobuf::{EnumOrUnknown, MessageField};
+use sha2::{Digest, Sha256};
 
 use crate::modules::pe::authenticode::{
     AuthenticodeHasher, AuthenticodeParser, AuthenticodeSignature,
 };
 use crate::modules::pe::rva2off;
 use crate::modules::protos;
+use crate::modules::ModuleLimits;
 
 type Error<'a> = nom::error::Error<&'a [u8]>;
 
@@ -77,8 +79,9 @@ pub struct PE<'a> {
     /// [`PE::get_dir_entries`] is called for the first time.
     dir_entries: OnceCell<Option<Vec<DirEntry>>>,
 
-    /// Path to PDB file containing debug information for the PE.
-    pdb_path: OnceCell<Option<&'a [u8]>>,
+    /// Entries in the debug directory. Debug entries are parsed lazily when
+    /// [`PE::get_debug_entries`] is called for the first time.
+    debug_entries: OnceCell<Option<Vec<DbgEntry<'a>>>>,
 
     /// Vector with the DLLs imported by this PE file. Each item in the vector
     /// is a tuple composed of a DLL name and a vector of [`ImportedFunc`] that
@@ -93,6 +96,24 @@ pub struct PE<'a> {
     /// Export information about this PE file.
     exports: OnceCell<Option<ExportInfo<'a>>>,
 
+    /// Limits on the number of imports, exports and resources.
+    limits: ModuleLimits,
+
+    /// True if the imports were truncated because the number of imported
+    /// functions exceeds `limits.max_imports`.
+    imports_truncated: Cell<bool>,
+
+    /// Similar to `imports_truncated` but for the delayed imports.
+    delayed_imports_truncated: Cell<bool>,
+
+    /// True if the exports were truncated because the number of exported
+    /// functions exceeds `limits.max_exports`.
+    exports_truncated: Cell<bool>,
+
+    /// True if the resources were truncated because the number of resources
+    /// exceeds `limits.max_resources`.
+    resources_truncated: Cell<bool>,
+
     /// DOS header already parsed.
     pub dos_hdr: DOSHeader,
 
@@ -197,6 +218,15 @@ impl<'a> PE<'a> {
     /// Given the content of PE file, parses it and returns a [`PE`] object
     /// representing the file.
     pub fn parse(data: &'a [u8]) -> Result<Self, Err<Error<'a>>> {
+        Self::parse_with_limits(data, ModuleLimits::default())
+    }
+
+    /// Similar to [`PE::parse`], but the number of imports, exports and
+    /// resources is limited according to `limits`.
+    pub fn parse_with_limits(
+        data: &'a [u8],
+        limits: ModuleLimits,
+    ) -> Result<Self, Err<Error<'a>>> {
         // Parse the MZ header.
         let (_, dos_hdr) = Self::parse_dos_header(data)?;
 
@@ -254,6 +284,7 @@ impl<'a> PE<'a> {
             optional_hdr,
             dos_stub,
             directory,
+            limits,
             ..Default::default()
         })
     }
@@ -309,6 +340,33 @@ impl<'a> PE<'a> {
         self.sections.as_slice()
     }
 
+    /// Returns the MS-DOS stub.
+    ///
+    /// The MS-DOS stub is the small program that goes right after the DOS
+    /// header, and prints a message like "This program cannot be run in DOS
+    /// mode" when the file is executed in MS-DOS. The returned slice goes
+    /// from the end of the DOS header to the start of the rich header, or
+    /// to the start of the PE header if there's no rich header. The result
+    /// is `None` if the PE header overlaps the DOS header.
+    pub fn get_dos_stub(&self) -> Option<&'a [u8]> {
+        let end = match self.get_rich_header() {
+            Some(rich_header) => rich_header.offset,
+            None => self.dos_stub.len(),
+        };
+        self.dos_stub.get(Self::SIZE_OF_DOS_HEADER..end)
+    }
+
+    /// Returns true if the MS-DOS stub is the one produced by Microsoft
+    /// linkers, optionally followed by zeroes used as padding. The result
+    /// is `None` if the PE doesn't have a MS-DOS stub.
+    pub fn dos_stub_is_standard(&self) -> Option<bool> {
+        let stub = self.get_dos_stub()?;
+        Some(
+            stub.strip_prefix(Self::STANDARD_DOS_STUB)
+                .is_some_and(|padding| padding.iter().all(|b| *b == 0)),
+        )
+    }
+
     /// Returns information about the rich header.
     ///
     /// The rich header is an undocumented chunk of data found between the DOS
@@ -355,7 +413,19 @@ impl<'a> PE<'a> {
     /// a CLSID. Is not clear what the CLSID means. Example:
     /// 6c2abf4b80a87e63eee2996e5cea8f004d49ec0c1806080fa72e960529cba14c
     pub fn get_pdb_path(&self) -> Option<&'a [u8]> {
-        *self.pdb_path.get_or_init(|| self.parse_dbg())
+        self.get_debug_entries()
+            .iter()
+            .find_map(|entry| entry.codeview.as_ref())
+            .map(|codeview| codeview.pdb_path)
+    }
+
+    /// Returns a slice of [`DbgEntry`] structures, one per each entry in
+    /// the debug directory.
+    pub fn get_debug_entries(&self) -> &[DbgEntry<'a>] {
+        self.debug_entries
+            .get_or_init(|| self.parse_dbg())
+            .as_deref()
+            .unwrap_or_default()
     }
 
     /// Returns a slice of [`Resource`] structures, one per each resource
@@ -380,6 +450,14 @@ impl<'a> PE<'a> {
             .map(|(dir, _resources)| dir)
     }
 
+    /// Returns the data for a given resource, or `None` if the resource
+    /// offset and length are not within the bounds of the file.
+    pub fn get_resource_data(&self, resource: &Resource) -> Option<&'a [u8]> {
+        let start = resource.offset? as usize;
+        let end = start.checked_add(resource.length as usize)?;
+        self.data.get(start..end)
+    }
+
     /// Returns the entries found in the PE directory table.
     ///
     /// The number of entries is limited to MAX_DIR_ENTRIES (16), which is the
@@ -395,6 +473,20 @@ impl<'a> PE<'a> {
             .unwrap_or_default()
     }
 
+    /// Returns true if some section is both writable and executable.
+    pub fn has_rwx_section(&self) -> bool {
+        const WX: u32 = PE::IMAGE_SCN_MEM_WRITE | PE::IMAGE_SCN_MEM_EXECUTE;
+        self.sections.iter().any(|section| section.characteristics & WX == WX)
+    }
+
+    /// Returns true if the entry at `index` is present in the PE directory
+    /// table, and both its address and size are non-zero.
+    pub fn has_dir_entry(&self, index: usize) -> bool {
+        self.get_dir_entries()
+            .get(index)
+            .is_some_and(|entry| entry.addr != 0 && entry.size != 0)
+    }
+
     /// Returns the RVA, size and data associated to a given directory entry.
     ///
     /// The returned tuple is `(addr, size, data)`, where `addr` and `size` are
@@ -507,6 +599,8 @@ impl<'a> PE<'a> {
     pub const IMAGE_DIRECTORY_ENTRY_RESOURCE: usize = 2;
     pub const IMAGE_DIRECTORY_ENTRY_SECURITY: usize = 4;
     pub const IMAGE_DIRECTORY_ENTRY_DEBUG: usize = 6;
+    pub const IMAGE_DIRECTORY_ENTRY_TLS: usize = 9;
+    pub const IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG: usize = 10;
     pub const IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT: usize = 13;
     pub const IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR: usize = 14;
 
@@ -515,6 +609,14 @@ impl<'a> PE<'a> {
     const RICH_TAG: &'static [u8] = &[0x52_u8, 0x69, 0x63, 0x68];
     const DANS_TAG: u32 = 0x536e6144;
 
+    // The MS-DOS stub generated by Microsoft linkers.
+    const STANDARD_DOS_STUB: &'static [u8] =
+        b"\x0e\x1f\xba\x0e\x00\xb4\x09\xcd\x21\xb8\x01\x4c\xcd\x21\
+          This program cannot be run in DOS mode.\r\r\n$";
+
+    // size of IMAGE_DOS_HEADER.
+    const SIZE_OF_DOS_HEADER: usize = 64;
+
     // size of PE signature (PE\0\0).
     const SIZE_OF_PE_SIGNATURE: usize = 4;
 
@@ -532,10 +634,11 @@ impl<'a> PE<'a> {
     const SIZE_OF_DIR_ENTRY: usize = 8;
     const SIZE_OF_SYMBOL: u32 = 18;
 
+    const IMAGE_SCN_MEM_EXECUTE: u32 = 0x20000000;
+    const IMAGE_SCN_MEM_WRITE: u32 = 0x80000000;
+
     const MAX_PE_SECTIONS: usize = 96;
     const MAX_PE_IMPORTS: usize = 16384;
-    const MAX_PE_EXPORTS: usize = 16384;
-    const MAX_PE_RESOURCES: usize = 65536;
     const MAX_DIR_ENTRIES: usize = 16;
 
     fn parse_dos_header(input: &[u8]) -> IResult<&[u8], DOSHeader> {
@@ -1265,8 +1368,11 @@ impl<'a> PE<'a> {
             let (_, length) = le_u16(input)?;
             let length = Self::round_up::<4, _>(length);
 
-            // Read the structure's bytes.
-            let (remainder, structure) = take(length)(input)?;
+            // Read the structure's bytes. If the structure is truncated, use
+            // the available bytes, so that whatever is parseable within them
+            // is not lost.
+            let (remainder, structure) =
+                take(min(length, input.len()))(input)?;
 
             // Parse the structure's first fields.
             let (_, (consumed, (_, value_len, _type, key))) =
@@ -1375,6 +1481,12 @@ impl<'a> PE<'a> {
         let mut resources = vec![];
         let mut resources_info = ResourceDir::default();
 
+        // Offsets of the subdirectories that have been already queued. In
+        // corrupted or maliciously crafted files subdirectory entries can
+        // point to some directory that was visited before, creating loops
+        // or causing the same directory to be processed over and over.
+        let mut visited_dirs = HashSet::new();
+
         let ids = (
             ResourceId::Unknown, // type
             ResourceId::Unknown, // resource
@@ -1444,7 +1556,10 @@ impl<'a> PE<'a> {
                         _ => continue,
                     };
 
-                    if dir_entry.is_subdir {
+                    if dir_entry.is_subdir
+                        && level < 2
+                        && visited_dirs.insert(dir_entry.offset)
+                    {
                         queue.push_back((level + 1, ids, entry_data));
                     }
                     if let Ok((_, rsrc_entry)) =
@@ -1454,6 +1569,11 @@ impl<'a> PE<'a> {
                             && rsrc_entry.offset > 0
                             && (rsrc_entry.size as usize) < self.data.len()
                         {
+                            if resources.len() == self.limits.max_resources {
+                                self.resources_truncated.set(true);
+                                return Some((resources_info, resources));
+                            }
+
                             resources.push(Resource {
                                 type_id: ids.0,
                                 rsrc_id: ids.1,
@@ -1465,10 +1585,6 @@ impl<'a> PE<'a> {
                                 rva: rsrc_entry.offset,
                                 length: rsrc_entry.size,
                             });
-
-                            if resources.len() == Self::MAX_PE_RESOURCES {
-                                return Some((resources_info, resources));
-                            }
                         }
                     }
                 }
@@ -1558,8 +1674,9 @@ impl<'a> PE<'a> {
             .ok()
     }
 
-    /// Parses the PE debug information and extracts the PDB path.
-    fn parse_dbg(&self) -> Option<&'a [u8]> {
+    /// Parses the PE debug directory, including the CodeView information
+    /// that contains the PDB path.
+    fn parse_dbg(&self) -> Option<Vec<DbgEntry<'a>>> {
         let (_, _, dbg_section) =
             self.get_dir_entry_data(Self::IMAGE_DIRECTORY_ENTRY_DEBUG, true)?;
 
@@ -1567,95 +1684,124 @@ impl<'a> PE<'a> {
             .map(|(_, entries)| entries)
             .ok()?;
 
-        for entry in entries
-            .iter()
-            .filter(|entry| entry.type_ == Self::IMAGE_DEBUG_TYPE_CODEVIEW)
-        {
-            // The debug info offset may be present either as RVA or as raw
-            // offset. The RVA has higher priority, but if it is 0 or can't
-            // be resolved to a file offset, then the raw offset is used
-            // instead.
-            let offset = if entry.virtual_address != 0 {
-                self.rva_to_offset(entry.virtual_address)
-            } else {
-                None
-            };
+        Some(
+            entries
+                .into_iter()
+                .map(|entry| {
+                    let codeview =
+                        if entry.type_ == Self::IMAGE_DEBUG_TYPE_CODEVIEW {
+                            self.parse_codeview(&entry)
+                        } else {
+                            None
+                        };
+                    DbgEntry { entry, codeview }
+                })
+                .collect(),
+        )
+    }
 
-            let offset = match offset.or(Some(entry.raw_data_offset)) {
-                Some(offset) if offset > 0 => offset,
-                Some(_) | None => continue,
-            };
+    /// Parses the CodeView information pointed to by a debug directory
+    /// entry.
+    fn parse_codeview(&self, entry: &DbgDirEntry) -> Option<CodeView<'a>> {
+        // The debug info offset may be present either as RVA or as raw
+        // offset. The RVA has higher priority, but if it is 0 or can't
+        // be resolved to a file offset, then the raw offset is used
+        // instead.
+        let offset = if entry.virtual_address != 0 {
+            self.rva_to_offset(entry.virtual_address)
+        } else {
+            None
+        };
 
-            let cv_info = match self.data.get(offset as usize..) {
-                Some(cv_info) => cv_info,
-                None => continue,
-            };
+        let offset = match offset.or(Some(entry.raw_data_offset)) {
+            Some(offset) if offset > 0 => offset,
+            Some(_) | None => return None,
+        };
+
+        let cv_info = self.data.get(offset as usize..)?;
 
-            // The CodeView information can come in different formats, but all
-            // of them start with 32-bits signature that allows to distinguish
-            // between them. Here we recognize three different signatures:
-            // "RSDS" (PDB 7.0), "NB10" (PDB 2.0) and "MTOC".
+        // The CodeView information can come in different formats, but all
+        // of them start with 32-bits signature that allows to distinguish
+        // between them. Here we recognize three different signatures:
+        // "RSDS" (PDB 7.0), "NB10" (PDB 2.0) and "MTOC".
+        //
+        // Signatures "NDB09" (CodeView 4.10) and "NDB11" (CodeView 5.0)
+        // also exists, but those are used when debug information is
+        // included in the PE itself, instead of an external PDB file,
+        // therefore in such cases there's no PDB file name to extract.
+        //
+        // See: https://www.debuginfo.com/articles/debuginfomatch.html
+        let (_, codeview) = alt((
+            // "RSDS" means that the debug information is stored in a
+            // PDB 7.0 file. The structure is:
             //
-            // Signatures "NDB09" (CodeView 4.10) and "NDB11" (CodeView 5.0)
-            // also exists, but those are used when debug information is
-            // included in the PE itself, instead of an external PDB file,
-            // therefore in such cases there's no PDB file name to extract.
+            //   DWORD      signature;
+            //   BYTE[16]   guid;
+            //   DWORD      age;
+            //   BYTE[..]   pdb_path;
             //
-            // See: https://www.debuginfo.com/articles/debuginfomatch.html
-            match alt((
-                // "RSDS" means that the debug information is stored in a
-                // PDB 7.0 file. The structure is:
-                //
-                //   DWORD      signature;
-                //   BYTE[16]   guid;
-                //   DWORD      age;
-                //   BYTE[..]   pdb_path;
-                //
+            map(
                 tuple((
                     verify(le_u32::<&[u8], Error>, |signature| {
                         *signature == 0x53445352 // "RSDS"
                     }),
-                    take(20_usize), // skip guid and age
+                    take(16_usize),
+                    le_u32,
                     take_till(|c| c == 0),
                 )),
-                // "NB10" means that the debug information is stored in a
-                // PDB 2.0 file. The structure is:
-                //
-                //   DWORD      signature;
-                //   DWORD      offset;
-                //   DWORD      timestamp;
-                //   DWORD      age;
-                //   BYTE[..]   pdb_path;
-                //
+                |(_, guid, age, pdb_path)| CodeView {
+                    guid: Some(guid),
+                    age: Some(age),
+                    pdb_path,
+                },
+            ),
+            // "NB10" means that the debug information is stored in a
+            // PDB 2.0 file. The structure is:
+            //
+            //   DWORD      signature;
+            //   DWORD      offset;
+            //   DWORD      timestamp;
+            //   DWORD      age;
+            //   BYTE[..]   pdb_path;
+            //
+            map(
                 tuple((
                     verify(le_u32::<&[u8], Error>, |signature| {
                         *signature == 0x3031424e // "NB10"
                     }),
-                    take(12_usize), // skip offset, timestamp, and age
+                    take(8_usize), // skip offset and timestamp
+                    le_u32,
                     take_till(|c| c == 0),
                 )),
-                //
-                //   DWORD      signature;
-                //   BYTE[16]   guid;
-                //   BYTE[..]   pdb_path;
-                //
+                |(_, _, age, pdb_path)| CodeView {
+                    guid: None,
+                    age: Some(age),
+                    pdb_path,
+                },
+            ),
+            //
+            //   DWORD      signature;
+            //   BYTE[16]   guid;
+            //   BYTE[..]   pdb_path;
+            //
+            map(
                 tuple((
                     verify(le_u32::<&[u8], Error>, |signature| {
                         *signature == 0x434f544d // "MTOC"
                     }),
-                    take(16_usize), // skip guid
+                    take(16_usize),
                     take_till(|c| c == 0),
                 )),
-            ))(cv_info)
-            {
-                Ok((_, (_signature, _padding, pdb_path))) => {
-                    return Some(pdb_path)
-                }
-                Err(_) => continue,
-            };
-        }
+                |(_, guid, pdb_path)| CodeView {
+                    guid: Some(guid),
+                    age: None,
+                    pdb_path,
+                },
+            ),
+        ))(cv_info)
+        .ok()?;
 
-        None
+        Some(codeview)
     }
 
     /// Parse the IMAGE_DEBUG_DIRECTORY structure.
@@ -1705,7 +1851,11 @@ impl<'a> PE<'a> {
             return None;
         }
 
-        self.parse_import_impl(import_data, Self::parse_import_descriptor)
+        self.parse_import_impl(
+            import_data,
+            Self::parse_import_descriptor,
+            &self.imports_truncated,
+        )
     }
 
     /// Parses PE delayed imports.
@@ -1721,7 +1871,11 @@ impl<'a> PE<'a> {
             return None;
         }
 
-        self.parse_import_impl(import_data, Self::parse_delay_load_descriptor)
+        self.parse_import_impl(
+            import_data,
+            Self::parse_delay_load_descriptor,
+            &self.delayed_imports_truncated,
+        )
     }
 
     /// Common logic for parsing ordinary and delayed imports.
@@ -1754,6 +1908,7 @@ impl<'a> PE<'a> {
         &self,
         input: &'a [u8],
         descriptor_parser: P,
+        truncated: &Cell<bool>,
     ) -> Option<Vec<(&'a str, Vec<ImportedFunc>)>>
     where
         P: FnMut(&'a [u8]) -> IResult<&'a [u8], ImportDescriptor>,
@@ -1770,6 +1925,7 @@ impl<'a> PE<'a> {
             self.optional_hdr.magic != Self::IMAGE_NT_OPTIONAL_HDR64_MAGIC;
 
         let mut imported_funcs = Vec::new();
+        let mut num_imported_funcs = 0;
 
         // Parse import descriptors until finding one that is empty (filled
         // with null values), which indicates the end of the directory table;
@@ -1885,13 +2041,24 @@ impl<'a> PE<'a> {
                 }
 
                 if func.ordinal.is_some() || func.name.is_some() {
+                    // Stop parsing thunks when the maximum number of
+                    // imported functions is reached.
+                    if num_imported_funcs == self.limits.max_imports {
+                        truncated.set(true);
+                        break;
+                    }
                     funcs.push(func);
+                    num_imported_funcs += 1;
                 }
             }
 
             if !funcs.is_empty() {
                 imported_funcs.push((dll_name, funcs));
             }
+
+            if truncated.get() {
+                break;
+            }
         }
 
         Some(imported_funcs)
@@ -1999,11 +2166,17 @@ impl<'a> PE<'a> {
         // Parse the IMAGE_EXPORT_DIRECTORY structure.
         let (_, exports) = Self::parse_exports_dir_entry(exports_data).ok()?;
 
+        if exports.number_of_functions as usize > self.limits.max_exports
+            || exports.number_of_names as usize > self.limits.max_exports
+        {
+            self.exports_truncated.set(true);
+        }
+
         let num_exports =
-            min(exports.number_of_functions as usize, Self::MAX_PE_EXPORTS);
+            min(exports.number_of_functions as usize, self.limits.max_exports);
 
         let num_names =
-            min(exports.number_of_names as usize, Self::MAX_PE_EXPORTS);
+            min(exports.number_of_names as usize, self.limits.max_exports);
 
         // The IMAGE_EXPORT_DIRECTORY structure points to three arrays. The
         // only required array is the Export Address Table (EAT), which is an
@@ -2266,9 +2439,19 @@ impl From<PE<'_>> for protos::pe::PE {
             .sections
             .extend(pe.get_sections().iter().map(protos::pe::Section::from));
 
+        result
+            .debug
+            .extend(pe.get_debug_entries().iter().map(protos::pe::DebugEntry::from));
+
         result
             .resources
-            .extend(pe.get_resources().iter().map(protos::pe::Resource::from));
+            .extend(pe.get_resources().iter().map(|resource| {
+                let mut r = protos::pe::Resource::from(resource);
+                r.sha256 = pe.get_resource_data(resource).map(|data| {
+                    format!("{:x}", Sha256::digest(data))
+                });
+                r
+            }));
         
         result
             .signatures
@@ -2302,6 +2485,8 @@ impl From<PE<'_>> for protos::pe::PE {
             }
         }
 
+        result.set_imports_truncated(pe.imports_truncated.get());
+        result.set_delayed_imports_truncated(pe.delayed_imports_truncated.get());
         result.set_number_of_imported_functions(num_imported_funcs as u64);
         result.set_number_of_delayed_imported_functions(num_delayed_imported_funcs as u64);
         
@@ -2310,6 +2495,8 @@ impl From<PE<'_>> for protos::pe::PE {
             result.export_timestamp = Some(exports.timestamp);
             result.export_details.extend(exports.functions.iter().map(protos::pe::Export::from));
         }
+
+        result.set_exports_truncated(pe.exports_truncated.get());
         
         for (key, value) in pe.get_version_info() {
             let mut kv = protos::pe::KeyValue::new();
@@ -2319,6 +2506,15 @@ impl From<PE<'_>> for protos::pe::PE {
             result.version_info.insert(key.to_owned(), value.to_owned());
         }
         
+        result.dos_stub = pe.get_dos_stub().map(|stub| stub.to_vec());
+        result.dos_stub_is_standard = pe.dos_stub_is_standard();
+
+        result.has_tls =
+            Some(pe.has_dir_entry(PE::IMAGE_DIRECTORY_ENTRY_TLS));
+        result.has_load_config =
+            Some(pe.has_dir_entry(PE::IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG));
+        result.has_rwx_section = Some(pe.has_rwx_section());
+
         if let Some(rich_header) = pe.get_rich_header() {
             result.rich_signature = MessageField::some(protos::pe::RichSignature {
                 offset: Some(rich_header.offset.try_into().unwrap()),
@@ -2353,6 +2549,7 @@ impl From<PE<'_>> for protos::pe::PE {
         };
         
             
+        result.set_resources_truncated(pe.resources_truncated.get());
         result.set_number_of_resources(
             result.resources.len().try_into().unwrap());
         
@@ -2725,6 +2922,61 @@ pub struct DbgDirEntry {
     raw_data_offset: u32,
 }
 
+/// An entry in the debug directory, together with its CodeView
+/// information, if any.
+#[derive(Debug)]
+pub struct DbgEntry<'a> {
+    entry: DbgDirEntry,
+    codeview: Option<CodeView<'a>>,
+}
+
+/// CodeView information that points to the PDB file with the debug
+/// information for the PE.
+#[derive(Debug)]
+pub struct CodeView<'a> {
+    /// GUID that identifies the PDB file (only in "RSDS" and "MTOC").
+    guid: Option<&'a [u8]>,
+    /// Number of times the PDB file has been updated (only in "RSDS" and
+    /// "NB10").
+    age: Option<u32>,
+    /// Path to the PDB file.
+    pdb_path: &'a [u8],
+}
+
+impl From<&DbgEntry<'_>> for protos::pe::DebugEntry {
+    fn from(value: &DbgEntry) -> Self {
+        let mut entry = protos::pe::DebugEntry::new();
+        entry.type_ = Some(value.entry.type_);
+        entry.timestamp = Some(value.entry.timestamp);
+        if let Some(codeview) = &value.codeview {
+            entry.guid = codeview.guid.map(format_guid);
+            entry.age = codeview.age;
+            entry.pdb_path = Some(codeview.pdb_path.to_vec());
+        }
+        entry
+    }
+}
+
+/// Formats a GUID stored in its binary form, where the first three
+/// components are little-endian integers, as in
+/// `"3f2504e0-4f89-11d3-9a0c-0305e82c3301"`.
+fn format_guid(guid: &[u8]) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        u32::from_le_bytes([guid[0], guid[1], guid[2], guid[3]]),
+        u16::from_le_bytes([guid[4], guid[5]]),
+        u16::from_le_bytes([guid[6], guid[7]]),
+        guid[8],
+        guid[9],
+        guid[10],
+        guid[11],
+        guid[12],
+        guid[13],
+        guid[14],
+        guid[15],
+    )
+}
+
 /// Parser that reads a 32-bits or 64-bits unsigned integer, depending on
 /// its argument. The result is always an `u64`.
 fn uint(_32bits: bool) -> impl FnMut(&[u8]) -> IResult<&[u8], u64> {
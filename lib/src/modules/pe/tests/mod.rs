@@ -1,6 +1,7 @@
 use pretty_assertions::assert_eq;
 
 use crate::modules::tests::create_binary_from_zipped_ihex;
+use crate::tests::rule_false;
 use crate::tests::rule_true;
 use crate::tests::test_rule;
 
@@ -28,6 +29,234 @@ fn rich_signature() {
     );
 }
 
+#[test]
+fn debug_entries() {
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/00a1067fc96eb2c1d440bb5b44b32f43b9900fdd3a65c985d65a63b8f1535ef5.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.pdb_path == "D:\\MyProject\\StreetPlayer\\ExtraProgram\\KillPot\\x64\\Release\\KillPot64.pdb" and
+            pe.debug[0].type == 2 and
+            pe.debug[0].timestamp == 1527751881 and
+            pe.debug[0].guid == "a8a9ea2a-5804-463d-bd5b-132437de25ab" and
+            pe.debug[0].age == 1 and
+            pe.debug[0].pdb_path == pe.pdb_path and
+            pe.debug[1].type == 12 and
+            not defined pe.debug[1].guid and
+            not defined pe.debug[1].pdb_path
+        }
+        "#,
+        &pe
+    );
+
+    rule_false!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            defined pe.pdb_path or defined pe.debug[0].type
+        }
+        "#,
+        &[]
+    );
+}
+
+#[test]
+fn data_directories() {
+    // PE32 file with TLS and load configuration directories.
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/29eeeecf2c458ea3da1ce9d6d54742c0fad490cb2165f371f53b61941eedf072.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.has_tls and
+            pe.has_load_config and
+            pe.data_directories[pe.IMAGE_DIRECTORY_ENTRY_TLS].virtual_address == 206256 and
+            pe.data_directories[pe.IMAGE_DIRECTORY_ENTRY_TLS].size == 24 and
+            pe.data_directories[pe.IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG].virtual_address == 206184 and
+            pe.data_directories[pe.IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG].size == 64
+        }
+        "#,
+        &pe
+    );
+
+    // PE32+ file with a TLS directory, but without load configuration.
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/b7a3b9f050d3fdf5ee4b5f8c625b10ab74adfc7923f7f2be70bfe785b374e05c.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.is_64bit() and
+            pe.has_tls and
+            not pe.has_load_config and
+            pe.data_directories[pe.IMAGE_DIRECTORY_ENTRY_TLS].virtual_address == 1265760 and
+            pe.data_directories[pe.IMAGE_DIRECTORY_ENTRY_TLS].size == 40
+        }
+        "#,
+        &pe
+    );
+
+    // PE32 file without TLS directory.
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/079a472d22290a94ebb212aa8015cdc8dd28a968c6b4d3b88acdd58ce2d3b885.in.zip",
+    );
+
+    rule_false!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.has_tls
+        }
+        "#,
+        &pe
+    );
+}
+
+#[test]
+fn rwx_sections() {
+    // PE32 file packed with UPX, where the sections UPX0 and UPX1 are both
+    // writable and executable.
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/0ba6042247d90a187919dd88dc2d55cd882c80e5afc511c4f7b2e0e193968f7f.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.has_rwx_section and
+            pe.sections[0].characteristics & pe.SECTION_MEM_WRITE != 0 and
+            pe.sections[0].characteristics & pe.SECTION_MEM_EXECUTE != 0 and
+            pe.sections[2].characteristics & pe.SECTION_MEM_WRITE != 0 and
+            pe.sections[2].characteristics & pe.SECTION_MEM_EXECUTE == 0
+        }
+        "#,
+        &pe
+    );
+
+    // PE32 file where the code section is executable but not writable, and
+    // the data sections are writable but not executable.
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/079a472d22290a94ebb212aa8015cdc8dd28a968c6b4d3b88acdd58ce2d3b885.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            not pe.has_rwx_section and
+            pe.sections[0].characteristics & pe.SECTION_MEM_EXECUTE != 0 and
+            pe.sections[0].characteristics & pe.SECTION_MEM_WRITE == 0
+        }
+        "#,
+        &pe
+    );
+}
+
+#[test]
+fn module_errors() {
+    let rules = crate::compile(
+        r#"
+        import "pe"
+        rule test { condition: not pe.is_pe }
+        "#,
+    )
+    .unwrap();
+
+    // A DOS header where `e_lfanew` points outside the file.
+    let mut corrupt_pe = vec![0_u8; 64];
+    corrupt_pe[0..2].copy_from_slice(b"MZ");
+    corrupt_pe[0x3c..0x40].copy_from_slice(&0x1000_u32.to_le_bytes());
+
+    let mut errors = Vec::new();
+    let mut scanner = crate::Scanner::new(&rules);
+
+    scanner.on_module_error(|module, error| {
+        errors.push((module.to_string(), error.clone()))
+    });
+
+    // The error doesn't change the results, `pe.is_pe` is still false.
+    assert_eq!(scanner.scan(&corrupt_pe).unwrap().matching_rules().len(), 1);
+
+    // Data that is not a PE at all is not an error.
+    assert_eq!(scanner.scan(b"ELF").unwrap().matching_rules().len(), 1);
+
+    drop(scanner);
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, "pe");
+    assert!(matches!(
+        errors[0].1,
+        crate::ModuleError::MalformedData { .. }
+    ));
+}
+
+#[test]
+fn dos_stub() {
+    let mut pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/079a472d22290a94ebb212aa8015cdc8dd28a968c6b4d3b88acdd58ce2d3b885.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.dos_stub_is_standard and
+            pe.dos_stub contains "This program cannot be run in DOS mode."
+        }
+        "#,
+        &pe
+    );
+
+    // Modify the message in the MS-DOS stub, which starts right after the
+    // 64-bytes DOS header.
+    pe[0x4e..0x5a].copy_from_slice(b"THIS PROGRAM");
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            not pe.dos_stub_is_standard and
+            pe.dos_stub contains "THIS PROGRAM cannot be run in DOS mode."
+        }
+        "#,
+        &pe
+    );
+
+    // Files where the PE header overlaps the DOS header don't have a
+    // MS-DOS stub.
+    pe[0x3c..0x40].copy_from_slice(&0x20_u32.to_le_bytes());
+
+    rule_false!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            defined pe.dos_stub or defined pe.dos_stub_is_standard
+        }
+        "#,
+        &pe
+    );
+}
+
 #[test]
 fn imports() {
     let pe = create_binary_from_zipped_ihex(
@@ -340,6 +569,122 @@ fn checksum() {
     );
 }
 
+#[test]
+fn version_info_list() {
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/079a472d22290a94ebb212aa8015cdc8dd28a968c6b4d3b88acdd58ce2d3b885.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            for any entry in pe.version_info_list : (
+              entry.key == "Build Date" and
+              entry.value == "Tue Jun 5 21:07:13 IST 2018"
+            )
+        }
+        "#,
+        &pe
+    );
+
+    rule_false!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            for any entry in pe.version_info_list : (
+              entry.key == "NonExistingKey"
+            )
+        }
+        "#,
+        &pe
+    );
+
+    // The VS_VERSIONINFO block in this file is truncated in the middle of
+    // the "ProductVersion" string, the entries that precede the truncation
+    // point must be reported anyway.
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/d1ba6d9cd4fe68195fa90c95f5027d12b0a7d8a02ee657db19c1228696dba1a4.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            for any entry in pe.version_info_list : (
+              entry.key == "OriginalFilename" and
+              entry.value == "UnityEngine.Purchasing.AppleStub.dll"
+            )
+        }
+        "#,
+        &pe
+    );
+
+    rule_false!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            for any entry in pe.version_info_list : (
+              entry.key == "Assembly Version"
+            )
+        }
+        "#,
+        &pe
+    );
+}
+
+#[test]
+fn pattern_at_entry_point() {
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/af3f20a9272489cbef4281c8c86ad42ccfb04ccedd3ada1e8c26939c726a4c8e.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          strings:
+            $a = { 48 B8 A1 31 1B 5B }
+          condition:
+            $a at pe.entry_point
+        }
+        "#,
+        &pe
+    );
+
+    rule_false!(
+        r#"
+        import "pe"
+        rule test {
+          strings:
+            $a = { 48 B8 A1 31 1B 5B }
+          condition:
+            $a at pe.entry_point + 1
+        }
+        "#,
+        &pe
+    );
+
+    // `pe.entry_point` is undefined for non-PE files, and so is the result
+    // of the `at` operator.
+    rule_false!(
+        r#"
+        import "pe"
+        rule test {
+          strings:
+            $a = { 48 B8 A1 31 1B 5B }
+          condition:
+            not ($a at pe.entry_point)
+        }
+        "#,
+        b"\x48\xB8\xA1\x31\x1B\x5B"
+    );
+}
+
 #[test]
 fn locale_and_language() {
     let pe = create_binary_from_zipped_ihex(
@@ -423,6 +768,80 @@ fn is_dll() {
     );
 }
 
+#[test]
+fn is_driver() {
+    // Driver that imports from ntoskrnl.exe and FLTMGR.SYS.
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/130f9b2e65bfceae8d9e7cbe205471fc8ee34c3d9996f77baa3c3ab47a3b3068.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.is_driver() and
+            not pe.is_dll() and
+            pe.subsystem == pe.SUBSYSTEM_NATIVE
+        }
+        "#,
+        &pe
+    );
+
+    // Native application without kernel-mode imports.
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/d009038e4e371f9cdc3a96923700c17d29de9dfc156666c98581dfeccc07548e.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            not pe.is_driver() and
+            pe.subsystem == pe.SUBSYSTEM_NATIVE
+        }
+        "#,
+        &pe
+    );
+
+    // DLL.
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/079a472d22290a94ebb212aa8015cdc8dd28a968c6b4d3b88acdd58ce2d3b885.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            not pe.is_driver() and
+            pe.is_dll() and
+            pe.subsystem == pe.SUBSYSTEM_WINDOWS_GUI
+        }
+        "#,
+        &pe
+    );
+
+    // EXE.
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/00a1067fc96eb2c1d440bb5b44b32f43b9900fdd3a65c985d65a63b8f1535ef5.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            not pe.is_driver() and
+            not pe.is_dll() and
+            pe.subsystem == pe.SUBSYSTEM_WINDOWS_GUI
+        }
+        "#,
+        &pe
+    );
+}
+
 #[test]
 fn section_index() {
     let pe = create_binary_from_zipped_ihex(
@@ -523,3 +942,292 @@ fn valid_on() {
         &pe
     );
 }
+
+#[test]
+fn resources() {
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/0885cff80dd6e14559c6429a89d724d9f5553a34e0716a660376728b8987c61b.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.number_of_resources == 4 and
+            pe.resources[0].type == pe.RESOURCE_TYPE_ICON and
+            pe.resources[0].sha256 == "f9c64814132169e9c0fd863105ad9f6efebea49b84c83531910b269d1d667432" and
+            for any r in pe.resources : (
+              r.type == pe.RESOURCE_TYPE_MANIFEST and
+              r.length == 3566 and
+              r.sha256 == "16560ce8564d2ba1be39165e817846732ae45ec6b096f0a1b9d4dc4d1a7bbf90"
+            )
+        }
+        "#,
+        &pe
+    );
+}
+
+/// Builds a minimal 32-bit PE file that imports `n` functions by ordinal
+/// from a single DLL.
+fn pe_with_imports(n: usize) -> Vec<u8> {
+    // The only section starts at this file offset, and is mapped at the
+    // same RVA.
+    const SECTION: usize = 0x200;
+
+    // The section contains the import descriptor followed by the null
+    // descriptor, the thunks (terminated by a null thunk) and the DLL name.
+    let thunks = SECTION + 40;
+    let dll_name = thunks + (n + 1) * 4;
+    let section_size = dll_name + 6 - SECTION;
+
+    let mut pe = vec![0_u8; SECTION + section_size];
+
+    let put_u16 = |pe: &mut Vec<u8>, offset: usize, value: u16| {
+        pe[offset..offset + 2].copy_from_slice(&value.to_le_bytes())
+    };
+
+    // DOS header.
+    pe[0..2].copy_from_slice(b"MZ");
+    pe[0x3c..0x40].copy_from_slice(&0x40_u32.to_le_bytes());
+
+    // PE signature and file header.
+    pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+    put_u16(&mut pe, 0x44, 0x14c); // machine
+    put_u16(&mut pe, 0x46, 1); // number of sections
+    put_u16(&mut pe, 0x54, 0xe0); // size of optional header
+    put_u16(&mut pe, 0x56, 0x102); // characteristics
+
+    let put_u32 = |pe: &mut Vec<u8>, offset: usize, value: usize| {
+        pe[offset..offset + 4]
+            .copy_from_slice(&(value as u32).to_le_bytes())
+    };
+
+    // Optional header.
+    put_u16(&mut pe, 0x58, 0x10b); // magic
+    put_u32(&mut pe, 0x58 + 32, 0x200); // section alignment
+    put_u32(&mut pe, 0x58 + 36, 0x200); // file alignment
+    put_u32(&mut pe, 0x58 + 92, 16); // number of RVA and sizes
+    put_u32(&mut pe, 0x58 + 104, SECTION); // import directory RVA
+    put_u32(&mut pe, 0x58 + 108, 40); // import directory size
+
+    // Section header.
+    pe[0x138..0x140].copy_from_slice(b".idata\0\0");
+    put_u32(&mut pe, 0x138 + 8, section_size); // virtual size
+    put_u32(&mut pe, 0x138 + 12, SECTION); // virtual address
+    put_u32(&mut pe, 0x138 + 16, section_size); // raw data size
+    put_u32(&mut pe, 0x138 + 20, SECTION); // raw data offset
+
+    // Import descriptor.
+    put_u32(&mut pe, SECTION, thunks); // import name table
+    put_u32(&mut pe, SECTION + 12, dll_name); // name
+    put_u32(&mut pe, SECTION + 16, thunks); // import address table
+
+    // Thunks, all of them importing ordinal 1.
+    for i in 0..n {
+        put_u32(&mut pe, thunks + i * 4, 0x80000001);
+    }
+
+    pe[dll_name..dll_name + 5].copy_from_slice(b"a.dll");
+    pe
+}
+
+#[test]
+fn module_limits() {
+    let rules = crate::compile(
+        r#"
+        import "pe"
+        rule imports {
+          condition:
+            pe.imports_truncated
+        }
+        rule num_imported_functions_le_10 {
+          condition:
+            pe.number_of_imported_functions <= 10
+        }
+        rule num_imported_functions_is_1000000 {
+          condition:
+            pe.number_of_imported_functions == 1000000
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = crate::Scanner::new(&rules);
+
+    let matching = |scanner: &mut crate::Scanner, data: &[u8]| {
+        scanner
+            .scan(data)
+            .unwrap()
+            .matching_rules()
+            .map(|rule| rule.identifier().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // A small number of imports is not truncated.
+    assert_eq!(
+        matching(&mut scanner, &pe_with_imports(5)),
+        vec!["num_imported_functions_le_10"]
+    );
+
+    // The number of imported functions is not limited by default.
+    let pe = pe_with_imports(1_000_000);
+
+    assert_eq!(
+        matching(&mut scanner, &pe),
+        vec!["num_imported_functions_is_1000000"]
+    );
+
+    // The limit can be changed.
+    scanner.set_module_limits(crate::ModuleLimits {
+        max_imports: 10,
+        ..Default::default()
+    });
+
+    assert_eq!(
+        matching(&mut scanner, &pe),
+        vec!["imports", "num_imported_functions_le_10"]
+    );
+}
+
+#[test]
+fn partial_output() {
+    let rules = crate::compile(r#"import "pe" rule test { condition: true }"#)
+        .unwrap();
+
+    let mut errors = Vec::new();
+    let mut scanner = crate::Scanner::new(&rules);
+
+    scanner
+        .set_module_limits(crate::ModuleLimits {
+            max_imports: 10,
+            ..Default::default()
+        })
+        .on_module_error(|module, error| {
+            errors.push((module.to_string(), error.clone()))
+        });
+
+    scanner.scan(&pe_with_imports(5)).unwrap();
+    scanner.scan(&pe_with_imports(20)).unwrap();
+
+    drop(scanner);
+
+    assert_eq!(
+        errors,
+        [(
+            "pe".to_string(),
+            crate::ModuleError::PartialOutput {
+                reason: "too many imports".to_string()
+            }
+        )]
+    );
+}
+
+#[test]
+fn is_packed() {
+    // UPX-packed file.
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/0ba6042247d90a187919dd88dc2d55cd882c80e5afc511c4f7b2e0e193968f7f.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.is_packed() and pe.packer_score() > 0.8
+        }
+        "#,
+        &pe
+    );
+
+    // Unpacked file.
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/079a472d22290a94ebb212aa8015cdc8dd28a968c6b4d3b88acdd58ce2d3b885.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            not pe.is_packed() and pe.packer_score() < 0.2
+        }
+        "#,
+        &pe
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            not defined pe.is_packed() and not defined pe.packer_score()
+        }
+        "#,
+        b"not a PE"
+    );
+}
+
+#[test]
+fn section_range() {
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/079a472d22290a94ebb212aa8015cdc8dd28a968c6b4d3b88acdd58ce2d3b885.in.zip",
+    );
+
+    // The pattern is found at the start of the `.text` section.
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          strings:
+            $a = { 55 8B EC 8B 4D 0C 81 EC }
+          condition:
+            $a in pe.section_range(0)
+        }
+        "#,
+        &pe
+    );
+
+    rule_false!(
+        r#"
+        import "pe"
+        rule test {
+          strings:
+            $a = { 55 8B EC 8B 4D 0C 81 EC }
+          condition:
+            $a in pe.section_range(1)
+        }
+        "#,
+        &pe
+    );
+
+    // Ranges for non-existing sections are undefined.
+    rule_false!(
+        r#"
+        import "pe"
+        rule test {
+          strings:
+            $a = { 55 8B EC 8B 4D 0C 81 EC }
+          condition:
+            $a in pe.section_range(100)
+        }
+        "#,
+        &pe
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          strings:
+            $a = { 55 8B EC 8B 4D 0C 81 EC }
+          condition:
+            for any i in (0..pe.number_of_sections - 1) : (
+              $a in pe.section_range(i) and pe.sections[i].name == ".text"
+            )
+        }
+        "#,
+        &pe
+    );
+}
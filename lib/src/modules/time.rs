@@ -1,6 +1,5 @@
 use crate::modules::prelude::*;
 use crate::modules::protos::time::*;
-use std::time::{SystemTime, UNIX_EPOCH};
 
 #[module_main]
 fn main(_data: &[u8]) -> Time {
@@ -9,8 +8,8 @@ fn main(_data: &[u8]) -> Time {
 }
 
 #[module_export]
-fn now(_ctx: &ScanContext) -> Option<i64> {
-    Some(SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs() as i64)
+fn now(ctx: &ScanContext) -> Option<i64> {
+    Some(ctx.current_time)
 }
 
 #[cfg(test)]
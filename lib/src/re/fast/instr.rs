@@ -143,6 +143,83 @@ impl<'a> InstrParser<'a> {
     }
 }
 
+/// Returns the size of the instruction at the start of `code`, or [`None`]
+/// if the opcode is unknown or the instruction doesn't fit in `code`.
+fn instr_size(code: &[u8]) -> Option<usize> {
+    let operand = |i: usize| {
+        code.get(1 + i * size_of::<u16>()..1 + (i + 1) * size_of::<u16>())
+            .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    let size = match *code.first()? {
+        Instr::MATCH => 1,
+        Instr::LITERAL | Instr::ALTERNATION => {
+            1 + size_of::<u16>() + operand(0)? as usize
+        }
+        Instr::MASKED_LITERAL => {
+            1 + size_of::<u16>() + 2 * operand(0)? as usize
+        }
+        Instr::JUMP_EXACT | Instr::JUMP_EXACT_NO_NEWLINE => {
+            1 + size_of::<u16>()
+        }
+        Instr::JUMP | Instr::JUMP_NO_NEWLINE => {
+            let (min, max) = (operand(0)?, operand(1)?);
+            // When max is 0 it actually means unlimited max.
+            if max != 0 && min > max {
+                return None;
+            }
+            1 + 2 * size_of::<u16>()
+        }
+        _ => return None,
+    };
+    (size <= code.len()).then_some(size)
+}
+
+/// Checks that the code starting at each of the `starts` locations is made
+/// of valid instructions ending with [`Instr::Match`]. Returns the location
+/// of the first invalid instruction found, if any.
+///
+/// The Fast VM doesn't check bounds while decoding instructions, this must
+/// be used for code that doesn't come from the compiler.
+pub(crate) fn validate_code(
+    code: &[u8],
+    starts: impl IntoIterator<Item = usize>,
+) -> Result<(), usize> {
+    let mut visited = vec![false; code.len()];
+
+    for mut addr in starts {
+        loop {
+            match visited.get(addr) {
+                Some(false) => visited[addr] = true,
+                Some(true) => break,
+                None => return Err(addr),
+            }
+            let size = instr_size(&code[addr..]).ok_or(addr)?;
+            match code[addr] {
+                Instr::MATCH => break,
+                // Alternatives can be only literals or masked literals.
+                Instr::ALTERNATION => {
+                    let mut alternatives =
+                        &code[addr + 1 + size_of::<u16>()..addr + size];
+                    while !alternatives.is_empty() {
+                        if !matches!(
+                            alternatives[0],
+                            Instr::LITERAL | Instr::MASKED_LITERAL
+                        ) {
+                            return Err(addr);
+                        }
+                        let alt_size = instr_size(alternatives).ok_or(addr)?;
+                        alternatives = &alternatives[alt_size..];
+                    }
+                }
+                _ => {}
+            }
+            addr += size;
+        }
+    }
+
+    Ok(())
+}
+
 impl<'a> Iterator for InstrParser<'a> {
     type Item = Instr<'a>;
 
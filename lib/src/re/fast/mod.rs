@@ -40,3 +40,4 @@ mod compiler;
 mod instr;
 
 pub(crate) use compiler::Compiler;
+pub(crate) use instr::validate_code;
@@ -244,6 +244,57 @@ impl Hir {
             _ => false,
         }
     }
+
+    /// Returns true if the HIR contains an unbounded repetition (e.g: `+`,
+    /// `*`, `{n,}`) nested inside another unbounded repetition, like in
+    /// `(a+)+` or `(ab*c)*`.
+    pub fn has_nested_unbounded_repetitions(&self) -> bool {
+        fn visit(hir: &regex_syntax::hir::Hir, in_unbounded: bool) -> bool {
+            match hir.kind() {
+                HirKind::Repetition(rep) => {
+                    let unbounded = rep.max.is_none();
+                    (unbounded && in_unbounded)
+                        || visit(&rep.sub, in_unbounded || unbounded)
+                }
+                HirKind::Capture(cap) => visit(&cap.sub, in_unbounded),
+                HirKind::Concat(subs) | HirKind::Alternation(subs) => {
+                    subs.iter().any(|sub| visit(sub, in_unbounded))
+                }
+                _ => false,
+            }
+        }
+        visit(&self.inner, false)
+    }
+}
+
+impl Hir {
+    /// Returns the size of the regexp after expanding its repetitions.
+    ///
+    /// Each byte in a literal, each class and each assertion count as one,
+    /// and a repetition counts as many times as its maximum number of
+    /// repetitions, or its minimum if it's unbounded. For instance, the size
+    /// of `a{1000}{1000}` is 1000000. This is roughly proportional to the
+    /// size of the code produced for the regexp, as repetitions are
+    /// expanded by the compiler.
+    pub fn expanded_size(&self) -> usize {
+        fn size(hir: &regex_syntax::hir::Hir) -> usize {
+            match hir.kind() {
+                HirKind::Empty => 0,
+                HirKind::Literal(literal) => literal.0.len(),
+                HirKind::Class(_) | HirKind::Look(_) => 1,
+                HirKind::Repetition(rep) => {
+                    size(&rep.sub).saturating_mul(
+                        rep.max.unwrap_or(rep.min).max(1) as usize,
+                    )
+                }
+                HirKind::Capture(cap) => size(&cap.sub),
+                HirKind::Concat(subs) | HirKind::Alternation(subs) => subs
+                    .iter()
+                    .fold(0, |total, sub| total.saturating_add(size(sub))),
+            }
+        }
+        size(&self.inner)
+    }
 }
 
 impl Hir {
@@ -6,6 +6,7 @@ More specifically, the compiler produces two instruction sequences, one that
 matches the regexp left-to-right, and another one that matches right-to-left.
 */
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::io::{Cursor, Read, Seek, SeekFrom, Write};
@@ -127,6 +128,10 @@ pub(crate) struct Compiler {
     /// repetition doesn't make sense, atoms must be extracted from portions of
     /// the pattern that are required to be present in any matching string.
     zero_rep_depth: u32,
+
+    /// Atom length favored when choosing between sets of atoms with similar
+    /// quality. See [`AtomsQuality::cmp_with_preferred_len`].
+    preferred_atom_len: Option<usize>,
 }
 
 impl Compiler {
@@ -162,9 +167,17 @@ impl Compiler {
             best_atoms_stack: vec![RegexpAtoms::empty()],
             depth: 0,
             zero_rep_depth: 0,
+            preferred_atom_len: None,
         }
     }
 
+    /// Sets the atom length favored when choosing between sets of atoms
+    /// with similar quality.
+    pub fn preferred_atom_len(mut self, len: Option<usize>) -> Self {
+        self.preferred_atom_len = len;
+        self
+    }
+
     /// Given the high-level intermediate representation (HIR) of a regular
     /// expression, produces code for the PikeVM that matches the regular
     /// expression and returns a set of atoms extracted from it.
@@ -565,7 +578,10 @@ impl Compiler {
         // better than the best atoms found so far, and less than
         // MAX_ATOMS_PER_REGEXP.
         if alternative_atoms.len() <= MAX_ATOMS_PER_REGEXP
-            && best_atoms.quality < alternative_atoms.quality
+            && best_atoms.quality.cmp_with_preferred_len(
+                &alternative_atoms.quality,
+                self.preferred_atom_len,
+            ) == Ordering::Less
         {
             *best_atoms = alternative_atoms;
         }
@@ -979,7 +995,11 @@ impl hir::Visitor for Compiler {
                             seq.make_inexact()
                         }
                         let quality = AtomsQuality::from_seq(&seq);
-                        if quality > best_quality {
+                        if quality.cmp_with_preferred_len(
+                            &best_quality,
+                            self.preferred_atom_len,
+                        ) == Ordering::Greater
+                        {
                             best_quality = quality;
                             best_atoms = seq_to_atoms(seq);
                             code_loc = locations[i]
@@ -1064,7 +1084,11 @@ impl hir::Visitor for Compiler {
         let best_atoms = self.best_atoms_stack.last_mut().unwrap();
         let quality = AtomsQuality::from_atoms(atoms.iter());
 
-        if quality > best_atoms.quality {
+        if quality.cmp_with_preferred_len(
+            &best_atoms.quality,
+            self.preferred_atom_len,
+        ) == Ordering::Greater
+        {
             *best_atoms = RegexpAtoms {
                 quality,
                 atoms: atoms
@@ -341,6 +341,98 @@ impl<'a> InstrParser<'a> {
     }
 }
 
+/// Returns the size of the instruction at the start of `code`, or [`None`]
+/// if the opcode is unknown or the instruction doesn't fit in `code`.
+fn instr_size(code: &[u8]) -> Option<usize> {
+    let size = match code {
+        [OPCODE_PREFIX, opcode, ..] => match *opcode {
+            Instr::MATCH
+            | Instr::ANY_BYTE
+            | Instr::START
+            | Instr::END
+            | Instr::WORD_BOUNDARY
+            | Instr::WORD_BOUNDARY_NEG
+            | Instr::WORD_START
+            | Instr::WORD_END
+            | OPCODE_PREFIX => 2,
+            Instr::CASE_INSENSITIVE_CHAR => 3,
+            Instr::MASKED_BYTE => 4,
+            Instr::JUMP => 2 + size_of::<Offset>(),
+            Instr::SPLIT_A | Instr::SPLIT_B => {
+                2 + size_of::<SplitId>() + size_of::<Offset>()
+            }
+            Instr::SPLIT_N => {
+                let n = *code.get(2 + size_of::<SplitId>())? as usize;
+                2 + size_of::<SplitId>()
+                    + size_of::<NumAlt>()
+                    + size_of::<Offset>() * n
+            }
+            Instr::CLASS_RANGES => {
+                3 + size_of::<i16>() * *code.get(2)? as usize
+            }
+            Instr::CLASS_BITMAP => 2 + 32,
+            _ => return None,
+        },
+        [OPCODE_PREFIX] | [] => return None,
+        [_, ..] => 1,
+    };
+    (size <= code.len()).then_some(size)
+}
+
+/// Checks that the code reachable from each of the `starts` locations is
+/// made of valid instructions, and that jumps and splits lead to locations
+/// within `code`. Returns the location of the first invalid instruction
+/// found, if any.
+///
+/// The Pike VM doesn't check bounds while decoding instructions, this must
+/// be used for code that doesn't come from the compiler.
+pub(crate) fn validate_code(
+    code: &[u8],
+    starts: impl IntoIterator<Item = usize>,
+) -> Result<(), usize> {
+    let mut visited = vec![false; code.len()];
+    let mut pending: Vec<usize> = starts.into_iter().collect();
+
+    while let Some(addr) = pending.pop() {
+        match visited.get(addr) {
+            Some(false) => visited[addr] = true,
+            Some(true) => continue,
+            None => return Err(addr),
+        }
+
+        let size = instr_size(&code[addr..]).ok_or(addr)?;
+        let target = |offset: Offset| {
+            addr.checked_add_signed(offset as isize).ok_or(addr)
+        };
+        let check_split_id = |id: SplitId| {
+            if usize::from(id) < 1 << SplitId::BITS {
+                Ok(())
+            } else {
+                Err(addr)
+            }
+        };
+
+        match InstrParser::decode_instr(&code[addr..]).0 {
+            Instr::Match => {}
+            Instr::Jump(offset) => pending.push(target(offset)?),
+            Instr::SplitA(id, offset) | Instr::SplitB(id, offset) => {
+                check_split_id(id)?;
+                pending.push(addr + size);
+                pending.push(target(offset)?);
+            }
+            Instr::SplitN(split) => {
+                check_split_id(split.id())?;
+                for offset in split.offsets() {
+                    pending.push(target(offset)?);
+                }
+            }
+            _ => pending.push(addr + size),
+        }
+    }
+
+    Ok(())
+}
+
 impl<'a> Iterator for InstrParser<'a> {
     type Item = (Instr<'a>, usize);
 
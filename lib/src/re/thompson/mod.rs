@@ -14,3 +14,4 @@ mod instr;
 mod tests;
 
 pub(crate) use compiler::Compiler;
+pub(crate) use instr::validate_code;
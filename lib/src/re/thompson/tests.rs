@@ -1143,6 +1143,37 @@ fn re_code_22() {
     );
 }
 
+#[test]
+fn validate_code() {
+    let parser = re::parser::Parser::new();
+
+    let (fwd_code, _, _) = Compiler::new()
+        .compile_internal(
+            &parser
+                .parse(&ast::Regexp {
+                    literal: "/(0?F1?|2?f3?)abcd/",
+                    src: "(0?F1?|2?f3?)abcd",
+                    case_insensitive: false,
+                    dot_matches_new_line: true,
+                    span: ast::Span::default(),
+                })
+                .unwrap(),
+        )
+        .unwrap();
+
+    let mut code = fwd_code.as_ref().to_vec();
+
+    assert_eq!(super::validate_code(&code, [0x00, 0x39]), Ok(()));
+    assert_eq!(super::validate_code(&code, [code.len()]), Err(code.len()));
+
+    // Without the final MATCH the last instruction is followed by nothing.
+    assert_eq!(super::validate_code(&code[..0x3d], [0x00]), Err(0x3d));
+
+    // Make the JUMP at 0x20 lead outside the code.
+    code[0x22..0x26].copy_from_slice(0x1000_i32.to_le_bytes().as_slice());
+    assert_eq!(super::validate_code(&code, [0x00]), Err(0x1020));
+}
+
 #[test]
 fn re_atoms() {
     assert_re_atoms!(r#"abcd"#, vec![Atom::exact(b"abcd")]);
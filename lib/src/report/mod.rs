@@ -0,0 +1,423 @@
+/*! Exports compiler errors and warnings in formats understood by other tools,
+and renders scan results in human-readable form.
+
+Besides the human-readable reports returned by the [`std::fmt::Display`]
+implementation of errors and warnings, the diagnostics produced by the
+compiler can be exported in [SARIF][1] format, which is understood by
+code-scanning dashboards like the one in GitHub, or in a simpler JSON
+format intended for editors and other tools (see [`to_json`]).
+
+Scan results can be rendered as a plain text table with [`render_table`],
+which is useful for command-line tools that embed YARA-X.
+
+[1]: https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+*/
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write;
+
+use serde_json::{json, Value};
+
+use yara_x_parser::ast::Span;
+use yara_x_parser::{Error as ParseError, Warning};
+
+use crate::{CompileError, Compiler, ScanResults};
+
+#[cfg(test)]
+mod tests;
+
+/// An error or warning produced while compiling YARA rules.
+pub enum Diagnostic<'a> {
+    /// An error produced while parsing the source code.
+    ParseError(&'a ParseError),
+    /// An error produced while compiling the rules.
+    CompileError(&'a CompileError),
+    /// A warning.
+    Warning(&'a Warning),
+}
+
+impl<'a> From<&'a ParseError> for Diagnostic<'a> {
+    fn from(err: &'a ParseError) -> Self {
+        Self::ParseError(err)
+    }
+}
+
+impl<'a> From<&'a CompileError> for Diagnostic<'a> {
+    fn from(err: &'a CompileError) -> Self {
+        Self::CompileError(err)
+    }
+}
+
+impl<'a> From<&'a Warning> for Diagnostic<'a> {
+    fn from(warning: &'a Warning) -> Self {
+        Self::Warning(warning)
+    }
+}
+
+impl fmt::Display for Diagnostic<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::ParseError(err) => err.fmt(f),
+            Diagnostic::CompileError(err) => err.fmt(f),
+            Diagnostic::Warning(warning) => warning.fmt(f),
+        }
+    }
+}
+
+impl Diagnostic<'_> {
+    /// Code that identifies the type of error or warning (e.g.
+    /// `unknown_identifier`).
+    pub fn code(&self) -> &'static str {
+        match self {
+            Diagnostic::ParseError(err) => err.info().code(),
+            Diagnostic::CompileError(err) => err.code(),
+            Diagnostic::Warning(warning) => warning.code(),
+        }
+    }
+
+    /// Title of the error or warning, without any code snippet.
+    pub fn title(&self) -> String {
+        match self {
+            Diagnostic::ParseError(err) => err.info().title(),
+            Diagnostic::CompileError(err) => err.title(),
+            Diagnostic::Warning(warning) => warning.title(),
+        }
+    }
+
+    /// Span of the source code the error or warning refers to.
+    pub fn span(&self) -> Span {
+        match self {
+            Diagnostic::ParseError(err) => err.info().span(),
+            Diagnostic::CompileError(err) => err.span(),
+            Diagnostic::Warning(warning) => warning.span(),
+        }
+    }
+
+    /// Returns true if this is a warning.
+    pub fn is_warning(&self) -> bool {
+        matches!(self, Diagnostic::Warning(_))
+    }
+}
+
+/// Converts a set of errors and warnings into a [SARIF 2.1.0][1] log.
+///
+/// `compiler` must be the compiler that produced the diagnostics. The
+/// source files it has seen, including the ones included with `include`,
+/// are used for translating the spans in the diagnostics into line and
+/// column numbers. The origin of each source file (see
+/// [`yara_x_parser::SourceCode::with_origin`]) is used as the URI of the
+/// artifact. Spans that refer to an unknown source file produce results
+/// without location.
+///
+/// Columns are counted in Unicode code points, both lines and columns
+/// start at 1.
+///
+/// [1]: https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+pub fn to_sarif(diagnostics: &[Diagnostic], compiler: &Compiler) -> String {
+    // Each distinct code becomes a rule in the tool's driver, results
+    // refer to rules by their index.
+    let mut rules = BTreeMap::new();
+
+    for diagnostic in diagnostics {
+        let next_index = rules.len();
+        rules.entry(diagnostic.code()).or_insert(next_index);
+    }
+
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let mut result = json!({
+                "ruleId": diagnostic.code(),
+                "ruleIndex": rules[diagnostic.code()],
+                "level": if diagnostic.is_warning() { "warning" } else { "error" },
+                "message": { "text": diagnostic.title() },
+            });
+
+            let span = diagnostic.span();
+
+            if let Some(location) = Location::new(compiler, span) {
+                let mut region = json!({
+                    "region": {
+                        "startLine": location.start_line,
+                        "startColumn": location.start_column,
+                        "endLine": location.end_line,
+                        "endColumn": location.end_column,
+                    }
+                });
+
+                if let Some(origin) = location.origin {
+                    region["artifactLocation"] = json!({ "uri": origin });
+                }
+
+                result["locations"] = json!([{ "physicalLocation": region }]);
+            }
+
+            result
+        })
+        .collect();
+
+    let mut rules: Vec<(&str, usize)> = rules.into_iter().collect();
+    rules.sort_by_key(|(_, index)| *index);
+
+    let rules: Vec<Value> =
+        rules.into_iter().map(|(code, _)| json!({ "id": code })).collect();
+
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "yara-x",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_HOMEPAGE"),
+                    "rules": rules,
+                }
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }]
+    });
+
+    serde_json::to_string_pretty(&log).unwrap()
+}
+
+/// Converts a set of errors and warnings into a JSON array.
+///
+/// Each diagnostic is a JSON object like the following one:
+///
+/// ```json
+/// {
+///   "code": "unknown_identifier",
+///   "level": "error",
+///   "title": "unknown identifier `qux`",
+///   "text": "error: unknown identifier `qux`\n --> ...",
+///   "span": {
+///     "source_id": 0,
+///     "start": 22,
+///     "end": 25,
+///     "origin": "baz.yar",
+///     "start_line": 1,
+///     "start_column": 23,
+///     "end_line": 1,
+///     "end_column": 26
+///   }
+/// }
+/// ```
+///
+/// `code` is stable across versions and can be used for identifying the
+/// type of the diagnostic. `text` is the detailed report, as returned by
+/// the [`std::fmt::Display`] implementation. `start` and `end` in `span`
+/// are byte offsets within the source file. The remaining fields in `span`
+/// are present only if the source file is known by `compiler`, which has
+/// the same meaning as in [`to_sarif`]. `origin` is also omitted if the source
+/// file doesn't have one.
+pub fn to_json(diagnostics: &[Diagnostic], compiler: &Compiler) -> String {
+    let diagnostics: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let span = diagnostic.span();
+
+            let mut span_json = json!({
+                "source_id": usize::from(span.source_id()),
+                "start": span.start(),
+                "end": span.end(),
+            });
+
+            if let Some(location) = Location::new(compiler, span) {
+                if let Some(origin) = location.origin {
+                    span_json["origin"] = json!(origin);
+                }
+
+                span_json["start_line"] = json!(location.start_line);
+                span_json["start_column"] = json!(location.start_column);
+                span_json["end_line"] = json!(location.end_line);
+                span_json["end_column"] = json!(location.end_column);
+            }
+
+            json!({
+                "code": diagnostic.code(),
+                "level": if diagnostic.is_warning() { "warning" } else { "error" },
+                "title": diagnostic.title(),
+                "text": diagnostic.to_string(),
+                "span": span_json,
+            })
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&diagnostics).unwrap()
+}
+
+/// Location of a span in terms of lines and columns.
+struct Location {
+    origin: Option<String>,
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+impl Location {
+    /// Returns the location of `span` within one of the source files seen
+    /// by `compiler`, or `None` if the source file is unknown.
+    fn new(compiler: &Compiler, span: Span) -> Option<Self> {
+        let report_builder = compiler.report_builder();
+        let src = report_builder.source_code(span.source_id())?;
+
+        let (start_line, start_column) =
+            line_and_column(src.as_bytes(), span.start());
+        let (end_line, end_column) =
+            line_and_column(src.as_bytes(), span.end());
+
+        Some(Self {
+            origin: report_builder.source_origin(span.source_id()),
+            start_line,
+            start_column,
+            end_line,
+            end_column,
+        })
+    }
+}
+
+/// Given a byte offset within `src`, returns the line and column numbers,
+/// both starting at 1. The column is counted in Unicode code points.
+fn line_and_column(src: &[u8], offset: usize) -> (usize, usize) {
+    let prefix = &src[..offset.min(src.len())];
+    let line_start = prefix
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map(|pos| pos + 1)
+        .unwrap_or(0);
+    let line = prefix.iter().filter(|b| **b == b'\n').count() + 1;
+    let column =
+        String::from_utf8_lossy(&prefix[line_start..]).chars().count();
+    (line, column + 1)
+}
+
+/// Width used by [`render_table`], in characters.
+const DEFAULT_TABLE_WIDTH: usize = 80;
+
+/// Renders the matches in `results` as a plain text table.
+///
+/// The table has one row per match, with columns for the rule, namespace,
+/// pattern, offset and a preview of the matching data. Rules that matched
+/// without any pattern matching appear in a single row with empty pattern,
+/// offset and data columns. Non-printable bytes in the preview are escaped
+/// as `\xNN`, and the preview is truncated so that rows are no wider than
+/// 80 characters, use [`render_table_with_width`] for a different width.
+pub fn render_table(results: &ScanResults) -> String {
+    render_table_with_width(results, DEFAULT_TABLE_WIDTH)
+}
+
+/// Like [`render_table`], but truncates the previews so that rows are no
+/// wider than `width` characters.
+///
+/// The preview is never truncated below 8 characters, so rows can be
+/// wider than `width` if the other columns already fill it.
+pub fn render_table_with_width(results: &ScanResults, width: usize) -> String {
+    const HEADERS: [&str; 5] =
+        ["RULE", "NAMESPACE", "PATTERN", "OFFSET", "DATA"];
+    const MIN_PREVIEW_WIDTH: usize = 8;
+
+    let mut rows: Vec<[String; 4]> = Vec::new();
+    let mut data = Vec::new();
+
+    for rule in results.matching_rules() {
+        let mut matched = false;
+        for pattern in rule.patterns() {
+            for m in pattern.matches() {
+                matched = true;
+                rows.push([
+                    rule.identifier().to_string(),
+                    rule.namespace().to_string(),
+                    pattern.identifier().to_string(),
+                    format!("{:#x}", m.range().start),
+                ]);
+                data.push(m.data());
+            }
+        }
+        if !matched {
+            rows.push([
+                rule.identifier().to_string(),
+                rule.namespace().to_string(),
+                String::new(),
+                String::new(),
+            ]);
+            data.push(&[]);
+        }
+    }
+
+    // Width of each column, except the last one, is the width of its
+    // longest value.
+    let mut widths = HEADERS.map(|header| header.len());
+
+    for row in &rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    // Columns are separated by two spaces.
+    let preview_width = width
+        .saturating_sub(widths[..4].iter().map(|w| w + 2).sum())
+        .max(MIN_PREVIEW_WIDTH);
+
+    let mut table = String::new();
+
+    let mut write_row = |values: [&str; 5]| {
+        for (i, value) in values.iter().enumerate() {
+            if i < 4 {
+                write!(table, "{:width$}  ", value, width = widths[i])
+                    .unwrap();
+            } else if value.is_empty() {
+                // Don't leave trailing spaces in rows with an empty preview.
+                table.truncate(table.trim_end_matches(' ').len());
+            } else {
+                table.push_str(value);
+            }
+        }
+        table.push('\n');
+    };
+
+    write_row(HEADERS);
+
+    for (row, data) in rows.iter().zip(data) {
+        write_row([
+            row[0].as_str(),
+            row[1].as_str(),
+            row[2].as_str(),
+            row[3].as_str(),
+            preview(data, preview_width).as_str(),
+        ]);
+    }
+
+    table
+}
+
+/// Returns a preview of `data` that is at most `width` characters long.
+/// Printable ASCII characters are kept as is, while other bytes are escaped
+/// as `\xNN`. Backslashes are escaped as `\\`. The preview ends with `...`
+/// if `data` doesn't fit in `width` characters.
+fn preview(data: &[u8], width: usize) -> String {
+    let escape = |b: u8| match b {
+        b'\\' => "\\\\".to_string(),
+        b' '..=b'~' => (b as char).to_string(),
+        _ => format!("\\x{:02x}", b),
+    };
+
+    let mut result = String::new();
+
+    for (i, b) in data.iter().enumerate() {
+        let escaped = escape(*b);
+        // If this is the last byte, the `...` is not necessary.
+        let reserved = if i + 1 < data.len() { 3 } else { 0 };
+        if result.len() + escaped.len() + reserved > width {
+            result.push_str("...");
+            break;
+        }
+        result.push_str(&escaped);
+    }
+
+    result
+}
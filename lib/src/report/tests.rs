@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use pretty_assertions::assert_eq;
+use serde_json::Value;
+
+use yara_x_parser::SourceCode;
+
+use crate::report::{
+    render_table, render_table_with_width, to_json, to_sarif, Diagnostic,
+};
+use crate::{Compiler, Error};
+
+#[test]
+fn sarif() {
+    let sources = [
+        SourceCode::from("rule foo { condition: filesize > 0 }")
+            .with_origin("foo.yar"),
+        SourceCode::from("rule bar {\n  condition:\n    2\n}")
+            .with_origin("bar.yar"),
+        SourceCode::from("rule baz { condition: qux }").with_origin("baz.yar"),
+    ];
+
+    let mut compiler = Compiler::new();
+
+    compiler.add_source(sources[0].clone()).unwrap();
+    compiler.add_source(sources[1].clone()).unwrap();
+
+    let err = compiler.add_source(sources[2].clone()).unwrap_err();
+
+    let Error::CompileError(err) = err else {
+        panic!("expecting a compile error, got: {:?}", err)
+    };
+
+    let mut diagnostics: Vec<Diagnostic> =
+        compiler.warnings().iter().map(Diagnostic::from).collect();
+
+    diagnostics.push(Diagnostic::from(err.as_ref()));
+
+    let sarif: Value = serde_json::from_str(
+        to_sarif(diagnostics.as_slice(), &compiler).as_str(),
+    )
+    .unwrap();
+
+    assert_eq!(sarif["version"], "2.1.0");
+    assert!(sarif["$schema"].as_str().unwrap().contains("sarif-2.1.0"));
+
+    let run = &sarif["runs"][0];
+
+    assert_eq!(run["tool"]["driver"]["name"], "yara-x");
+    assert_eq!(
+        run["tool"]["driver"]["rules"][0]["id"],
+        "non_boolean_as_boolean"
+    );
+    assert_eq!(run["tool"]["driver"]["rules"][1]["id"], "unknown_identifier");
+
+    let results = run["results"].as_array().unwrap();
+
+    assert_eq!(results.len(), 2);
+
+    assert_eq!(results[0]["ruleId"], "non_boolean_as_boolean");
+    assert_eq!(results[0]["ruleIndex"], 0);
+    assert_eq!(results[0]["level"], "warning");
+    assert_eq!(
+        results[0]["message"]["text"],
+        "non-boolean expression used as boolean"
+    );
+
+    let location = &results[0]["locations"][0]["physicalLocation"];
+
+    assert_eq!(location["artifactLocation"]["uri"], "bar.yar");
+    assert_eq!(location["region"]["startLine"], 3);
+    assert_eq!(location["region"]["startColumn"], 5);
+    assert_eq!(location["region"]["endLine"], 3);
+    assert_eq!(location["region"]["endColumn"], 6);
+
+    assert_eq!(results[1]["ruleId"], "unknown_identifier");
+    assert_eq!(results[1]["ruleIndex"], 1);
+    assert_eq!(results[1]["level"], "error");
+    assert_eq!(results[1]["message"]["text"], "unknown identifier `qux`");
+
+    let location = &results[1]["locations"][0]["physicalLocation"];
+
+    assert_eq!(location["artifactLocation"]["uri"], "baz.yar");
+    assert_eq!(location["region"]["startLine"], 1);
+    assert_eq!(location["region"]["startColumn"], 23);
+    assert_eq!(location["region"]["endColumn"], 26);
+}
+
+#[test]
+fn sarif_with_includes() {
+    let mut includes = HashMap::new();
+
+    includes.insert(
+        "included.yar".to_string(),
+        "rule included { condition: true }".to_string(),
+    );
+
+    let mut compiler = Compiler::new();
+
+    compiler.enable_includes(true).include_resolver(includes);
+
+    compiler
+        .add_source(
+            SourceCode::from(r#"include "included.yar""#)
+                .with_origin("main.yar"),
+        )
+        .unwrap();
+
+    let err = compiler
+        .add_source(
+            SourceCode::from("rule foo {\n  condition: qux\n}")
+                .with_origin("foo.yar"),
+        )
+        .unwrap_err();
+
+    let Error::CompileError(err) = err else {
+        panic!("expecting a compile error, got: {:?}", err)
+    };
+
+    let sarif: Value = serde_json::from_str(
+        to_sarif(&[Diagnostic::from(err.as_ref())], &compiler).as_str(),
+    )
+    .unwrap();
+
+    let location =
+        &sarif["runs"][0]["results"][0]["locations"][0]["physicalLocation"];
+
+    assert_eq!(location["artifactLocation"]["uri"], "foo.yar");
+    assert_eq!(location["region"]["startLine"], 2);
+    assert_eq!(location["region"]["startColumn"], 14);
+    assert_eq!(location["region"]["endColumn"], 17);
+}
+
+#[test]
+fn json() {
+    let sources = [
+        SourceCode::from("rule bar {\n  condition:\n    2\n}")
+            .with_origin("bar.yar"),
+        SourceCode::from("rule baz { condition: qux }"),
+    ];
+
+    let mut compiler = Compiler::new();
+
+    compiler.add_source(sources[0].clone()).unwrap();
+
+    let err = compiler.add_source(sources[1].clone()).unwrap_err();
+
+    let Error::CompileError(err) = err else {
+        panic!("expecting a compile error, got: {:?}", err)
+    };
+
+    let mut diagnostics: Vec<Diagnostic> =
+        compiler.warnings().iter().map(Diagnostic::from).collect();
+
+    diagnostics.push(Diagnostic::from(err.as_ref()));
+
+    let json: Value = serde_json::from_str(
+        to_json(diagnostics.as_slice(), &compiler).as_str(),
+    )
+    .unwrap();
+
+    let diagnostics = json.as_array().unwrap();
+
+    assert_eq!(diagnostics.len(), 2);
+
+    assert_eq!(diagnostics[0]["code"], "non_boolean_as_boolean");
+    assert_eq!(diagnostics[0]["level"], "warning");
+    assert_eq!(
+        diagnostics[0]["title"],
+        "non-boolean expression used as boolean"
+    );
+    assert_eq!(diagnostics[0]["text"], compiler.warnings()[0].to_string());
+    assert_eq!(
+        diagnostics[0]["span"],
+        serde_json::json!({
+            "source_id": 0,
+            "start": 28,
+            "end": 29,
+            "origin": "bar.yar",
+            "start_line": 3,
+            "start_column": 5,
+            "end_line": 3,
+            "end_column": 6,
+        })
+    );
+
+    assert_eq!(diagnostics[1]["code"], "unknown_identifier");
+    assert_eq!(diagnostics[1]["level"], "error");
+    assert_eq!(diagnostics[1]["title"], "unknown identifier `qux`");
+    assert_eq!(diagnostics[1]["text"], err.to_string());
+    assert_eq!(
+        diagnostics[1]["span"],
+        serde_json::json!({
+            "source_id": 1,
+            "start": 22,
+            "end": 25,
+            "start_line": 1,
+            "start_column": 23,
+            "end_line": 1,
+            "end_column": 26,
+        })
+    );
+
+    // Spans that refer to unknown source files only have offsets.
+    let json: Value = serde_json::from_str(
+        to_json(&[Diagnostic::from(err.as_ref())], &Compiler::new()).as_str(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        json[0]["span"],
+        serde_json::json!({ "source_id": 1, "start": 22, "end": 25 })
+    );
+}
+
+#[test]
+fn table() {
+    let rules = crate::compile(
+        r#"
+        rule foo {
+          strings:
+            $a = "foo"
+            $b = { 00 01 02 }
+          condition:
+            $a and $b
+        }
+        rule bar { condition: true }
+        rule baz {
+          strings:
+            $long = /bar[\x00-\xff]+end/
+          condition:
+            $long
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut data = b"foo\x00\x01\x02 foo bar".to_vec();
+    data.extend(b"\\ 0123456789".repeat(10));
+    data.extend(b"end");
+
+    let mut scanner = crate::Scanner::new(&rules);
+    let results = scanner.scan(&data).unwrap();
+
+    assert_eq!(
+        render_table(&results),
+        r#"RULE  NAMESPACE  PATTERN  OFFSET  DATA
+foo   default    $a       0x0     foo
+foo   default    $a       0x7     foo
+foo   default    $b       0x3     \x00\x01\x02
+bar   default
+baz   default    $long    0xb     bar\\ 0123456789\\ 0123456789\\ 0123456789...
+"#
+    );
+
+    assert_eq!(
+        render_table_with_width(&results, 10),
+        r#"RULE  NAMESPACE  PATTERN  OFFSET  DATA
+foo   default    $a       0x0     foo
+foo   default    $a       0x7     foo
+foo   default    $b       0x3     \x00...
+bar   default
+baz   default    $long    0xb     bar\\...
+"#
+    );
+}
+
+#[test]
+fn table_preview_with_trailing_spaces() {
+    let rules = crate::compile(
+        r#"
+        rule foo {
+          strings:
+            $a = "foo  "
+          condition:
+            $a
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = crate::Scanner::new(&rules);
+    let results = scanner.scan(b"foo  ").unwrap();
+
+    // Trailing spaces in the matched data are part of the preview.
+    assert_eq!(
+        render_table(&results),
+        "RULE  NAMESPACE  PATTERN  OFFSET  DATA\n\
+         foo   default    $a       0x0     foo  \n"
+    );
+}
@@ -1,9 +1,14 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+#[cfg(feature = "math-module")]
+use std::collections::hash_map;
 use std::collections::VecDeque;
+use std::iter;
+use std::mem;
 use std::ops::{Range, RangeInclusive};
 use std::ptr::NonNull;
 use std::rc::Rc;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 #[cfg(feature = "logging")]
 use log::*;
@@ -26,14 +31,23 @@ use crate::compiler::{
     NamespaceId, PatternId, RegexpId, RuleId, Rules, SubPattern,
     SubPatternAtom, SubPatternFlagSet, SubPatternFlags, SubPatternId,
 };
+use crate::modules::{ModuleError, ModuleLimits};
 use crate::re::fast::fastvm::FastVM;
 use crate::re::thompson::pikevm::PikeVM;
 use crate::re::Action;
 use crate::scanner::matches::{Match, PatternMatches, UnconfirmedMatch};
-use crate::scanner::HEARTBEAT_COUNTER;
+#[cfg(feature = "math-module")]
+use crate::scanner::ENTROPY_CACHE_MAX_ENTRIES;
+use crate::scanner::{HEARTBEAT_COUNTER, PROGRESS_INTERVAL};
 use crate::types::{Array, Map, Struct};
 use crate::wasm::MATCHING_RULES_BITMAP_BASE;
-use crate::ScanError;
+use crate::{EntropyCacheStats, ScanError};
+
+/// Type of the callback set with [`crate::Scanner::on_module_error`].
+pub(crate) type ModuleErrorCallback<'r> = dyn FnMut(&str, &ModuleError) + 'r;
+
+/// Type of the callback set with [`crate::Scanner::set_progress_callback`].
+pub(crate) type ProgressCallback<'r> = dyn FnMut(u64, u64) + 'r;
 
 /// Structure that holds information about the current scan.
 pub(crate) struct ScanContext<'r> {
@@ -48,6 +62,10 @@ pub(crate) struct ScanContext<'r> {
     pub scanned_data: *const u8,
     /// Length of data being scanned.
     pub scanned_data_len: usize,
+    /// Ranges within the scanned data that must be treated as holes. Pattern
+    /// matches overlapping any of these ranges are discarded, and functions
+    /// like `uint32` return undefined when reading from them.
+    pub holes: Vec<Range<usize>>,
     /// Vector containing the IDs of the non-private rules that matched,
     /// including both global and non-global ones. Global rules are initially
     /// added to `global_matching_rules`, and once all the rules in the
@@ -57,8 +75,29 @@ pub(crate) struct ScanContext<'r> {
     /// Vector containing the IDs of the private rules that matched, including
     /// both global and non-global ones.
     pub private_matching_rules: Vec<RuleId>,
+    /// Vector containing the IDs of the non-private rules that matched, but
+    /// were removed from `non_private_matching_rules` because another rule
+    /// with the same name matched too. This is populated only when
+    /// [`crate::Scanner::dedup_by_rule_name`] is enabled.
+    pub duplicate_matching_rules: Vec<RuleId>,
+    /// Set containing the IDs of the rules that found an undefined value
+    /// while evaluating their conditions. This is populated only when rules
+    /// were compiled with [`crate::Compiler::strict_undefined`].
+    pub undefined_rules: FxHashSet<RuleId>,
+    /// Set containing the IDs of the rules that are skipped because of the
+    /// tags set with [`crate::Scanner::exclude_tags`] or
+    /// [`crate::Scanner::include_only_tags`].
+    pub excluded_rules: FxHashSet<RuleId>,
     /// Map containing the IDs of the global rules that matched.
     pub global_matching_rules: FxHashMap<NamespaceId, Vec<RuleId>>,
+    /// Map containing the ID of the global rule that didn't match in each
+    /// namespace. The rules in the namespace that come after this one are
+    /// not evaluated.
+    pub failed_global_rules: FxHashMap<NamespaceId, RuleId>,
+    /// Modules that didn't produce any output for the scanned data, or
+    /// that reported the data as malformed. See
+    /// [`crate::SkipReason::ModuleUnavailable`].
+    pub unavailable_modules: FxHashSet<&'r str>,
     /// Compiled rules for this scan.
     pub compiled_rules: &'r Rules,
     /// Structure that contains top-level symbols, like module names
@@ -95,17 +134,40 @@ pub(crate) struct ScanContext<'r> {
     /// Set that contains the PatternId for those patterns that have reached
     /// the maximum number of matches indicated by `max_matches_per_pattern`.
     pub limit_reached: FxHashSet<PatternId>,
+    /// Number of times the atoms of each pattern were found in the scanned
+    /// data. This is `None` unless pattern diagnostics were enabled with
+    /// [`crate::Scanner::collect_pattern_diagnostics`].
+    pub atom_hits: Option<FxHashMap<PatternId, usize>>,
     /// When [`HEARTBEAT_COUNTER`] is larger than this value, the scan is
     /// aborted due to a timeout.
     pub deadline: u64,
+    /// Flag that aborts the scan when set to `true`. See
+    /// [`crate::Scanner::set_cancellation_flag`].
+    pub cancellation_flag: Option<Arc<AtomicBool>>,
+    /// Time returned by `time.now()`, as the number of seconds since the
+    /// Unix epoch. It's fixed at the start of each scan.
+    pub current_time: i64,
     /// Hash map that serves as a cache for regexps used in expressions like
     /// `some_var matches /foobar/`. Compiling a regexp is a expensive
     /// operation. Instead of compiling the regexp each time the expression
     /// is evaluated, it is compiled the first time and stored in this hash
     /// map.
     pub regexp_cache: RefCell<FxHashMap<RegexpId, Regex>>,
+    /// Hash map that serves as a cache for the entropy of ranges of the
+    /// scanned data. When multiple rules compute the entropy of the same
+    /// range (e.g: `math.entropy(0, filesize)`) it is computed only once.
+    pub entropy_cache: RefCell<FxHashMap<Range<usize>, f64>>,
+    /// Hits and misses of `entropy_cache` during the current scan.
+    pub entropy_cache_stats: Cell<EntropyCacheStats>,
     /// Callback invoked every time a YARA rule calls `console.log`.
     pub console_log: Option<Box<dyn FnMut(String) + 'r>>,
+    /// Callback invoked every time a module reports an error.
+    pub module_error: Option<Box<ModuleErrorCallback<'r>>>,
+    /// Callback invoked periodically while searching for patterns in the
+    /// scanned data.
+    pub progress: Option<Box<ProgressCallback<'r>>>,
+    /// Limits on the size of the output produced by modules.
+    pub module_limits: ModuleLimits,
     /// Hash map that tracks the time spend on each pattern. Keys are pattern
     /// PatternIds and values are the cumulative time spent on verifying each
     /// pattern.
@@ -145,6 +207,38 @@ impl<'r> ScanContext<'r> {
 }
 
 impl ScanContext<'_> {
+    /// Returns true if the given range overlaps any of the holes in the
+    /// scanned data.
+    pub(crate) fn overlaps_hole(&self, range: &Range<usize>) -> bool {
+        self.holes
+            .iter()
+            .any(|hole| range.start < hole.end && hole.start < range.end)
+    }
+
+    /// Returns the ranges of the scanned data that are not covered by any
+    /// of the holes, in ascending order.
+    pub(crate) fn ranges_outside_holes(&self) -> Vec<Range<usize>> {
+        let mut holes = self.holes.clone();
+        let mut ranges = Vec::new();
+        let mut start = 0;
+
+        holes.sort_by_key(|hole| hole.start);
+
+        for hole in holes {
+            if hole.start > start {
+                ranges.push(start..hole.start.min(self.scanned_data_len));
+            }
+            start = start.max(hole.end);
+        }
+
+        if start < self.scanned_data_len {
+            ranges.push(start..self.scanned_data_len);
+        }
+
+        ranges.retain(|range| !range.is_empty());
+        ranges
+    }
+
     /// Returns a slice with the data being scanned.
     pub(crate) fn scanned_data<'a>(&self) -> &'a [u8] {
         unsafe {
@@ -169,6 +263,36 @@ impl ScanContext<'_> {
             .is_match(haystack)
     }
 
+    /// Returns the entropy of the scanned data within `range`. The entropy
+    /// is computed with `f` the first time that a given range is requested,
+    /// subsequent requests for the same range are served from a cache. The
+    /// cache holds up to [`ENTROPY_CACHE_MAX_ENTRIES`] ranges.
+    #[cfg(feature = "math-module")]
+    pub(crate) fn cached_entropy<F>(&self, range: Range<usize>, f: F) -> f64
+    where
+        F: FnOnce() -> f64,
+    {
+        let mut stats = self.entropy_cache_stats.get();
+        let mut cache = self.entropy_cache.borrow_mut();
+        let full = cache.len() >= ENTROPY_CACHE_MAX_ENTRIES;
+        let entropy = match cache.entry(range) {
+            hash_map::Entry::Occupied(entry) => {
+                stats.hits += 1;
+                *entry.get()
+            }
+            hash_map::Entry::Vacant(_) if full => {
+                stats.misses += 1;
+                f()
+            }
+            hash_map::Entry::Vacant(entry) => {
+                stats.misses += 1;
+                *entry.insert(f())
+            }
+        };
+        self.entropy_cache_stats.set(stats);
+        entropy
+    }
+
     /// Returns the protobuf struct produced by a module.
     ///
     /// The main function of a module returns a protobuf message with data
@@ -254,6 +378,8 @@ impl ScanContext<'_> {
         // This function must be called only for global rules.
         debug_assert!(rule.is_global);
 
+        self.failed_global_rules.entry(rule.namespace_id).or_insert(rule_id);
+
         // All the global rules that matched previously, and are in the same
         // namespace as the non-matching rule, must be removed from the
         // `global_matching_rules` map. Also, their corresponding bits in
@@ -317,6 +443,27 @@ impl ScanContext<'_> {
         bits.set(rule_id.into(), true);
     }
 
+    /// Leaves a single rule for each name in `non_private_matching_rules`,
+    /// moving the rest to `duplicate_matching_rules`. For each name, the
+    /// rule that is kept is the one with the lowest [`RuleId`], which is
+    /// the one that was added to the compiler first.
+    pub(crate) fn dedup_matching_rules(&mut self) {
+        let rules = self.compiled_rules;
+
+        self.non_private_matching_rules
+            .sort_unstable_by_key(|rule_id| usize::from(*rule_id));
+
+        let mut seen = FxHashSet::default();
+
+        for rule_id in mem::take(&mut self.non_private_matching_rules) {
+            if seen.insert(rules.get(rule_id).ident_id) {
+                self.non_private_matching_rules.push(rule_id);
+            } else {
+                self.duplicate_matching_rules.push(rule_id);
+            }
+        }
+    }
+
     /// Called during the scan process when a pattern has matched for tracking
     /// the matching patterns.
     pub(crate) fn track_pattern_match(
@@ -325,6 +472,11 @@ impl ScanContext<'_> {
         match_: Match,
         replace: bool,
     ) {
+        // Matches that overlap some hole in the data are ignored.
+        if self.overlaps_hole(&match_.range) {
+            return;
+        }
+
         let wasm_store = unsafe { self.wasm_store.as_mut() };
         let mem = self.main_memory.unwrap().data_mut(wasm_store);
         let num_rules = self.compiled_rules.num_rules();
@@ -342,6 +494,14 @@ impl ScanContext<'_> {
         }
     }
 
+    /// Returns `true` if the flag set with
+    /// [`crate::Scanner::set_cancellation_flag`] is `true`.
+    pub(crate) fn cancelled(&self) -> bool {
+        self.cancellation_flag
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
     /// Search for patterns in the data.
     ///
     /// The pattern search phase is when YARA scans the data looking for the
@@ -375,7 +535,64 @@ impl ScanContext<'_> {
         #[cfg(feature = "logging")]
         let mut atom_matches = 0_usize;
 
-        for ac_match in ac.find_overlapping_iter(scanned_data) {
+        // Atoms are searched only outside the holes, as any match found
+        // inside them would be discarded anyway. Each range is searched in
+        // chunks of `PROGRESS_INTERVAL` bytes, so that the progress can be
+        // reported as the search advances, even if no atoms are found. The
+        // search in each chunk extends past its end by the length of the
+        // longest atom minus one, so that atoms that cross the boundary
+        // between two chunks are found. Only the atoms that start within
+        // the chunk are reported, the remaining ones will be found in the
+        // next chunk. After all the atoms in a chunk, the item `(end, None)`
+        // indicates that the data has been processed up to offset `end`.
+        let max_atom_len = ac.max_atom_len();
+
+        let ac_matches = self
+            .ranges_outside_holes()
+            .into_iter()
+            .flat_map(|range| {
+                range.clone().step_by(PROGRESS_INTERVAL).map(move |start| {
+                    let end = start.saturating_add(PROGRESS_INTERVAL);
+                    (start, end.min(range.end), range.end)
+                })
+            })
+            .flat_map(|(start, end, range_end)| {
+                let search_end = end
+                    .saturating_add(max_atom_len.saturating_sub(1))
+                    .min(range_end);
+                ac.find_overlapping_iter(
+                    aho_corasick::Input::new(scanned_data)
+                        .range(start..search_end),
+                )
+                .filter(move |ac_match| ac_match.start() < end)
+                .map(move |ac_match| (end, Some(ac_match)))
+                .chain(iter::once((end, None)))
+            });
+
+        // Offset at which the progress callback will be invoked next.
+        let mut next_progress_report = PROGRESS_INTERVAL;
+
+        for (processed, ac_match) in ac_matches {
+            let ac_match = match ac_match {
+                Some(ac_match) => ac_match,
+                None => {
+                    // The last chunk is reported after the loop.
+                    if let Some(progress) = &mut self.progress {
+                        if processed >= next_progress_report
+                            && processed < scanned_data.len()
+                        {
+                            progress(
+                                processed as u64,
+                                scanned_data.len() as u64,
+                            );
+                            next_progress_report =
+                                processed.saturating_add(PROGRESS_INTERVAL);
+                        }
+                    }
+                    continue;
+                }
+            };
+
             #[cfg(feature = "logging")]
             {
                 atom_matches += 1;
@@ -390,6 +607,10 @@ impl ScanContext<'_> {
                 return Err(ScanError::Timeout);
             }
 
+            if self.cancelled() {
+                return Err(ScanError::Cancelled);
+            }
+
             let atom =
                 unsafe { atoms.get_unchecked(ac_match.pattern().as_usize()) };
 
@@ -411,6 +632,10 @@ impl ScanContext<'_> {
             let (pattern_id, sub_pattern) =
                 &self.compiled_rules.get_sub_pattern(sub_pattern_id);
 
+            if let Some(atom_hits) = &mut self.atom_hits {
+                *atom_hits.entry(*pattern_id).or_default() += 1;
+            }
+
             // Check if the potentially matching pattern has reached the
             // maximum number of allowed matches. In that case continue without
             // verifying the match. `get_unchecked` is used for performance
@@ -606,6 +831,13 @@ impl ScanContext<'_> {
                 }
             }
         }
+
+        self.pattern_matches.finish();
+
+        if let Some(progress) = &mut self.progress {
+            progress(scanned_data.len() as u64, scanned_data.len() as u64);
+        }
+
         Ok(())
     }
 
@@ -22,6 +22,8 @@ pub struct Match {
 #[derive(Debug, Default)]
 pub struct MatchList {
     matches: Vec<Match>,
+    /// Number of matches that were discarded by [`MatchList::sample`].
+    discarded: usize,
 }
 
 impl MatchList {
@@ -29,7 +31,7 @@ impl MatchList {
     /// without relocating. The capacity will increase if [`MatchList::add`]
     /// is called and there's no capacity to store the new item.
     pub fn with_capacity(capacity: usize) -> Self {
-        Self { matches: Vec::with_capacity(capacity) }
+        Self { matches: Vec::with_capacity(capacity), discarded: 0 }
     }
 
     /// Adds a new match to the list while keeping the matches sorted by
@@ -79,6 +81,41 @@ impl MatchList {
         }
     }
 
+    /// Removes the matches that overlap with some previous match in the
+    /// list.
+    ///
+    /// Matches are processed from left to right, a match is kept only if
+    /// it starts at or after the end of the last match that was kept. The
+    /// result is the set of non-overlapping matches found by a greedy,
+    /// leftmost-first search.
+    pub fn remove_overlapping(&mut self) {
+        let mut end = 0;
+        self.matches.retain(|m| {
+            if m.range.start >= end {
+                end = m.range.end;
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    /// Keeps only the first `n` matches in the list. The discarded matches
+    /// are still taken into account by [`MatchList::total_len`].
+    pub fn sample(&mut self, n: usize) {
+        if self.matches.len() > n {
+            self.discarded += self.matches.len() - n;
+            self.matches.truncate(n);
+        }
+    }
+
+    /// Returns the number of matches in the list, including the ones
+    /// discarded by [`MatchList::sample`].
+    #[inline]
+    pub fn total_len(&self) -> usize {
+        self.matches.len() + self.discarded
+    }
+
     #[inline]
     pub fn remove(&mut self, i: usize) -> Match {
         self.matches.remove(i)
@@ -141,7 +178,8 @@ impl MatchList {
 
     #[inline]
     pub fn clear(&mut self) {
-        self.matches.clear()
+        self.matches.clear();
+        self.discarded = 0;
     }
 
     #[inline]
@@ -208,6 +246,7 @@ pub struct UnconfirmedMatch {
 pub struct PatternMatches {
     matches: FxHashMap<PatternId, MatchList>,
     max_matches_per_pattern: usize,
+    overlapping_matches: bool,
     capacity: usize,
 }
 
@@ -219,6 +258,7 @@ impl PatternMatches {
         Self {
             matches: FxHashMap::default(),
             max_matches_per_pattern: Self::DEFAULT_MAX_MATCHES_PER_PATTERN,
+            overlapping_matches: true,
             capacity: 0,
         }
     }
@@ -231,6 +271,34 @@ impl PatternMatches {
         self
     }
 
+    /// Specifies whether overlapping matches are allowed.
+    ///
+    /// The default value is `true`. See [`PatternMatches::finish`].
+    pub fn overlapping_matches(&mut self, yes: bool) -> &mut Self {
+        self.overlapping_matches = yes;
+        self
+    }
+
+    /// Must be called after all matches have been added.
+    ///
+    /// If overlapping matches are not allowed, this removes the matches that
+    /// overlap with some previous match of the same pattern.
+    pub fn finish(&mut self) {
+        if !self.overlapping_matches {
+            for matches in self.matches.values_mut() {
+                matches.remove_overlapping();
+            }
+        }
+    }
+
+    /// Keeps only the first `n` matches of each pattern. See
+    /// [`MatchList::sample`].
+    pub fn sample(&mut self, n: usize) {
+        for matches in self.matches.values_mut() {
+            matches.sample(n);
+        }
+    }
+
     /// Returns the list of matches for a given pattern.
     pub fn get(&self, pattern_id: PatternId) -> Option<&MatchList> {
         self.matches.get(&pattern_id)
@@ -325,4 +393,22 @@ mod test {
             vec![(1..10), (2..10), (3..10), (4..10), (5..10)]
         )
     }
+
+    #[test]
+    fn match_list_remove_overlapping() {
+        let mut ml = MatchList::with_capacity(5);
+
+        ml.add(Match { range: (0..2), xor_key: None }, false);
+        ml.add(Match { range: (1..3), xor_key: None }, false);
+        ml.add(Match { range: (2..4), xor_key: None }, false);
+        ml.add(Match { range: (3..5), xor_key: None }, false);
+        ml.add(Match { range: (5..6), xor_key: None }, false);
+
+        ml.remove_overlapping();
+
+        assert_eq!(
+            ml.iter().map(|m| m.range.clone()).collect::<Vec<Range<usize>>>(),
+            vec![(0..2), (2..4), (5..6)]
+        )
+    }
 }
@@ -3,8 +3,8 @@
 The scanner takes the rules produces by the compiler and scans data with them.
 */
 
-use std::cell::RefCell;
-use std::collections::hash_map;
+use std::cell::{Cell, RefCell};
+use std::collections::{hash_map, BTreeMap};
 use std::io::Read;
 use std::ops::{Deref, Range};
 use std::path::{Path, PathBuf};
@@ -12,9 +12,9 @@ use std::pin::Pin;
 use std::ptr::{null, NonNull};
 use std::rc::Rc;
 use std::slice::Iter;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Once;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Once};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{cmp, fs, thread};
 
 use bitvec::prelude::*;
@@ -23,14 +23,15 @@ use fmmap::{MmapFile, MmapFileExt};
 use indexmap::IndexMap;
 use protobuf::{CodedInputStream, MessageDyn};
 use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use wasmtime::{
     AsContext, AsContextMut, Global, GlobalType, MemoryType, Mutability,
-    Store, TypedFunc, Val, ValType,
+    Store, TypedFunc, UpdateDeadline, Val, ValType,
 };
 
 use crate::compiler::{IdentId, PatternId, RuleId, RuleInfo, Rules};
-use crate::modules::{Module, BUILTIN_MODULES};
+use crate::modules::{Module, ModuleError, ModuleLimits, BUILTIN_MODULES};
 use crate::types::{Struct, TypeValue};
 use crate::variables::VariableError;
 use crate::wasm::{ENGINE, MATCHING_RULES_BITMAP_BASE};
@@ -38,9 +39,11 @@ use crate::{compiler, modules, wasm, Variable};
 
 pub(crate) use crate::scanner::context::*;
 use crate::scanner::matches::PatternMatches;
+pub use crate::scanner::stream::*;
 
 mod context;
 mod matches;
+mod stream;
 
 #[cfg(test)]
 mod tests;
@@ -51,6 +54,10 @@ pub enum ScanError {
     /// The scan was aborted after the timeout period.
     #[error("timeout")]
     Timeout,
+    /// The scan was aborted because the flag set with
+    /// [`Scanner::set_cancellation_flag`] was set to `true`.
+    #[error("cancelled")]
+    Cancelled,
     /// Could not open the scanned file.
     #[error("can not open `{path}`: {source}")]
     OpenError {
@@ -81,6 +88,10 @@ pub enum ScanError {
         /// Module name.
         module: String,
     },
+    /// A global variable in the context passed to [`Scanner::replay`] has
+    /// a type that doesn't match the one defined by the rules.
+    #[error(transparent)]
+    VariableError(#[from] VariableError),
 }
 
 /// Global counter that gets incremented every 1 second by a dedicated thread.
@@ -91,10 +102,21 @@ static HEARTBEAT_COUNTER: AtomicU64 = AtomicU64::new(0);
 /// Used for spawning the thread that increments `HEARTBEAT_COUNTER`.
 static INIT_HEARTBEAT: Once = Once::new();
 
+/// Minimum number of bytes processed between two consecutive invocations
+/// of the callback set with [`Scanner::set_progress_callback`].
+const PROGRESS_INTERVAL: usize = 1 << 20;
+
+/// Maximum number of ranges whose entropy is kept in the entropy cache
+/// during a scan. Once the cache is full, the entropy of ranges that are
+/// not in the cache is computed every time.
+#[cfg(feature = "math-module")]
+const ENTROPY_CACHE_MAX_ENTRIES: usize = 1024;
+
 pub enum ScannedData<'a> {
     Slice(&'a [u8]),
     Vec(Vec<u8>),
     Mmap(MmapFile),
+    Owned(Box<dyn AsRef<[u8]>>),
 }
 
 impl<'a> AsRef<[u8]> for ScannedData<'a> {
@@ -103,6 +125,7 @@ impl<'a> AsRef<[u8]> for ScannedData<'a> {
             ScannedData::Slice(s) => s,
             ScannedData::Vec(v) => v.as_ref(),
             ScannedData::Mmap(m) => m.as_slice(),
+            ScannedData::Owned(o) => o.as_ref().as_ref(),
         }
     }
 }
@@ -117,7 +140,13 @@ pub struct Scanner<'r> {
     wasm_store: Pin<Box<Store<ScanContext<'r>>>>,
     wasm_main_func: TypedFunc<(), i32>,
     filesize: Global,
+    rule_filter_enabled: Global,
     timeout: Option<Duration>,
+    match_sample_limit: Option<usize>,
+    excluded_tags: Vec<String>,
+    included_tags: Option<Vec<String>>,
+    dedup_by_rule_name: bool,
+    current_time: Option<i64>,
 }
 
 impl<'r> Scanner<'r> {
@@ -144,21 +173,35 @@ impl<'r> Scanner<'r> {
                 runtime_objects: IndexMap::new(),
                 compiled_rules: rules,
                 console_log: None,
+                module_error: None,
+                progress: None,
+                module_limits: ModuleLimits::default(),
                 current_struct: None,
                 root_struct: rules.globals().make_root(),
                 scanned_data: null(),
                 scanned_data_len: 0,
+                holes: Vec::new(),
                 private_matching_rules: Vec::new(),
                 non_private_matching_rules: Vec::new(),
+                duplicate_matching_rules: Vec::new(),
                 global_matching_rules: FxHashMap::default(),
+                failed_global_rules: FxHashMap::default(),
+                unavailable_modules: FxHashSet::default(),
+                undefined_rules: FxHashSet::default(),
+                excluded_rules: FxHashSet::default(),
                 main_memory: None,
                 module_outputs: FxHashMap::default(),
                 user_provided_module_outputs: FxHashMap::default(),
                 pattern_matches: PatternMatches::new(),
                 unconfirmed_matches: FxHashMap::default(),
                 deadline: 0,
+                cancellation_flag: None,
+                current_time: 0,
                 limit_reached: FxHashSet::default(),
+                atom_hits: None,
                 regexp_cache: RefCell::new(FxHashMap::default()),
+                entropy_cache: RefCell::new(FxHashMap::default()),
+                entropy_cache_stats: Cell::new(EntropyCacheStats::default()),
                 #[cfg(feature = "rules-profiling")]
                 time_spent_in_pattern: FxHashMap::default(),
             },
@@ -197,6 +240,15 @@ impl<'r> Scanner<'r> {
         )
         .unwrap();
 
+        // Global variable that is set to `true` when some rules must be
+        // skipped because of their tags.
+        let rule_filter_enabled = Global::new(
+            wasm_store.as_context_mut(),
+            GlobalType::new(ValType::I32, Mutability::Var),
+            Val::I32(0),
+        )
+        .unwrap();
+
         // Compute the base offset for the bitmap that contains matching
         // information for patterns. This bitmap has 1 bit per pattern,
         // the N-th bit is set if pattern with PatternId = N matched. The
@@ -228,7 +280,14 @@ impl<'r> Scanner<'r> {
         // Instantiate the module. This takes the wasm code provided by the
         // `wasm_mod` function and links its imported functions with the
         // implementations that YARA provides.
-        let wasm_instance = wasm::new_linker()
+        let mut linker = wasm::new_linker();
+
+        // Functions added with `Compiler::add_function`.
+        for host_function in rules.host_functions() {
+            host_function.define(&mut linker);
+        }
+
+        let wasm_instance = linker
             .define(wasm_store.as_context(), "yara_x", "filesize", filesize)
             .unwrap()
             .define(
@@ -245,6 +304,13 @@ impl<'r> Scanner<'r> {
                 timeout_occurred,
             )
             .unwrap()
+            .define(
+                wasm_store.as_context(),
+                "yara_x",
+                "rule_filter_enabled",
+                rule_filter_enabled,
+            )
+            .unwrap()
             .define(
                 wasm_store.as_context(),
                 "yara_x",
@@ -269,7 +335,18 @@ impl<'r> Scanner<'r> {
 
         wasm_store.data_mut().main_memory = Some(main_memory);
 
-        Self { wasm_store, wasm_main_func, filesize, timeout: None }
+        Self {
+            wasm_store,
+            wasm_main_func,
+            filesize,
+            rule_filter_enabled,
+            timeout: None,
+            match_sample_limit: None,
+            excluded_tags: Vec::new(),
+            included_tags: None,
+            dedup_by_rule_name: false,
+            current_time: None,
+        }
     }
 
     /// Sets a timeout for scan operations.
@@ -285,6 +362,35 @@ impl<'r> Scanner<'r> {
         self
     }
 
+    /// Sets the time returned by `time.now()`, as the number of seconds
+    /// since the Unix epoch.
+    ///
+    /// By default `time.now()` returns the time at which each scan started.
+    /// Setting a fixed time makes the scan results independent of the moment
+    /// in which the scan takes place.
+    pub fn set_current_time(&mut self, timestamp: i64) -> &mut Self {
+        self.current_time = Some(timestamp);
+        self
+    }
+
+    /// Sets a flag that cancels scan operations when set to `true`.
+    ///
+    /// The flag is intended to be set from another thread while a scan is
+    /// in progress. When the scanner finds it set, the scan functions return
+    /// [`ScanError::Cancelled`]. The flag is checked as often as the scan
+    /// timeout, which means that the scan is not aborted immediately, but
+    /// about a second after the flag is set at most.
+    ///
+    /// The scanner never clears the flag, subsequent scans will be cancelled
+    /// as well until it is set back to `false`.
+    pub fn set_cancellation_flag(
+        &mut self,
+        flag: Arc<AtomicBool>,
+    ) -> &mut Self {
+        self.wasm_store.data_mut().cancellation_flag = Some(flag);
+        self
+    }
+
     /// Sets the maximum number of matches per pattern.
     ///
     /// When some pattern reaches the maximum number of patterns it won't
@@ -294,6 +400,127 @@ impl<'r> Scanner<'r> {
         self
     }
 
+    /// Sets the maximum number of matches per pattern that are kept in the
+    /// scan results.
+    ///
+    /// Unlike [`Scanner::max_matches_per_pattern`], this doesn't affect the
+    /// evaluation of conditions. Operators like `#a`, `@a[i]` and `!a[i]`
+    /// still see all the matches, but once the conditions are evaluated only
+    /// the first `n` matches of each pattern are kept. [`Pattern::matches`]
+    /// returns at most `n` matches, while [`Pattern::count`] returns the
+    /// total number of matches found.
+    pub fn set_match_sample_limit(&mut self, n: usize) -> &mut Self {
+        self.match_sample_limit = Some(n);
+        self
+    }
+
+    /// Specifies whether the scanner collects the information returned by
+    /// [`ScanResults::pattern_diagnostics`].
+    ///
+    /// This is disabled by default, as it slows down the scanning a bit.
+    pub fn collect_pattern_diagnostics(&mut self, yes: bool) -> &mut Self {
+        self.wasm_store.data_mut().atom_hits =
+            if yes { Some(FxHashMap::default()) } else { None };
+        self
+    }
+
+    /// Returns statistics about the cache used by `math.entropy` during the
+    /// most recent scan.
+    ///
+    /// When multiple rules compute the entropy of the same range of the
+    /// scanned data, it is computed only once and the result is reused. The
+    /// cache is discarded at the start of every scan.
+    pub fn entropy_cache_stats(&self) -> EntropyCacheStats {
+        self.wasm_store.data().entropy_cache_stats.get()
+    }
+
+    /// Specifies whether overlapping matches of the same pattern are
+    /// reported.
+    ///
+    /// By default, a pattern produces a match at every offset where it
+    /// matches, even if the match overlaps with some previous match of the
+    /// same pattern. For instance, `$a = "aa"` matches three times in
+    /// `aaaa`, at offsets 0, 1 and 2. This is the same behavior as YARA.
+    ///
+    /// When this is set to `false`, matches are selected from left to right,
+    /// and any match that overlaps with a previously selected one is
+    /// discarded. With this setting `$a = "aa"` matches only twice in `aaaa`,
+    /// at offsets 0 and 2.
+    ///
+    /// This affects all the operators that depend on the matches of a
+    /// pattern, like `#a`, `@a[i]` and `!a[i]`, as well as the matches
+    /// returned in the scan results.
+    pub fn overlapping_matches(&mut self, yes: bool) -> &mut Self {
+        self.wasm_store.data_mut().pattern_matches.overlapping_matches(yes);
+        self
+    }
+
+    /// Excludes the rules that have any of the given tags.
+    ///
+    /// Excluded rules are skipped, their conditions are not evaluated and
+    /// they are reported as non-matching. This is useful for excluding
+    /// rules that are not ready for production, for instance, by tagging
+    /// them as `experimental`. Each call replaces the tags set by the
+    /// previous one, calling this function with an empty slice makes the
+    /// scanner evaluate all the rules again.
+    ///
+    /// If a global rule is excluded, it doesn't have any effect on the
+    /// remaining rules in its namespace, as if it didn't exist. However, the
+    /// rules in a namespace are still affected by the global rules that are
+    /// not excluded, regardless of whether they are excluded or not.
+    ///
+    /// Rules that use an excluded rule in their conditions are still
+    /// evaluated, but the excluded rule is always `false` for them, as it
+    /// never matches. For instance, if `rule b { condition: not a }` uses
+    /// the excluded rule `a`, then `b` matches. Make sure that the rules
+    /// that depend on the excluded ones are excluded too, for instance by
+    /// giving them the same tags.
+    ///
+    /// This can be combined with [`Scanner::include_only_tags`], in which
+    /// case a rule is evaluated only if it isn't excluded by any of them.
+    pub fn exclude_tags(&mut self, tags: &[&str]) -> &mut Self {
+        self.excluded_tags = tags.iter().map(|t| t.to_string()).collect();
+        self.update_excluded_rules();
+        self
+    }
+
+    /// Evaluates only the rules that have at least one of the given tags.
+    ///
+    /// Any other rule, including the ones without tags, is excluded as
+    /// explained in [`Scanner::exclude_tags`]. Each call replaces the tags
+    /// set by the previous one, calling this function with an empty slice
+    /// removes the restriction.
+    pub fn include_only_tags(&mut self, tags: &[&str]) -> &mut Self {
+        self.included_tags = if tags.is_empty() {
+            None
+        } else {
+            Some(tags.iter().map(|t| t.to_string()).collect())
+        };
+        self.update_excluded_rules();
+        self
+    }
+
+    /// Specifies whether matching rules with the same name are reported
+    /// only once.
+    ///
+    /// When the same rule is present in multiple namespaces, for instance
+    /// because the scanner is using a combination of multiple rule packs,
+    /// all the copies of the rule are reported when they match. With this
+    /// setting enabled, [`ScanResults::matching_rules`] yields a single
+    /// rule for each name. The reported rule is the one that was added to
+    /// the compiler first, which usually is the one in the first namespace.
+    /// As the copies of the rule may have different tags, metadata or
+    /// patterns, notice that these are always the ones of the reported rule.
+    /// The remaining copies are neither reported as matching nor as
+    /// non-matching.
+    ///
+    /// This only affects the results of a scan, the conditions of all the
+    /// rules are evaluated as usual. The default setting is `false`.
+    pub fn dedup_by_rule_name(&mut self, yes: bool) -> &mut Self {
+        self.dedup_by_rule_name = yes;
+        self
+    }
+
     /// Sets a callback that is invoked every time a YARA rule calls the
     /// `console` module.
     ///
@@ -309,6 +536,55 @@ impl<'r> Scanner<'r> {
         self
     }
 
+    /// Sets a callback that is invoked every time a module reports an error
+    /// while processing the scanned data.
+    ///
+    /// The `callback` function is invoked with the module's name and the
+    /// error. For instance, the `pe` module reports an error when the data
+    /// looks like a PE file but it can't be parsed, or when some parts of
+    /// the file were not parsed because they exceed the limits set with
+    /// [`Scanner::set_module_limits`]. These errors don't abort the scan,
+    /// and don't change the results in any way. If no callback is set the
+    /// errors are ignored.
+    pub fn on_module_error<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: FnMut(&str, &ModuleError) + 'r,
+    {
+        self.wasm_store.data_mut().module_error = Some(Box::new(callback));
+        self
+    }
+
+    /// Sets a callback that is invoked periodically with the progress of
+    /// the scan.
+    ///
+    /// The `callback` function receives the number of bytes processed so
+    /// far and the total number of bytes in the scanned data. It's invoked
+    /// while the scanned data is being searched for patterns, each time the
+    /// search advances by one MiB, regardless of whether the patterns are
+    /// found or not, and a final time when the search reaches the end of
+    /// the data. It's never invoked while rule conditions are being
+    /// evaluated. Notice that if the rules don't need to search for patterns
+    /// in the data, the callback is not invoked at all.
+    pub fn set_progress_callback<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: FnMut(u64, u64) + 'r,
+    {
+        self.wasm_store.data_mut().progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Sets the limits on the size of the output produced by modules.
+    ///
+    /// When a module reaches some of these limits, its output is truncated
+    /// and a flag indicating so is set in the output. For instance, if the
+    /// scanned file imports more functions than allowed, the `pe` module
+    /// returns only the first ones and sets `pe.imports_truncated` to
+    /// `true`. See [`ModuleLimits`] for the default limits.
+    pub fn set_module_limits(&mut self, limits: ModuleLimits) -> &mut Self {
+        self.wasm_store.data_mut().module_limits = limits;
+        self
+    }
+
     /// Scans a file.
     pub fn scan_file<'a, P>(
         &'a mut self,
@@ -346,7 +622,68 @@ impl<'r> Scanner<'r> {
         self.scan_impl(data)
     }
 
+    /// Scans multiple files in parallel.
+    ///
+    /// The files in `paths` are distributed among `num_threads` worker
+    /// threads. Each worker has its own [`Scanner`] for the same rules, and
+    /// with the same timeout as this scanner. Other settings, like the
+    /// values of global variables or module outputs set with
+    /// [`Scanner::set_module_output`], are not copied to the workers.
+    ///
+    /// `callback` is called once per file, from the worker thread that
+    /// scanned it, with the path of the file and the result of the scan.
+    /// As [`ScanResults`] borrows the worker's scanner, results must be
+    /// processed within the callback. Errors while opening or reading a file
+    /// are passed to the callback too, they don't interrupt the scanning of
+    /// the remaining files.
+    ///
+    /// This function returns once all the files have been scanned.
+    pub fn scan_paths<I, P, F>(
+        &self,
+        paths: I,
+        num_threads: usize,
+        callback: F,
+    ) where
+        I: IntoIterator<Item = P>,
+        I::IntoIter: Send,
+        P: AsRef<Path> + Send,
+        F: Fn(&Path, Result<ScanResults, ScanError>) + Sync,
+    {
+        let rules = self.wasm_store.data().compiled_rules;
+        let timeout = self.timeout;
+        let paths = Mutex::new(paths.into_iter());
+
+        thread::scope(|s| {
+            for _ in 0..cmp::max(num_threads, 1) {
+                s.spawn(|| {
+                    let mut scanner = Scanner::new(rules);
+
+                    if let Some(timeout) = timeout {
+                        scanner.set_timeout(timeout);
+                    }
+
+                    loop {
+                        // The lock is released before scanning the file, so
+                        // that other workers can take the next path.
+                        let next = paths.lock().unwrap().next();
+                        let Some(path) = next else {
+                            break;
+                        };
+                        let path = path.as_ref();
+                        callback(path, scanner.scan_file(path));
+                    }
+                });
+            }
+        });
+    }
+
     /// Scans in-memory data.
+    ///
+    /// The returned [`ScanResults`] borrow both the scanner and `data`, so
+    /// the bytes that matched can be obtained directly with [`Match::data`],
+    /// without passing the scanned buffer around. As a consequence, the
+    /// scanner can't be used again, and `data` can't be modified, until the
+    /// results are dropped.
     pub fn scan<'a>(
         &'a mut self,
         data: &'a [u8],
@@ -354,6 +691,108 @@ impl<'r> Scanner<'r> {
         self.scan_impl(ScannedData::Slice(data))
     }
 
+    /// Scans in-memory data, taking ownership of it.
+    ///
+    /// `data` can be any owned type that implements `AsRef<[u8]>`, like
+    /// `Arc<[u8]>` or `Vec<u8>`. The data is not copied, the scanner uses
+    /// the same buffer that `data` points to, and the slices returned by
+    /// [`Match::data`] point to that buffer too. This is useful when the
+    /// data is shared with other parts of the program via a reference
+    /// counted pointer, in which case the pointer can be cloned and passed
+    /// to this function, without copying the data itself.
+    pub fn scan_owned<'a, D>(
+        &'a mut self,
+        data: D,
+    ) -> Result<ScanResults<'a, 'r>, ScanError>
+    where
+        D: AsRef<[u8]> + 'static,
+    {
+        self.scan_impl(ScannedData::Owned(Box::new(data)))
+    }
+
+    /// Scans in-memory data that contains holes.
+    ///
+    /// Holes are ranges within `data` that don't contain actual data, like
+    /// the unmapped regions in a memory dump that were filled with zeroes.
+    /// Pattern matches that overlap any of the holes are ignored, and
+    /// functions like `uint32` return undefined when reading from a hole.
+    pub fn scan_with_holes<'a>(
+        &'a mut self,
+        data: &'a [u8],
+        holes: &[Range<usize>],
+    ) -> Result<ScanResults<'a, 'r>, ScanError> {
+        self.wasm_store.data_mut().holes = holes.to_vec();
+        self.scan_impl(ScannedData::Slice(data))
+    }
+
+    /// Scans only the first `head` bytes and the last `tail` bytes of
+    /// `data`.
+    ///
+    /// This is intended as a fast pre-pass for very large inputs, like disk
+    /// images, where headers and footers are the most interesting parts.
+    /// The bytes between the head and the tail are treated as a hole (see
+    /// [`Scanner::scan_with_holes`]): patterns are not searched there, any
+    /// match that extends into them is ignored, and functions like `uint32`
+    /// return undefined when reading from them. Matches found in the tail
+    /// have offsets relative to the start of `data`, and `filesize` is the
+    /// total length of `data`, so conditions like `$a at filesize - 4` work
+    /// as usual.
+    ///
+    /// YARA modules still receive the whole data. If `head + tail` is
+    /// larger than the data, all of it is scanned.
+    pub fn scan_head_tail<'a>(
+        &'a mut self,
+        data: &'a [u8],
+        head: usize,
+        tail: usize,
+    ) -> Result<ScanResults<'a, 'r>, ScanError> {
+        let middle = head..data.len().saturating_sub(tail);
+        if middle.is_empty() {
+            self.scan(data)
+        } else {
+            self.scan_with_holes(data, std::slice::from_ref(&middle))
+        }
+    }
+
+    /// Searches for the atoms extracted from the patterns, without verifying
+    /// the matches or evaluating the conditions.
+    ///
+    /// This is much faster than a full scan, and can be used as a cheap
+    /// pre-screening stage for deciding whether the full scan is worth
+    /// doing. An atom hit means that the pattern could match, so the
+    /// returned [`PrefilterHits`] contain every pattern that actually
+    /// matches, plus some that don't. Patterns anchored at a fixed offset,
+    /// like `$a at 0`, are not searched by means of atoms, and are always
+    /// included in the result.
+    ///
+    /// Holes, timeouts and the rest of scan options don't apply to this
+    /// kind of scan.
+    pub fn prefilter_scan(&self, data: &[u8]) -> PrefilterHits<'r> {
+        let rules = self.wasm_store.data().compiled_rules;
+        let atoms = rules.atoms();
+
+        let mut patterns: FxHashSet<PatternId> = rules
+            .anchored_sub_patterns()
+            .iter()
+            .map(|sub_pattern_id| rules.get_sub_pattern(*sub_pattern_id).0)
+            .collect();
+
+        for ac_match in rules
+            .ac_automaton()
+            .find_overlapping_iter(aho_corasick::Input::new(data))
+        {
+            let atom = &atoms[ac_match.pattern().as_usize()];
+            // If subtracting the backtrack value from the offset where the
+            // atom matched is negative, the pattern can't match here.
+            if ac_match.start() < atom.backtrack() {
+                continue;
+            }
+            patterns.insert(rules.get_sub_pattern(atom.sub_pattern_id()).0);
+        }
+
+        PrefilterHits { rules, patterns }
+    }
+
     /// Sets the value of a global variable.
     ///
     /// The variable must has been previously defined by calling
@@ -465,6 +904,123 @@ impl<'r> Scanner<'r> {
         name: &str,
         data: &[u8],
     ) -> Result<(), ScanError> {
+        self.set_module_output(Self::parse_module_output(name, data)?)
+    }
+
+    /// Captures the inputs used by the last scan, other than the scanned
+    /// data itself.
+    ///
+    /// The returned [`CapturedScanContext`] contains the value of every
+    /// global variable, the time returned by `time.now()`, and the output
+    /// produced by each module. It can be serialized, stored, and passed
+    /// later to [`Scanner::replay`] for re-scanning the same data under the
+    /// same conditions.
+    pub fn capture_scan_context(&self) -> CapturedScanContext {
+        let ctx = self.wasm_store.data();
+
+        // The globals structure also contains the imported modules, but
+        // those are not global variables.
+        let imports: Vec<&str> = ctx.compiled_rules.imports().collect();
+
+        let globals = ctx
+            .compiled_rules
+            .globals()
+            .fields()
+            .filter(|(name, _)| !imports.contains(name))
+            .map(|(name, _)| {
+                let field = ctx.root_struct.field_by_name(name).unwrap();
+                (name.to_string(), field.type_value.clone())
+            })
+            .collect();
+
+        let mut module_outputs: Vec<(String, Vec<u8>)> = ctx
+            .module_outputs
+            .iter()
+            .map(|(name, output)| {
+                (name.clone(), output.write_to_bytes_dyn().unwrap())
+            })
+            .collect();
+
+        // `module_outputs` is a hash map, sort the outputs so that the
+        // captured context doesn't depend on the iteration order.
+        module_outputs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        CapturedScanContext {
+            current_time: ctx.current_time,
+            globals,
+            module_outputs,
+        }
+    }
+
+    /// Scans `data` with the inputs captured by
+    /// [`Scanner::capture_scan_context`].
+    ///
+    /// The global variables and the current time are set to the captured
+    /// values, and are retained in subsequent scans, as if they were set
+    /// with [`Scanner::set_global`] and [`Scanner::set_current_time`].
+    /// Globals that are not defined by the rules used by this scanner are
+    /// ignored, but if some of them has a different type than the one
+    /// defined by the rules the result is [`ScanError::VariableError`] and
+    /// no global is changed. The captured module outputs are used instead
+    /// of invoking the modules' main functions. Globals are not changed
+    /// either if some of the module outputs is invalid.
+    pub fn replay<'a>(
+        &'a mut self,
+        context: &CapturedScanContext,
+        data: &'a [u8],
+    ) -> Result<ScanResults<'a, 'r>, ScanError> {
+        // Parse all the module outputs before changing anything, so that
+        // the scanner is left untouched if some of them is invalid.
+        let module_outputs = context
+            .module_outputs
+            .iter()
+            .map(|(name, output)| Self::parse_module_output(name, output))
+            .collect::<Result<Vec<_>, ScanError>>()?;
+
+        let ctx = self.wasm_store.data_mut();
+
+        // Check the types of all the globals before changing any of them.
+        for (name, value) in &context.globals {
+            if let Some(field) = ctx.root_struct.field_by_name(name) {
+                if !value.eq_type(&field.type_value) {
+                    return Err(VariableError::InvalidType {
+                        variable: name.clone(),
+                        expected_type: field.type_value.ty().to_string(),
+                        actual_type: value.ty().to_string(),
+                    }
+                    .into());
+                }
+            }
+        }
+
+        for (name, value) in &context.globals {
+            if let Some(field) = ctx.root_struct.field_by_name_mut(name) {
+                field.type_value = value.clone();
+            }
+        }
+
+        for output in module_outputs {
+            ctx.user_provided_module_outputs.insert(
+                output.descriptor_dyn().full_name().to_string(),
+                output,
+            );
+        }
+
+        self.current_time = Some(context.current_time);
+        self.scan(data)
+    }
+}
+
+impl<'r> Scanner<'r> {
+    /// Parses the raw output for the module `name`.
+    ///
+    /// `name` can be either the YARA module name or the fully-qualified
+    /// name for the protobuf message associated to the module, as in
+    /// [`Scanner::set_module_output_raw`].
+    fn parse_module_output(
+        name: &str,
+        data: &[u8],
+    ) -> Result<Box<dyn MessageDyn>, ScanError> {
         // Try to find the module by name first, if not found, then try
         // to find a module where the fully-qualified name for its protobuf
         // message matches the `name` arguments.
@@ -490,15 +1046,45 @@ impl<'r> Scanner<'r> {
         // nested structures like the process tree in the `vt` module.
         is.set_recursion_limit(500);
 
-        self.set_module_output(
-            descriptor.unwrap().parse_from(&mut is).map_err(|err| {
-                ScanError::ProtoError { module: name.to_string(), err }
-            })?,
-        )
+        descriptor.unwrap().parse_from(&mut is).map_err(|err| {
+            ScanError::ProtoError { module: name.to_string(), err }
+        })
+    }
+
+    /// Updates the set of excluded rules after the tags passed to
+    /// [`Scanner::exclude_tags`] or [`Scanner::include_only_tags`] change.
+    fn update_excluded_rules(&mut self) {
+        let ctx = self.wasm_store.data_mut();
+        let rules = ctx.compiled_rules;
+
+        let has_tag = |rule_info: &RuleInfo, tags: &[String]| {
+            rule_info.tags.iter().any(|tag| {
+                let tag = rules.ident_pool().get(*tag).unwrap();
+                tags.iter().any(|t| t == tag)
+            })
+        };
+
+        ctx.excluded_rules = rules
+            .rules()
+            .iter()
+            .enumerate()
+            .filter(|(_, rule_info)| {
+                has_tag(rule_info, self.excluded_tags.as_slice())
+                    || self
+                        .included_tags
+                        .as_ref()
+                        .is_some_and(|tags| !has_tag(rule_info, tags))
+            })
+            .map(|(rule_id, _)| rule_id.into())
+            .collect();
+
+        let enabled = !ctx.excluded_rules.is_empty();
+
+        self.rule_filter_enabled
+            .set(self.wasm_store.as_context_mut(), Val::I32(enabled as i32))
+            .unwrap();
     }
-}
 
-impl<'r> Scanner<'r> {
     fn scan_impl<'a>(
         &'a mut self,
         data: ScannedData<'a>,
@@ -525,16 +1111,38 @@ impl<'r> Scanner<'r> {
         // Sets the deadline for the WASM store. The WASM main function will
         // abort if the deadline is reached while the function is being
         // executed.
-        self.wasm_store.set_epoch_deadline(timeout_secs);
-        self.wasm_store
-            .epoch_deadline_callback(|_| Err(ScanError::Timeout.into()));
-
-        // If the user specified some timeout, start the heartbeat thread, if
-        // not previously started. The heartbeat thread increments the WASM
-        // engine epoch and HEARTBEAT_COUNTER every second. There's a single
-        // instance of this thread, independently of the number of concurrent
-        // scans.
-        if self.timeout.is_some() {
+        //
+        // When a cancellation flag is set, the deadline is renewed on every
+        // tick of the epoch, and the callback decides if the scan must be
+        // aborted due to cancellation or timeout.
+        let cancellable = self.wasm_store.data().cancellation_flag.is_some();
+
+        if cancellable {
+            self.wasm_store.set_epoch_deadline(1);
+            self.wasm_store.epoch_deadline_callback(|store| {
+                let ctx = store.data();
+                if ctx.cancelled() {
+                    Err(ScanError::Cancelled.into())
+                } else if HEARTBEAT_COUNTER.load(Ordering::Relaxed)
+                    >= ctx.deadline
+                {
+                    Err(ScanError::Timeout.into())
+                } else {
+                    Ok(UpdateDeadline::Continue(1))
+                }
+            });
+        } else {
+            self.wasm_store.set_epoch_deadline(timeout_secs);
+            self.wasm_store
+                .epoch_deadline_callback(|_| Err(ScanError::Timeout.into()));
+        }
+
+        // If the user specified some timeout or cancellation flag, start the
+        // heartbeat thread, if not previously started. The heartbeat thread
+        // increments the WASM engine epoch and HEARTBEAT_COUNTER every second.
+        // There's a single instance of this thread, independently of the
+        // number of concurrent scans.
+        if self.timeout.is_some() || cancellable {
             INIT_HEARTBEAT.call_once(|| {
                 thread::spawn(|| loop {
                     thread::sleep(Duration::from_secs(1));
@@ -564,6 +1172,11 @@ impl<'r> Scanner<'r> {
             HEARTBEAT_COUNTER.load(Ordering::Relaxed) + timeout_secs;
         ctx.scanned_data = data.as_ref().as_ptr();
         ctx.scanned_data_len = data.as_ref().len();
+        ctx.current_time = self.current_time.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as i64)
+        });
 
         // Free all runtime objects left around by previous scans.
         ctx.runtime_objects.clear();
@@ -581,14 +1194,32 @@ impl<'r> Scanner<'r> {
             // calling `Scanner::set_module_output`, use that output. If not,
             // call the module's main function (if the module has a main
             // function) for getting its output.
+            let mut errors = Vec::new();
+
             let module_output = if let Some(output) =
                 ctx.user_provided_module_outputs.remove(root_struct_name)
             {
                 Some(output)
             } else {
-                module.main_fn.map(|main_fn| main_fn(data.as_ref()))
+                module.main_fn.map(|main_fn| {
+                    main_fn(data.as_ref(), &ctx.module_limits, &mut errors)
+                })
             };
 
+            if let Some(module_error) = &mut ctx.module_error {
+                for error in &errors {
+                    module_error(module_name, error);
+                }
+            }
+
+            if module_output.is_none()
+                || errors.iter().any(|err| {
+                    matches!(err, ModuleError::MalformedData { .. })
+                })
+            {
+                ctx.unavailable_modules.insert(module_name);
+            }
+
             if let Some(module_output) = &module_output {
                 // Make sure that the module is returning a protobuf message of the
                 // expected type.
@@ -652,7 +1283,8 @@ impl<'r> Scanner<'r> {
         // scanning) only if necessary.
         //
         // This will return Err(ScanError::Timeout), when the scan timeout is
-        // reached while WASM code is being executed. If the timeout occurs
+        // reached while WASM code is being executed, or Err(ScanError::Cancelled)
+        // if the scan is cancelled. If the timeout or cancellation occurs
         // while ScanContext::search_for_patterns is being executed, the result
         // will be Ok(1). If the scan completes successfully the result is
         // Ok(0).`
@@ -666,6 +1298,15 @@ impl<'r> Scanner<'r> {
         ctx.scanned_data = null();
         ctx.scanned_data_len = 0;
 
+        // Holes only apply to the current scan.
+        ctx.holes.clear();
+
+        // Conditions have been evaluated already, the matches that exceed
+        // the sample limit are not needed anymore.
+        if let Some(n) = self.match_sample_limit {
+            ctx.pattern_matches.sample(n);
+        }
+
         // Clear the value of `current_struct` as it may contain a reference
         // to some struct.
         ctx.current_struct = None;
@@ -683,8 +1324,15 @@ impl<'r> Scanner<'r> {
             }
         }
 
+        if self.dedup_by_rule_name {
+            ctx.dedup_matching_rules();
+        }
+
         match func_result {
             Ok(0) => Ok(ScanResults::new(self.wasm_store.data(), data)),
+            Ok(1) if self.wasm_store.data().cancelled() => {
+                Err(ScanError::Cancelled)
+            }
             Ok(1) => Err(ScanError::Timeout),
             Ok(_) => unreachable!(),
             Err(err) if err.is::<ScanError>() => {
@@ -711,6 +1359,24 @@ impl<'r> Scanner<'r> {
         // Clear the unconfirmed matches.
         ctx.unconfirmed_matches.clear();
 
+        // Clear the rules that found undefined values.
+        ctx.undefined_rules.clear();
+
+        // Clear the atom hits counted in the previous scan.
+        if let Some(atom_hits) = &mut ctx.atom_hits {
+            atom_hits.clear();
+        }
+
+        // Clear the global rules that didn't match.
+        ctx.failed_global_rules.clear();
+
+        // Clear the modules that were unavailable in the previous scan.
+        ctx.unavailable_modules.clear();
+
+        // Clear the entropies computed in the previous scan.
+        ctx.entropy_cache.get_mut().clear();
+        ctx.entropy_cache_stats.take();
+
         // If some pattern or rule matched, clear the matches. Notice that a
         // rule may match without any pattern being matched, because there
         // are rules without patterns, or that match if the pattern is not
@@ -718,10 +1384,12 @@ impl<'r> Scanner<'r> {
         if !ctx.pattern_matches.is_empty()
             || !ctx.non_private_matching_rules.is_empty()
             || !ctx.private_matching_rules.is_empty()
+            || !ctx.duplicate_matching_rules.is_empty()
         {
             ctx.pattern_matches.clear();
             ctx.non_private_matching_rules.clear();
             ctx.private_matching_rules.clear();
+            ctx.duplicate_matching_rules.clear();
 
             let mem = ctx
                 .main_memory
@@ -746,6 +1414,17 @@ impl<'r> Scanner<'r> {
     }
 }
 
+/// Inputs used by a scan, as returned by [`Scanner::capture_scan_context`].
+///
+/// This type implements [`Serialize`] and [`Deserialize`], so it can be
+/// stored and passed to [`Scanner::replay`] at a later time.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CapturedScanContext {
+    current_time: i64,
+    globals: Vec<(String, TypeValue)>,
+    module_outputs: Vec<(String, Vec<u8>)>,
+}
+
 /// Results of a scan operation.
 ///
 /// Allows iterating over both the matching and non-matching rules.
@@ -770,6 +1449,128 @@ impl<'a, 'r> ScanResults<'a, 'r> {
         NonMatchingRules::new(self.ctx, &self.data)
     }
 
+    /// Returns an iterator that yields every non-private rule together with
+    /// its [`RuleStatus`], which tells whether the rule's condition was
+    /// evaluated or not.
+    ///
+    /// Rules are yielded in the same order they were declared.
+    ///
+    /// ```
+    /// # use yara_x::{RuleStatus, Scanner, SkipReason};
+    /// let rules = yara_x::compile(r#"
+    ///     global rule is_mz { condition: uint16(0) == 0x5A4D }
+    ///     rule test { condition: true }
+    /// "#).unwrap();
+    ///
+    /// let mut scanner = Scanner::new(&rules);
+    /// let results = scanner.scan(b"ELF").unwrap();
+    /// let statuses: Vec<_> = results
+    ///     .rule_statuses()
+    ///     .map(|(rule, status)| (rule.identifier(), status))
+    ///     .collect();
+    ///
+    /// assert_eq!(
+    ///     statuses,
+    ///     [
+    ///         ("is_mz", RuleStatus::NotMatched),
+    ///         (
+    ///             "test",
+    ///             RuleStatus::Skipped { reason: SkipReason::GlobalRule }
+    ///         ),
+    ///     ]
+    /// );
+    /// ```
+    pub fn rule_statuses(&'a self) -> RuleStatuses<'a, 'r> {
+        RuleStatuses::new(self.ctx, &self.data)
+    }
+
+    /// Returns an iterator that yields a [`PatternDiagnostics`] for every
+    /// pattern in every rule, including private ones.
+    ///
+    /// For each pattern it tells how many times its atoms were found in the
+    /// scanned data, and how many of those hits were confirmed as matches.
+    /// Patterns where the number of atom hits is much larger than the number
+    /// of matches have atoms that are too common, and are expensive to scan
+    /// for.
+    ///
+    /// The iterator yields nothing unless the diagnostics were enabled with
+    /// [`Scanner::collect_pattern_diagnostics`].
+    pub fn pattern_diagnostics(
+        &self,
+    ) -> impl Iterator<Item = PatternDiagnostics<'r>> + 'a {
+        let ctx = self.ctx;
+        let rules = ctx.compiled_rules;
+        let ident_pool = rules.ident_pool();
+
+        ctx.atom_hits.iter().flat_map(move |atom_hits| {
+            rules.rules().iter().flat_map(move |rule_info| {
+                rule_info.patterns.iter().map(move |(ident_id, pattern_id)| {
+                    PatternDiagnostics {
+                        namespace: ident_pool
+                            .get(rule_info.namespace_ident_id)
+                            .unwrap(),
+                        rule: ident_pool.get(rule_info.ident_id).unwrap(),
+                        pattern: ident_pool.get(*ident_id).unwrap(),
+                        atom_hits: atom_hits
+                            .get(pattern_id)
+                            .copied()
+                            .unwrap_or_default(),
+                        matches: ctx
+                            .pattern_matches
+                            .get(*pattern_id)
+                            .map(|matches| matches.total_len())
+                            .unwrap_or_default(),
+                    }
+                })
+            })
+        })
+    }
+
+    /// Returns the source code of the matching rule with the given
+    /// identifier.
+    ///
+    /// The result is `None` if no matching rule has that identifier, or if
+    /// the rules were not compiled with [`crate::Compiler::embed_sources`]
+    /// enabled. If rules with the same identifier matched in different
+    /// namespaces, the source of the first one is returned.
+    pub fn matching_rule_source(
+        &'a self,
+        identifier: &str,
+    ) -> Option<&'r str> {
+        self.matching_rules()
+            .find(|rule| rule.identifier() == identifier)
+            .and_then(|rule| rule.source())
+    }
+
+    /// Returns an iterator that yields the namespaces that have at least one
+    /// matching rule, together with the number of matching rules in each of
+    /// them.
+    ///
+    /// Namespaces are yielded in the same order in which they were created
+    /// during compilation. Private rules are not taken into account.
+    pub fn matched_namespaces(
+        &self,
+    ) -> impl Iterator<Item = (&'r str, usize)> {
+        let rules = self.ctx.compiled_rules;
+        let mut namespaces = BTreeMap::new();
+
+        for rule_id in self.ctx.non_private_matching_rules.iter() {
+            let rule_info = rules.get(*rule_id);
+            namespaces
+                .entry(rule_info.namespace_id)
+                .or_insert_with(|| {
+                    let name = rules
+                        .ident_pool()
+                        .get(rule_info.namespace_ident_id)
+                        .unwrap();
+                    (name, 0)
+                })
+                .1 += 1;
+        }
+
+        namespaces.into_values()
+    }
+
     /// Returns the protobuf produced by a YARA module after processing the
     /// data.
     ///
@@ -817,7 +1618,13 @@ impl<'a, 'r> Iterator for MatchingRules<'a, 'r> {
         let rule_id = *self.iterator.next()?;
         let rules = self.ctx.compiled_rules;
         let rule_info = rules.get(rule_id);
-        Some(Rule { rule_info, rules, ctx: self.ctx, data: self.data })
+        Some(Rule {
+            rule_id,
+            rule_info,
+            rules,
+            ctx: self.ctx,
+            data: self.data,
+        })
     }
 }
 
@@ -860,12 +1667,12 @@ impl<'a, 'r> NonMatchingRules<'a, 'r> {
             ctx,
             data,
             iterator: matching_rules_bitmap.iter_zeros(),
-            // The number of non-matching rules is the total number of rules
-            // minus the number of matching rules, both private and
-            // non-private.
-            len: ctx.compiled_rules.num_rules()
-                - ctx.private_matching_rules.len()
-                - ctx.non_private_matching_rules.len(),
+            // The number of non-matching rules is the number of non-private
+            // rules minus the number of non-private matching rules. Private
+            // rules are not taken into account, as they are never returned.
+            len: ctx.compiled_rules.num_non_private_rules()
+                - ctx.non_private_matching_rules.len()
+                - ctx.duplicate_matching_rules.len(),
         }
     }
 }
@@ -875,14 +1682,15 @@ impl<'a, 'r> Iterator for NonMatchingRules<'a, 'r> {
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            self.len = self.len.saturating_sub(1);
             let rule_id = RuleId::from(self.iterator.next()?);
             let rules = self.ctx.compiled_rules;
             let rule_info = rules.get(rule_id);
             // Private rules are not returned, if the current rule is private
             // keep in the loop and try with the next one.
             if !rule_info.is_private {
+                self.len -= 1;
                 return Some(Rule {
+                    rule_id,
                     rule_info,
                     rules,
                     ctx: self.ctx,
@@ -900,6 +1708,231 @@ impl<'a, 'r> ExactSizeIterator for NonMatchingRules<'a, 'r> {
     }
 }
 
+/// Statistics about the entropy cache, as returned by
+/// [`Scanner::entropy_cache_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EntropyCacheStats {
+    /// Number of times that the entropy of some range was found in the
+    /// cache.
+    pub hits: usize,
+    /// Number of times that the entropy of some range was not found in the
+    /// cache, and had to be computed.
+    pub misses: usize,
+}
+
+/// Diagnostic information about a pattern, as returned by
+/// [`ScanResults::pattern_diagnostics`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternDiagnostics<'r> {
+    /// Namespace of the rule the pattern belongs to.
+    pub namespace: &'r str,
+    /// Identifier of the rule the pattern belongs to.
+    pub rule: &'r str,
+    /// Identifier of the pattern (e.g. `$a`).
+    pub pattern: &'r str,
+    /// Number of times that some atom extracted from the pattern was found
+    /// in the scanned data. Each of these hits is a potential match that
+    /// must be verified.
+    pub atom_hits: usize,
+    /// Number of matches of the pattern. Matches of patterns that can
+    /// appear only at a fixed offset are found without using atoms, so
+    /// these patterns may have matches and no atom hits.
+    pub matches: usize,
+}
+
+/// Patterns that may match, as returned by [`Scanner::prefilter_scan`].
+pub struct PrefilterHits<'r> {
+    rules: &'r Rules,
+    patterns: FxHashSet<PatternId>,
+}
+
+impl<'r> PrefilterHits<'r> {
+    /// Returns true if no pattern can match.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Returns true if the pattern with the given identifier (e.g. `$a`),
+    /// in the given rule, may match.
+    pub fn contains(
+        &self,
+        namespace: &str,
+        rule: &str,
+        pattern: &str,
+    ) -> bool {
+        self.iter().any(|hit| {
+            hit.namespace == namespace
+                && hit.rule == rule
+                && hit.pattern == pattern
+        })
+    }
+
+    /// Returns an iterator that yields the patterns that may match, together
+    /// with the rule they belong to.
+    ///
+    /// Identical patterns declared in different rules are searched only
+    /// once, and when they have an atom hit all of them are yielded.
+    pub fn iter(&self) -> impl Iterator<Item = PrefilterHit<'r>> + '_ {
+        let rules = self.rules;
+        let ident_pool = rules.ident_pool();
+
+        rules.rules().iter().flat_map(move |rule_info| {
+            rule_info
+                .patterns
+                .iter()
+                .filter(|(_, pattern_id)| self.patterns.contains(pattern_id))
+                .map(move |(ident_id, _)| PrefilterHit {
+                    namespace: ident_pool
+                        .get(rule_info.namespace_ident_id)
+                        .unwrap(),
+                    rule: ident_pool.get(rule_info.ident_id).unwrap(),
+                    pattern: ident_pool.get(*ident_id).unwrap(),
+                })
+        })
+    }
+}
+
+/// A pattern that may match, as returned by [`PrefilterHits::iter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefilterHit<'r> {
+    /// Namespace of the rule the pattern belongs to.
+    pub namespace: &'r str,
+    /// Identifier of the rule the pattern belongs to.
+    pub rule: &'r str,
+    /// Identifier of the pattern (e.g. `$a`).
+    pub pattern: &'r str,
+}
+
+/// Status of a rule after a scan, as returned by
+/// [`ScanResults::rule_statuses`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleStatus {
+    /// The rule's condition was evaluated and it was true.
+    Matched,
+    /// The rule's condition was evaluated and it was false. Notice that
+    /// conditions that depend on undefined values, like fields of a
+    /// module that didn't produce any output for the scanned data, are
+    /// evaluated as false.
+    NotMatched,
+    /// The rule's condition was not evaluated.
+    Skipped {
+        /// The reason why the rule was skipped.
+        reason: SkipReason,
+    },
+}
+
+/// Reason why the condition of a rule was not evaluated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SkipReason {
+    /// A global rule in the same namespace didn't match.
+    GlobalRule,
+    /// The rule was excluded because of its tags. See
+    /// [`Scanner::exclude_tags`] and [`Scanner::include_only_tags`].
+    ExcludedByTags,
+    /// The rule didn't match and it uses a module that didn't produce any
+    /// output for the scanned data, or that reported the data as
+    /// malformed (see [`crate::ModuleError::MalformedData`]). The
+    /// condition may have been evaluated, but without the information
+    /// that the module should have provided.
+    ///
+    /// Rules that use a module ignored with
+    /// [`crate::Compiler::ignore_module`] are not compiled at all, so they
+    /// don't have any status.
+    ModuleUnavailable,
+}
+
+/// Iterator that yields all the non-private rules together with their
+/// [`RuleStatus`].
+pub struct RuleStatuses<'a, 'r> {
+    ctx: &'a ScanContext<'r>,
+    data: &'a ScannedData<'a>,
+    matching_rules_bitmap: &'a BitSlice<u8, Lsb0>,
+    next_rule: usize,
+}
+
+impl<'a, 'r> RuleStatuses<'a, 'r> {
+    fn new(ctx: &'a ScanContext<'r>, data: &'a ScannedData<'a>) -> Self {
+        let num_rules = ctx.compiled_rules.num_rules();
+        let main_memory =
+            ctx.main_memory.unwrap().data(unsafe { ctx.wasm_store.as_ref() });
+
+        let base = MATCHING_RULES_BITMAP_BASE as usize;
+
+        let matching_rules_bitmap = &BitSlice::<_, Lsb0>::from_slice(
+            &main_memory[base..base + num_rules / 8 + 1],
+        )[0..num_rules];
+
+        Self { ctx, data, matching_rules_bitmap, next_rule: 0 }
+    }
+
+    fn status(&self, rule_id: RuleId, rule_info: &RuleInfo) -> RuleStatus {
+        if self.matching_rules_bitmap[usize::from(rule_id)] {
+            return RuleStatus::Matched;
+        }
+
+        if self.ctx.excluded_rules.contains(&rule_id) {
+            return RuleStatus::Skipped { reason: SkipReason::ExcludedByTags };
+        }
+
+        // Global rules are evaluated in the order they were declared,
+        // before any non-global rule in the same namespace. The global rule
+        // that didn't match and the global ones that precede it were
+        // evaluated, any other rule in the namespace was not.
+        if let Some(failed) =
+            self.ctx.failed_global_rules.get(&rule_info.namespace_id)
+        {
+            if !rule_info.is_global
+                || usize::from(rule_id) > usize::from(*failed)
+            {
+                return RuleStatus::Skipped { reason: SkipReason::GlobalRule };
+            }
+        }
+
+        let ident_pool = self.ctx.compiled_rules.ident_pool();
+
+        if rule_info.modules.iter().any(|module| {
+            self.ctx
+                .unavailable_modules
+                .contains(ident_pool.get(*module).unwrap())
+        }) {
+            return RuleStatus::Skipped {
+                reason: SkipReason::ModuleUnavailable,
+            };
+        }
+
+        RuleStatus::NotMatched
+    }
+}
+
+impl<'a, 'r> Iterator for RuleStatuses<'a, 'r> {
+    type Item = (Rule<'a, 'r>, RuleStatus);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rules = self.ctx.compiled_rules;
+        while self.next_rule < rules.num_rules() {
+            let rule_id = RuleId::from(self.next_rule);
+            let rule_info = rules.get(rule_id);
+            self.next_rule += 1;
+            if rule_info.is_private {
+                continue;
+            }
+            let status = self.status(rule_id, rule_info);
+            return Some((
+                Rule {
+                    rule_id,
+                    rule_info,
+                    rules,
+                    ctx: self.ctx,
+                    data: self.data,
+                },
+                status,
+            ));
+        }
+        None
+    }
+}
+
 /// Iterator that returns the outputs produced by YARA modules.
 pub struct ModuleOutputs<'a, 'r> {
     ctx: &'a ScanContext<'r>,
@@ -933,6 +1966,7 @@ impl<'a, 'r> Iterator for ModuleOutputs<'a, 'r> {
 pub struct Rule<'a, 'r> {
     ctx: &'a ScanContext<'r>,
     data: &'a ScannedData<'a>,
+    rule_id: RuleId,
     pub(crate) rules: &'r Rules,
     pub(crate) rule_info: &'r RuleInfo,
 }
@@ -948,13 +1982,42 @@ impl<'a, 'r> Rule<'a, 'r> {
         self.rules.ident_pool().get(self.rule_info.namespace_ident_id).unwrap()
     }
 
+    /// Returns true if an undefined value was found while evaluating the
+    /// rule's condition.
+    ///
+    /// This is always false unless the rules were compiled with
+    /// [`crate::Compiler::strict_undefined`] enabled.
+    pub fn has_evaluation_error(&self) -> bool {
+        self.ctx.undefined_rules.contains(&self.rule_id)
+    }
+
     /// Returns the metadata associated to this rule.
     pub fn metadata(&self) -> Metadata<'a, 'r> {
-        Metadata {
-            ctx: self.ctx,
-            iterator: self.rule_info.metadata.iter(),
-            len: self.rule_info.metadata.len(),
-        }
+        Metadata::new(self.rules, self.rule_info)
+    }
+
+    /// Returns the tags associated to this rule, in the order they were
+    /// declared.
+    pub fn tags(&self) -> impl ExactSizeIterator<Item = &'r str> {
+        let ident_pool = self.rules.ident_pool();
+        self.rule_info.tags.iter().map(|tag| ident_pool.get(*tag).unwrap())
+    }
+
+    /// Returns the rule's source code.
+    ///
+    /// The result is `None` unless the rules were compiled with
+    /// [`crate::Compiler::embed_sources`] enabled.
+    pub fn source(&self) -> Option<&'r str> {
+        self.rule_info.source.as_deref()
+    }
+
+    /// Returns the rule's doc comment.
+    ///
+    /// The result is `None` if the rule doesn't have a doc comment, or if
+    /// the rules were not compiled with
+    /// [`crate::Compiler::embed_doc_comments`] enabled.
+    pub fn doc_comment(&self) -> Option<&'r str> {
+        self.rule_info.doc_comment.as_deref()
     }
 
     /// Returns the patterns defined by this rule.
@@ -973,11 +2036,21 @@ impl<'a, 'r> Rule<'a, 'r> {
 /// The iterator returns (&str, [`MetaValue`]) pairs, where the first item
 /// is the identifier, and the second one the metadata value.
 pub struct Metadata<'a, 'r> {
-    ctx: &'a ScanContext<'r>,
+    rules: &'r Rules,
     iterator: Iter<'a, (IdentId, compiler::MetaValue)>,
     len: usize,
 }
 
+impl<'a, 'r: 'a> Metadata<'a, 'r> {
+    pub(crate) fn new(rules: &'r Rules, rule_info: &'r RuleInfo) -> Self {
+        Self {
+            rules,
+            iterator: rule_info.metadata.iter(),
+            len: rule_info.metadata.len(),
+        }
+    }
+}
+
 /// A metadata value.
 #[derive(Debug, PartialEq)]
 pub enum MetaValue<'r> {
@@ -990,36 +2063,43 @@ pub enum MetaValue<'r> {
     /// A valid UTF-8 string.
     String(&'r str),
     /// An arbitrary string. Used when the value contains invalid UTF-8
-    /// characters.
+    /// characters, or when it is written as a sequence of hex bytes (e.g:
+    /// `{ 01 02 FF }`).
     Bytes(&'r BStr),
 }
 
-impl<'a, 'r> Iterator for Metadata<'a, 'r> {
-    type Item = (&'r str, MetaValue<'r>);
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let (ident_id, value) = self.iterator.next()?;
-
-        let ident =
-            self.ctx.compiled_rules.ident_pool().get(*ident_id).unwrap();
-
-        let value = match value {
+impl<'r> MetaValue<'r> {
+    /// Creates a [`MetaValue`] from its compiled representation, resolving
+    /// string and bytes values with the literals pool in `rules`.
+    pub(crate) fn from_compiled(
+        rules: &'r Rules,
+        value: &compiler::MetaValue,
+    ) -> Self {
+        match value {
             compiler::MetaValue::Bool(b) => MetaValue::Bool(*b),
             compiler::MetaValue::Integer(i) => MetaValue::Integer(*i),
             compiler::MetaValue::Float(f) => MetaValue::Float(*f),
             compiler::MetaValue::String(id) => {
-                let s = self.ctx.compiled_rules.lit_pool().get(*id).unwrap();
+                let s = rules.lit_pool().get(*id).unwrap();
                 // We can be sure that s is a valid UTF-8 string, because
                 // the type of meta is MetaValue::String.
                 let s = unsafe { s.to_str_unchecked() };
                 MetaValue::String(s)
             }
-            compiler::MetaValue::Bytes(id) => MetaValue::Bytes(
-                self.ctx.compiled_rules.lit_pool().get(*id).unwrap(),
-            ),
-        };
+            compiler::MetaValue::Bytes(id) => {
+                MetaValue::Bytes(rules.lit_pool().get(*id).unwrap())
+            }
+        }
+    }
+}
 
-        Some((ident, value))
+impl<'a, 'r> Iterator for Metadata<'a, 'r> {
+    type Item = (&'r str, MetaValue<'r>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (ident_id, value) = self.iterator.next()?;
+        let ident = self.rules.ident_pool().get(*ident_id).unwrap();
+        Some((ident, MetaValue::from_compiled(self.rules, value)))
     }
 }
 
@@ -1074,6 +2154,14 @@ impl<'a, 'r> Pattern<'a, 'r> {
     }
 
     /// Returns the matches found for this pattern.
+    ///
+    /// Matches are returned in ascending order of starting offset,
+    /// regardless of the order in which they were found while scanning.
+    /// This doesn't have any additional cost, as matches are kept sorted
+    /// while they are being added. There's at most one match per starting
+    /// offset: when several matches start at the same offset (for instance,
+    /// overlapping matches with different lengths, or matches of a `xor`
+    /// pattern with different keys) only one of them is reported.
     pub fn matches(&self) -> Matches<'a> {
         Matches {
             data: self.data,
@@ -1084,6 +2172,18 @@ impl<'a, 'r> Pattern<'a, 'r> {
                 .map(|matches| matches.iter()),
         }
     }
+
+    /// Returns the total number of matches found for this pattern.
+    ///
+    /// This is the value of `#a` in the rule's condition, and it can be
+    /// larger than the number of matches returned by [`Pattern::matches`]
+    /// when [`Scanner::set_match_sample_limit`] is used.
+    pub fn count(&self) -> usize {
+        self.ctx
+            .pattern_matches
+            .get(self.pattern_id)
+            .map_or(0, |matches| matches.total_len())
+    }
 }
 
 /// Iterator that returns the matches for a pattern.
@@ -1122,6 +2222,9 @@ impl<'a> Match<'a> {
 
     /// XOR key used for decrypting the data if the pattern had the `xor`
     /// modifier, or `None` if otherwise.
+    ///
+    /// A pattern never has more than one match at the same offset, as the
+    /// key is fully determined by the data at that offset.
     #[inline]
     pub fn xor_key(&self) -> Option<u8> {
         self.inner.xor_key
@@ -0,0 +1,222 @@
+/*! Scanning of continuous streams of data.
+
+This module implements [`StreamScanner`], a wrapper around [`Scanner`] that
+receives the data in chunks and uses a bounded amount of memory, regardless
+of the total size of the stream, and [`WindowScanner`], which scans
+overlapping windows provided by the caller without reporting the same match
+twice.
+*/
+
+use std::ops::Range;
+
+use rustc_hash::FxHashSet;
+
+use crate::compiler::Rules;
+use crate::scanner::{ScanError, Scanner};
+
+/// A pattern match found by [`StreamScanner`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StreamMatch<'r> {
+    /// Namespace of the rule that matched.
+    pub namespace: &'r str,
+    /// Identifier of the rule that matched.
+    pub rule: &'r str,
+    /// Identifier of the pattern that matched (e.g. `$a`).
+    pub pattern: &'r str,
+    /// Range of the match, relative to the start of the stream.
+    pub range: Range<u64>,
+}
+
+/// Scans a continuous stream of data that is received in chunks.
+///
+/// Each chunk passed to [`StreamScanner::push`] is scanned together with
+/// the last `max_pattern_len - 1` bytes of the data received before it,
+/// which means that patterns that are at most `max_pattern_len` bytes long
+/// are found even if they are split across multiple chunks. Only those
+/// trailing bytes are retained between calls, so the memory used by the
+/// scanner doesn't depend on the size of the stream.
+///
+/// The condition of each rule is evaluated independently for every chunk,
+/// with only the data in the current window (the retained bytes followed by
+/// the new chunk) in view. Patterns that matched in previous windows are not
+/// taken into account, and conditions that depend on the whole data, like
+/// the ones using `filesize`, reading integers at fixed offsets with
+/// functions like `uint32(0)`, or using YARA modules, are not supported
+/// in streaming mode, and their results are undefined.
+///
+/// Matches are reported only once, even if they are contained in the
+/// bytes that are retained for the next window, and their ranges are
+/// relative to the start of the stream.
+pub struct StreamScanner<'r> {
+    scanner: Scanner<'r>,
+    window: Vec<u8>,
+    /// Offset within the stream where `window` starts.
+    window_offset: u64,
+    /// Number of bytes retained from one window to the next.
+    overlap: usize,
+}
+
+impl<'r> StreamScanner<'r> {
+    /// Creates a new stream scanner.
+    ///
+    /// `max_pattern_len` is the length of the longest match that can be
+    /// found across chunks. Matches longer than that are found only if at
+    /// most `max_pattern_len - 1` bytes of them are in previous chunks.
+    pub fn new(rules: &'r Rules, max_pattern_len: usize) -> Self {
+        Self {
+            scanner: Scanner::new(rules),
+            window: Vec::new(),
+            window_offset: 0,
+            overlap: max_pattern_len.saturating_sub(1),
+        }
+    }
+
+    /// Returns the underlying [`Scanner`], which can be used for changing
+    /// its settings, like the timeout or the values of global variables.
+    pub fn scanner(&mut self) -> &mut Scanner<'r> {
+        &mut self.scanner
+    }
+
+    /// Returns the total number of bytes pushed to the scanner so far.
+    pub fn stream_len(&self) -> u64 {
+        self.window_offset + self.window.len() as u64
+    }
+
+    /// Scans a new chunk of data, calling `callback` for every match of a
+    /// pattern that belongs to a matching rule.
+    pub fn push<F>(
+        &mut self,
+        chunk: &[u8],
+        mut callback: F,
+    ) -> Result<(), ScanError>
+    where
+        F: FnMut(&StreamMatch<'r>),
+    {
+        // Bytes at the start of the window that were already scanned in
+        // the previous call.
+        let retained = self.window.len();
+
+        self.window.extend_from_slice(chunk);
+
+        let results = self.scanner.scan(self.window.as_slice())?;
+
+        for rule in results.matching_rules() {
+            for pattern in rule.patterns() {
+                for m in pattern.matches() {
+                    let range = m.range();
+                    // Matches that end within the retained bytes were
+                    // reported by some previous call.
+                    if range.end <= retained {
+                        continue;
+                    }
+                    callback(&StreamMatch {
+                        namespace: rule.namespace(),
+                        rule: rule.identifier(),
+                        pattern: pattern.identifier(),
+                        range: self.window_offset + range.start as u64
+                            ..self.window_offset + range.end as u64,
+                    });
+                }
+            }
+        }
+
+        let discarded = self.window.len().saturating_sub(self.overlap);
+
+        self.window.drain(..discarded);
+        self.window_offset += discarded as u64;
+
+        Ok(())
+    }
+}
+
+/// Scans overlapping fixed-size windows of a stream, reporting each match
+/// only once.
+///
+/// This is useful when the caller already splits the data in windows of
+/// `window_size` bytes, where each window starts with the last `overlap`
+/// bytes of the previous one. Windows must be passed to
+/// [`WindowScanner::scan`] in order, and all of them except the last one
+/// must be exactly `window_size` bytes long.
+///
+/// Matches are identified by their range relative to the start of the
+/// stream, so a match that lies in the overlap between two windows, and
+/// therefore is found while scanning both of them, is reported only for
+/// the first window. As with [`StreamScanner`], conditions are evaluated
+/// independently for each window.
+pub struct WindowScanner<'r> {
+    scanner: Scanner<'r>,
+    /// Distance between the start of one window and the next one.
+    step: u64,
+    /// Offset within the stream where the next window starts.
+    window_offset: u64,
+    /// Matches already reported that could be found again in the next
+    /// window.
+    reported: FxHashSet<StreamMatch<'r>>,
+}
+
+impl<'r> WindowScanner<'r> {
+    /// Creates a new window scanner.
+    ///
+    /// # Panics
+    ///
+    /// If `overlap` is not less than `window_size`.
+    pub fn new(rules: &'r Rules, window_size: usize, overlap: usize) -> Self {
+        assert!(
+            overlap < window_size,
+            "overlap must be less than the window size"
+        );
+        Self {
+            scanner: Scanner::new(rules),
+            step: (window_size - overlap) as u64,
+            window_offset: 0,
+            reported: FxHashSet::default(),
+        }
+    }
+
+    /// Returns the underlying [`Scanner`], which can be used for changing
+    /// its settings, like the timeout or the values of global variables.
+    pub fn scanner(&mut self) -> &mut Scanner<'r> {
+        &mut self.scanner
+    }
+
+    /// Scans the next window, calling `callback` for every match of a
+    /// pattern that belongs to a matching rule, except those that were
+    /// already reported for a previous window.
+    pub fn scan<F>(
+        &mut self,
+        window: &[u8],
+        mut callback: F,
+    ) -> Result<(), ScanError>
+    where
+        F: FnMut(&StreamMatch<'r>),
+    {
+        let results = self.scanner.scan(window)?;
+
+        for rule in results.matching_rules() {
+            for pattern in rule.patterns() {
+                for m in pattern.matches() {
+                    let range = m.range();
+                    let m = StreamMatch {
+                        namespace: rule.namespace(),
+                        rule: rule.identifier(),
+                        pattern: pattern.identifier(),
+                        range: self.window_offset + range.start as u64
+                            ..self.window_offset + range.end as u64,
+                    };
+                    if !self.reported.contains(&m) {
+                        callback(&m);
+                        self.reported.insert(m);
+                    }
+                }
+            }
+        }
+
+        self.window_offset += self.step;
+
+        // Matches that start before the next window can't be found again.
+        let next_window = self.window_offset;
+        self.reported.retain(|m| m.range.start >= next_window);
+
+        Ok(())
+    }
+}
@@ -1,9 +1,19 @@
+use std::cell::RefCell;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
 use pretty_assertions::assert_eq;
 use protobuf::MessageDyn;
 use protobuf::{Message, MessageFull};
 
 use crate::mods;
-use crate::scanner::{MetaValue, Scanner};
+use crate::scanner::{
+    CapturedScanContext, EntropyCacheStats, MetaValue, RuleStatus, ScanError,
+    Scanner, SkipReason, StreamMatch, StreamScanner, WindowScanner,
+};
 use crate::variables::VariableError;
 
 #[test]
@@ -40,6 +50,33 @@ rule rule_4 { condition: false }
     assert!(iter.next().is_none());
 }
 
+#[test]
+fn non_matching_rules_with_private_rules() {
+    let rules = crate::compile(
+        r#"
+private rule rule_1 { condition: false }
+rule rule_2 { condition: rule_1 }
+private rule rule_3 { condition: false }
+rule rule_4 { condition: rule_3 }
+rule rule_5 { condition: true }
+"#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+    let results = scanner.scan(&[]).expect("scan should not fail");
+
+    let mut iter = results.non_matching_rules();
+
+    assert_eq!(iter.len(), 2);
+    assert_eq!(iter.next().unwrap().identifier(), "rule_2");
+    assert_eq!(iter.len(), 1);
+    assert_eq!(iter.next().unwrap().identifier(), "rule_4");
+    assert_eq!(iter.len(), 0);
+    assert!(iter.next().is_none());
+    assert_eq!(iter.len(), 0);
+}
+
 #[test]
 fn matches() {
     let rules = crate::compile(
@@ -150,6 +187,79 @@ fn xor_matches() {
     assert_eq!(matches, [("$a", 0..11, Some(1))])
 }
 
+#[test]
+fn xor_matches_by_offset() {
+    let rules = crate::compile(
+        r#"
+        rule test {
+            strings:
+                $a = "abc" xor(0-3) ascii wide
+            condition:
+                #a == 3
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut data = Vec::new();
+
+    data.extend(b"abc".map(|b| b ^ 1));
+    data.extend(b"-----");
+    data.extend(b"a\0b\0c\0".map(|b| b ^ 2));
+    data.extend(b"-----");
+    data.extend(b"abc");
+
+    let mut scanner = Scanner::new(&rules);
+    let scan_results = scanner.scan(&data).expect("scan should not fail");
+    let matching_rule = scan_results.matching_rules().next().unwrap();
+    let pattern = matching_rule.patterns().next().unwrap();
+
+    // Each offset produces a single match, with the key that decrypts the
+    // data at that offset.
+    assert_eq!(
+        pattern
+            .matches()
+            .map(|m| (m.range(), m.xor_key()))
+            .collect::<Vec<_>>(),
+        [(0..3, Some(1)), (8..14, Some(2)), (19..22, Some(0))]
+    );
+}
+
+#[test]
+fn matches_sorted_by_offset() {
+    let rules = crate::compile(
+        r#"
+        rule test {
+            strings:
+                $a = { 61 [0-16] 62 }
+                $b = /c.{0,16}d/
+            condition:
+                $a and $b
+        }
+        "#,
+    )
+    .unwrap();
+
+    let data = b"a--a-b--ab-c-c--cd-c--d----a----b--ccd";
+
+    let mut scanner = Scanner::new(&rules);
+    let scan_results = scanner.scan(data).expect("scan should not fail");
+    let matching_rule = scan_results.matching_rules().next().unwrap();
+
+    for pattern in matching_rule.patterns() {
+        let offsets: Vec<usize> =
+            pattern.matches().map(|m| m.range().start).collect();
+
+        assert!(offsets.len() > 3, "{:?}", offsets);
+        assert!(
+            offsets.windows(2).all(|w| w[0] < w[1]),
+            "matches for {} are not sorted: {:?}",
+            pattern.identifier(),
+            offsets
+        );
+    }
+}
+
 #[cfg(feature = "test_proto2-module")]
 #[test]
 fn reuse_scanner() {
@@ -441,6 +551,198 @@ fn global_rules() {
     assert!(non_matching.next().is_none());
 }
 
+#[test]
+fn pattern_diagnostics() {
+    let rules = crate::compile(
+        r#"
+        rule test {
+            strings:
+                $weak = "a" fullword
+                $strong = "hello"
+            condition:
+                any of them
+        }"#,
+    )
+    .unwrap();
+
+    let mut data = vec![b'a'; 1000];
+    data.extend_from_slice(b" a hello");
+
+    let mut scanner = Scanner::new(&rules);
+
+    // Diagnostics are not collected by default.
+    let results = scanner.scan(data.as_slice()).expect("scan should not fail");
+    assert_eq!(results.pattern_diagnostics().count(), 0);
+
+    scanner.collect_pattern_diagnostics(true);
+
+    let results = scanner.scan(data.as_slice()).expect("scan should not fail");
+    let diagnostics: Vec<_> = results.pattern_diagnostics().collect();
+
+    assert_eq!(diagnostics.len(), 2);
+
+    assert_eq!(diagnostics[0].rule, "test");
+    assert_eq!(diagnostics[0].pattern, "$weak");
+    assert_eq!(diagnostics[0].atom_hits, 1001);
+    assert_eq!(diagnostics[0].matches, 1);
+
+    assert_eq!(diagnostics[1].pattern, "$strong");
+    assert_eq!(diagnostics[1].atom_hits, 1);
+    assert_eq!(diagnostics[1].matches, 1);
+}
+
+#[test]
+fn content_icontains() {
+    let rules = crate::compile(
+        r#"
+        rule test {
+            condition:
+                content_icontains("hello")
+        }"#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+
+    scanner.collect_pattern_diagnostics(true);
+
+    let results = scanner.scan(b"...HeLLo...").expect("scan should not fail");
+
+    assert_eq!(results.matching_rules().len(), 1);
+
+    // The string is searched for with a synthetic pattern, which is found
+    // using its atoms like any other pattern.
+    let diagnostics: Vec<_> = results.pattern_diagnostics().collect();
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].pattern, "$");
+    assert_eq!(diagnostics[0].atom_hits, 1);
+    assert_eq!(diagnostics[0].matches, 1);
+}
+
+#[test]
+fn rule_statuses() {
+    let mut compiler = crate::Compiler::new();
+
+    compiler
+        .add_source(
+            r#"
+        global rule is_mz {
+            condition:
+                uint16(0) == 0x5A4D
+        }
+        rule gated {
+            condition:
+                true
+        }
+        "#,
+        )
+        .unwrap()
+        .new_namespace("other")
+        .add_source(
+            r#"
+        rule matching : foo {
+            condition:
+                true
+        }
+        rule non_matching : foo {
+            condition:
+                false
+        }
+        rule excluded : bar {
+            condition:
+                true
+        }
+        private rule hidden {
+            condition:
+                true
+        }
+        "#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+
+    scanner.exclude_tags(&["bar"]);
+
+    let results = scanner.scan(b"ELF").expect("scan should not fail");
+
+    let statuses: Vec<_> = results
+        .rule_statuses()
+        .map(|(rule, status)| (rule.identifier(), status))
+        .collect();
+
+    assert_eq!(
+        statuses,
+        [
+            ("is_mz", RuleStatus::NotMatched),
+            ("gated", RuleStatus::Skipped { reason: SkipReason::GlobalRule }),
+            ("matching", RuleStatus::Matched),
+            ("non_matching", RuleStatus::NotMatched),
+            (
+                "excluded",
+                RuleStatus::Skipped { reason: SkipReason::ExcludedByTags }
+            ),
+        ]
+    );
+
+    let results = scanner.scan(b"MZ").expect("scan should not fail");
+
+    let statuses: Vec<_> = results
+        .rule_statuses()
+        .map(|(rule, status)| (rule.identifier(), status))
+        .collect();
+
+    assert_eq!(statuses[0], ("is_mz", RuleStatus::Matched));
+    assert_eq!(statuses[1], ("gated", RuleStatus::Matched));
+}
+
+#[cfg(feature = "pe-module")]
+#[test]
+fn rule_statuses_module_unavailable() {
+    let rules = crate::compile(
+        r#"
+        import "pe"
+        rule is_dll { condition: pe.is_dll() }
+        rule not_pe { condition: not pe.is_pe }
+        rule no_module { condition: false }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+
+    // The data starts with `MZ` but it's not a valid PE file, the `pe`
+    // module reports it as malformed.
+    let results = scanner.scan(b"MZ").expect("scan should not fail");
+
+    let statuses: Vec<_> = results
+        .rule_statuses()
+        .map(|(rule, status)| (rule.identifier(), status))
+        .collect();
+
+    assert_eq!(
+        statuses,
+        [
+            (
+                "is_dll",
+                RuleStatus::Skipped { reason: SkipReason::ModuleUnavailable }
+            ),
+            ("not_pe", RuleStatus::Matched),
+            ("no_module", RuleStatus::NotMatched),
+        ]
+    );
+
+    // Data that is not a PE at all is not malformed.
+    let results = scanner.scan(b"ELF").expect("scan should not fail");
+
+    assert_eq!(
+        results.rule_statuses().next().map(|(_, status)| status),
+        Some(RuleStatus::NotMatched)
+    );
+}
+
 #[test]
 fn private_rules() {
     let mut compiler = crate::Compiler::new();
@@ -604,3 +906,989 @@ fn set_module_output() {
     let scan_results = scanner.scan(b"").expect("scan should not fail");
     assert_eq!(scan_results.matching_rules().len(), 1);
 }
+
+#[test]
+fn scan_owned() {
+    let rules = crate::compile(
+        r#"
+        rule foobar {
+            strings:
+                $a = "foobar"
+            condition:
+                $a and filesize == 12
+        }
+        "#,
+    )
+    .unwrap();
+
+    let data: Arc<[u8]> = Arc::from(b"xxxfoobarxxx".as_slice());
+    let mut scanner = Scanner::new(&rules);
+
+    let results =
+        scanner.scan_owned(data.clone()).expect("scan should not fail");
+
+    let rule = results.matching_rules().next().unwrap();
+    let pattern = rule.patterns().next().unwrap();
+    let m = pattern.matches().next().unwrap();
+
+    assert_eq!(rule.identifier(), "foobar");
+    assert_eq!(m.data(), b"foobar");
+    assert_eq!(m.range(), 3..9);
+
+    // The matching data points to the buffer owned by the `Arc`, which
+    // means that the data was not copied.
+    assert_eq!(m.data().as_ptr(), data[3..].as_ptr());
+
+    // Scanning a `String` produces the same results.
+    let results = scanner
+        .scan_owned(String::from("xxxfoobarxxx"))
+        .expect("scan should not fail");
+
+    assert_eq!(results.matching_rules().len(), 1);
+}
+
+#[test]
+fn scan_paths() {
+    let rules = crate::compile(
+        r#"
+        rule foo {
+            strings:
+                $a = "foo"
+            condition:
+                $a
+        }
+        "#,
+    )
+    .unwrap();
+
+    let dir = std::env::temp_dir()
+        .join(format!("yara-x-scan-paths-{}", std::process::id()));
+
+    fs::create_dir_all(&dir).unwrap();
+
+    let mut paths = Vec::new();
+
+    for i in 0..6 {
+        let path = dir.join(format!("file_{}", i));
+        let content = if i % 2 == 0 { "xxfooxx" } else { "xxbarxx" };
+        fs::write(&path, content).unwrap();
+        paths.push(path);
+    }
+
+    // This file doesn't exist, so it can't be read.
+    paths.push(dir.join("missing"));
+
+    let scanner = Scanner::new(&rules);
+    let results = Mutex::new(Vec::new());
+
+    scanner.scan_paths(paths.iter(), 3, |path, result| {
+        let result = result.map(|r| r.matching_rules().len());
+        results.lock().unwrap().push((path.to_path_buf(), result.ok()));
+    });
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    let mut results = results.into_inner().unwrap();
+    results.sort();
+
+    assert_eq!(
+        results,
+        [
+            (dir.join("file_0"), Some(1)),
+            (dir.join("file_1"), Some(0)),
+            (dir.join("file_2"), Some(1)),
+            (dir.join("file_3"), Some(0)),
+            (dir.join("file_4"), Some(1)),
+            (dir.join("file_5"), Some(0)),
+            (dir.join("missing"), None),
+        ]
+    );
+}
+
+#[test]
+fn exclude_tags() {
+    let rules = crate::compile(
+        r#"
+        rule stable : production { condition: true }
+        rule new : experimental beta { condition: true }
+        rule untagged { condition: true }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+
+    let matching = |scanner: &mut Scanner| {
+        scanner
+            .scan(b"")
+            .unwrap()
+            .matching_rules()
+            .map(|r| r.identifier().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(matching(&mut scanner), ["stable", "new", "untagged"]);
+
+    scanner.exclude_tags(&["experimental"]);
+    assert_eq!(matching(&mut scanner), ["stable", "untagged"]);
+
+    // Excluded rules are reported as non-matching.
+    let results = scanner.scan(b"").unwrap();
+    let non_matching = results.non_matching_rules().next().unwrap();
+
+    assert_eq!(non_matching.identifier(), "new");
+    assert_eq!(
+        non_matching.tags().collect::<Vec<_>>(),
+        ["experimental", "beta"]
+    );
+
+    scanner.exclude_tags(&[]);
+    assert_eq!(matching(&mut scanner), ["stable", "new", "untagged"]);
+
+    let rules = crate::compile(
+        r#"
+        rule a : experimental { condition: true }
+        rule b { condition: not a }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+
+    assert_eq!(matching(&mut scanner), ["a"]);
+
+    // Rules that use an excluded rule see it as `false`.
+    scanner.exclude_tags(&["experimental"]);
+    assert_eq!(matching(&mut scanner), ["b"]);
+}
+
+#[test]
+fn include_only_tags() {
+    let rules = crate::compile(
+        r#"
+        rule stable : production { condition: true }
+        rule new : experimental { condition: true }
+        rule untagged { condition: true }
+
+        global rule gate : experimental { condition: false }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+
+    let matching = |scanner: &mut Scanner| {
+        scanner
+            .scan(b"")
+            .unwrap()
+            .matching_rules()
+            .map(|r| r.identifier().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // The global rule `gate` doesn't match, so no rule matches.
+    assert!(matching(&mut scanner).is_empty());
+
+    // `gate` is excluded, so it doesn't affect the other rules.
+    scanner.include_only_tags(&["production"]);
+    assert_eq!(matching(&mut scanner), ["stable"]);
+
+    scanner.include_only_tags(&["production", "experimental"]);
+    assert!(matching(&mut scanner).is_empty());
+
+    // Both settings can be combined.
+    scanner.exclude_tags(&["experimental"]).include_only_tags(&[]);
+    assert_eq!(matching(&mut scanner), ["stable", "untagged"]);
+}
+
+#[test]
+fn dedup_by_rule_name() {
+    let mut compiler = crate::Compiler::new();
+
+    compiler
+        .new_namespace("first")
+        .add_source(r#"rule dup : first { condition: true }"#)
+        .unwrap()
+        .new_namespace("second")
+        .add_source(
+            r#"
+            rule dup : second { condition: true }
+            rule other { condition: false }
+            "#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+
+    assert_eq!(scanner.scan(b"").unwrap().matching_rules().len(), 2);
+
+    scanner.dedup_by_rule_name(true);
+
+    let results = scanner.scan(b"").unwrap();
+    let matching = results.matching_rules().collect::<Vec<_>>();
+
+    // Only the rule in the first namespace is reported, with its own tags.
+    assert_eq!(matching.len(), 1);
+    assert_eq!(matching[0].namespace(), "first");
+    assert_eq!(matching[0].identifier(), "dup");
+    assert_eq!(matching[0].tags().collect::<Vec<_>>(), ["first"]);
+
+    // The duplicate is not reported as non-matching either.
+    let non_matching = results.non_matching_rules();
+
+    assert_eq!(non_matching.len(), 1);
+    assert_eq!(
+        non_matching.map(|r| r.identifier()).collect::<Vec<_>>(),
+        ["other"]
+    );
+}
+
+#[test]
+fn prefilter_scan() {
+    let rules = crate::compile(
+        r#"
+        rule test_1 {
+          strings:
+            $a = "foobar"
+            $b = "missing"
+            $c = /ba[rz]{2}/ nocase
+          condition:
+            all of them
+        }
+        rule test_2 {
+          strings:
+            $a = { 01 02 [2-4] 05 06 }
+            $b = "secret" xor(1-10)
+            $c = "qux" wide
+          condition:
+            any of them
+        }
+        rule test_3 {
+          strings:
+            $a = "exe"
+            $b = "MZ"
+          condition:
+            $a at 2 or $b at 0
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut data = b"MZfoobar BARZ ".to_vec();
+    data.extend_from_slice(&[0x01, 0x02, 0xff, 0xff, 0xff, 0x05, 0x06]);
+    data.extend(b"secret".iter().map(|b| b ^ 5));
+    data.extend_from_slice(b" missin");
+
+    let mut scanner = Scanner::new(&rules);
+    let hits = scanner.prefilter_scan(data.as_slice());
+
+    assert!(!hits.is_empty());
+    assert!(!hits.contains("default", "test_1", "$b"));
+    assert!(!hits.contains("default", "test_2", "$c"));
+
+    let hits = hits
+        .iter()
+        .map(|hit| (hit.rule.to_string(), hit.pattern.to_string()))
+        .collect::<Vec<_>>();
+
+    let results = scanner.scan(data.as_slice()).unwrap();
+
+    let matches = results
+        .matching_rules()
+        .chain(results.non_matching_rules())
+        .flat_map(|rule| {
+            rule.patterns()
+                .filter(|pattern| pattern.count() > 0)
+                .map(|pattern| {
+                    (
+                        rule.identifier().to_string(),
+                        pattern.identifier().to_string(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(matches.len(), 5);
+
+    // Every pattern that matched had some atom hit.
+    for m in &matches {
+        assert!(hits.contains(m), "{:?} not in prefilter hits", m);
+    }
+}
+
+#[test]
+fn stream_scanner() {
+    let rules = crate::compile(
+        r#"
+        rule foobar {
+            strings:
+                $a = "foobarbaz"
+            condition:
+                $a
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = StreamScanner::new(&rules, 9);
+    let mut matches = Vec::new();
+
+    // The pattern is split across three chunks.
+    for chunk in [b"xxxxfoo".as_slice(), b"bar", b"bazxx", b"xxxxxxxxxxxx"] {
+        scanner
+            .push(chunk, |m| matches.push(m.clone()))
+            .expect("scan should not fail");
+    }
+
+    assert_eq!(
+        matches,
+        [StreamMatch {
+            namespace: "default",
+            rule: "foobar",
+            pattern: "$a",
+            range: 4..13,
+        }]
+    );
+
+    assert_eq!(scanner.stream_len(), 27);
+
+    // A second occurrence is reported with its offset in the stream.
+    matches.clear();
+
+    scanner.push(b"foobarbaz", |m| matches.push(m.clone())).unwrap();
+
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].range, 27..36);
+}
+
+#[test]
+fn window_scanner() {
+    let rules = crate::compile(
+        r#"
+        rule foobar {
+            strings:
+                $a = "foobar"
+            condition:
+                $a
+        }
+        "#,
+    )
+    .unwrap();
+
+    let data = b"xxxxxxxxfoobarxxxxxxfoobar";
+    let mut scanner = WindowScanner::new(&rules, 16, 8);
+    let mut matches = Vec::new();
+
+    // The windows start at offsets 0, 8 and 16. The first occurrence of
+    // the pattern lies in the overlap between the first two windows.
+    for window in [&data[0..16], &data[8..24], &data[16..]] {
+        scanner
+            .scan(window, |m| matches.push(m.range.clone()))
+            .expect("scan should not fail");
+    }
+
+    assert_eq!(matches, [8..14, 20..26]);
+}
+
+#[test]
+fn scan_head_tail() {
+    let rules = crate::compile(
+        r#"
+        rule head { strings: $a = "HEAD" condition: $a at 0 }
+        rule middle { strings: $a = "MIDDLE" condition: $a }
+        rule tail { strings: $a = "TAIL" condition: $a at filesize - 4 }
+        rule size { condition: filesize == 1000 }
+        "#,
+    )
+    .unwrap();
+
+    let mut data = vec![0_u8; 1000];
+
+    data[0..4].copy_from_slice(b"HEAD");
+    data[500..506].copy_from_slice(b"MIDDLE");
+    data[996..1000].copy_from_slice(b"TAIL");
+
+    let mut scanner = Scanner::new(&rules);
+
+    assert_eq!(
+        scanner
+            .scan_head_tail(data.as_slice(), 100, 100)
+            .unwrap()
+            .matching_rules()
+            .map(|r| r.identifier())
+            .collect::<Vec<_>>(),
+        ["head", "tail", "size"]
+    );
+
+    // The head and the tail cover the whole data.
+    assert_eq!(
+        scanner
+            .scan_head_tail(data.as_slice(), 600, 600)
+            .unwrap()
+            .matching_rules()
+            .map(|r| r.identifier())
+            .collect::<Vec<_>>(),
+        ["head", "middle", "tail", "size"]
+    );
+}
+
+#[test]
+fn scan_with_holes() {
+    let rules = crate::compile(
+        r#"
+        rule zeroes {
+            strings:
+                $a = { 00 00 00 00 00 00 00 00 }
+            condition:
+                $a
+        }
+        rule foobar {
+            strings:
+                $a = "foobar"
+            condition:
+                $a
+        }
+        rule read_in_hole {
+            condition:
+                not defined uint32(8)
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut data = vec![0_u8; 32];
+    data[24..30].copy_from_slice(b"foobar");
+
+    let mut scanner = Scanner::new(&rules);
+
+    // Without holes the zero-filled region matches, and uint32(8) is defined.
+    let results = scanner.scan(data.as_slice()).expect("scan should not fail");
+    let matching: Vec<_> =
+        results.matching_rules().map(|r| r.identifier()).collect();
+
+    assert_eq!(matching, vec!["zeroes", "foobar"]);
+
+    // The holes cover most of the zeroes, so that no 8-bytes run of zeroes
+    // is left outside of them.
+    let results = scanner
+        .scan_with_holes(data.as_slice(), &[4..12, 12..20])
+        .expect("scan should not fail");
+
+    let matching: Vec<_> =
+        results.matching_rules().map(|r| r.identifier()).collect();
+
+    assert_eq!(matching, vec!["foobar", "read_in_hole"]);
+
+    // Holes don't persist across scans.
+    let results = scanner.scan(data.as_slice()).expect("scan should not fail");
+    assert_eq!(results.matching_rules().len(), 2);
+}
+
+#[test]
+fn matched_namespaces() {
+    let mut compiler = crate::Compiler::new();
+
+    compiler
+        .add_source(
+            r#"
+            rule foo_1 { condition: true }
+            rule foo_2 { condition: true }
+            rule foo_3 { condition: false }
+            "#,
+        )
+        .unwrap()
+        .new_namespace("bar")
+        .add_source(
+            r#"
+            rule bar_1 { condition: true }
+            private rule bar_2 { condition: true }
+            "#,
+        )
+        .unwrap()
+        .new_namespace("baz")
+        .add_source(
+            r#"
+            rule baz_1 { condition: false }
+            "#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+    let results = scanner.scan(&[]).expect("scan should not fail");
+
+    assert_eq!(
+        results.matched_namespaces().collect::<Vec<_>>(),
+        vec![("default", 2), ("bar", 1)]
+    );
+}
+
+#[cfg(feature = "pe-module")]
+#[test]
+fn strict_undefined() {
+    let src = r#"
+        import "pe"
+        rule pe_sections { condition: pe.number_of_sections > 5 }
+        rule pe_sections_and { condition: true and pe.number_of_sections > 5 }
+        rule pe_defined { condition: defined pe.number_of_sections }
+        rule not_pe { condition: filesize < 10 }
+        "#;
+
+    let mut compiler = crate::Compiler::new();
+    compiler.strict_undefined(true).add_source(src).unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+    let results = scanner.scan(b"not a PE").expect("scan should not fail");
+
+    let mut errors: Vec<_> = results
+        .non_matching_rules()
+        .chain(results.matching_rules())
+        .filter(|r| r.has_evaluation_error())
+        .map(|r| r.identifier())
+        .collect();
+
+    errors.sort();
+
+    assert_eq!(errors, vec!["pe_sections", "pe_sections_and"]);
+
+    // Without strict mode no rule has evaluation errors.
+    let rules = crate::compile(src).unwrap();
+    let mut scanner = Scanner::new(&rules);
+    let results = scanner.scan(b"not a PE").expect("scan should not fail");
+
+    assert!(!results.non_matching_rules().any(|r| r.has_evaluation_error()));
+}
+
+#[test]
+fn overlapping_matches() {
+    let rules = crate::compile(
+        r#"
+        rule literal_overlapping {
+            strings:
+                $a = "aa"
+            condition:
+                #a == 3 and @a[1] == 0 and @a[2] == 1 and @a[3] == 2 and
+                !a[1] == 2 and !a[3] == 2
+        }
+        rule literal_non_overlapping {
+            strings:
+                $a = "aa"
+            condition:
+                #a == 2 and @a[1] == 0 and @a[2] == 2 and not defined @a[3]
+        }
+        rule regexp_overlapping {
+            strings:
+                $a = /a+/
+            condition:
+                #a == 4 and !a[1] == 4 and @a[4] == 3 and !a[4] == 1
+        }
+        rule regexp_non_overlapping {
+            strings:
+                $a = /a+/
+            condition:
+                #a == 1 and @a[1] == 0 and !a[1] == 4
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+
+    let results = scanner.scan(b"aaaa").expect("scan should not fail");
+    let matching: Vec<_> =
+        results.matching_rules().map(|r| r.identifier()).collect();
+
+    assert_eq!(matching, vec!["literal_overlapping", "regexp_overlapping"]);
+
+    scanner.overlapping_matches(false);
+
+    let results = scanner.scan(b"aaaa").expect("scan should not fail");
+    let matching: Vec<_> =
+        results.matching_rules().map(|r| r.identifier()).collect();
+
+    assert_eq!(
+        matching,
+        vec!["literal_non_overlapping", "regexp_non_overlapping"]
+    );
+}
+
+#[test]
+fn match_sample_limit() {
+    let rules = crate::compile(
+        r#"
+        rule test {
+            strings:
+                $a = "a"
+            condition:
+                #a == 10 and @a[10] == 9
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+
+    scanner.set_match_sample_limit(3);
+
+    let scan_results = scanner.scan(b"aaaaaaaaaa").unwrap();
+    let rule = scan_results.matching_rules().next().unwrap();
+    let pattern = rule.patterns().next().unwrap();
+
+    assert_eq!(pattern.matches().count(), 3);
+    assert_eq!(pattern.count(), 10);
+}
+
+#[test]
+fn matching_rule_source() {
+    let src = r#"
+rule foo : bar {
+  strings:
+    $a = "foo"
+  condition:
+    $a
+}
+
+private rule baz { condition: true }
+"#;
+
+    let mut compiler = crate::Compiler::new();
+    compiler.embed_sources(true).add_source(src).unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+    let scan_results = scanner.scan(b"foo").unwrap();
+
+    assert_eq!(
+        scan_results.matching_rule_source("foo"),
+        Some("rule foo : bar {\n  strings:\n    $a = \"foo\"\n  condition:\n    $a\n}")
+    );
+
+    // Private rules are not reported as matching rules.
+    assert_eq!(scan_results.matching_rule_source("baz"), None);
+
+    // Sources survive serialization.
+    let rules = crate::Rules::deserialize(rules.serialize().unwrap()).unwrap();
+    let mut scanner = Scanner::new(&rules);
+    let scan_results = scanner.scan(b"foo").unwrap();
+
+    assert!(scan_results.matching_rule_source("foo").is_some());
+
+    // Without embedded sources the result is `None`.
+    let rules = crate::compile(src).unwrap();
+    let mut scanner = Scanner::new(&rules);
+    let scan_results = scanner.scan(b"foo").unwrap();
+
+    assert_eq!(scan_results.matching_rules().len(), 1);
+    assert_eq!(scan_results.matching_rule_source("foo"), None);
+}
+
+#[test]
+fn rule_doc_comment() {
+    let src = r#"
+/*
+  Detects files containing "foo".
+*/
+rule foo {
+  strings:
+    $a = "foo"
+  condition:
+    $a
+}
+
+/* This comment is not followed by a rule. */
+import "test_proto2"
+
+// Line comments are not doc comments.
+rule bar { condition: filesize > 0 }
+"#;
+
+    let mut compiler = crate::Compiler::new();
+    compiler.embed_doc_comments(true).add_source(src).unwrap();
+
+    // Doc comments survive serialization.
+    let rules =
+        crate::Rules::deserialize(compiler.build().serialize().unwrap())
+            .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+    let scan_results = scanner.scan(b"foo").unwrap();
+
+    let doc_comments: Vec<_> = scan_results
+        .matching_rules()
+        .map(|rule| (rule.identifier(), rule.doc_comment()))
+        .collect();
+
+    assert_eq!(
+        doc_comments,
+        [("foo", Some(r#"Detects files containing "foo"."#)), ("bar", None)]
+    );
+
+    // Without embedded doc comments the result is `None`.
+    let rules = crate::compile(src).unwrap();
+    let mut scanner = Scanner::new(&rules);
+    let scan_results = scanner.scan(b"foo").unwrap();
+
+    assert!(scan_results
+        .matching_rules()
+        .all(|rule| rule.doc_comment().is_none()));
+}
+
+#[test]
+#[cfg(feature = "math-module")]
+fn entropy_cache_stats() {
+    let rules = crate::compile(
+        r#"
+import "math"
+rule foo { condition: math.entropy(0, 4) == 2.0 }
+rule bar { condition: math.entropy(0, 4) > 1.0 and math.entropy(4, 2) == 0.0 }
+"#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+
+    assert_eq!(scanner.entropy_cache_stats(), EntropyCacheStats::default());
+    assert_eq!(scanner.scan(b"abcdee").unwrap().matching_rules().len(), 2);
+
+    // The entropy of the range 0..4 is computed once, and reused by the
+    // second rule.
+    assert_eq!(
+        scanner.entropy_cache_stats(),
+        EntropyCacheStats { hits: 1, misses: 2 }
+    );
+
+    // The cache doesn't survive across scans.
+    scanner.scan(b"abcdee").unwrap();
+
+    assert_eq!(
+        scanner.entropy_cache_stats(),
+        EntropyCacheStats { hits: 1, misses: 2 }
+    );
+}
+
+#[test]
+#[cfg(feature = "math-module")]
+fn entropy_cache_max_entries() {
+    let num_ranges = super::ENTROPY_CACHE_MAX_ENTRIES + 100;
+
+    // Both rules compute the entropy of more ranges than the cache can hold.
+    let rules = crate::compile(
+        format!(
+            r#"
+import "math"
+rule foo {{ condition: for all i in (0..{n}) : (math.entropy(i, 1) == 0.0) }}
+rule bar {{ condition: for all i in (0..{n}) : (math.entropy(i, 1) == 0.0) }}
+"#,
+            n = num_ranges - 1
+        )
+        .as_str(),
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+    let data = vec![0_u8; num_ranges];
+
+    assert_eq!(scanner.scan(&data).unwrap().matching_rules().len(), 2);
+
+    assert_eq!(
+        scanner.wasm_store.data().entropy_cache.borrow().len(),
+        super::ENTROPY_CACHE_MAX_ENTRIES
+    );
+
+    // The second rule finds in the cache only the ranges that fit in it.
+    assert_eq!(
+        scanner.entropy_cache_stats(),
+        EntropyCacheStats {
+            hits: super::ENTROPY_CACHE_MAX_ENTRIES,
+            misses: num_ranges + 100,
+        }
+    );
+}
+
+#[test]
+fn progress_callback() {
+    let rules = crate::compile(
+        r#"rule test { strings: $a = "foobar" condition: #a == 80 }"#,
+    )
+    .unwrap();
+
+    // 5 MiB of data with a match of `$a` every 64 KiB.
+    let mut data = vec![0_u8; 5 << 20];
+
+    for offset in (0..data.len()).step_by(64 << 10) {
+        data[offset..offset + 6].copy_from_slice(b"foobar");
+    }
+
+    let progress = RefCell::new(Vec::new());
+    let mut scanner = Scanner::new(&rules);
+
+    scanner.set_progress_callback(|processed, total| {
+        progress.borrow_mut().push((processed, total))
+    });
+
+    assert_eq!(scanner.scan(&data).unwrap().matching_rules().len(), 1);
+
+    drop(scanner);
+
+    let progress = progress.into_inner();
+    let total = data.len() as u64;
+
+    // The callback is invoked once per MiB, plus one final time.
+    assert_eq!(progress.len(), 5);
+    assert!(progress.iter().all(|(_, t)| *t == total));
+    assert!(progress.windows(2).all(|w| w[0].0 < w[1].0));
+    assert_eq!(progress.last(), Some(&(total, total)));
+}
+
+#[test]
+fn progress_callback_without_matches() {
+    let rules = crate::compile(
+        r#"rule test { strings: $a = "foobar" condition: $a }"#,
+    )
+    .unwrap();
+
+    // 5 MiB of data where `$a` is found only once, crossing the boundary
+    // between the fourth and fifth MiB.
+    let mut data = vec![0_u8; 5 << 20];
+    let len = data.len();
+
+    data[(4 << 20) - 3..(4 << 20) + 3].copy_from_slice(b"foobar");
+
+    let progress = RefCell::new(Vec::new());
+    let mut scanner = Scanner::new(&rules);
+
+    scanner.set_progress_callback(|processed, _| {
+        progress.borrow_mut().push(processed)
+    });
+
+    assert_eq!(scanner.scan(&data).unwrap().matching_rules().len(), 1);
+
+    drop(scanner);
+
+    // The progress is reported even if there are no matches.
+    assert_eq!(
+        progress.into_inner(),
+        [1 << 20, 2 << 20, 3 << 20, 4 << 20, len as u64]
+    );
+}
+
+#[test]
+fn cancellation_flag() {
+    // A condition that takes a very long time to evaluate.
+    let rules = crate::compile(
+        r#"
+        rule test {
+          condition:
+            for all i in (0..10000000000) : (i >= 0)
+        }"#,
+    )
+    .unwrap();
+
+    let flag = Arc::new(AtomicBool::new(false));
+    let mut scanner = Scanner::new(&rules);
+
+    scanner.set_cancellation_flag(flag.clone());
+
+    let setter = {
+        let flag = flag.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            flag.store(true, Ordering::Relaxed);
+        })
+    };
+
+    let start = Instant::now();
+
+    assert!(matches!(scanner.scan(b"").err(), Some(ScanError::Cancelled)));
+    assert!(start.elapsed() < Duration::from_secs(10));
+
+    setter.join().unwrap();
+
+    // While the flag remains set every scan is cancelled.
+    assert!(matches!(scanner.scan(b"").err(), Some(ScanError::Cancelled)));
+}
+
+#[test]
+fn capture_and_replay() {
+    let mut compiler = crate::Compiler::new();
+
+    compiler
+        .define_global("threshold", 0)
+        .unwrap()
+        .add_source(
+            r#"
+            import "time"
+            import "test_proto2"
+            rule test {
+              condition:
+                time.now() == 1000 and
+                threshold == 5 and
+                test_proto2.file_size == 3
+            }"#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+
+    scanner.set_current_time(1000).set_global("threshold", 5).unwrap();
+
+    assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 1);
+
+    let json = serde_json::to_string(&scanner.capture_scan_context()).unwrap();
+
+    let context: CapturedScanContext =
+        serde_json::from_str(json.as_str()).unwrap();
+
+    // A new scanner doesn't match the same data, neither the time nor the
+    // global are the same.
+    let mut scanner = Scanner::new(&rules);
+
+    assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 0);
+
+    // When replaying, the module output is the one produced when scanning
+    // the original data, even if the data passed to `replay` is different.
+    let results = scanner.replay(&context, b"").unwrap();
+    let matching: Vec<_> =
+        results.matching_rules().map(|rule| rule.identifier()).collect();
+
+    assert_eq!(matching, vec!["test"]);
+
+    // Replaying a context with an invalid module output fails, and the
+    // globals are not changed.
+    let mut invalid_context: CapturedScanContext =
+        serde_json::from_str(json.as_str()).unwrap();
+
+    invalid_context.module_outputs.push(("foo.Foo".to_string(), vec![]));
+
+    let mut scanner = Scanner::new(&rules);
+
+    assert!(matches!(
+        scanner.replay(&invalid_context, b"foo").err(),
+        Some(ScanError::UnknownModule { .. })
+    ));
+
+    scanner.set_current_time(1000);
+
+    assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 0);
+
+    // Replaying with rules where the global has a different type fails.
+    let mut compiler = crate::Compiler::new();
+
+    compiler
+        .define_global("threshold", "")
+        .unwrap()
+        .add_source(r#"rule test { condition: threshold == "5" }"#)
+        .unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+
+    assert!(matches!(
+        scanner.replay(&context, b"").err(),
+        Some(ScanError::VariableError(VariableError::InvalidType { .. }))
+    ));
+}
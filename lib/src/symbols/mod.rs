@@ -140,6 +140,15 @@ impl SymbolTable {
         self.map.insert(ident.into(), symbol)
     }
 
+    /// Removes a symbol from the symbol table, returning it if it was in
+    /// the table.
+    pub fn remove<I>(&mut self, ident: I) -> Option<Symbol>
+    where
+        I: AsRef<str>,
+    {
+        self.map.remove(ident.as_ref())
+    }
+
     /// Returns true if the symbol table already contains a symbol with
     /// the given identifier.
     #[inline]
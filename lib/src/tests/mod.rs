@@ -184,12 +184,31 @@ fn arithmetic_operations() {
     condition_true!("-0o10 == -8");
     condition_true!("0o100 == 64");
     condition_true!("0o755 == 493");
+    condition_true!("0b1010 == 10");
+    condition_true!("-0b11 == -3");
+    condition_true!("0x7FFFFFFFFFFFFFFF == 9223372036854775807");
+    condition_true!("0xFFFFFFFFFFFFFFFF == -1");
+    condition_true!("0o1777777777777777777777 == -1");
+    condition_true!(
+        "0b1111111111111111111111111111111111111111111111111111111111111111 == -1"
+    );
     condition_true!("1 + 2 + 3 == 6");
     condition_true!("2 - 1 - 1 == 0");
     condition_true!("2 * 3 * 4 == 24");
     condition_true!("5 \\ 2 \\ 2 == 1");
     condition_true!("7 \\ 2 \\ 2.0 == 1.5");
     condition_true!("7 % 4 % 2 == 1");
+    condition_true!("2KB == 2048");
+    condition_true!("10MB == 10485760");
+    condition_true!("2GB == 2147483648");
+    condition_true!("-1KB == -1024");
+    condition_true!("1MB == 1024KB");
+    condition_true!("1 + 0.5 == 1.5");
+    condition_true!("0.5 + 1 == 1.5");
+    condition_true!("2 * 1.5 == 3");
+    condition_true!("1 - 0.5 == 0.5");
+    condition_true!("1 \\ 2.0 == 0.5");
+    condition_true!("1 + 2 * 0.5 == 2.0");
 }
 
 #[test]
@@ -353,6 +372,68 @@ fn uintxx() {
     condition_false!("uint16(19) != 0", &data);
     condition_false!("uint32(17) == 0", &data);
     condition_false!("uint32(17) != 0", &data);
+
+    // With a default value, reads beyond the end of the data are not
+    // undefined anymore.
+    condition_false!("uint32(filesize) == 0", &data);
+    condition_true!("uint32(filesize, 0) == 0", &data);
+    condition_true!("uint32(0, 0) == 0x04030201", &data);
+    condition_true!("uint8(20, 1) == 1", &data);
+    condition_true!("uint16be(19, 0xffff) == 0xffff", &data);
+    condition_true!("int8(20, -128) == -128", &data);
+    // Defaults that are not constant are clamped to the range of the
+    // returned type.
+    condition_true!("uint8(20, filesize * 1000) == 255", &data);
+    condition_true!("uint16be(19, filesize - 1000) == 0", &data);
+    condition_true!("int32(-1, 5) == 5", &data);
+    condition_true!("int32be(17, 0) + 1 == 1", &data);
+}
+
+#[test]
+fn matches_range() {
+    let data = b"xx GET /index.html HTTP/1.1 POST /foo";
+
+    condition_true!(r#"matches_range(/GET \/[a-z]+/, 0, 1024)"#, data);
+    condition_true!(r#"matches_range(/GET \/[a-z]+/, 3, 8)"#, data);
+    condition_true!(r#"matches_range(/POST/, 28, 4)"#, data);
+    condition_false!(r#"matches_range(/GET \/[a-z]+/, 4, 1024)"#, data);
+    condition_false!(r#"matches_range(/GET \/[a-z]+/, 0, 7)"#, data);
+    condition_false!(r#"matches_range(/POST/, 0, 28)"#, data);
+    condition_false!(r#"matches_range(/GET/, 100, 10)"#, data);
+    condition_false!(r#"matches_range(/GET/, -1, 10)"#, data);
+}
+
+#[test]
+fn content_icontains() {
+    let data = b"xx GET /Index.HTML HTTP/1.1";
+
+    condition_true!(r#"content_icontains("index.html")"#, data);
+    condition_true!(r#"content_icontains("GET /INDEX")"#, data);
+    condition_true!(r#"content_icontains("")"#, data);
+    condition_false!(r#"content_icontains("index.htm ")"#, data);
+    condition_false!(r#"content_icontains("POST")"#, data);
+
+    rule_true!(
+        r#"
+        rule test {
+            strings:
+                $a = "HTTP"
+            condition:
+                content_icontains("get") and all of them
+        }"#,
+        data
+    );
+
+    rule_false!(
+        r#"
+        rule test {
+            strings:
+                $a = "HTTP"
+            condition:
+                content_icontains("post") or not $a
+        }"#,
+        data
+    );
 }
 
 #[test]
@@ -393,6 +474,15 @@ fn intxx() {
     condition_false!("int16(19) != 0", &data);
     condition_false!("int32(17) == 0", &data);
     condition_false!("int32(17) != 0", &data);
+
+    // Values with the most significant bit set are sign-extended.
+    let data = [0x80, 0x00, 0x00, 0x00];
+
+    condition_true!("int8(0) == -128", &data);
+    condition_true!("int16(0) == 0x80", &data);
+    condition_true!("int16be(0) == -32768", &data);
+    condition_true!("int32be(0) == -2147483648", &data);
+    condition_true!("uint16be(0) == 0x8000", &data);
 }
 
 #[test]
@@ -966,6 +1056,77 @@ fn hex_patterns() {
     );
 }
 
+#[test]
+fn hex_fragments() {
+    rule_true!(
+        r#"
+        fragment HEADER = { 4D 5A }
+        rule test {
+          strings:
+            $a = { HEADER 90 90 }
+          condition:
+            $a
+        }
+        "#,
+        &[0x4D, 0x5A, 0x90, 0x90]
+    );
+
+    rule_false!(
+        r#"
+        fragment HEADER = { 4D 5A }
+        rule test {
+          strings:
+            $a = { HEADER 90 90 }
+          condition:
+            $a
+        }
+        "#,
+        &[0x4D, 0x5B, 0x90, 0x90]
+    );
+
+    // Fragments can use other fragments, and can be used inside
+    // alternatives. They can also be declared after the rules that use
+    // them.
+    rule_true!(
+        r#"
+        rule test {
+          strings:
+            $a = { 00 ( FOO | BAR ) 00 }
+          condition:
+            #a == 2
+        }
+        fragment FOO = { 01 [1-2] BAR }
+        fragment BAR = { 02 03 }
+        "#,
+        &[0x00, 0x01, 0xFF, 0x02, 0x03, 0x00, 0x02, 0x03, 0x00]
+    );
+
+    // Fragments declared in one source can be used by the sources added
+    // afterwards.
+    let mut compiler = crate::Compiler::new();
+
+    compiler
+        .add_source("fragment MZ = { 4D 5A }")
+        .unwrap()
+        .new_namespace("other")
+        .add_source(
+            r#"rule test { strings: $a = { MZ [2] MZ } condition: $a }"#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = crate::Scanner::new(&rules);
+
+    assert_eq!(
+        scanner
+            .scan(&[0x4D, 0x5A, 0x00, 0x00, 0x4D, 0x5A])
+            .unwrap()
+            .matching_rules()
+            .len(),
+        1
+    );
+}
+
 #[test]
 fn regexp_patterns_1() {
     pattern_match!(r#"/abc/"#, b"abc", b"abc");
@@ -2920,6 +3081,46 @@ fn for_of() {
     );
 }
 
+#[test]
+fn single_anonymous_pattern() {
+    rule_true!(
+        r#"
+        rule test {
+          strings:
+            $ = "foo"
+          condition:
+            $
+        }
+        "#,
+        b"foobar"
+    );
+
+    rule_false!(
+        r#"
+        rule test {
+          strings:
+            $ = "foo"
+          condition:
+            $
+        }
+        "#,
+        b"bar"
+    );
+
+    rule_true!(
+        r#"
+        rule test {
+          strings:
+            $a = "bar"
+            $ = "foo"
+          condition:
+            $ at 0 and $a
+        }
+        "#,
+        b"foobar"
+    );
+}
+
 #[test]
 fn of() {
     condition_true!(r#"any of (false, true)"#);
@@ -3040,6 +3241,122 @@ fn of() {
     );
 }
 
+#[test]
+fn of_quantifiers() {
+    condition_true!(r#"2 to 3 of (true, true, false, true)"#);
+    condition_false!(r#"1 to 2 of (true, true, true)"#);
+    condition_false!(r#"2 to 3 of (true, false, false)"#);
+    condition_true!(r#"0 to 0 of (false, false)"#);
+    condition_true!(r#"75% of (true, true, true, false)"#);
+    condition_false!(r#"75% of (true, false, true, false)"#);
+    condition_true!(r#"for 2 to 3 i in (1..5) : (i > 3)"#);
+    condition_false!(r#"for 1 to 2 i in (1..5) : (i > 1)"#);
+
+    let rule = |quantifier: &str| {
+        format!(
+            r#"
+            rule test {{
+              strings:
+                $a = "foo"
+                $b = "bar"
+                $c = "baz"
+                $d = "qux"
+              condition:
+                {} of them
+            }}
+            "#,
+            quantifier
+        )
+    };
+
+    // Data where 0, 1, 2, 3 and 4 of the patterns match.
+    let data: [&[u8]; 5] =
+        [b"", b"foo", b"foo bar", b"foo bar baz", b"foo bar baz qux"];
+
+    for (matching, data) in data.iter().enumerate() {
+        test_rule!(rule("none").as_str(), data, matching == 0);
+        // 50% of 4 patterns is 2.
+        test_rule!(rule("50%").as_str(), data, matching >= 2);
+        // 75% of 4 patterns is 3.
+        test_rule!(rule("75%").as_str(), data, matching >= 3);
+        test_rule!(rule("2 to 4").as_str(), data, (2..=4).contains(&matching));
+        test_rule!(rule("1 to 2").as_str(), data, (1..=2).contains(&matching));
+    }
+}
+
+#[test]
+fn weightsum() {
+    rule_true!(
+        r#"
+        rule test {
+          strings:
+            $a = "foo" weight(3)
+            $b = "bar" weight(5)
+            $c = "baz" weight(10)
+          condition:
+            weightsum of them == 8
+        }
+        "#,
+        b"foo bar foo"
+    );
+
+    rule_true!(
+        r#"
+        rule test {
+          strings:
+            $a = "foo" weight(3)
+            $b = "bar" weight(5)
+            $c = "baz" weight(10)
+          condition:
+            weightsum of them == 0
+        }
+        "#,
+        b"qux"
+    );
+
+    rule_true!(
+        r#"
+        rule test {
+          strings:
+            $a1 = "foo" weight(3)
+            $a2 = /ba[rz]/ weight(-2)
+            $b = { 71 75 78 }
+          condition:
+            weightsum of ($a*) == 1 and weightsum of ($b) == 1
+        }
+        "#,
+        b"foo baz qux"
+    );
+
+    rule_false!(
+        r#"
+        rule test {
+          strings:
+            $a = "foo" weight(3)
+            $b = "bar" weight(5)
+            $c = "baz" weight(10)
+          condition:
+            weightsum of them > 10
+        }
+        "#,
+        b"foo bar"
+    );
+
+    rule_true!(
+        r#"
+        rule test {
+          strings:
+            $a = "foo" weight(3)
+            $b = "bar" weight(5)
+            $c = "baz" weight(10)
+          condition:
+            weightsum of them > 10
+        }
+        "#,
+        b"foo baz"
+    );
+}
+
 #[test]
 fn rule_reuse_1() {
     let rules = crate::compile(
@@ -3231,3 +3548,34 @@ fn test_defined_3() {
     condition_true!(r#"not test_proto3.bool_undef"#);
     condition_true!(r#"test_proto3.string_undef == """#);
 }
+
+#[test]
+fn test_defined_4() {
+    let rule = |condition: &str| {
+        format!(
+            r#"
+            rule test {{
+                strings:
+                    $a = "foo"
+                condition:
+                    {condition}
+            }}
+            "#
+        )
+    };
+
+    // `$a` matches once, so `@a[1]` and `!a[1]` are defined, while `@a[2]`
+    // and `!a[2]` are not.
+    rule_true!(rule("defined @a[1]").as_str(), b"xxfoo");
+    rule_false!(rule("defined @a[2]").as_str(), b"xxfoo");
+    rule_true!(rule("not defined @a[2]").as_str(), b"xxfoo");
+    rule_true!(rule("defined !a[1]").as_str(), b"xxfoo");
+    rule_false!(rule("defined !a[2]").as_str(), b"xxfoo");
+    rule_true!(rule("defined #a").as_str(), b"xxfoo");
+
+    // The undefined value doesn't propagate beyond `defined`.
+    rule_true!(rule("not defined @a[2] or @a[2] < 1000").as_str(), b"xxfoo");
+    rule_false!(rule("defined @a[2] and @a[2] < 1000").as_str(), b"xxfoo");
+    rule_true!(rule("defined @a[2] and @a[2] < 1000").as_str(), b"xxfoofoo");
+    rule_true!(rule("defined @a[1] and @a[1] == 2").as_str(), b"xxfoo");
+}
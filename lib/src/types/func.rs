@@ -45,6 +45,7 @@ use crate::types::{TypeValue, Value};
 /// foo() -> Option<()>            ->  foo@@u
 /// foo() -> Option<f32>           ->  foo@@fu
 /// foo() -> Option<(f64,f64)>     ->  foo@@ffu
+/// foo() -> Option<Range<i64>>    ->  foo@@Ru
 /// ```
 #[derive(Serialize, Deserialize)]
 pub(crate) struct MangledFnName(String);
@@ -83,6 +84,7 @@ impl MangledFnName {
                 "f" => TypeValue::Float(Value::Unknown),
                 "b" => TypeValue::Bool(Value::Unknown),
                 "s" => TypeValue::String(Value::Unknown),
+                "R" => TypeValue::Range,
                 "u" => TypeValue::Unknown,
                 _ => panic!("unexpected return type: `{}`", ret),
             }
@@ -27,6 +27,7 @@ pub(crate) enum Type {
     Bool,
     String,
     Regexp,
+    Range,
     Struct,
     Array,
     Map,
@@ -48,6 +49,7 @@ impl Debug for Type {
             Self::Bool => write!(f, "boolean"),
             Self::String => write!(f, "string"),
             Self::Regexp => write!(f, "regexp"),
+            Self::Range => write!(f, "range"),
             Self::Struct => write!(f, "struct"),
             Self::Array => write!(f, "array"),
             Self::Map => write!(f, "map"),
@@ -171,6 +173,7 @@ pub(crate) enum TypeValue {
     Bool(Value<bool>),
     String(Value<Rc<BString>>),
     Regexp(Option<Regexp>),
+    Range,
     Struct(Rc<Struct>),
     Array(Rc<Array>),
     Map(Rc<Map>),
@@ -190,6 +193,7 @@ impl TypeValue {
             TypeValue::Bool(value) => value.is_const(),
             TypeValue::String(value) => value.is_const(),
             TypeValue::Regexp(_) => false,
+            TypeValue::Range => false,
             TypeValue::Struct(_) => false,
             TypeValue::Array(_) => false,
             TypeValue::Map(_) => false,
@@ -209,6 +213,7 @@ impl TypeValue {
             (Self::Float(_), Self::Float(_)) => true,
             (Self::String(_), Self::String(_)) => true,
             (Self::Bool(_), Self::Bool(_)) => true,
+            (Self::Range, Self::Range) => true,
             (Self::Array(a), Self::Array(b)) => {
                 a.deputy().eq_type(&b.deputy())
             }
@@ -246,6 +251,7 @@ impl TypeValue {
             Self::Bool(_) => Type::Bool,
             Self::String(_) => Type::String,
             Self::Regexp(_) => Type::Regexp,
+            Self::Range => Type::Range,
             Self::Map(_) => Type::Map,
             Self::Struct(_) => Type::Struct,
             Self::Array(_) => Type::Array,
@@ -261,6 +267,7 @@ impl TypeValue {
             Self::Bool(_) => Self::Bool(Value::Unknown),
             Self::String(_) => Self::String(Value::Unknown),
             Self::Regexp(_) => Self::Regexp(None),
+            Self::Range => Self::Range,
             Self::Map(v) => Self::Map(v.clone()),
             Self::Struct(v) => Self::Struct(v.clone()),
             Self::Array(v) => Self::Array(v.clone()),
@@ -507,6 +514,7 @@ impl Debug for TypeValue {
                     write!(f, "regexp(unknown)")
                 }
             }
+            Self::Range => write!(f, "range"),
             Self::Map(_) => write!(f, "map"),
             Self::Struct(_) => write!(f, "struct"),
             Self::Array(_) => write!(f, "array"),
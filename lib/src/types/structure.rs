@@ -181,6 +181,13 @@ impl Struct {
         self.fields.get_index(index).map(|(_, v)| v)
     }
 
+    /// Returns an iterator that yields the name and value of each field, in
+    /// insertion order.
+    #[inline]
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &StructField)> {
+        self.fields.iter().map(|(name, field)| (name.as_str(), field))
+    }
+
     /// Get a field by name.
     #[inline]
     pub fn field_by_name(&self, name: &str) -> Option<&StructField> {
@@ -4,7 +4,8 @@ use walrus::ir::{Block, InstrSeqId};
 use walrus::ValType::{F64, I32, I64};
 use walrus::{FunctionBuilder, FunctionId, InstrSeqBuilder};
 
-use super::WasmSymbols;
+use super::host::HostFunction;
+use super::{wasmtime_to_walrus, WasmSymbols};
 
 macro_rules! global_var {
     ($module:ident, $name:ident, $ty:ident) => {
@@ -163,6 +164,7 @@ impl WasmModuleBuilder {
         global_var!(module, filesize, I64);
         global_var!(module, pattern_search_done, I32);
         global_var!(module, timeout_occurred, I32);
+        global_var!(module, rule_filter_enabled, I32);
 
         let (main_memory, _) =
             module.add_import_memory("yara_x", "main_memory", false, 1, None);
@@ -173,6 +175,7 @@ impl WasmModuleBuilder {
             filesize,
             pattern_search_done,
             timeout_occurred,
+            rule_filter_enabled,
             i64_tmp: module.locals.add(I64),
             i32_tmp: module.locals.add(I32),
             f64_tmp: module.locals.add(F64),
@@ -238,6 +241,22 @@ impl WasmModuleBuilder {
         self.wasm_exports.clone()
     }
 
+    /// Adds an import for a function implemented by the host application,
+    /// returning its identifier.
+    pub fn add_host_function(&mut self, func: &HostFunction) -> FunctionId {
+        let args: Vec<_> =
+            func.wasm_args().iter().map(wasmtime_to_walrus).collect();
+        let results: Vec<_> =
+            func.wasm_results().iter().map(wasmtime_to_walrus).collect();
+        let ty = self.module.types.add(args.as_slice(), results.as_slice());
+        let (func_id, _) = self.module.add_import_func(
+            HostFunction::MODULE,
+            func.mangled_name.as_str(),
+            ty,
+        );
+        func_id
+    }
+
     /// Configure the number of YARA that namespaces that will be put in each
     /// WASM function.
     pub fn namespaces_per_func(&mut self, n: usize) -> &mut Self {
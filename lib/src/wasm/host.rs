@@ -0,0 +1,252 @@
+/*! Functions implemented by the host application.
+
+These are functions added with [`crate::Compiler::add_function`], which
+are callable from rule conditions like any other built-in function, but
+are implemented by the application that embeds YARA-X.
+*/
+
+use std::fmt;
+use std::sync::Arc;
+
+use bstr::BString;
+use wasmtime::{Caller, FuncType, Linker, Val, ValType};
+
+use crate::scanner::ScanContext;
+use crate::wasm::string::RuntimeString;
+use crate::wasm::ENGINE;
+
+/// A value passed to, or returned by, a function added with
+/// [`crate::Compiler::add_function`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum FuncValue {
+    /// An integer value.
+    Integer(i64),
+    /// A float value.
+    Float(f64),
+    /// A boolean value.
+    Bool(bool),
+    /// A string value. Strings in YARA are not necessarily valid UTF-8.
+    String(BString),
+}
+
+/// A type that can be returned by a function added with
+/// [`crate::Compiler::add_function`].
+///
+/// This is implemented for `i64`, `f64`, `bool` and [`BString`], which
+/// correspond to the types `i`, `f`, `b` and `s` in the function's
+/// signature.
+pub trait FuncResult: Into<FuncValue> {
+    /// Character that represents this type in function signatures.
+    const TYPE: char;
+}
+
+impl FuncResult for i64 {
+    const TYPE: char = 'i';
+}
+
+impl FuncResult for f64 {
+    const TYPE: char = 'f';
+}
+
+impl FuncResult for bool {
+    const TYPE: char = 'b';
+}
+
+impl FuncResult for BString {
+    const TYPE: char = 's';
+}
+
+impl From<i64> for FuncValue {
+    fn from(value: i64) -> Self {
+        Self::Integer(value)
+    }
+}
+
+impl From<f64> for FuncValue {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<bool> for FuncValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<BString> for FuncValue {
+    fn from(value: BString) -> Self {
+        Self::String(value)
+    }
+}
+
+/// Type of the closures that implement host functions.
+pub(crate) type HostFn =
+    dyn Fn(&[FuncValue]) -> Option<FuncValue> + Send + Sync + 'static;
+
+/// A function implemented by the host application.
+#[derive(Clone)]
+pub(crate) struct HostFunction {
+    /// Function's mangled name (e.g: `reputation@s@iu`).
+    pub mangled_name: String,
+    /// Types of the function's arguments, one character per argument, as
+    /// they appear in the mangled name.
+    pub args: Vec<char>,
+    /// Type of the function's result, as it appears in the mangled name.
+    pub result: char,
+    /// The closure that implements the function.
+    pub func: Arc<HostFn>,
+}
+
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostFunction")
+            .field("mangled_name", &self.mangled_name)
+            .finish()
+    }
+}
+
+impl HostFunction {
+    /// Name of the WASM module where host functions are imported from.
+    pub const MODULE: &'static str = "yara_x_host";
+
+    /// Creates a new host function from its name and a signature of the
+    /// form `<arguments>@<return type>` (e.g: `si@b`). Returns `None` if
+    /// the signature is not valid.
+    pub fn new(
+        name: &str,
+        signature: &str,
+        func: Arc<HostFn>,
+    ) -> Option<Self> {
+        let (args, result) = signature.split_once('@')?;
+
+        let args: Vec<char> = args.chars().collect();
+        let mut result = result.chars();
+
+        let result = match (result.next(), result.next()) {
+            (Some(result), None) => result,
+            _ => return None,
+        };
+
+        if !args.iter().chain([&result]).all(|t| "ifbs".contains(*t)) {
+            return None;
+        }
+
+        Some(Self {
+            // The result of host functions is always allowed to be
+            // undefined, which is indicated by the trailing `u`.
+            mangled_name: format!("{name}@{signature}u"),
+            args,
+            result,
+            func,
+        })
+    }
+
+    /// Returns the WASM types for the function's arguments.
+    pub fn wasm_args(&self) -> Vec<ValType> {
+        self.args.iter().map(|t| Self::wasm_type(*t)).collect()
+    }
+
+    /// Returns the WASM types for the function's results. The result is
+    /// followed by a `i32` that indicates whether it is undefined.
+    pub fn wasm_results(&self) -> Vec<ValType> {
+        vec![Self::wasm_type(self.result), ValType::I32]
+    }
+
+    /// Defines this function in `linker`.
+    pub fn define(&self, linker: &mut Linker<ScanContext>) {
+        let func_type =
+            FuncType::new(&ENGINE, self.wasm_args(), self.wasm_results());
+
+        let host_fn = self.clone();
+
+        linker
+            .func_new(
+                Self::MODULE,
+                self.mangled_name.as_str(),
+                func_type,
+                move |mut caller: Caller<'_, ScanContext>,
+                      params: &[Val],
+                      results: &mut [Val]| {
+                    let args: Vec<FuncValue> = host_fn
+                        .args
+                        .iter()
+                        .zip(params)
+                        .map(|(t, param)| host_fn.arg(&mut caller, *t, param))
+                        .collect();
+
+                    match (host_fn.func)(args.as_slice()) {
+                        Some(value) => {
+                            results[0] = host_fn.result(&mut caller, value);
+                            results[1] = Val::I32(0);
+                        }
+                        None => {
+                            results[0] = Self::zero(host_fn.result);
+                            results[1] = Val::I32(1);
+                        }
+                    }
+
+                    Ok(())
+                },
+            )
+            .unwrap();
+    }
+
+    fn wasm_type(t: char) -> ValType {
+        match t {
+            'i' | 's' => ValType::I64,
+            'f' => ValType::F64,
+            'b' => ValType::I32,
+            _ => unreachable!(),
+        }
+    }
+
+    fn zero(t: char) -> Val {
+        match t {
+            'i' | 's' => Val::I64(0),
+            'f' => Val::F64(0),
+            'b' => Val::I32(0),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Converts an argument received from WASM into a [`FuncValue`].
+    fn arg(
+        &self,
+        caller: &mut Caller<'_, ScanContext>,
+        t: char,
+        param: &Val,
+    ) -> FuncValue {
+        match (t, param) {
+            ('i', Val::I64(v)) => FuncValue::Integer(*v),
+            ('f', Val::F64(v)) => FuncValue::Float(f64::from_bits(*v)),
+            ('b', Val::I32(v)) => FuncValue::Bool(*v != 0),
+            ('s', Val::I64(v)) => {
+                let s = RuntimeString::from_wasm(caller.data_mut(), *v);
+                FuncValue::String(s.as_bstr(caller.data()).to_owned())
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Converts the value returned by the host function into a value that
+    /// can be passed to WASM.
+    ///
+    /// The type of the value always matches the declared result type, this
+    /// is verified by [`crate::Compiler::add_function`].
+    fn result(
+        &self,
+        caller: &mut Caller<'_, ScanContext>,
+        value: FuncValue,
+    ) -> Val {
+        match (self.result, value) {
+            ('i', FuncValue::Integer(v)) => Val::I64(v),
+            ('f', FuncValue::Float(v)) => Val::F64(v.to_bits()),
+            ('b', FuncValue::Bool(v)) => Val::I32(v as i32),
+            ('s', FuncValue::String(v)) => Val::I64(
+                RuntimeString::new(v).into_wasm_with_ctx(caller.data_mut()),
+            ),
+            _ => unreachable!(),
+        }
+    }
+}
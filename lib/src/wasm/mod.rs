@@ -79,6 +79,7 @@ See the [`lookup_field`] function.
  */
 use std::any::{type_name, TypeId};
 use std::mem;
+use std::ops::Range;
 use std::rc::Rc;
 
 use bstr::{BString, ByteSlice};
@@ -102,6 +103,7 @@ use crate::wasm::string::RuntimeString;
 use crate::ScanError;
 
 pub(crate) mod builder;
+pub(crate) mod host;
 pub(crate) mod string;
 
 /// Offset in module's main memory where the space for loop variables start.
@@ -502,6 +504,19 @@ impl WasmResult for Rc<Struct> {
     }
 }
 
+/// Ranges are returned as their lower and upper bounds. In YARA the upper
+/// bound is inclusive (e.g: `(0..10)` includes 10), while in Rust it is
+/// exclusive, so the upper bound is decremented by one.
+impl WasmResult for Range<i64> {
+    fn values(self, _: &mut ScanContext) -> WasmResultArray<ValRaw> {
+        smallvec![ValRaw::i64(self.start), ValRaw::i64(self.end - 1)]
+    }
+
+    fn types() -> WasmResultArray<wasmtime::ValType> {
+        smallvec![wasmtime::ValType::I64, wasmtime::ValType::I64]
+    }
+}
+
 impl<A, B> WasmResult for (A, B)
 where
     A: WasmResult,
@@ -697,6 +712,11 @@ pub(crate) struct WasmSymbols {
     /// phase.
     pub timeout_occurred: walrus::GlobalId,
 
+    /// Global variable that is set to true when some rules must be skipped
+    /// because of the tags set with [`crate::Scanner::exclude_tags`] or
+    /// [`crate::Scanner::include_only_tags`].
+    pub rule_filter_enabled: walrus::GlobalId,
+
     /// Local variables used for temporary storage.
     pub i64_tmp: walrus::LocalId,
     pub i32_tmp: walrus::LocalId,
@@ -753,14 +773,15 @@ pub(crate) fn log_rule_eval_start(
 
 /// Invoked from WASM for triggering the pattern search phase.
 ///
-/// Returns `true` on success and `false` when a timeout occurs.
+/// Returns `true` on success and `false` when a timeout occurs or the scan
+/// is cancelled.
 #[wasm_export]
 pub(crate) fn search_for_patterns(
     caller: &mut Caller<'_, ScanContext>,
 ) -> bool {
     match caller.data_mut().search_for_patterns() {
         Ok(_) => true,
-        Err(ScanError::Timeout) => false,
+        Err(ScanError::Timeout | ScanError::Cancelled) => false,
         Err(_) => unreachable!(),
     }
 }
@@ -774,6 +795,27 @@ pub(crate) fn rule_match(
     caller.data_mut().track_rule_match(rule_id);
 }
 
+/// Invoked from WASM to notify that an undefined value was found while
+/// evaluating the condition of a rule. Only used when the rules were compiled
+/// with [`crate::Compiler::strict_undefined`] enabled.
+#[wasm_export]
+pub(crate) fn rule_undef(
+    caller: &mut Caller<'_, ScanContext>,
+    rule_id: RuleId,
+) {
+    caller.data_mut().undefined_rules.insert(rule_id);
+}
+
+/// Invoked from WASM to ask whether a rule must be skipped. Only called
+/// when some rules are excluded because of their tags.
+#[wasm_export]
+pub(crate) fn rule_is_excluded(
+    caller: &mut Caller<'_, ScanContext>,
+    rule_id: RuleId,
+) -> bool {
+    caller.data().excluded_rules.contains(&rule_id)
+}
+
 /// Invoked from WASM to notify when a global rule doesn't match.
 #[wasm_export]
 pub(crate) fn global_rule_no_match(
@@ -1438,6 +1480,28 @@ pub(crate) fn str_matches(
     ctx.regexp_matches(rhs, lhs.as_bstr(ctx))
 }
 
+/// Returns true if the regular expression matches somewhere within the range
+/// of the scanned data that starts at `offset` and has `length` bytes.
+///
+/// If the range extends beyond the end of the data, only the portion of the
+/// range within the data is searched. The result is undefined if `offset`
+/// or `length` are negative, or if `offset` is beyond the end of the data.
+#[wasm_export(public = true)]
+pub(crate) fn matches_range(
+    caller: &mut Caller<'_, ScanContext>,
+    regexp_id: RegexpId,
+    offset: i64,
+    length: i64,
+) -> Option<bool> {
+    let ctx = caller.data();
+    let data = ctx.scanned_data();
+    let start = usize::try_from(offset).ok()?;
+    let length = usize::try_from(length).ok()?;
+    let end = start.saturating_add(length).min(data.len());
+    let range = data.get(start..end)?;
+    Some(ctx.regexp_matches(regexp_id, range))
+}
+
 macro_rules! gen_xint_fn {
     ($name:ident, $return_type:ty, $from_fn:ident) => {
         #[wasm_export(public = true)]
@@ -1446,15 +1510,39 @@ macro_rules! gen_xint_fn {
             offset: i64,
         ) -> Option<i64> {
             let offset = usize::try_from(offset).ok()?;
-            caller
-                .data()
-                .scanned_data()
-                .get(offset..offset + mem::size_of::<$return_type>())
-                .map_or(None, |bytes| {
-                    let value =
-                        <$return_type>::$from_fn(bytes.try_into().unwrap());
-                    Some(value as i64)
+            let range = offset..offset + mem::size_of::<$return_type>();
+            let ctx = caller.data();
+            if ctx.overlaps_hole(&range) {
+                return None;
+            }
+            ctx.scanned_data().get(range).map_or(None, |bytes| {
+                let value =
+                    <$return_type>::$from_fn(bytes.try_into().unwrap());
+                Some(value as i64)
+            })
+        }
+
+        // Overload that receives a default value that is returned when the
+        // read would be undefined. The function lives in a module with the
+        // same name because both must have the same name in YARA. Constant
+        // defaults that don't fit in the returned type are rejected by the
+        // compiler, other defaults are clamped to the type's range.
+        pub(crate) mod $name {
+            use super::*;
+
+            #[wasm_export(public = true)]
+            pub(crate) fn $name(
+                caller: &mut Caller<'_, ScanContext>,
+                offset: i64,
+                default: i64,
+            ) -> i64 {
+                super::$name(caller, offset).unwrap_or_else(|| {
+                    default.clamp(
+                        <$return_type>::MIN as i64,
+                        <$return_type>::MAX as i64,
+                    )
                 })
+            }
         }
     };
 }
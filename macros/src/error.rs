@@ -76,19 +76,102 @@ fn impl_enum_error_macro(
     // with #[error(...)] or #[warning(...)].
     let mut funcs = Vec::new();
     let mut variants = Vec::new();
+    let mut codes = Vec::new();
+    let mut all_codes = Vec::new();
+    let mut titles = Vec::new();
+    let mut spans = Vec::new();
     // For each variant in the enum...
     for variant in &data_enum.variants {
         // ...look for #[error(...)] or #[warning(...)] attributes.
         for attr in &variant.attrs {
             if let Some((attr_type, attr_args)) = parse_attr(attr)? {
                 variants.push(&variant.ident);
+                codes.push(gen_code_arm(variant));
+                all_codes.push(variant.ident.to_string().to_case(Case::Snake));
+                titles.push(gen_title_arm(&attr_args, variant));
+                spans.push(gen_span_arm(attr_type, variant)?);
                 funcs.push(gen_build_func(attr_type, attr_args, variant)?);
             }
         }
     }
+
+    funcs.push(quote!(
+        /// Returns a code that identifies the type of error or warning.
+        ///
+        /// The code is the name of the variant in snake-case (e.g.
+        /// `duplicate_tag` for `DuplicateTag`).
+        pub fn code(&self) -> &'static str {
+            match self {
+                #( #codes ),*
+            }
+        }
+
+        /// Returns the codes of all the errors or warnings of this type.
+        pub fn all_codes() -> &'static [&'static str] {
+            &[ #( #all_codes ),* ]
+        }
+
+        /// Returns the title of the error or warning, without the code
+        /// snippets and labels included in the detailed report.
+        #[allow(unused_variables)]
+        pub fn title(&self) -> String {
+            match self {
+                #( #titles ),*
+            }
+        }
+
+        /// Returns the span the error or warning refers to, which is the
+        /// span associated to its first label.
+        pub fn span(&self) -> Span {
+            match self {
+                #( #spans ),*
+            }
+        }
+    ));
+
     Ok((variants, funcs))
 }
 
+// Given an error or warning variant, generates the match arm that returns
+// the variant's code.
+fn gen_code_arm(variant: &Variant) -> TokenStream {
+    let variant_ident = &variant.ident;
+    let code = variant_ident.to_string().to_case(Case::Snake);
+    quote!(Self::#variant_ident { .. } => #code)
+}
+
+// Given an error or warning variant, generates the match arm that builds
+// the variant's title.
+fn gen_title_arm(attr_args: &AttrArgs, variant: &Variant) -> TokenStream {
+    let variant_ident = &variant.ident;
+    let field_identifiers =
+        variant.fields.iter().map(|field| field.ident.as_ref().unwrap());
+    quote!(
+        Self::#variant_ident { #( #field_identifiers ),* } => {
+            format!(#attr_args)
+        }
+    )
+}
+
+// Given an error or warning variant, generates the match arm that returns
+// the span of the variant's main label.
+fn gen_span_arm(
+    report_type: &str,
+    variant: &Variant,
+) -> syn::Result<TokenStream> {
+    let variant_ident = &variant.ident;
+    let labels = get_labels(report_type, variant)?;
+    let (main_label_span, _) = labels.first().ok_or_else(|| {
+        syn::Error::new_spanned(
+            variant,
+            "#[error(...)] must be accompanied by at least one instance of #[label(...)}",
+        )
+    })?;
+    Ok(quote!(
+        Self::#variant_ident { #main_label_span, .. } => *#main_label_span
+    ))
+}
+
 // Checks if an attribute is #[error(...)] and returns its arguments if that's
 // the case. Otherwise it returns None.
 fn parse_attr(
@@ -145,6 +145,16 @@ pub fn span_macro_derive(input: TokenStream) -> TokenStream {
 ///     tag: String,
 ///     tag_span: Span) -> Error
 /// ```
+///
+/// Finally, the enum gets the following methods, which are useful for
+/// applications that need the individual pieces of each error or warning
+/// instead of the detailed report:
+///
+/// - `code()`: returns the name of the variant in snake-case (e.g.
+///   `duplicate_tag`), which identifies the type of error or warning.
+/// - `title()`: returns the title built from `#[error(...)]` or
+///   `#[warning(...)]` (e.g. "duplicate tag `foo`").
+/// - `span()`: returns the span associated to the first label.
 #[proc_macro_derive(Error, attributes(error, warning, label, note))]
 pub fn error_macro_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
@@ -161,6 +171,10 @@ pub fn error_macro_derive(input: TokenStream) -> TokenStream {
 /// to the module. The function can have any name, as long as it is marked with
 /// `#[module_main]`, but it's a good practice to name it `main`.
 ///
+/// Optionally, the function can receive a second argument of type
+/// `&mut Vec<ModuleError>`, where it can report the errors found while
+/// processing the data.
+///
 /// # Example
 ///
 /// ```text
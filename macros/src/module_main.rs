@@ -9,10 +9,24 @@ pub(crate) fn impl_module_main_macro(
 ) -> syn::Result<TokenStream> {
     let fn_name = &input.sig.ident;
 
+    // The main function can optionally receive a second argument where it
+    // can report errors. If it receives three arguments, the second one is
+    // the limits for the module's output, and the third one is for errors.
+    let call = match input.sig.inputs.len() {
+        3 => quote! { #fn_name(data, limits, errors) },
+        2 => quote! { #fn_name(data, errors) },
+        _ => quote! { #fn_name(data) },
+    };
+
     let main_stub = quote! {
         use protobuf::MessageDyn;
-        pub(crate) fn __main__(data: &[u8]) -> Box<dyn MessageDyn> {
-            Box::new(#fn_name(data))
+        #[allow(unused_variables, clippy::ptr_arg)]
+        pub(crate) fn __main__(
+            data: &[u8],
+            limits: &crate::modules::ModuleLimits,
+            errors: &mut Vec<crate::modules::ModuleError>,
+        ) -> Box<dyn MessageDyn> {
+            Box::new(#call)
         }
     };
 
@@ -39,6 +39,7 @@ impl<'ast> FuncSignatureParser<'ast> {
             "RuntimeString" => Ok(Cow::Borrowed("s")),
             "RuntimeObjectHandle" => Ok(Cow::Borrowed("i")),
             "Rc" => Ok(Cow::Borrowed("i")),
+            "Range" => Ok(Cow::Borrowed("R")),
             type_ident => Err(syn::Error::new_spanned(
                 type_path,
                 format!(
@@ -305,5 +306,11 @@ mod tests {
         };
 
         assert_eq!(parser.parse(&func).unwrap(), "@@is");
+
+        let func = parse_quote! {
+          fn foo(caller: &mut Caller<'_, ScanContext>) -> Option<Range<i64>> { None }
+        };
+
+        assert_eq!(parser.parse(&func).unwrap(), "@@Ru");
     }
 }
@@ -217,6 +217,13 @@ pub(crate) fn expr_ascii_tree(expr: &Expr) -> Tree {
                             ),
                         ],
                     ),
+                    MatchAnchor::InExpr(anchor_in) => Node(
+                        format!("{} in <expr>", s.identifier.name),
+                        vec![Node(
+                            "<expr>".to_string(),
+                            vec![expr_ascii_tree(&anchor_in.expr)],
+                        )],
+                    ),
                 }
             } else {
                 Leaf(vec![s.identifier.name.to_string()])
@@ -290,6 +297,13 @@ pub(crate) fn expr_ascii_tree(expr: &Expr) -> Tree {
 
             Node(format!("<callable>({})", comma_sep_labels), children)
         }
+        Expr::WeightSum(w) => Node(
+            "weightsum of <items>".to_string(),
+            vec![Node(
+                "<items: pattern_set>".to_string(),
+                vec![pattern_set_ascii_tree(&w.pattern_set)],
+            )],
+        ),
         Expr::Of(of) => {
             let set_ascii_tree = match &of.items {
                 OfItems::PatternSet(set) => Node(
@@ -335,6 +349,13 @@ pub(crate) fn expr_ascii_tree(expr: &Expr) -> Tree {
                         "<quantifier> of <items> in (<start>..<end>)"
                             .to_string()
                     }
+                    MatchAnchor::InExpr(anchor_in) => {
+                        children.push(Node(
+                            "<expr>".to_string(),
+                            vec![expr_ascii_tree(&anchor_in.expr)],
+                        ));
+                        "<quantifier> of <items> in <expr>".to_string()
+                    }
                 }
             } else {
                 "<quantifier> of <items>".to_string()
@@ -436,6 +457,10 @@ pub(crate) fn quantifier_ascii_tree(quantifier: &Quantifier) -> Tree {
             Node("percentage".to_string(), vec![expr_ascii_tree(expr)])
         }
         Quantifier::Expr(expr) => expr_ascii_tree(expr),
+        Quantifier::Range { min, max, .. } => Node(
+            "range".to_string(),
+            vec![expr_ascii_tree(min), expr_ascii_tree(max)],
+        ),
     }
 }
 
@@ -471,6 +496,13 @@ pub(crate) fn pattern_ascii_tree(pattern: &Pattern) -> Tree {
     }
 }
 
+pub(crate) fn fragment_ascii_tree(fragment: &Fragment) -> Tree {
+    Node(
+        format!("fragment {}", fragment.identifier.name),
+        vec![hex_tokens_ascii_tree(&fragment.tokens)],
+    )
+}
+
 pub(crate) fn hex_tokens_ascii_tree(tokens: &HexTokens) -> Tree {
     let nodes = tokens
         .tokens
@@ -491,6 +523,9 @@ pub(crate) fn hex_tokens_ascii_tree(tokens: &HexTokens) -> Tree {
                 j.start.map_or("".to_string(), |v| v.to_string()),
                 j.end.map_or("".to_string(), |v| v.to_string())
             )]),
+            HexToken::Fragment(f) => {
+                Leaf(vec![format!("fragment {}", f.name)])
+            }
         })
         .collect();
 
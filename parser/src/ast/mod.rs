@@ -24,7 +24,7 @@ mod span;
 
 use std::borrow::Cow;
 use std::collections::btree_map::Values;
-use std::collections::{BTreeMap, HashSet};
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fmt::{Debug, Display, Formatter};
 use std::slice::Iter;
@@ -40,8 +40,12 @@ use crate::{SourceCode, Warnings};
 pub struct AST<'src> {
     /// The source code that produced this AST.
     pub source: SourceCode<'src>,
+    /// The list of includes.
+    pub includes: Vec<Include>,
     /// The list of imports.
     pub imports: Vec<Import>,
+    /// The list of fragments declared in the source code.
+    pub fragments: Vec<Fragment<'src>>,
     /// The list of rules in the AST.
     pub rules: Vec<Rule<'src>>,
     /// Warnings generated while building this AST.
@@ -66,10 +70,15 @@ impl<'src> Debug for AST<'src> {
 impl<'src> AST<'src> {
     /// Returns a printable ASCII tree representing the AST.
     pub fn ascii_tree(&self) -> ::ascii_tree::Tree {
+        use crate::ast::ascii_tree::fragment_ascii_tree;
         use crate::ast::ascii_tree::rule_ascii_tree;
         ::ascii_tree::Tree::Node(
             "root".to_string(),
-            self.rules.iter().map(rule_ascii_tree).collect(),
+            self.fragments
+                .iter()
+                .map(fragment_ascii_tree)
+                .chain(self.rules.iter().map(rule_ascii_tree))
+                .collect(),
         )
     }
 }
@@ -85,6 +94,13 @@ bitmask! {
     }
 }
 
+/// An include statement.
+#[derive(Debug, HasSpan)]
+pub struct Include {
+    pub span: Span,
+    pub file_name: String,
+}
+
 /// An import statement.
 #[derive(Debug, HasSpan)]
 pub struct Import {
@@ -93,14 +109,18 @@ pub struct Import {
 }
 
 /// A YARA rule.
-#[derive(Debug)]
+#[derive(Debug, HasSpan)]
 pub struct Rule<'src> {
+    pub span: Span,
     pub flags: RuleFlags,
     pub identifier: Ident<'src>,
-    pub tags: Option<HashSet<&'src str>>,
+    pub tags: Option<Vec<&'src str>>,
     pub meta: Option<Vec<Meta<'src>>>,
     pub patterns: Option<Vec<Pattern<'src>>>,
     pub condition: Expr<'src>,
+    /// Text of the block comment (`/* ... */`) that appears right before
+    /// the rule, without the delimiters and surrounding whitespaces.
+    pub doc_comment: Option<&'src str>,
 }
 
 /// A metadata entry in a YARA rule.
@@ -213,6 +233,11 @@ impl<'src> PatternModifiers<'src> {
     pub fn xor(&self) -> Option<&PatternModifier<'src>> {
         self.modifiers.get("xor")
     }
+
+    #[inline]
+    pub fn weight(&self) -> Option<&PatternModifier<'src>> {
+        self.modifiers.get("weight")
+    }
 }
 
 /// Iterator that returns all the modifiers in a [`PatternModifiers`].
@@ -241,6 +266,7 @@ pub enum PatternModifier<'src> {
     Base64 { span: Span, alphabet: Option<&'src str> },
     Base64Wide { span: Span, alphabet: Option<&'src str> },
     Xor { span: Span, start: u8, end: u8 },
+    Weight { span: Span, weight: i64 },
 }
 
 impl PatternModifier<'_> {
@@ -254,6 +280,7 @@ impl PatternModifier<'_> {
             PatternModifier::Base64 { .. } => "base64",
             PatternModifier::Base64Wide { .. } => "base64wide",
             PatternModifier::Xor { .. } => "xor",
+            PatternModifier::Weight { .. } => "weight",
         }
     }
 }
@@ -299,6 +326,9 @@ impl Display for PatternModifier<'_> {
                     write!(f, "xor({}-{})", start, end)
                 }
             }
+            PatternModifier::Weight { weight, .. } => {
+                write!(f, "weight({})", weight)
+            }
         }
     }
 }
@@ -330,8 +360,19 @@ pub struct HexPattern<'src> {
     pub modifiers: PatternModifiers<'src>,
 }
 
+/// A fragment declaration (e.g. `fragment HEADER = { 4D 5A }`).
+///
+/// Fragments are named sequences of hex tokens that can be referenced from
+/// hex patterns, or from other fragments.
+#[derive(Debug, HasSpan)]
+pub struct Fragment<'src> {
+    pub span: Span,
+    pub identifier: Ident<'src>,
+    pub tokens: HexTokens,
+}
+
 /// A sequence of tokens that conform a hex pattern (a.k.a hex string).
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct HexTokens {
     pub tokens: Vec<HexToken>,
 }
@@ -339,13 +380,15 @@ pub struct HexTokens {
 /// Each of the types of tokens in a hex pattern (a.k.a hex string).
 ///
 /// A token can be a single byte, a negated byte (e.g. `~XX`), an
-/// alternative (e.g `(XXXX|YYYY)`), or a jump (e.g `[0-10]`).
-#[derive(Debug)]
+/// alternative (e.g `(XXXX|YYYY)`), a jump (e.g `[0-10]`), or a reference
+/// to a fragment (e.g. `HEADER`).
+#[derive(Clone, Debug)]
 pub enum HexToken {
     Byte(HexByte),
     NotByte(HexByte),
     Alternative(Box<HexAlternative>),
     Jump(HexJump),
+    Fragment(Box<HexFragment>),
 }
 
 /// A single byte in a hex pattern (a.k.a hex string).
@@ -367,13 +410,23 @@ pub struct HexByte {
 /// An alternative in a hex pattern (a.k.a hex string).
 ///
 /// Alternatives are sequences of hex tokens separated by `|`.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct HexAlternative {
     pub alternatives: Vec<HexTokens>,
 }
 
+/// A reference to a fragment in a hex pattern (a.k.a hex string).
+///
+/// The AST doesn't replace the reference with the fragment's tokens, as
+/// the fragment can be declared in some other source file.
+#[derive(Clone, Debug, HasSpan)]
+pub struct HexFragment {
+    pub span: Span,
+    pub name: String,
+}
+
 /// A jump in a hex pattern (a.k.a hex string).
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct HexJump {
     pub start: Option<u16>,
     pub end: Option<u16>,
@@ -461,6 +514,10 @@ pub enum Expr<'src> {
     /// Pattern length expression (e.g. `!`, `!a`, `!a[1]`)
     PatternLength(Box<IdentWithIndex<'src>>),
 
+    /// Sum of the weights of the matching patterns in a set (e.g.
+    /// `weightsum of them`, `weightsum of ($a*, $b)`)
+    WeightSum(Box<WeightSum<'src>>),
+
     /// Array or dictionary lookup expression (e.g. `array[1]`, `dict["key"]`)
     Lookup(Box<Lookup<'src>>),
 
@@ -570,6 +627,13 @@ pub enum Expr<'src> {
     ForIn(Box<ForIn<'src>>),
 }
 
+/// A `weightsum` expression (e.g. `weightsum of them`).
+#[derive(Debug, HasSpan)]
+pub struct WeightSum<'src> {
+    pub span: Span,
+    pub pattern_set: PatternSet<'src>,
+}
+
 /// A pattern match expression (e.g. `$a`, `$b at 0`, `$c in (0..10)`).
 #[derive(Debug, HasSpan)]
 pub struct PatternMatch<'src> {
@@ -588,6 +652,7 @@ pub struct PatternMatch<'src> {
 pub enum MatchAnchor<'src> {
     At(Box<At<'src>>),
     In(Box<In<'src>>),
+    InExpr(Box<InExpr<'src>>),
 }
 
 /// In expressions like `$a at 0`, this type represents the anchor
@@ -614,6 +679,15 @@ pub struct In<'src> {
     pub range: Range<'src>,
 }
 
+/// In expressions like `$a in pe.section_range(0)`, this struct represents
+/// the anchor when the range is given by an expression of type range,
+/// instead of a pair of bounds.
+#[derive(Debug, HasSpan)]
+pub struct InExpr<'src> {
+    pub span: Span,
+    pub expr: Expr<'src>,
+}
+
 /// An identifier (e.g. `some_ident`).
 #[derive(Debug, Clone, HasSpan)]
 pub struct Ident<'src> {
@@ -880,6 +954,12 @@ pub enum Quantifier<'src> {
     Percentage(Expr<'src>),
     /// Used in expressions like `10 of them`.
     Expr(Expr<'src>),
+    /// Used in expressions like `2 to 4 of them`.
+    Range {
+        span: Span,
+        min: Expr<'src>,
+        max: Expr<'src>,
+    },
 }
 
 /// Possible iterable expressions that can use in a [`ForIn`].
@@ -28,6 +28,10 @@ pub(crate) struct Context<'src, 'rb> {
     /// the unused ones.
     pub(crate) unused_patterns: HashSet<&'src str>,
 
+    /// Number of anonymous patterns (i.e: `$ = ...`) declared by the rule
+    /// that is being currently parsed.
+    pub(crate) anonymous_patterns: usize,
+
     /// Boolean that indicates if the parser is currently inside the expression
     /// of a `for .. of .. : (<expr>)` statement.
     pub(crate) inside_for_of: bool,
@@ -40,17 +44,30 @@ pub(crate) struct Context<'src, 'rb> {
 
     /// Warnings generated during the parsing process.
     pub(crate) warnings: Warnings,
+
+    /// Current nesting depth of the expression being parsed. Each
+    /// parenthesized expression and each unary operator adds one level.
+    pub(crate) nesting_depth: usize,
+
+    /// Maximum allowed value for `nesting_depth`.
+    pub(crate) max_nesting_depth: usize,
 }
 
 impl<'src, 'rb> Context<'src, 'rb> {
-    pub(crate) fn new(report_builder: &'rb ReportBuilder) -> Self {
+    pub(crate) fn new(
+        report_builder: &'rb ReportBuilder,
+        max_nesting_depth: usize,
+    ) -> Self {
         Self {
             inside_for_of: false,
             declared_patterns: HashMap::new(),
             unused_patterns: HashSet::new(),
+            anonymous_patterns: 0,
             current_pattern: None,
             report_builder,
             warnings: Warnings::default(),
+            nesting_depth: 0,
+            max_nesting_depth,
         }
     }
 
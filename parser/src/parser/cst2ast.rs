@@ -216,6 +216,14 @@ lazy_static! {
             ("base64", vec![GrammarRule::string_lit]),
             ("base64wide", vec![GrammarRule::string_lit]),
             ("xor", vec![GrammarRule::string_lit]),
+            (
+                "weight",
+                vec![
+                    GrammarRule::string_lit,
+                    GrammarRule::regexp,
+                    GrammarRule::hex_pattern,
+                ],
+            ),
         ]);
 }
 
@@ -280,16 +288,53 @@ fn check_pattern_modifiers(
     Ok(())
 }
 
+/// Items declared at the top level of a source file: includes, imports,
+/// fragments and rules.
+type TopLevelItems<'src> =
+    (Vec<Include>, Vec<Import>, Vec<Fragment<'src>>, Vec<Rule<'src>>);
+
 pub(crate) fn ast_from_cst<'src>(
     ctx: &mut Context<'src, '_>,
     cst: CST<'src>,
-) -> Result<(Vec<Import>, Vec<Rule<'src>>), Error> {
+) -> Result<TopLevelItems<'src>, Error> {
+    let mut includes: Vec<Include> = Vec::new();
     let mut imports: Vec<Import> = Vec::new();
+    let mut fragments: Vec<Fragment> = Vec::new();
     let mut rules: Vec<Rule> = Vec::new();
 
+    // Last block comment found, if it was not followed by anything other
+    // than whitespaces.
+    let mut last_comment = None;
+
     for node in cst {
+        // Comments are returned only for the top level nodes, their
+        // children are traversed without them.
+        let node = node.comments(false);
+
         match node.as_rule() {
-            // Top level rules are either import statements...
+            // Block comments are kept, as they are the documentation for
+            // the rule that comes next, if any.
+            GrammarRule::COMMENT => {
+                last_comment = node
+                    .as_str()
+                    .strip_prefix("/*")
+                    .and_then(|comment| comment.strip_suffix("*/"))
+                    .map(|comment| comment.trim());
+                continue;
+            }
+            // Top level rules are either include statements...
+            GrammarRule::include_stmt => {
+                let span = ctx.span(&node);
+                let mut children = node.into_inner();
+                expect!(children.next().unwrap(), GrammarRule::k_INCLUDE);
+
+                let file_name =
+                    utf8_string_lit_from_cst(ctx, children.next().unwrap())?;
+
+                includes
+                    .push(Include { span, file_name: file_name.to_string() });
+            }
+            // ... import statements ...
             GrammarRule::import_stmt => {
                 let span = ctx.span(&node);
                 let mut children = node.into_inner();
@@ -303,9 +348,13 @@ pub(crate) fn ast_from_cst<'src>(
                     module_name: module_name.to_string(),
                 });
             }
+            // .. fragment declarations ...
+            GrammarRule::fragment_decl => {
+                fragments.push(fragment_from_cst(ctx, node)?);
+            }
             // .. or rule declarations.
             GrammarRule::rule_decl => {
-                rules.push(rule_from_cst(ctx, node)?);
+                rules.push(rule_from_cst(ctx, node, last_comment)?);
             }
             // The End Of Input (EOI) rule is ignored.
             GrammarRule::EOI => {}
@@ -313,8 +362,57 @@ pub(crate) fn ast_from_cst<'src>(
             // This should not be reached.
             rule => unreachable!("unexpected grammar rule: `{:?}`", rule),
         }
+
+        last_comment = None;
     }
-    Ok((imports, rules))
+    Ok((includes, imports, fragments, rules))
+}
+
+/// Given a CST node corresponding to the grammar rule `fragment_decl`,
+/// returns a [`Fragment`] structure describing the fragment.
+fn fragment_from_cst<'src>(
+    ctx: &mut Context<'src, '_>,
+    fragment_decl: CSTNode<'src>,
+) -> Result<Fragment<'src>, Error> {
+    expect!(fragment_decl, GrammarRule::fragment_decl);
+
+    let span = ctx.span(&fragment_decl);
+    let mut children = fragment_decl.into_inner();
+
+    expect!(children.next().unwrap(), GrammarRule::k_FRAGMENT);
+
+    let identifier = ident_from_cst(ctx, children.next().unwrap());
+
+    // Names formed only by hex digits, like `CAFE`, are interpreted as bytes
+    // when they appear in a hex pattern.
+    if identifier.name.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(Error::from(ErrorInfo::invalid_fragment_name(
+            ctx.report_builder,
+            identifier.name.to_string(),
+            identifier.span,
+            Some(format!(
+                "`{{ {} }}` is a sequence of bytes, not a reference to a fragment",
+                identifier.name
+            )),
+        )));
+    }
+
+    expect!(children.next().unwrap(), GrammarRule::EQUAL);
+
+    let mut hex_pattern = children.next().unwrap().into_inner();
+
+    expect!(hex_pattern.next().unwrap(), GrammarRule::LBRACE);
+
+    // Errors found while parsing the fragment's tokens refer to the fragment
+    // by its identifier, as if it was a pattern.
+    ctx.current_pattern = Some(identifier);
+
+    let tokens = hex_pattern_from_cst(ctx, hex_pattern.next().unwrap())?;
+    let identifier = ctx.current_pattern.take().unwrap();
+
+    expect!(hex_pattern.next().unwrap(), GrammarRule::RBRACE);
+
+    Ok(Fragment { span, identifier, tokens })
 }
 
 /// Given a CST node corresponding to the grammar rule` rule_decl`, returns a
@@ -322,9 +420,11 @@ pub(crate) fn ast_from_cst<'src>(
 fn rule_from_cst<'src>(
     ctx: &mut Context<'src, '_>,
     rule_decl: CSTNode<'src>,
+    doc_comment: Option<&'src str>,
 ) -> Result<Rule<'src>, Error> {
     expect!(rule_decl, GrammarRule::rule_decl);
 
+    let span = ctx.span(&rule_decl);
     let mut children = rule_decl.into_inner();
     let mut node = children.next().unwrap();
     let mut flags = RuleFlags::none();
@@ -370,7 +470,8 @@ fn rule_from_cst<'src>(
     // └─ ident "baz"
     //
     let tags = if let GrammarRule::rule_tags = node.as_rule() {
-        let mut tags = HashSet::new();
+        let mut tags = Vec::new();
+        let mut seen = HashSet::new();
 
         // Iterate over all `ident`s that are children of `rule_tags`,
         // ignoring other grammar rules like `COLON`.
@@ -379,13 +480,14 @@ fn rule_from_cst<'src>(
             .filter(|item| item.as_rule() == GrammarRule::ident);
 
         for ident in idents {
-            if !tags.insert(ident.as_str()) {
+            if !seen.insert(ident.as_str()) {
                 return Err(Error::from(ErrorInfo::duplicate_tag(
                     ctx.report_builder,
                     ident.as_str().to_string(),
                     ctx.span(&ident),
                 )));
             }
+            tags.push(ident.as_str());
         }
 
         node = children.next().unwrap();
@@ -449,6 +551,7 @@ fn rule_from_cst<'src>(
     // Clear `declared_patterns` so that the next call to `rule_from_cst`
     // finds it empty.
     ctx.declared_patterns.clear();
+    ctx.anonymous_patterns = 0;
 
     // The closing brace should come next.
     expect!(node, GrammarRule::RBRACE);
@@ -456,7 +559,16 @@ fn rule_from_cst<'src>(
     // Nothing more after the closing brace.
     assert!(children.next().is_none());
 
-    Ok(Rule { flags, identifier, tags, meta, patterns, condition })
+    Ok(Rule {
+        span,
+        flags,
+        identifier,
+        tags,
+        meta,
+        patterns,
+        condition,
+        doc_comment,
+    })
 }
 
 /// Given a CST node corresponding to the grammar rule` pattern_defs`, returns
@@ -494,6 +606,8 @@ fn patterns_from_cst<'src>(
                     existing_pattern_ident.span,
                 )));
             }
+        } else {
+            ctx.anonymous_patterns += 1;
         }
 
         // String identifiers are also stored in `unused_patterns`, they will
@@ -600,6 +714,22 @@ fn pattern_from_cst<'src>(
                 )));
             }
 
+            if looks_like_regexp(text.as_ref()) {
+                let report_builder = ctx.report_builder;
+                ctx.warnings.add(|| {
+                    Warning::literal_looks_like_regex(
+                        report_builder,
+                        span,
+                        Some(
+                            "if this is meant to be a regular expression, \
+                             enclose it in slashes instead of quotes (e.g: \
+                             /foo[0-9]/)"
+                                .to_string(),
+                        ),
+                    )
+                });
+            }
+
             // Take the identifier and set ctx.current_pattern
             // to None.
             let identifier = ctx.current_pattern.take().unwrap();
@@ -730,7 +860,9 @@ fn pattern_mods_from_cst<'src>(
                         let lower_bound_span = ctx.span(&node);
 
                         // Parse the integer after the opening parenthesis `(`.
-                        lower_bound = integer_lit_from_cst::<u8>(ctx, node)?;
+                        lower_bound = integer_lit_without_suffix_from_cst::<u8>(
+                            ctx, node,
+                        )?;
 
                         // See what comes next, it could be a hyphen `-` or the
                         // closing parenthesis `)`
@@ -743,10 +875,11 @@ fn pattern_mods_from_cst<'src>(
                             // If a hyphen follows, parse the integer after the
                             // hyphen.
                             GrammarRule::HYPHEN => {
-                                let integer = integer_lit_from_cst::<u8>(
-                                    ctx,
-                                    children.next().unwrap(),
-                                )?;
+                                let integer =
+                                    integer_lit_without_suffix_from_cst::<u8>(
+                                        ctx,
+                                        children.next().unwrap(),
+                                    )?;
                                 expect!(
                                     children.next().unwrap(),
                                     GrammarRule::RPAREN
@@ -774,6 +907,20 @@ fn pattern_mods_from_cst<'src>(
                     start: lower_bound,
                 }
             }
+            GrammarRule::k_WEIGHT => {
+                // The `weight` modifier is always followed by an integer
+                // between parenthesis. e.g: `weight(3)`.
+                expect!(children.next().unwrap(), GrammarRule::LPAREN);
+
+                let weight = integer_lit_without_suffix_from_cst::<i64>(
+                    ctx,
+                    children.next().unwrap(),
+                )?;
+
+                expect!(children.next().unwrap(), GrammarRule::RPAREN);
+
+                PatternModifier::Weight { span: ctx.span(&node), weight }
+            }
             rule @ (GrammarRule::k_BASE64 | GrammarRule::k_BASE64WIDE) => {
                 let mut alphabet = None;
                 if let Some(node) = children.peek() {
@@ -881,6 +1028,16 @@ fn meta_from_cst<'src>(
                     Cow::Owned(s) => MetaValue::Bytes(s),
                 }
             }
+            GrammarRule::meta_bytes => MetaValue::Bytes(
+                value_node
+                    .into_inner()
+                    .filter(|node| node.as_rule() == GrammarRule::meta_byte)
+                    // `meta_byte` has been verified by the grammar to be
+                    // exactly two hex digits.
+                    .map(|byte| u8::from_str_radix(byte.as_str(), 16).unwrap())
+                    .collect::<Vec<u8>>()
+                    .into(),
+            ),
             rule => unreachable!("{:?}", rule),
         };
 
@@ -959,6 +1116,34 @@ lazy_static! {
         .op(Op::infix(GrammarRule::DOT, Assoc::Left));
 }
 
+/// Calls `f` with the nesting depth increased by one level.
+///
+/// Returns [`ErrorInfo::TooDeeplyNested`] if the new depth exceeds the
+/// maximum allowed. `span` is the span of the parenthesis or operator that
+/// introduces the new level.
+fn nested<'src, 'rb, T, F>(
+    ctx: &mut Context<'src, 'rb>,
+    span: Span,
+    f: F,
+) -> Result<T, Error>
+where
+    F: FnOnce(&mut Context<'src, 'rb>) -> Result<T, Error>,
+{
+    if ctx.nesting_depth >= ctx.max_nesting_depth {
+        return Err(Error::from(ErrorInfo::too_deeply_nested(
+            ctx.report_builder,
+            ctx.max_nesting_depth,
+            span,
+        )));
+    }
+
+    ctx.nesting_depth += 1;
+    let result = f(ctx);
+    ctx.nesting_depth -= 1;
+
+    result
+}
+
 /// From a CST node corresponding to the grammar rule `boolean_expr`, returns
 /// an [`Expr`] describing the boolean expression.
 fn boolean_expr_from_cst<'src>(
@@ -1013,7 +1198,9 @@ fn boolean_term_from_cst<'src>(
 
             // The child after the `not` is the negated boolean term.
             let term = children.next().unwrap();
-            let expr = boolean_term_from_cst(ctx, term)?;
+            let expr = nested(ctx, ctx.span(&not), |ctx| {
+                boolean_term_from_cst(ctx, term)
+            })?;
 
             create_unary_expr(ctx, not, expr)?
         }
@@ -1023,16 +1210,20 @@ fn boolean_term_from_cst<'src>(
 
             // The child after the `defined` is the boolean term.
             let term = children.next().unwrap();
-            let expr = boolean_term_from_cst(ctx, term)?;
+            let expr = nested(ctx, ctx.span(&defined), |ctx| {
+                boolean_term_from_cst(ctx, term)
+            })?;
 
             create_unary_expr(ctx, defined, expr)?
         }
         GrammarRule::LPAREN => {
             // Consume the opening parenthesis.
-            children.next();
+            let lparen = children.next().unwrap();
 
             // The next node should be a boolean expression.
-            let expr = boolean_expr_from_cst(ctx, children.next().unwrap())?;
+            let expr = nested(ctx, ctx.span(&lparen), |ctx| {
+                boolean_expr_from_cst(ctx, children.next().unwrap())
+            })?;
 
             // The boolean expression must be followed by a closing
             // parenthesis.
@@ -1059,13 +1250,32 @@ fn boolean_term_from_cst<'src>(
                 }
                 ctx.unused_patterns.remove(&ident_name[1..]);
             }
-            // `$` used outside a `for .. of` statement, that's invalid.
+            // `$` used outside a `for .. of` statement is valid only if the
+            // rule has a single anonymous pattern, in which case `$` refers
+            // to that pattern.
             else if !ctx.inside_for_of {
-                return Err(Error::from(ErrorInfo::syntax_error(
-                    ctx.report_builder,
-                    "this `$` is outside of the condition of a `for .. of` statement".to_string(),
-                    ctx.span(&ident),
-                )));
+                match ctx.anonymous_patterns {
+                    0 => {
+                        return Err(Error::from(ErrorInfo::syntax_error(
+                            ctx.report_builder,
+                            "this `$` is outside of the condition of a `for .. of` statement".to_string(),
+                            ctx.span(&ident),
+                        )));
+                    }
+                    1 => {
+                        ctx.unused_patterns.remove("");
+                    }
+                    n => {
+                        return Err(Error::from(
+                            ErrorInfo::ambiguous_pattern_reference(
+                                ctx.report_builder,
+                                n,
+                                ctx.span(&ident),
+                                Some("give each pattern a unique identifier and use it instead of `$`".to_string()),
+                            ),
+                        ));
+                    }
+                }
             }
 
             Expr::PatternMatch(Box::new(PatternMatch {
@@ -1204,16 +1414,39 @@ fn primary_expr_from_cst<'src>(
         GrammarRule::k_ENTRYPOINT => {
             Expr::Entrypoint { span: ctx.span(&node) }
         }
-        GrammarRule::MINUS => {
-            let operand = term_from_cst(ctx, children.next().unwrap())?;
-            create_unary_expr(ctx, node, operand)?
+        GrammarRule::k_WEIGHTSUM => {
+            expect!(children.next().unwrap(), GrammarRule::k_OF);
+
+            let node = children.next().unwrap();
+
+            let pattern_set = match node.as_rule() {
+                GrammarRule::k_THEM => {
+                    // `them` was used in the condition, all the patterns are
+                    // used.
+                    ctx.unused_patterns.clear();
+                    PatternSet::Them { span: ctx.span(&node) }
+                }
+                GrammarRule::pattern_ident_tuple => {
+                    PatternSet::Set(pattern_ident_tuple(ctx, node)?)
+                }
+                rule => unreachable!("{:?}", rule),
+            };
+
+            Expr::WeightSum(Box::new(WeightSum {
+                span: term_span,
+                pattern_set,
+            }))
         }
-        GrammarRule::BITWISE_NOT => {
-            let operand = term_from_cst(ctx, children.next().unwrap())?;
+        GrammarRule::MINUS | GrammarRule::BITWISE_NOT => {
+            let operand = nested(ctx, ctx.span(&node), |ctx| {
+                term_from_cst(ctx, children.next().unwrap())
+            })?;
             create_unary_expr(ctx, node, operand)?
         }
         GrammarRule::LPAREN => {
-            let expr = expr_from_cst(ctx, children.next().unwrap())?;
+            let expr = nested(ctx, ctx.span(&node), |ctx| {
+                expr_from_cst(ctx, children.next().unwrap())
+            })?;
             expect!(children.next().unwrap(), GrammarRule::RPAREN);
             expr
         }
@@ -1571,11 +1804,20 @@ fn anchor_from_cst<'src>(
                 Some(MatchAnchor::At(Box::new(At { span, expr })))
             }
             GrammarRule::k_IN => {
-                let range = range_from_cst(ctx, iter.next().unwrap())?;
-                // The span of `in <range>` is the span of `in` combined with
-                // the span of `<range>`.
-                let span = ctx.span(&node).combine(&range.span());
-                Some(MatchAnchor::In(Box::new(In { span, range })))
+                let node_ = iter.next().unwrap();
+                if node_.as_rule() == GrammarRule::range {
+                    let range = range_from_cst(ctx, node_)?;
+                    // The span of `in <range>` is the span of `in` combined
+                    // with the span of `<range>`.
+                    let span = ctx.span(&node).combine(&range.span());
+                    Some(MatchAnchor::In(Box::new(In { span, range })))
+                } else {
+                    let expr = expr_from_cst(ctx, node_)?;
+                    // The span of `in <expr>` is the span of `in` combined
+                    // with the span of `<expr>`.
+                    let span = ctx.span(&node).combine(&expr.span());
+                    Some(MatchAnchor::InExpr(Box::new(InExpr { span, expr })))
+                }
             }
             rule => unreachable!("{:?}", rule),
         }
@@ -1593,13 +1835,24 @@ fn quantifier_from_cst<'src>(
 ) -> Result<Quantifier<'src>, Error> {
     expect!(quantifier, GrammarRule::quantifier);
 
-    let mut children = quantifier.into_inner();
+    let mut children = quantifier.into_inner().peekable();
     let node = children.next().unwrap();
 
     let quantifier = match node.as_rule() {
         GrammarRule::k_ALL => Quantifier::All { span: ctx.span(&node) },
         GrammarRule::k_ANY => Quantifier::Any { span: ctx.span(&node) },
         GrammarRule::k_NONE => Quantifier::None { span: ctx.span(&node) },
+        // `<expr> to <expr>`
+        GrammarRule::expr if children.peek().is_some() => {
+            let min = expr_from_cst(ctx, node)?;
+            expect!(children.next().unwrap(), GrammarRule::k_TO);
+            let max = expr_from_cst(ctx, children.next().unwrap())?;
+            Quantifier::Range {
+                span: min.span().combine(&max.span()),
+                min,
+                max,
+            }
+        }
         GrammarRule::expr => Quantifier::Expr(expr_from_cst(ctx, node)?),
         GrammarRule::primary_expr => {
             let expr = primary_expr_from_cst(ctx, node)?;
@@ -1784,29 +2037,73 @@ where
         multiplier = 1024 * 1024;
     }
 
+    if let Some(without_suffix) = literal.strip_suffix("GB") {
+        literal = without_suffix;
+        multiplier = 1024 * 1024 * 1024;
+    }
+
     if let Some(without_sign) = literal.strip_prefix('-') {
         literal = without_sign;
         multiplier = -multiplier;
     }
 
-    let value = if literal.starts_with("0x") {
-        T::from_str_radix(literal.strip_prefix("0x").unwrap(), 16)
-    } else if literal.starts_with("0o") {
-        T::from_str_radix(literal.strip_prefix("0o").unwrap(), 8)
+    let (digits, radix) = if let Some(digits) = literal.strip_prefix("0x") {
+        (digits, 16)
+    } else if let Some(digits) = literal.strip_prefix("0o") {
+        (digits, 8)
+    } else if let Some(digits) = literal.strip_prefix("0b") {
+        (digits, 2)
     } else {
-        T::from_str_radix(literal, 10)
+        (literal, 10)
+    };
+
+    // True if T is a signed integer type with at least 64 bits.
+    let is_signed_64 = T::from_i64(i64::MIN).is_some();
+
+    let value = if radix == 10 {
+        T::from_str_radix(digits, radix).ok()
+    } else {
+        // Hexadecimal, octal and binary literals can use the whole 64-bits
+        // range. If T is a signed 64-bits integer, literals that exceed its
+        // maximum value are interpreted as the two's complement of a negative
+        // number, like in YARA (e.g: 0xFFFFFFFFFFFFFFFF is -1). This doesn't
+        // apply to literals that already have a minus sign.
+        u64::from_str_radix(digits, radix).ok().and_then(|value| {
+            T::from_u64(value).or_else(|| {
+                if is_signed_64 && multiplier > 0 {
+                    T::from_i64(value as i64)
+                } else {
+                    None
+                }
+            })
+        })
     };
 
     let build_error = || {
-        Error::from(ErrorInfo::invalid_integer(
-            ctx.report_builder,
-            format!(
-                "this number is out of the valid range: [{}, {}]",
-                T::min_value(),
-                T::max_value()
-            ),
-            span,
-        ))
+        let error_msg = format!(
+            "this number is out of the valid range: [{}, {}]",
+            T::min_value(),
+            T::max_value()
+        );
+        if radix == 10 {
+            Error::from(ErrorInfo::invalid_integer(
+                ctx.report_builder,
+                error_msg,
+                span,
+            ))
+        } else if is_signed_64 {
+            Error::from(ErrorInfo::integer_overflow(
+                ctx.report_builder,
+                "this number doesn't fit in 64 bits".to_string(),
+                span,
+            ))
+        } else {
+            Error::from(ErrorInfo::integer_overflow(
+                ctx.report_builder,
+                error_msg,
+                span,
+            ))
+        }
     };
 
     // Report errors that occur while parsing the literal. Some errors
@@ -1814,7 +2111,7 @@ where
     // the grammar ensures that only valid integers reach this point,
     // however the grammar doesn't make sure that the integer fits in
     // type T.
-    let value = value.map_err(|_| build_error())?;
+    let value = value.ok_or_else(build_error)?;
 
     // The multiplier may not fit in type T.
     let multiplier = T::from_i32(multiplier).ok_or_else(build_error)?;
@@ -1825,6 +2122,31 @@ where
     Ok(value)
 }
 
+/// Like [`integer_lit_from_cst`], but returns an error if the literal has a
+/// size suffix (i.e: `KB`, `MB`, `GB`). This is used in contexts where sizes
+/// don't make sense, like xor ranges and the bounds of hex pattern jumps.
+fn integer_lit_without_suffix_from_cst<'src, T>(
+    ctx: &Context<'src, '_>,
+    integer_lit: CSTNode<'src>,
+) -> Result<T, Error>
+where
+    T: Num + Bounded + CheckedMul + FromPrimitive + std::fmt::Display,
+{
+    expect!(integer_lit, GrammarRule::integer_lit);
+
+    let literal = integer_lit.as_str();
+
+    if ["KB", "MB", "GB"].iter().any(|suffix| literal.ends_with(suffix)) {
+        return Err(Error::from(ErrorInfo::invalid_integer(
+            ctx.report_builder,
+            "size suffixes are not allowed here".to_string(),
+            ctx.span(&integer_lit),
+        )));
+    }
+
+    integer_lit_from_cst(ctx, integer_lit)
+}
+
 /// From a CST node corresponding to the grammar rule `float_lit`, returns
 /// the `f64` representing the literal.
 fn float_lit_from_cst<'src>(
@@ -2079,6 +2401,12 @@ fn hex_pattern_from_cst<'src>(
             GrammarRule::hex_alternative => HexToken::Alternative(Box::new(
                 hex_alternative_from_cst(ctx, node)?,
             )),
+            GrammarRule::hex_fragment => {
+                HexToken::Fragment(Box::new(HexFragment {
+                    span: ctx.span(&node),
+                    name: node.as_str().to_string(),
+                }))
+            }
             GrammarRule::hex_jump => {
                 let mut jump_span = ctx.span(&node);
                 let mut jump = hex_jump_from_cst(ctx, node)?;
@@ -2170,7 +2498,7 @@ fn hex_jump_from_cst<'src>(
     let mut end = None;
 
     if let GrammarRule::integer_lit = node.as_rule() {
-        start = Some(integer_lit_from_cst(ctx, node)?);
+        start = Some(integer_lit_without_suffix_from_cst(ctx, node)?);
     };
 
     node = children.next().unwrap();
@@ -2178,7 +2506,7 @@ fn hex_jump_from_cst<'src>(
     if let GrammarRule::HYPHEN = node.as_rule() {
         node = children.next().unwrap();
         if let GrammarRule::integer_lit = node.as_rule() {
-            end = Some(integer_lit_from_cst(ctx, node)?);
+            end = Some(integer_lit_without_suffix_from_cst(ctx, node)?);
             node = children.next().unwrap();
         }
     } else {
@@ -2217,6 +2545,37 @@ fn hex_alternative_from_cst<'src>(
     Ok(HexAlternative { alternatives })
 }
 
+/// Returns true if `text` contains constructs that are common in regular
+/// expressions but rare in literal strings, like character ranges (e.g:
+/// `[0-9]`), a wildcard followed by a quantifier (e.g: `.*`), or grouped
+/// alternatives (e.g: `(exe|dll)`).
+fn looks_like_regexp(text: &[u8]) -> bool {
+    // Returns the bytes enclosed between each occurrence of `open` and the
+    // first occurrence of `close` that follows it.
+    let enclosed = |open: u8, close: u8| {
+        text.iter().enumerate().filter(move |(_, b)| **b == open).filter_map(
+            move |(i, _)| {
+                let rest = &text[i + 1..];
+                rest.find_byte(close).map(|j| &rest[..j])
+            },
+        )
+    };
+
+    let is_range = |class: &[u8]| {
+        class.windows(3).any(|w| {
+            w[1] == b'-'
+                && w[0].is_ascii_alphanumeric()
+                && w[2].is_ascii_alphanumeric()
+                && w[0] < w[2]
+        })
+    };
+
+    text.find(".*").is_some()
+        || text.find(".+").is_some()
+        || enclosed(b'[', b']').any(is_range)
+        || enclosed(b'(', b')').any(|group| group.contains(&b'|'))
+}
+
 fn ident_from_cst<'src>(
     ctx: &Context<'src, '_>,
     ident: CSTNode<'src>,
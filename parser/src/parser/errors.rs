@@ -126,6 +126,16 @@ pub enum ErrorInfo {
         pattern_ident_span: Span,
     },
 
+    #[error("ambiguous pattern reference")]
+    #[label("this `$` could refer to any of the {num_patterns} anonymous patterns", span)]
+    #[note(note)]
+    AmbiguousPatternReference {
+        detailed_report: String,
+        num_patterns: usize,
+        span: Span,
+        note: Option<String>,
+    },
+
     #[error("invalid pattern `{pattern_ident}`")]
     #[label("{error_msg}", error_span)]
     #[note(note)]
@@ -153,6 +163,14 @@ pub enum ErrorInfo {
         error_span: Span,
     },
 
+    #[error("integer overflow")]
+    #[label("{error_msg}", error_span)]
+    IntegerOverflow {
+        detailed_report: String,
+        error_msg: String,
+        error_span: Span,
+    },
+
     #[error("invalid float")]
     #[label("{error_msg}", error_span)]
     InvalidFloat {
@@ -189,6 +207,31 @@ pub enum ErrorInfo {
     InvalidUTF8 {
         detailed_report: String,
         error_span: Span},
+
+    #[error("code is nested too deeply")]
+    #[label("this exceeds the maximum nesting depth of {limit}", span)]
+    TooDeeplyNested {
+        detailed_report: String,
+        limit: usize,
+        span: Span,
+    },
+
+    #[error("unterminated comment")]
+    #[label("this comment is never closed", span)]
+    UnterminatedComment {
+        detailed_report: String,
+        span: Span,
+    },
+
+    #[error("invalid fragment name `{fragment_ident}`")]
+    #[label("this name can't be distinguished from a sequence of bytes", span)]
+    #[note(note)]
+    InvalidFragmentName {
+        detailed_report: String,
+        fragment_ident: String,
+        span: Span,
+        note: Option<String>,
+    },
 }
 
 impl From<ErrorInfo> for Error {
@@ -307,9 +350,11 @@ impl ErrorInfo {
             Rule::k_FALSE => "`false`",
             Rule::k_FILESIZE => "`filesize`",
             Rule::k_FOR => "`for`",
+            Rule::k_FRAGMENT => "`fragment`",
             Rule::k_FULLWORD => "`fullword`",
             Rule::k_GLOBAL => "`global`",
             Rule::k_IMPORT => "`import`",
+            Rule::k_INCLUDE => "`include`",
             Rule::k_IN => "`in`",
             Rule::k_META => "`meta`",
             Rule::k_NOCASE => "`nocase`",
@@ -320,7 +365,10 @@ impl ErrorInfo {
             Rule::k_RULE => "`rule`",
             Rule::k_STRINGS => "`strings`",
             Rule::k_THEM => "`them`",
+            Rule::k_TO => "`to`",
             Rule::k_TRUE => "`true`",
+            Rule::k_WEIGHT => "`weight`",
+            Rule::k_WEIGHTSUM => "`weightsum`",
             Rule::k_WIDE => "`wide`",
             Rule::k_XOR => "`xor`",
 
@@ -329,11 +377,15 @@ impl ErrorInfo {
             Rule::expr | Rule::primary_expr | Rule::term => "expression",
 
             Rule::hex_byte => "byte",
+            Rule::hex_fragment => "fragment",
             Rule::hex_tokens => "bytes",
+            Rule::meta_byte => "byte",
+            Rule::meta_bytes => "bytes",
             Rule::ident => "identifier",
             Rule::integer_lit => "number",
             Rule::float_lit => "number",
             Rule::rule_decl => "rule declaration",
+            Rule::fragment_decl => "fragment declaration",
             Rule::source_file => "YARA rules",
             Rule::string_lit => "string literal",
             Rule::regexp => "regular expression",
@@ -401,6 +453,7 @@ impl ErrorInfo {
             | Rule::string_op
             | Rule::block_comment
             | Rule::single_line_comment
+            | Rule::include_stmt
             | Rule::import_stmt
             | Rule::ident_chars
             | Rule::pattern_count
@@ -69,6 +69,16 @@ impl<'src> SourceCode<'src> {
         }
     }
 
+    /// Returns the origin of the source code, if any.
+    pub fn origin(&self) -> Option<&str> {
+        self.origin.as_deref()
+    }
+
+    /// Returns the source code as a slice of bytes.
+    pub fn as_bytes(&self) -> &'src [u8] {
+        self.raw.as_bytes()
+    }
+
     /// Returns the source code as a `&str`.
     ///
     /// If the source code is not valid UTF-8 it will return an error.
@@ -114,13 +124,22 @@ impl<'src> From<&'src [u8]> for SourceCode<'src> {
 
 /// Receives YARA source code and produces either a Concrete Syntax Tree (CST)
 /// or an Abstract Syntax Tree (AST).
-#[derive(Default)]
 pub struct Parser<'a> {
     external_report_builder: Option<&'a ReportBuilder>,
     own_report_builder: ReportBuilder,
+    max_nesting_depth: usize,
+}
+
+impl Default for Parser<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<'a> Parser<'a> {
+    /// Default value for [`Parser::max_nesting_depth`].
+    pub const DEFAULT_MAX_NESTING_DEPTH: usize = 128;
+
     /// Creates a new YARA parser.
     pub fn new() -> Self {
         // This imposes a limit on the number of calls that can be made to some
@@ -138,9 +157,23 @@ impl<'a> Parser<'a> {
         Self {
             external_report_builder: None,
             own_report_builder: ReportBuilder::new(),
+            max_nesting_depth: Self::DEFAULT_MAX_NESTING_DEPTH,
         }
     }
 
+    /// Sets the maximum nesting depth for expressions.
+    ///
+    /// The parser is recursive and deeply nested expressions, like the
+    /// ones produced by fuzzers, can exhaust the stack. Each parenthesis,
+    /// bracket or unary operator (`not`, `defined`, `-` and `~`) adds one
+    /// level, and source code nested beyond this limit is rejected with
+    /// [`ErrorInfo::TooDeeplyNested`]. The default value is
+    /// [`Parser::DEFAULT_MAX_NESTING_DEPTH`].
+    pub fn max_nesting_depth(&mut self, limit: usize) -> &mut Self {
+        self.max_nesting_depth = limit;
+        self
+    }
+
     /// Specifies whether the parser should produce colorful error messages.
     ///
     /// Colorized error messages contain ANSI escape sequences that make them
@@ -200,11 +233,21 @@ impl<'a> Parser<'a> {
 
         let report_builder = self.get_report_builder();
 
-        let mut ctx = Context::new(report_builder);
+        let mut ctx = Context::new(report_builder, self.max_nesting_depth);
 
-        let (imports, rules) = ast_from_cst(&mut ctx, root.into_inner())?;
+        // Comments are enabled only at the top level of the CST, so that
+        // the block comments that precede each rule can be found.
+        let (includes, imports, fragments, rules) =
+            ast_from_cst(&mut ctx, root.comments(true).into_inner())?;
 
-        Ok(AST { source: src, imports, rules, warnings: ctx.warnings })
+        Ok(AST {
+            source: src,
+            includes,
+            imports,
+            fragments,
+            rules,
+            warnings: ctx.warnings,
+        })
     }
 
     /// Build the Concrete Syntax Tree (CST) for a YARA source.
@@ -272,8 +315,43 @@ impl<'a> Parser<'a> {
 
         match src.as_str() {
             Ok(src) => {
+                if let Some(offset) =
+                    Self::nesting_depth_exceeded(src, self.max_nesting_depth)
+                {
+                    return Err(Error::from(ErrorInfo::too_deeply_nested(
+                        report_builder,
+                        self.max_nesting_depth,
+                        Span::new(
+                            report_builder.current_source_id().unwrap(),
+                            offset,
+                            offset + 1,
+                        ),
+                    )));
+                }
+
                 let pairs = grammar::ParserImpl::parse(rule, src).map_err(
-                    |pest_error| report_builder.convert_pest_error(pest_error),
+                    |pest_error| {
+                        // A block comment that is never closed makes the
+                        // parser fail at some unrelated point, report the
+                        // unterminated comment instead.
+                        match Self::unterminated_comment(src) {
+                            Some(offset) => {
+                                Error::from(ErrorInfo::unterminated_comment(
+                                    report_builder,
+                                    Span::new(
+                                        report_builder
+                                            .current_source_id()
+                                            .unwrap(),
+                                        offset,
+                                        offset + 2,
+                                    ),
+                                ))
+                            }
+                            None => {
+                                report_builder.convert_pest_error(pest_error)
+                            }
+                        }
+                    },
                 )?;
 
                 Ok(CST {
@@ -308,6 +386,180 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Returns the offset of the first parenthesis, bracket or unary operator
+    /// in `src` that is nested more than `limit` levels deep, if any.
+    ///
+    /// Each parenthesis or bracket adds one level, and so does each unary
+    /// operator (`not`, `defined`, `-` and `~`) applied to the operand that
+    /// follows it. For instance, both `((x))` and `not -x` have depth 2.
+    ///
+    /// This doesn't parse the code, it only skips comments, string literals
+    /// and regular expressions, so that parenthesis and brackets inside them
+    /// are not taken into account. A slash is considered the start of a
+    /// regular expression when it comes after `=`, `(`, `,` or the
+    /// `matches` operator, and a division operator otherwise. A minus sign
+    /// is considered a unary operator when it doesn't come after an operand.
+    fn nesting_depth_exceeded(src: &str, limit: usize) -> Option<usize> {
+        let bytes = src.as_bytes();
+        // Number of unary operators that precede each of the currently open
+        // parenthesis or brackets.
+        let mut unary_ops = Vec::new();
+        // Number of consecutive unary operators that precede the current
+        // operand.
+        let mut pending_unary_ops = 0_usize;
+        // Current depth, including both brackets and unary operators.
+        let mut depth = 0_usize;
+        // Last byte that is not a whitespace, or part of a comment.
+        let mut prev = 0_u8;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let c = bytes[i];
+            match c {
+                b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                    i = src[i..].find('\n').map_or(bytes.len(), |j| i + j);
+                    continue;
+                }
+                b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                    i = src[i + 2..]
+                        .find("*/")
+                        .map_or(bytes.len(), |j| i + j + 4);
+                    continue;
+                }
+                _ if c.is_ascii_whitespace() => {
+                    i += 1;
+                    continue;
+                }
+                b'/' if Self::is_regexp_start(src, i) => {
+                    i = Self::skip_until(bytes, i + 1, b'/');
+                    depth -= pending_unary_ops;
+                    pending_unary_ops = 0;
+                }
+                b'"' => {
+                    i = Self::skip_until(bytes, i + 1, b'"');
+                    depth -= pending_unary_ops;
+                    pending_unary_ops = 0;
+                }
+                b'(' | b'[' => {
+                    depth += 1;
+                    if depth > limit {
+                        return Some(i);
+                    }
+                    unary_ops.push(pending_unary_ops);
+                    pending_unary_ops = 0;
+                }
+                b')' | b']' => {
+                    depth -= pending_unary_ops;
+                    if let Some(n) = unary_ops.pop() {
+                        depth -= n + 1;
+                    }
+                    pending_unary_ops = 0;
+                }
+                b'~' => {
+                    depth += 1;
+                    if depth > limit {
+                        return Some(i);
+                    }
+                    pending_unary_ops += 1;
+                }
+                b'-' if !(prev.is_ascii_alphanumeric()
+                    || matches!(prev, b'_' | b')' | b']' | b'"')) =>
+                {
+                    depth += 1;
+                    if depth > limit {
+                        return Some(i);
+                    }
+                    pending_unary_ops += 1;
+                }
+                _ if c.is_ascii_alphanumeric() || c == b'_' => {
+                    let start = i;
+                    while bytes.get(i + 1).is_some_and(|c| {
+                        c.is_ascii_alphanumeric() || *c == b'_'
+                    }) {
+                        i += 1;
+                    }
+                    let word = &src[start..=i];
+                    if matches!(word, "not" | "defined")
+                        && !matches!(prev, b'$' | b'#' | b'@' | b'!' | b'.')
+                    {
+                        depth += 1;
+                        if depth > limit {
+                            return Some(start);
+                        }
+                        pending_unary_ops += 1;
+                    } else {
+                        depth -= pending_unary_ops;
+                        pending_unary_ops = 0;
+                    }
+                }
+                _ => {
+                    depth -= pending_unary_ops;
+                    pending_unary_ops = 0;
+                }
+            }
+            prev = bytes[i.min(bytes.len() - 1)];
+            i += 1;
+        }
+
+        None
+    }
+
+    /// Returns the offset of the first block comment in `src` that is not
+    /// closed, if any.
+    ///
+    /// Like [`Parser::nesting_depth_exceeded`], this doesn't parse the code,
+    /// it only skips line comments, string literals and regular expressions,
+    /// so that `/*` inside them is not taken into account. Block comments
+    /// can't be nested, the first `*/` closes the comment.
+    fn unterminated_comment(src: &str) -> Option<usize> {
+        let bytes = src.as_bytes();
+        let mut i = 0;
+
+        while i < bytes.len() {
+            match bytes[i] {
+                b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                    i = src[i..].find('\n').map_or(bytes.len(), |j| i + j);
+                }
+                b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                    match src[i + 2..].find("*/") {
+                        Some(j) => i += j + 3,
+                        None => return Some(i),
+                    }
+                }
+                b'/' if Self::is_regexp_start(src, i) => {
+                    i = Self::skip_until(bytes, i + 1, b'/');
+                }
+                b'"' => {
+                    i = Self::skip_until(bytes, i + 1, b'"');
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+
+        None
+    }
+
+    /// Returns the offset of the first occurrence of `delimiter` in `bytes`
+    /// after `start` that is not escaped with a backslash.
+    fn skip_until(bytes: &[u8], start: usize, delimiter: u8) -> usize {
+        let mut j = start;
+        while j < bytes.len() && bytes[j] != delimiter {
+            j += if bytes[j] == b'\\' { 2 } else { 1 };
+        }
+        j
+    }
+
+    /// Returns true if a slash at `offset` in `src` is the start of a regular
+    /// expression.
+    fn is_regexp_start(src: &str, offset: usize) -> bool {
+        let before = src[..offset].trim_end();
+        before.ends_with(['=', '(', ','])
+            || before.strip_suffix("matches").is_some_and(|s| {
+                !s.ends_with(|c: char| c.is_alphanumeric() || c == '_')
+            })
+    }
+
     /// Sets the report builder used by the Parser.
     ///
     /// This is optional, if the report builder is not set the Parser will
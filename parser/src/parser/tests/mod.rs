@@ -1,6 +1,8 @@
 use pretty_assertions::assert_eq;
 
-use crate::parser::Parser;
+use crate::ast::Pattern;
+use crate::cst::CST;
+use crate::parser::{ErrorInfo, GrammarRule, Parser};
 
 #[cfg(feature = "ascii-tree")]
 #[test]
@@ -56,5 +58,196 @@ fn pathological_case() {
         .is_err());
 }
 
+#[test]
+fn max_nesting_depth() {
+    let nested = |depth: usize| {
+        format!(
+            "rule test {{ strings: $a = /(((x)))/ condition: {}$a{} }}",
+            "(".repeat(depth),
+            ")".repeat(depth)
+        )
+    };
+
+    // Deeply nested code must produce an error instead of overflowing the
+    // stack.
+    let err = Parser::new().build_ast(nested(100_000).as_str()).unwrap_err();
+
+    assert!(matches!(
+        err.info(),
+        ErrorInfo::TooDeeplyNested {
+            limit: Parser::DEFAULT_MAX_NESTING_DEPTH,
+            ..
+        }
+    ));
+
+    // Parenthesis inside the regexp are not taken into account.
+    assert!(Parser::new()
+        .max_nesting_depth(5)
+        .build_ast(nested(5).as_str())
+        .is_ok());
+
+    assert_eq!(
+        Parser::new()
+            .max_nesting_depth(5)
+            .build_ast(nested(6).as_str())
+            .unwrap_err()
+            .to_string(),
+        r#"error: code is nested too deeply
+ --> line:1:53
+  |
+1 | rule test { strings: $a = /(((x)))/ condition: (((((($a)))))) }
+  |                                                     ^ this exceeds the maximum nesting depth of 5
+  |"#
+    );
+}
+
+#[test]
+fn max_nesting_depth_unary_operators() {
+    // Long chains of unary operators are as deep as nested parenthesis.
+    for op in ["not ", "defined ", "-", "~", "- "] {
+        let src =
+            format!("rule test {{ condition: {}1 }}", op.repeat(100_000));
+
+        let err = Parser::new().build_ast(src.as_str()).unwrap_err();
+
+        assert!(matches!(
+            err.info(),
+            ErrorInfo::TooDeeplyNested {
+                limit: Parser::DEFAULT_MAX_NESTING_DEPTH,
+                ..
+            }
+        ));
+    }
+
+    // Parenthesis and unary operators add up.
+    assert!(Parser::new()
+        .max_nesting_depth(6)
+        .build_ast("rule test { condition: not (-(~(1))) == 1 }")
+        .is_ok());
+
+    assert!(Parser::new()
+        .max_nesting_depth(6)
+        .build_ast("rule test { condition: not (-(~(-1))) == 1 }")
+        .is_err());
+
+    // The quick check performed before parsing takes the minus sign after
+    // `and` for a binary operator, the depth is enforced while building the
+    // AST anyway.
+    assert_eq!(
+        Parser::new()
+            .max_nesting_depth(2)
+            .build_ast("rule test { condition: true and -(-x) == 1 }")
+            .unwrap_err()
+            .to_string(),
+        r#"error: code is nested too deeply
+ --> line:1:35
+  |
+1 | rule test { condition: true and -(-x) == 1 }
+  |                                   ^ this exceeds the maximum nesting depth of 2
+  |"#
+    );
+
+    // Unary operators applied to different operands are not nested, and
+    // neither are binary operators.
+    assert!(Parser::new()
+        .max_nesting_depth(2)
+        .build_ast(
+            "rule test { condition: not defined 1 and not not 2 - -3 == 1 }"
+        )
+        .is_ok());
+}
+
+#[test]
+fn comments() {
+    let ast = Parser::new()
+        .build_ast(
+            r#"
+// Line comment before the rule.
+rule test {  // Line comment after the brace.
+  /* Block comment */
+  strings:
+    $a = "// not a comment" /* Block
+    comment spanning multiple lines */
+    $b = "/* not a comment */"
+  condition:
+    $a and /* inline */ $b // Line comment at the end.
+}
+// Line comment without newline at the end"#,
+        )
+        .unwrap();
+
+    let rule = ast.rules.first().unwrap();
+
+    let texts: Vec<_> = rule
+        .patterns
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|pattern| match pattern {
+            Pattern::Text(text) => text.text.to_string(),
+            _ => unreachable!(),
+        })
+        .collect();
+
+    assert_eq!(texts, vec!["// not a comment", "/* not a comment */"]);
+
+    // Comments are kept in the CST.
+    fn collect_comments<'src>(cst: CST<'src>, comments: &mut Vec<&'src str>) {
+        for node in cst {
+            if node.as_rule() == GrammarRule::COMMENT {
+                comments.push(node.as_str());
+            } else {
+                collect_comments(node.into_inner(), comments);
+            }
+        }
+    }
+
+    let mut comments = Vec::new();
+
+    collect_comments(
+        Parser::new()
+            .build_cst("rule test { condition: /* foo */ true // bar\n}")
+            .unwrap()
+            .comments(true),
+        &mut comments,
+    );
+
+    assert_eq!(comments, vec!["/* foo */", "// bar"]);
+}
+
+#[test]
+fn unterminated_comment() {
+    let err = Parser::new()
+        .build_ast("rule test { /* strings: $a = \"*/\" condition: $a }")
+        .unwrap_err();
+
+    // The first `*/` closes the comment, even if it's inside what looks
+    // like a string literal, and the `"` that follows opens a string that
+    // is never closed.
+    assert!(!matches!(err.info(), ErrorInfo::UnterminatedComment { .. }));
+
+    let err = Parser::new()
+        .build_ast("rule test { condition: true } /* /* */ */")
+        .unwrap_err();
+
+    // Block comments can't be nested.
+    assert!(!matches!(err.info(), ErrorInfo::UnterminatedComment { .. }));
+
+    assert_eq!(
+        Parser::new()
+            .build_ast(
+                "rule test {\n  strings:\n    $a = \"/*\"\n  condition:\n    /* $a\n}"
+            )
+            .unwrap_err()
+            .to_string(),
+        r#"error: unterminated comment
+ --> line:5:5
+  |
+5 |     /* $a
+  |     ^^ this comment is never closed
+  |"#
+    );
+}
+
 mod ast;
 mod cst;
@@ -1,5 +1,5 @@
 use std::borrow::Cow;
-use std::cell::{Cell, RefCell};
+use std::cell::{Cell, Ref, RefCell};
 use std::collections::HashMap;
 use std::fmt::Debug;
 
@@ -28,6 +28,14 @@ pub type Level = annotate_snippets::Level;
 #[derive(Hash, Eq, PartialEq, Clone, Copy, Debug, Default)]
 pub struct SourceId(u32);
 
+impl From<SourceId> for usize {
+    /// Returns the position of the source file in the order in which source
+    /// files were registered, starting at 0.
+    fn from(source_id: SourceId) -> Self {
+        source_id.0 as usize
+    }
+}
+
 /// Builds error and warning reports.
 ///
 /// `ReportBuilder` helps to create error and warning reports. It stores a copy
@@ -117,6 +125,28 @@ impl ReportBuilder {
         self
     }
 
+    /// Returns the code of the source file identified by `source_id`, or
+    /// `None` if no such source file was registered.
+    ///
+    /// Invalid UTF-8 characters in the original code are replaced with the
+    /// UTF-8 replacement character, and tabs are replaced with spaces.
+    pub fn source_code(&self, source_id: SourceId) -> Option<Ref<'_, str>> {
+        Ref::filter_map(self.cache.borrow(), |cache| {
+            cache.data.get(&source_id).map(|entry| entry.code.as_str())
+        })
+        .ok()
+    }
+
+    /// Returns the origin of the source file identified by `source_id`, if
+    /// the source file was registered and has an origin.
+    pub fn source_origin(&self, source_id: SourceId) -> Option<String> {
+        self.cache
+            .borrow()
+            .data
+            .get(&source_id)
+            .and_then(|entry| entry.origin.clone())
+    }
+
     /// Creates a new error or warning report.
     pub fn create_report(
         &self,
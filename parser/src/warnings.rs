@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::fmt::{Debug, Display, Formatter};
 use yara_x_macros::Error;
 
@@ -7,7 +8,7 @@ use crate::report::ReportBuilder;
 
 /// A warning raised while parsing YARA rules.
 #[rustfmt::skip]
-#[derive(Error)]
+#[derive(Error, PartialEq, Eq)]
 pub enum Warning {
     #[warning("consecutive jumps in hex pattern `{pattern_ident}`")]
     #[label("these consecutive jumps will be treated as {coalesced_jump}", jumps_span)]
@@ -80,6 +81,25 @@ pub enum Warning {
         span: Span,
     },
 
+    #[warning("text pattern looks like a regular expression")]
+    #[label("this pattern is matched literally", span)]
+    #[note(note)]
+    LiteralLooksLikeRegex {
+        detailed_report: String,
+        span: Span,
+        note: Option<String>,
+    },
+
+    #[warning("potentially expensive regular expression")]
+    #[label("`{identifier}` has nested unbounded quantifiers", span)]
+    #[note(note)]
+    PotentiallyExpensiveRegex {
+        detailed_report: String,
+        identifier: String,
+        span: Span,
+        note: Option<String>,
+    },
+
     #[warning("module `{module_name}` is not supported")]
     #[label("module `{module_name}` used here", span)]
     #[note(note)]
@@ -99,17 +119,85 @@ pub enum Warning {
         module_name: String,
         span: Span,
     },
+
+    #[warning("redundant pattern `{redundant}`")]
+    #[label("this pattern can't match without `{dominant}` matching too", redundant_span)]
+    #[label("`{dominant}` declared here", dominant_span, style="note")]
+    #[note(note)]
+    RedundantPattern {
+        detailed_report: String,
+        redundant: String,
+        dominant: String,
+        redundant_span: Span,
+        dominant_span: Span,
+        note: Option<String>,
+    },
+
+    #[warning("suspicious match index")]
+    #[label("this is undefined if `{pattern_ident}` has less than {index} matches", span)]
+    #[note(note)]
+    SuspiciousMatchIndex {
+        detailed_report: String,
+        pattern_ident: String,
+        index: i64,
+        span: Span,
+        note: Option<String>,
+    },
+
+    #[warning("rule `{rule}` always matches")]
+    #[label("this condition is always true", span)]
+    #[note(note)]
+    AlwaysMatches {
+        detailed_report: String,
+        rule: String,
+        span: Span,
+        note: Option<String>,
+    },
+
+    #[warning("rules `{first}` and `{second}` have the same logic")]
+    #[label("`{second}` has the same patterns and condition as `{first}`", second_span)]
+    #[label("`{first}` declared here", first_span, style="note")]
+    DuplicateRuleLogic {
+        detailed_report: String,
+        first: String,
+        second: String,
+        first_span: Span,
+        second_span: Span,
+    },
+
+    #[warning("potentially expensive loop")]
+    #[label("this range depends on `filesize`", span)]
+    #[note(note)]
+    ExpensiveLoop {
+        detailed_report: String,
+        span: Span,
+        note: Option<String>,
+    },
 }
 
 /// Represents a list of warnings.
+///
+/// Warnings can be disabled or treated as errors by their code (see
+/// [`Warning::code`]). Disabled warnings are discarded when added to the
+/// list, while warnings treated as errors are put apart, and they can be
+/// retrieved with [`Warnings::take_errors`].
 pub struct Warnings {
     warnings: Vec<Warning>,
     max_warnings: usize,
+    disabled: HashSet<&'static str>,
+    as_errors: HashSet<&'static str>,
+    errors: Vec<Warning>,
 }
 
 impl Default for Warnings {
     fn default() -> Self {
-        Self { warnings: Vec::new(), max_warnings: 100 }
+        Self {
+            warnings: Vec::new(),
+            max_warnings: 100,
+            disabled: HashSet::new(),
+            as_errors: HashSet::new(),
+            errors: Vec::new(),
+        }
     }
 }
 
@@ -126,11 +214,65 @@ impl Warnings {
 
     #[inline]
     pub fn add(&mut self, f: impl Fn() -> Warning) {
-        if self.warnings.len() < self.max_warnings {
-            self.warnings.push(f());
+        // Warnings treated as errors are not subject to the limit.
+        if self.warnings.len() < self.max_warnings
+            || !self.as_errors.is_empty()
+        {
+            self.push(f());
         }
     }
 
+    /// Enables or disables the warning with the given code.
+    ///
+    /// Returns `false` if the code doesn't correspond to any warning.
+    pub fn switch_warning(&mut self, code: &str, enabled: bool) -> bool {
+        match Self::static_code(code) {
+            Some(code) if enabled => self.disabled.remove(code),
+            Some(code) => self.disabled.insert(code),
+            None => return false,
+        };
+        true
+    }
+
+    /// Enables or disables all warnings.
+    pub fn switch_all_warnings(&mut self, enabled: bool) {
+        if enabled {
+            self.disabled.clear();
+        } else {
+            self.disabled.extend(Warning::all_codes());
+        }
+    }
+
+    /// Makes the warning with the given code be treated as an error, or
+    /// as a normal warning again if `as_error` is false.
+    ///
+    /// Returns `false` if the code doesn't correspond to any warning.
+    pub fn warning_as_error(&mut self, code: &str, as_error: bool) -> bool {
+        match Self::static_code(code) {
+            Some(code) if as_error => self.as_errors.insert(code),
+            Some(code) => self.as_errors.remove(code),
+            None => return false,
+        };
+        true
+    }
+
+    /// Removes and returns the warnings that must be treated as errors.
+    pub fn take_errors(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Returns true if some warning that must be treated as error has been
+    /// added since the last call to [`Warnings::take_errors`].
+    #[inline]
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    /// Returns true if warnings with the given code are treated as errors.
+    pub fn is_error(&self, code: &str) -> bool {
+        !self.disabled.contains(code) && self.as_errors.contains(code)
+    }
+
     #[inline]
     pub fn as_slice(&self) -> &[Warning] {
         self.warnings.as_slice()
@@ -138,12 +280,32 @@ impl Warnings {
 
     pub fn append(&mut self, mut warnings: Self) {
         for w in warnings.warnings.drain(0..) {
-            if self.warnings.len() == self.max_warnings {
-                break;
-            }
-            self.warnings.push(w)
+            self.push(w)
         }
     }
+
+    pub fn extend(&mut self, warnings: impl IntoIterator<Item = Warning>) {
+        for w in warnings {
+            self.push(w)
+        }
+    }
+
+    fn push(&mut self, warning: Warning) {
+        let code = warning.code();
+        if self.disabled.contains(code) {
+            return;
+        }
+        if self.as_errors.contains(code) {
+            self.errors.push(warning);
+        } else if self.warnings.len() < self.max_warnings {
+            self.warnings.push(warning);
+        }
+    }
+
+    /// Given a warning code, returns the same code as a static string.
+    fn static_code(code: &str) -> Option<&'static str> {
+        Warning::all_codes().iter().find(|c| **c == code).copied()
+    }
 }
 
 impl From<Warnings> for Vec<Warning> {